@@ -99,6 +99,40 @@ pub enum Commands {
         #[clap(long, short)]
         path: Option<String>,
     },
+    /// Migrate the postgres database schema after changing the rindexer.yaml or ABIs.
+    ///
+    /// Safe changes like new tables and columns are applied, unsafe changes like changing a
+    /// column type are listed and nothing is applied.
+    ///
+    /// Example:
+    /// `rindexer migrate` or `rindexer migrate --dry-run`
+    #[clap(name = "migrate")]
+    Migrate {
+        /// optional - Only show the migration plan without applying it.
+        #[clap(long)]
+        dry_run: bool,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
+    /// Inspect the stream outboxes and send dead letters again
+    ///
+    /// Messages which a stream could not publish after all of their attempts are moved to the
+    /// dead letters of the stream, they can be listed and moved back to the outbox.
+    ///
+    /// Example:
+    /// `rindexer streams status` or `rindexer streams dead-letters` or
+    /// `rindexer streams redrive --sink <SINK>`
+    #[clap(name = "streams")]
+    Streams {
+        #[clap(subcommand)]
+        subcommand: StreamsSubcommands,
+
+        /// optional - The path to run the command in, default will be where the command is run.
+        #[clap(long, short)]
+        path: Option<String>,
+    },
     /// Use phantom events to add your own events to contracts
     ///
     /// This command helps you use phantom events within rindexer.
@@ -216,6 +250,43 @@ pub enum CodegenSubcommands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum StreamsSubcommands {
+    /// Shows how many messages each stream has waiting in its outbox and in its dead letters.
+    ///
+    /// Example:
+    /// `rindexer streams status`
+    #[clap(name = "status")]
+    Status,
+
+    /// Lists the dead letters with the error they last failed with.
+    ///
+    /// Example:
+    /// `rindexer streams dead-letters` or `rindexer streams dead-letters --sink <SINK>`
+    #[clap(name = "dead-letters")]
+    DeadLetters {
+        /// optional - Only the dead letters of this stream
+        #[arg(long)]
+        sink: Option<String>,
+    },
+
+    /// Moves dead letters back to the outbox so they are sent again, a running indexer picks
+    /// them up within a few seconds.
+    ///
+    /// Example:
+    /// `rindexer streams redrive` or `rindexer streams redrive --sink <SINK> --id <ID>`
+    #[clap(name = "redrive")]
+    Redrive {
+        /// optional - Only the dead letters of this stream
+        #[arg(long)]
+        sink: Option<String>,
+
+        /// optional - Only the dead letter with this message id
+        #[arg(long)]
+        id: Option<String>,
+    },
+}
+
 #[derive(Args, Debug)]
 pub struct PhantomBaseArgs {
     /// The name of the contract
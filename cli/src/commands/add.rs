@@ -177,6 +177,7 @@ pub async fn handle_add_contract_command(
                 None,
             )],
             abi: StringOrArray::Single(abi_path_relative),
+            abi_versions: None,
             include_events: None,
             index_event_in_order: None,
             dependency_events: None,
@@ -184,6 +185,16 @@ pub async fn handle_add_contract_command(
             generate_csv: None,
             streams: None,
             chat: None,
+            raw_logs: None,
+            conditions: None,
+            computed: None,
+            tables: None,
+            rollups: None,
+            tuple_storage: None,
+            tuple_inputs: None,
+            numeric_type: None,
+            numeric_inputs: None,
+            retention: None,
         });
 
         write_manifest(&manifest, &rindexer_yaml_path).map_err(|e| {
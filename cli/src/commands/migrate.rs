@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use rindexer::{
+    manifest::yaml::{read_manifest, YAML_CONFIG_NAME},
+    migrate_postgres, plan_indexer_migration, PartitionedTables, PostgresClient,
+};
+
+use crate::console::{print_error_message, print_success_message, print_warn_message};
+
+pub async fn handle_migrate_command(
+    project_path: PathBuf,
+    dry_run: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let manifest = read_manifest(&project_path.join(YAML_CONFIG_NAME)).map_err(|e| {
+        print_error_message(&format!("Could read the rindexer.yaml please make sure you are running the command with rindexer.yaml in root: trace: {}", e));
+        e
+    })?;
+
+    if !manifest.storage.postgres_enabled() {
+        print_success_message("Postgres storage is not enabled. Nothing to migrate.");
+        return Ok(());
+    }
+
+    if manifest.storage.postgres_disable_create_tables() {
+        print_success_message(
+            "`disable_create_tables` is enabled so the tables are managed by you. Nothing to migrate.",
+        );
+        return Ok(());
+    }
+
+    let postgres_client = PostgresClient::new().await.map_err(|e| {
+        print_error_message(&format!("Could not connect to Postgres, make sure your connection string is mapping in the .env correctly: trace: {}", e));
+        e
+    })?;
+
+    let partitioned_tables =
+        PartitionedTables::load(&postgres_client, &manifest).await.map_err(|e| {
+            print_error_message(&format!("Could not read the database: trace: {}", e));
+            e
+        })?;
+
+    let (plan, _) = plan_indexer_migration(
+        &postgres_client,
+        &project_path,
+        &manifest.to_indexer(),
+        &partitioned_tables,
+    )
+    .await
+    .map_err(|e| {
+        print_error_message(&format!("Could not work out the migration: trace: {}", e));
+        e
+    })?;
+
+    if plan.is_blocked() {
+        print_warn_message(&format!("{}", plan));
+        print_error_message("\nThe database schema can not be migrated safely, resolve the changes marked with ! by hand or delete the data with `rindexer delete` and resync.");
+        return Err("Unsafe migration".into());
+    }
+
+    if dry_run {
+        if plan.is_empty() {
+            print_success_message("The database schema is up to date.");
+        } else {
+            print_success_message(&format!(
+                "{}\n\nRun `rindexer migrate` to apply these changes.",
+                plan
+            ));
+        }
+        return Ok(());
+    }
+
+    let plan = migrate_postgres(&postgres_client, &project_path, &manifest).await.map_err(|e| {
+        print_error_message(&format!("Could not migrate the database: trace: {}", e));
+        e
+    })?;
+
+    print_success_message(&format!("{}\n\nSuccessfully migrated the database schema.", plan));
+
+    Ok(())
+}
@@ -1,8 +1,10 @@
 pub mod add;
 pub mod codegen;
 pub mod delete;
+pub mod migrate;
 pub mod new;
 pub mod phantom;
 pub mod start;
+pub mod streams;
 
 const BACKUP_ETHERSCAN_API_KEY: &str = "DHBPB1EJ84JMSWP7C86387NK7IIRRQJVV1";
@@ -13,7 +13,7 @@ use rindexer::{
         contract::{Contract, ContractDetails},
         core::{Manifest, ProjectType},
         network::Network,
-        storage::{CsvDetails, PostgresDetails, Storage},
+        storage::{CsvDetails, FileRotationDetails, PostgresDetails, Storage},
         yaml::{write_manifest, YAML_CONFIG_NAME},
     },
     write_file, StringOrArray, WriteFileError,
@@ -158,6 +158,7 @@ pub fn handle_new_command(
                 Some(U64::from(19000000)),
             )],
             abi: StringOrArray::Single(abi_example_path.display().to_string()),
+            abi_versions: None,
             include_events: Some(vec!["Transfer".to_string(), "Approval".to_string()]),
             index_event_in_order: None,
             dependency_events: None,
@@ -165,6 +166,16 @@ pub fn handle_new_command(
             generate_csv: None,
             streams: None,
             chat: None,
+            raw_logs: None,
+            conditions: None,
+            computed: None,
+            tables: None,
+            rollups: None,
+            tuple_storage: None,
+            tuple_inputs: None,
+            numeric_type: None,
+            numeric_inputs: None,
+            retention: None,
         }],
         phantom: None,
         global: None,
@@ -176,6 +187,7 @@ pub fn handle_new_command(
                     relationships: None,
                     indexes: None,
                     disable_create_tables: None,
+                    partitions: None,
                 })
             } else {
                 None
@@ -185,10 +197,16 @@ pub fn handle_new_command(
                     enabled: true,
                     path: "./generated_csv".to_string(),
                     disable_create_headers: None,
+                    rotation: FileRotationDetails::default(),
                 })
             } else {
                 None
             },
+            parquet: None,
+            jsonl: None,
+            s3: None,
+            clickhouse: None,
+            sqlite: None,
         },
         graphql: None,
     };
@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+use rindexer::{redrive_stream_dead_letters, stream_dead_letters, stream_outbox_status};
+
+use crate::{
+    cli_interface::StreamsSubcommands,
+    console::{print_error_message, print_success_message, print_warn_message},
+};
+
+pub async fn handle_streams_command(
+    project_path: PathBuf,
+    subcommand: &StreamsSubcommands,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match subcommand {
+        StreamsSubcommands::Status => {
+            let statuses = stream_outbox_status(&project_path).await.map_err(|e| {
+                print_error_message(&format!("Could not read the stream outboxes: trace: {}", e));
+                e
+            })?;
+
+            if statuses.is_empty() {
+                print_success_message("No streams have queued any messages yet.");
+                return Ok(());
+            }
+
+            for status in statuses {
+                let message = format!(
+                    "{} - {} pending - {} dead letters",
+                    status.sink, status.pending, status.dead_letters
+                );
+                if status.dead_letters > 0 {
+                    print_warn_message(&message);
+                } else {
+                    print_success_message(&message);
+                }
+            }
+
+            Ok(())
+        }
+        StreamsSubcommands::DeadLetters { sink } => {
+            let dead_letters =
+                stream_dead_letters(&project_path, sink.as_deref()).await.map_err(|e| {
+                    print_error_message(&format!("Could not read the dead letters: trace: {}", e));
+                    e
+                })?;
+
+            if dead_letters.is_empty() {
+                print_success_message("No dead letters.");
+                return Ok(());
+            }
+
+            for dead_letter in &dead_letters {
+                let message = &dead_letter.message;
+                print_warn_message(&format!(
+                    "{} - {} - {}::{} - network: {} - to block: {} - attempts: {} - error: {}",
+                    dead_letter.sink,
+                    message.id,
+                    message.contract_name,
+                    message.event_name,
+                    message.message.network,
+                    message.to_block,
+                    message.attempts,
+                    message.last_error.as_deref().unwrap_or_default()
+                ));
+            }
+            print_success_message(&format!(
+                "\n{} dead letters - run `rindexer streams redrive` to send them again.",
+                dead_letters.len()
+            ));
+
+            Ok(())
+        }
+        StreamsSubcommands::Redrive { sink, id } => {
+            let redriven =
+                redrive_stream_dead_letters(&project_path, sink.as_deref(), id.as_deref())
+                    .await
+                    .map_err(|e| {
+                        print_error_message(&format!(
+                            "Could not redrive the dead letters: trace: {}",
+                            e
+                        ));
+                        e
+                    })?;
+
+            if redriven == 0 {
+                print_success_message("No dead letters to redrive.");
+            } else {
+                print_success_message(&format!(
+                    "Moved {} dead letters back to the outbox, a running indexer sends them within a few seconds or the next one to start will.",
+                    redriven
+                ));
+            }
+
+            Ok(())
+        }
+    }
+}
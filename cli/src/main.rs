@@ -21,8 +21,8 @@ use crate::{
     cli_interface::{AddSubcommands, Commands, NewSubcommands, CLI},
     commands::{
         add::handle_add_contract_command, codegen::handle_codegen_command,
-        delete::handle_delete_command, new::handle_new_command, phantom::handle_phantom_commands,
-        start::start,
+        delete::handle_delete_command, migrate::handle_migrate_command, new::handle_new_command,
+        phantom::handle_phantom_commands, start::start, streams::handle_streams_command,
     },
     console::print_error_message,
 };
@@ -111,6 +111,15 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             load_env_from_project_path(&resolved_path);
             handle_delete_command(resolved_path).await
         }
+        Commands::Migrate { dry_run, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            load_env_from_project_path(&resolved_path);
+            handle_migrate_command(resolved_path, *dry_run).await
+        }
+        Commands::Streams { subcommand, path } => {
+            let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
+            handle_streams_command(resolved_path, subcommand).await
+        }
         Commands::Phantom { subcommand, path } => {
             let resolved_path = resolve_path(path).inspect_err(|e| print_error_message(e))?;
             load_env_from_project_path(&resolved_path);
@@ -1,4 +1,8 @@
-use std::{collections::HashSet, fs, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
 
 use ethers::{
     types::{ValueOrArray, H256},
@@ -13,10 +17,10 @@ use crate::{
     },
     event::contract_setup::IndexingContractSetup,
     helpers::camel_to_snake,
-    manifest::contract::{Contract, ParseAbiError},
+    manifest::contract::{AbiVersion, Contract, NumericColumn, ParseAbiError, TupleStorage},
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ABIInput {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub indexed: Option<bool>,
@@ -30,6 +34,10 @@ pub struct ABIInput {
     pub components: Option<Vec<ABIInput>>,
 }
 
+/// The type a tuple input stored as a single JSONB document is given, the components are kept so
+/// the document can be built from the log
+pub const JSONB_TUPLE_TYPE: &str = "jsonb";
+
 #[derive(thiserror::Error, Debug)]
 pub enum ParamTypeError {
     #[error("tuple type specified but no components found")]
@@ -65,6 +73,25 @@ impl GenerateAbiNamePropertiesResult {
 }
 
 impl ABIInput {
+    pub fn is_tuple(&self) -> bool {
+        self.type_.starts_with("tuple")
+    }
+
+    pub fn is_jsonb(&self) -> bool {
+        self.type_ == JSONB_TUPLE_TYPE
+    }
+
+    /// Single int and uint inputs, arrays keep the default mapping
+    pub fn is_int(&self) -> bool {
+        (self.type_.starts_with("int") || self.type_.starts_with("uint")) &&
+            !self.type_.ends_with(']')
+    }
+
+    /// The column the contract stores an int input in when it overrides the default mapping
+    pub fn numeric_column(&self) -> Option<NumericColumn> {
+        NumericColumn::from_abi_type(&self.type_)
+    }
+
     pub fn format_param_type(&self) -> Result<String, ParamTypeError> {
         match self.type_.as_str() {
             "tuple" => {
@@ -89,7 +116,7 @@ impl ABIInput {
         inputs
             .iter()
             .flat_map(|input| {
-                if let Some(components) = &input.components {
+                if let Some(components) = input.components.as_ref().filter(|_| !input.is_jsonb()) {
                     let new_prefix = match prefix {
                         Some(p) => format!("{}_{}", p, camel_to_snake(&input.name)),
                         None => camel_to_snake(&input.name),
@@ -174,6 +201,9 @@ pub enum ReadAbiError {
 
     #[error("{0}")]
     ParseAbiError(#[from] ParseAbiError),
+
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
 }
 
 impl ABIItem {
@@ -189,6 +219,12 @@ impl ABIItem {
         Ok(format!("{}({})", name, params))
     }
 
+    /// Events can be referenced by name or by the full signature to pick a single overload
+    pub fn is_included_in(&self, event_names: &[String]) -> bool {
+        event_names.contains(&self.name) ||
+            self.format_event_signature().is_ok_and(|signature| event_names.contains(&signature))
+    }
+
     fn format_param_type(input: &ABIInput) -> Result<String, ParamTypeError> {
         let base_type = input.type_.split('[').next().unwrap_or(&input.type_);
         let array_suffix = input.type_.strip_prefix(base_type).unwrap_or("");
@@ -222,6 +258,37 @@ impl ABIItem {
                 events.push(EventInfo::new(item, signature));
             }
         }
+
+        // overloaded events share a name so they are numbered in the order they are in the ABI,
+        // this matches the names ethers abigen gives the generated event structs
+        let mut name_counts: HashMap<String, usize> = HashMap::new();
+        for event in &events {
+            *name_counts.entry(event.abi_name.clone()).or_default() += 1;
+        }
+
+        let mut overloaded_seen: HashMap<String, usize> = HashMap::new();
+        for event in events.iter_mut() {
+            if name_counts[&event.abi_name] > 1 {
+                let index = overloaded_seen.entry(event.abi_name.clone()).or_default();
+                *index += 1;
+                event.rename(format!("{}{}", event.abi_name, index));
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Reads the events with the layout they are stored in, see `EventInfo::apply_storage_types`
+    pub fn read_abi_events(
+        project_path: &Path,
+        contract: &Contract,
+    ) -> Result<Vec<EventInfo>, ReadAbiError> {
+        let abi_items = Self::read_abi_items(project_path, contract)?;
+        let mut events = Self::extract_contract_events(contract, abi_items)?;
+        for event in events.iter_mut() {
+            event.apply_storage_types(contract);
+        }
+
         Ok(events)
     }
 
@@ -232,15 +299,82 @@ impl ABIItem {
         let abi_str = contract.parse_abi(project_path)?;
         let abi_items: Vec<ABIItem> = serde_json::from_str(&abi_str)?;
 
-        let filtered_abi_items = match &contract.include_events {
-            Some(events) => abi_items
+        Ok(abi_items)
+    }
+
+    pub fn read_abi_version_items(
+        project_path: &Path,
+        contract: &Contract,
+        abi_version: &AbiVersion,
+    ) -> Result<Vec<ABIItem>, ReadAbiError> {
+        let abi_str = contract.parse_abi_version(project_path, abi_version)?;
+        let abi_items: Vec<ABIItem> = serde_json::from_str(&abi_str)?;
+
+        Ok(abi_items)
+    }
+
+    /// The events of the ABI the contract indexes, overloads are numbered over the whole ABI
+    /// before `include_events` is applied so an included overload keeps the name abigen gives it
+    pub fn extract_contract_events(
+        contract: &Contract,
+        abi_items: Vec<ABIItem>,
+    ) -> Result<Vec<EventInfo>, ParamTypeError> {
+        let events = Self::extract_event_names_and_signatures_from_abi(abi_items)?;
+
+        Ok(match &contract.include_events {
+            Some(include_events) => events
                 .into_iter()
-                .filter(|item| item.type_ != "event" || events.contains(&item.name))
+                .filter(|event| {
+                    include_events.contains(&event.abi_name) ||
+                        include_events.contains(&event.signature)
+                })
                 .collect(),
-            None => abi_items,
+            None => events,
+        })
+    }
+
+    /// Reads the events for every ABI version of the contract, events which have the same layout
+    /// as an event in the current ABI share its table, any other layout gets its own versioned
+    /// table named after the first ABI version it appears in
+    pub fn read_versioned_events(
+        project_path: &Path,
+        contract: &Contract,
+        current_events: &[EventInfo],
+    ) -> Result<Vec<VersionedEventInfo>, ReadAbiError> {
+        let abi_versions = match &contract.abi_versions {
+            Some(abi_versions) => abi_versions,
+            None => return Ok(vec![]),
         };
 
-        Ok(filtered_abi_items)
+        let mut versioned_events: Vec<VersionedEventInfo> = Vec::new();
+        for (index, abi_version) in abi_versions.iter().enumerate() {
+            let abi_items = Self::read_abi_version_items(project_path, contract, abi_version)?;
+            for mut event_info in Self::extract_contract_events(contract, abi_items)? {
+                event_info.apply_storage_types(contract);
+                let event_name = versioned_event_name(&event_info.name, index);
+
+                let table_event_name = if current_events
+                    .iter()
+                    .any(|current| current.has_same_layout(&event_info))
+                {
+                    event_info.name.clone()
+                } else {
+                    versioned_events
+                        .iter()
+                        .find(|versioned| versioned.event_info.has_same_layout(&event_info))
+                        .map_or(event_name.clone(), |versioned| versioned.table_event_name.clone())
+                };
+
+                versioned_events.push(VersionedEventInfo {
+                    event_info,
+                    event_name,
+                    table_event_name,
+                    abi_version: abi_version.clone(),
+                });
+            }
+        }
+
+        Ok(versioned_events)
     }
 
     pub fn get_abi_items(
@@ -280,13 +414,41 @@ impl ABIItem {
 
 #[derive(Debug, Clone)]
 pub struct EventInfo {
+    /// The unique name of the event, overloaded events are numbered for example `Transfer1`
     pub name: String,
+    /// The name of the event in the ABI which overloaded events share
+    pub abi_name: String,
     pub inputs: Vec<ABIInput>,
     signature: String,
     struct_result: String,
     struct_data: String,
 }
 
+/// The name an event from an ABI version is registered under, versions are numbered from 1 in the
+/// order they are defined
+pub fn versioned_event_name(event_name: &str, abi_version_index: usize) -> String {
+    format!("{}V{}", event_name, abi_version_index + 1)
+}
+
+#[derive(Debug, Clone)]
+pub struct VersionedEventInfo {
+    pub event_info: EventInfo,
+    /// The name the event is registered and synced under, for example `SwapV1`
+    pub event_name: String,
+    /// The event name the table and csv file are named after
+    pub table_event_name: String,
+    pub abi_version: AbiVersion,
+}
+
+impl VersionedEventInfo {
+    /// The event info with the name of the table the logs are stored in
+    pub fn table_event_info(&self) -> EventInfo {
+        let mut event_info = self.event_info.clone();
+        event_info.rename(self.table_event_name.clone());
+        event_info
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum CreateCsvFileForEvent {
     #[error("Could not create the dir {0}")]
@@ -297,7 +459,49 @@ impl EventInfo {
     pub fn new(item: ABIItem, signature: String) -> Self {
         let struct_result = format!("{}Result", item.name);
         let struct_data = format!("{}Data", item.name);
-        EventInfo { name: item.name, inputs: item.inputs, signature, struct_result, struct_data }
+        EventInfo {
+            abi_name: item.name.clone(),
+            name: item.name,
+            inputs: item.inputs,
+            signature,
+            struct_result,
+            struct_data,
+        }
+    }
+
+    fn rename(&mut self, name: String) {
+        self.struct_result = format!("{}Result", name);
+        self.struct_data = format!("{}Data", name);
+        self.name = name;
+    }
+
+    pub fn signature(&self) -> &str {
+        &self.signature
+    }
+
+    /// Applies how the contract stores the inputs, tuple inputs stored as JSONB are collapsed into
+    /// a single input so they get one column instead of a column per component and int inputs
+    /// with a numeric column keep it after their solidity type for example `uint256:text`
+    pub fn apply_storage_types(&mut self, contract: &Contract) {
+        for input in self.inputs.iter_mut() {
+            if input.is_tuple() &&
+                contract.tuple_storage(&self.name, &self.signature, &input.name) ==
+                    TupleStorage::Jsonb
+            {
+                input.type_ = JSONB_TUPLE_TYPE.to_string();
+            } else if input.is_int() {
+                if let Some(numeric_column) =
+                    contract.numeric_column(&self.name, &self.signature, &input.name)
+                {
+                    input.type_ = format!("{}:{}", input.type_, numeric_column.name());
+                }
+            }
+        }
+    }
+
+    /// Events with the same layout decode to the same columns so can share a table
+    pub fn has_same_layout(&self, other: &EventInfo) -> bool {
+        self.name == other.name && self.signature == other.signature && self.inputs == other.inputs
     }
 
     pub fn topic_id(&self) -> H256 {
@@ -345,21 +549,30 @@ impl EventInfo {
         contract: &Contract,
         csv_path: &str,
     ) -> Result<String, CreateCsvFileForEvent> {
-        let csv_file_name = format!("{}-{}.csv", contract.name, self.name).to_lowercase();
-        let csv_folder = project_path.join(csv_path).join(&contract.name);
+        create_csv_file_for_event_name(project_path, contract, csv_path, &self.name)
+    }
+}
 
-        // Create directory if it does not exist.
-        if let Err(e) = fs::create_dir_all(&csv_folder) {
-            return Err(CreateCsvFileForEvent::CreateDirFailed(e));
-        }
+pub fn create_csv_file_for_event_name(
+    project_path: &Path,
+    contract: &Contract,
+    csv_path: &str,
+    event_name: &str,
+) -> Result<String, CreateCsvFileForEvent> {
+    let csv_file_name = format!("{}-{}.csv", contract.name, event_name).to_lowercase();
+    let csv_folder = project_path.join(csv_path).join(&contract.name);
 
-        // Create last-synced-blocks if it does not exist.
-        if let Err(e) = fs::create_dir_all(csv_folder.join("last-synced-blocks")) {
-            return Err(CreateCsvFileForEvent::CreateDirFailed(e));
-        }
+    // Create directory if it does not exist.
+    if let Err(e) = fs::create_dir_all(&csv_folder) {
+        return Err(CreateCsvFileForEvent::CreateDirFailed(e));
+    }
 
-        Ok(csv_folder.join(csv_file_name).display().to_string())
+    // Create last-synced-blocks if it does not exist.
+    if let Err(e) = fs::create_dir_all(csv_folder.join("last-synced-blocks")) {
+        return Err(CreateCsvFileForEvent::CreateDirFailed(e));
     }
+
+    Ok(csv_folder.join(csv_file_name).display().to_string())
 }
 
 pub struct GetAbiItemWithDbMap {
@@ -374,11 +587,11 @@ pub enum GetAbiItemWithDbMapError {
 }
 
 pub fn get_abi_item_with_db_map(
-    abi_items: &[ABIItem],
+    events: &[EventInfo],
     event_name: &str,
     parameter_mapping: &[&str],
 ) -> Result<GetAbiItemWithDbMap, GetAbiItemWithDbMapError> {
-    let event_item = abi_items.iter().find(|item| item.name == event_name && item.type_ == "event");
+    let event_item = events.iter().find(|event| event.name == event_name);
 
     match event_item {
         Some(item) => {
@@ -442,3 +655,194 @@ pub fn get_abi_item_with_db_map(
         ))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transfer_event(extra_input: Option<&str>) -> ABIItem {
+        let mut inputs: Vec<ABIInput> = ["address", "address", "uint256"]
+            .iter()
+            .enumerate()
+            .map(|(i, type_)| ABIInput {
+                indexed: Some(i < 2),
+                name: format!("arg{}", i),
+                type_: type_.to_string(),
+                components: None,
+            })
+            .collect();
+        if let Some(type_) = extra_input {
+            inputs.push(ABIInput {
+                indexed: Some(false),
+                name: "extra".to_string(),
+                type_: type_.to_string(),
+                components: None,
+            });
+        }
+
+        ABIItem { inputs, name: "Transfer".to_string(), type_: "event".to_string() }
+    }
+
+    #[test]
+    fn test_overloaded_events_are_numbered_in_abi_order() {
+        let events = ABIItem::extract_event_names_and_signatures_from_abi(vec![
+            transfer_event(None),
+            transfer_event(Some("bytes")),
+        ])
+        .unwrap();
+
+        assert_eq!(events[0].name, "Transfer1");
+        assert_eq!(events[0].signature(), "Transfer(address,address,uint256)");
+        assert_eq!(events[0].struct_data(), "Transfer1Data");
+        assert_eq!(events[1].name, "Transfer2");
+        assert_eq!(events[1].signature(), "Transfer(address,address,uint256,bytes)");
+        assert!(events.iter().all(|event| event.abi_name == "Transfer"));
+        assert_ne!(events[0].topic_id(), events[1].topic_id());
+    }
+
+    #[test]
+    fn test_tuple_inputs_stored_as_jsonb_get_a_single_column() {
+        let component = |name: &str, type_: &str| ABIInput {
+            indexed: None,
+            name: name.to_string(),
+            type_: type_.to_string(),
+            components: None,
+        };
+        let mut item = transfer_event(None);
+        item.inputs.push(ABIInput {
+            indexed: Some(false),
+            name: "order".to_string(),
+            type_: "tuple".to_string(),
+            components: Some(vec![component("maker", "address"), component("amount", "uint256")]),
+        });
+        let mut event =
+            ABIItem::extract_event_names_and_signatures_from_abi(vec![item]).unwrap().remove(0);
+
+        let columns = |event: &EventInfo| {
+            ABIInput::generate_abi_name_properties(
+                &event.inputs,
+                &GenerateAbiPropertiesType::PostgresWithDataTypes,
+                None,
+            )
+            .into_iter()
+            .map(|property| property.value)
+            .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            columns(&event)[3..],
+            ["\"order_maker\" CHAR(42)", "\"order_amount\" VARCHAR(78)"]
+        );
+
+        let contract: Contract = serde_yaml::from_str(
+            "name: Exchange\ndetails: []\nabi: ./abis/Exchange.abi.json\ntuple_storage: jsonb",
+        )
+        .unwrap();
+        event.apply_storage_types(&contract);
+
+        assert_eq!(columns(&event)[3..], ["\"order\" JSONB"]);
+        assert_eq!(event.signature(), "Transfer(address,address,uint256,(address,uint256))");
+    }
+
+    #[test]
+    fn test_events_can_be_included_by_signature() {
+        let transfer = transfer_event(Some("bytes"));
+
+        assert!(transfer.is_included_in(&["Transfer".to_string()]));
+        assert!(transfer.is_included_in(&["Transfer(address,address,uint256,bytes)".to_string()]));
+        assert!(!transfer.is_included_in(&["Transfer(address,address,uint256)".to_string()]));
+    }
+
+    #[test]
+    fn test_an_included_overload_keeps_its_number_from_the_whole_abi() {
+        let contract = |include_events: &str| -> Contract {
+            serde_yaml::from_str(&format!(
+                "name: Token\ndetails: []\nabi: ./abis/Token.abi.json\ninclude_events: {}",
+                include_events
+            ))
+            .unwrap()
+        };
+        let abi_items = || vec![transfer_event(None), transfer_event(Some("bytes"))];
+
+        let events = ABIItem::extract_contract_events(
+            &contract("['Transfer(address,address,uint256,bytes)']"),
+            abi_items(),
+        )
+        .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].name, "Transfer2");
+        assert_eq!(events[0].struct_data(), "Transfer2Data");
+        assert_eq!(events[0].signature(), "Transfer(address,address,uint256,bytes)");
+
+        let events =
+            ABIItem::extract_contract_events(&contract("[Transfer]"), abi_items()).unwrap();
+        let names: Vec<&str> = events.iter().map(|event| event.name.as_str()).collect();
+        assert_eq!(names, ["Transfer1", "Transfer2"]);
+    }
+
+    #[test]
+    fn test_versioned_events_share_a_table_with_an_event_of_the_same_layout() {
+        let event = |name: &str, types: &[&str]| ABIItem {
+            inputs: types
+                .iter()
+                .enumerate()
+                .map(|(i, type_)| ABIInput {
+                    indexed: Some(false),
+                    name: format!("arg{}", i),
+                    type_: type_.to_string(),
+                    components: None,
+                })
+                .collect(),
+            name: name.to_string(),
+            type_: "event".to_string(),
+        };
+        let project = tempfile::tempdir().unwrap();
+        let write_abi = |file: &str, items: Vec<ABIItem>| {
+            std::fs::write(project.path().join(file), serde_json::to_string(&items).unwrap())
+                .unwrap();
+        };
+        write_abi("Pool.abi.json", vec![event("Swap", &["address", "uint256"])]);
+        write_abi(
+            "PoolV1.abi.json",
+            vec![event("Swap", &["address", "uint128"]), event("Sync", &["uint112"])],
+        );
+        write_abi("PoolV2.abi.json", vec![event("Swap", &["address", "uint128"])]);
+        write_abi("PoolV3.abi.json", vec![event("Swap", &["address", "uint256"])]);
+
+        let contract: Contract = serde_yaml::from_str(
+            "name: Pool\ndetails: []\nabi: Pool.abi.json\nabi_versions:\n  \
+             - abi: PoolV1.abi.json\n    end_block: 99\n  \
+             - abi: PoolV2.abi.json\n    start_block: 100\n    end_block: 199\n  \
+             - abi: PoolV3.abi.json\n    start_block: 200\n    end_block: 299",
+        )
+        .unwrap();
+        let current_events = ABIItem::extract_contract_events(
+            &contract,
+            ABIItem::read_abi_items(project.path(), &contract).unwrap(),
+        )
+        .unwrap();
+
+        let versioned_events =
+            ABIItem::read_versioned_events(project.path(), &contract, &current_events).unwrap();
+        let names: Vec<(&str, &str, Option<u64>)> = versioned_events
+            .iter()
+            .map(|versioned| {
+                (
+                    versioned.event_name.as_str(),
+                    versioned.table_event_name.as_str(),
+                    versioned.abi_version.end_block.map(|block| block.as_u64()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("SwapV1", "SwapV1", Some(99)),
+                ("SyncV1", "SyncV1", Some(99)),
+                ("SwapV2", "SwapV1", Some(199)),
+                ("SwapV3", "Swap", Some(299)),
+            ]
+        );
+        assert_eq!(versioned_events[2].table_event_info().name, "SwapV1");
+        assert_eq!(contract.current_abi_start_block(), Some(ethers::types::U64::from(300)));
+    }
+}
@@ -18,7 +18,7 @@ use crate::{
         telegram::{TelegramBot, TelegramError},
         template::Template,
     },
-    event::{filter_event_data_by_conditions, EventMessage},
+    event::EventMessage,
     manifest::chat::{
         ChatConfig, DiscordConfig, DiscordEvent, SlackConfig, SlackEvent, TelegramConfig,
         TelegramEvent,
@@ -131,7 +131,7 @@ impl ChatClients {
             .iter()
             .filter(|event_data| {
                 if let Some(conditions) = &event_for.conditions {
-                    filter_event_data_by_conditions(event_data, conditions)
+                    conditions.matches(event_data)
                 } else {
                     true
                 }
@@ -160,7 +160,7 @@ impl ChatClients {
             .iter()
             .filter(|event_data| {
                 if let Some(conditions) = &event_for.conditions {
-                    filter_event_data_by_conditions(event_data, conditions)
+                    conditions.matches(event_data)
                 } else {
                     true
                 }
@@ -189,7 +189,7 @@ impl ChatClients {
             .iter()
             .filter(|event_data| {
                 if let Some(conditions) = &event_for.conditions {
-                    filter_event_data_by_conditions(event_data, conditions)
+                    conditions.matches(event_data)
                 } else {
                     true
                 }
@@ -230,7 +230,7 @@ impl ChatClients {
                             .config
                             .messages
                             .iter()
-                            .find(|e| e.event_name == event_message.event_name);
+                            .find(|e| event_message.matches_event_name(&e.event_name));
 
                         if let Some(telegram_event) = telegram_event {
                             let message = self.telegram_send_message_tasks(
@@ -251,7 +251,7 @@ impl ChatClients {
                             .config
                             .messages
                             .iter()
-                            .find(|e| e.event_name == event_message.event_name);
+                            .find(|e| event_message.matches_event_name(&e.event_name));
 
                         if let Some(discord_event) = discord_event {
                             let message = self.discord_send_message_tasks(
@@ -272,7 +272,7 @@ impl ChatClients {
                             .config
                             .messages
                             .iter()
-                            .find(|e| e.event_name == event_message.event_name);
+                            .find(|e| event_message.matches_event_name(&e.event_name));
 
                         if let Some(slack_event) = slack_event {
                             let message =
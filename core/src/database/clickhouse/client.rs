@@ -0,0 +1,180 @@
+use std::env;
+
+use dotenv::dotenv;
+use reqwest::Client;
+use serde_json::{json, Map, Value};
+use tracing::debug;
+
+use crate::database::postgres::sql_type_wrapper::{
+    ethereum_wrapper_to_json, EthereumSqlTypeWrapper,
+};
+
+/// Rows are sent to ClickHouse in chunks so a large backfill never builds a single huge request
+const INSERT_BATCH_SIZE: usize = 50_000;
+
+pub struct ClickhouseConnection {
+    pub url: String,
+    pub user: String,
+    pub password: String,
+}
+
+pub fn connection_details() -> Result<ClickhouseConnection, env::VarError> {
+    dotenv().ok();
+    let url = env::var("CLICKHOUSE_URL")?;
+    let user = env::var("CLICKHOUSE_USER").unwrap_or_else(|_| "default".to_string());
+    let password = env::var("CLICKHOUSE_PASSWORD").unwrap_or_default();
+    Ok(ClickhouseConnection { url, user, password })
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClickhouseConnectionError {
+    #[error("The clickhouse connection details are wrong please check your environment: {0}")]
+    ClickhouseConnectionConfigWrong(#[from] env::VarError),
+
+    #[error("Could not build the clickhouse http client: {0}")]
+    CouldNotBuildClient(#[from] reqwest::Error),
+
+    #[error("Can not connect to clickhouse please make sure your CLICKHOUSE_URL is correct: {0}")]
+    CanNotConnectToClickhouse(ClickhouseError),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ClickhouseError {
+    #[error("HTTP request error: {0}")]
+    ReqwestError(#[from] reqwest::Error),
+
+    #[error("Could not parse response: {0}")]
+    CouldNotParseResponse(#[from] serde_json::Error),
+
+    #[error("ClickHouse error: {0}")]
+    QueryError(String),
+}
+
+/// Talks to ClickHouse over its HTTP interface
+pub struct ClickhouseClient {
+    client: Client,
+    connection: ClickhouseConnection,
+}
+
+impl ClickhouseClient {
+    pub async fn new() -> Result<Self, ClickhouseConnectionError> {
+        let connection = connection_details()?;
+        let client = Client::builder().build()?;
+        let clickhouse = Self { client, connection };
+
+        clickhouse
+            .execute("SELECT 1")
+            .await
+            .map_err(ClickhouseConnectionError::CanNotConnectToClickhouse)?;
+
+        Ok(clickhouse)
+    }
+
+    async fn request(&self, query: &str, body: Option<String>) -> Result<String, ClickhouseError> {
+        let request = self
+            .client
+            .post(&self.connection.url)
+            .header("X-ClickHouse-User", &self.connection.user)
+            .header("X-ClickHouse-Key", &self.connection.password);
+
+        // inserts send the statement as a parameter so the rows can be the body
+        let request = match body {
+            Some(body) => request.query(&[("query", query)]).body(body),
+            None => request.body(query.to_string()),
+        };
+
+        let response = request.send().await?;
+        let status = response.status();
+        let response_text = response.text().await?;
+        if !status.is_success() {
+            return Err(ClickhouseError::QueryError(response_text.trim().to_string()));
+        }
+
+        Ok(response_text)
+    }
+
+    pub async fn execute(&self, query: &str) -> Result<(), ClickhouseError> {
+        self.request(query, None).await?;
+        Ok(())
+    }
+
+    /// The HTTP interface only runs a single statement per request
+    pub async fn execute_all(&self, statements: &[String]) -> Result<(), ClickhouseError> {
+        for statement in statements {
+            debug!("{}", statement);
+            self.execute(statement).await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn query(&self, query: &str) -> Result<Vec<Map<String, Value>>, ClickhouseError> {
+        let response_text = self.request(&format!("{} FORMAT JSONEachRow", query), None).await?;
+
+        let mut rows = vec![];
+        for line in response_text.lines().filter(|line| !line.is_empty()) {
+            rows.push(serde_json::from_str(line)?);
+        }
+
+        Ok(rows)
+    }
+
+    pub async fn insert_bulk(
+        &self,
+        table_name: &str,
+        column_names: &[String],
+        bulk_data: &[Vec<EthereumSqlTypeWrapper>],
+    ) -> Result<(), ClickhouseError> {
+        let query = format!(
+            "INSERT INTO {} ({}) FORMAT JSONEachRow",
+            table_name,
+            column_names.iter().map(|name| format!("`{}`", name)).collect::<Vec<_>>().join(", ")
+        );
+
+        for chunk in bulk_data.chunks(INSERT_BATCH_SIZE) {
+            let body = chunk
+                .iter()
+                .map(|row| {
+                    let row: Map<String, Value> = column_names
+                        .iter()
+                        .zip(row)
+                        .map(|(name, wrapper)| {
+                            (name.clone(), ethereum_wrapper_to_clickhouse_json(wrapper))
+                        })
+                        .collect();
+                    Value::Object(row).to_string()
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            self.request(&query, Some(body)).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The event JSON is reused apart from the values ClickHouse can not parse as is
+fn ethereum_wrapper_to_clickhouse_json(wrapper: &EthereumSqlTypeWrapper) -> Value {
+    match wrapper {
+        // ethers serializes these as hex strings
+        EthereumSqlTypeWrapper::U64(u) => json!(u.as_u64()),
+        EthereumSqlTypeWrapper::VecU64(u64s) => {
+            json!(u64s.iter().map(|u| u.as_u64()).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::U256Bytes(u) | EthereumSqlTypeWrapper::U256BytesNullable(u) => {
+            let mut bytes = [0u8; 32];
+            u.to_big_endian(&mut bytes);
+            json!(format!("0x{}", hex::encode(bytes)))
+        }
+        EthereumSqlTypeWrapper::I256Bytes(i) | EthereumSqlTypeWrapper::I256BytesNullable(i) => {
+            let mut bytes = [0u8; 32];
+            i.into_raw().to_big_endian(&mut bytes);
+            json!(format!("0x{}", hex::encode(bytes)))
+        }
+        EthereumSqlTypeWrapper::DateTime(date_time) => json!(date_time.timestamp()),
+        EthereumSqlTypeWrapper::JSONB(json) => json!(json.to_string()),
+        EthereumSqlTypeWrapper::NumericNullable(s) if s.is_empty() => Value::Null,
+        _ => ethereum_wrapper_to_json(wrapper),
+    }
+}
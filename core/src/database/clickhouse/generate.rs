@@ -0,0 +1,218 @@
+use std::path::Path;
+
+use tracing::info;
+
+use crate::{
+    abi::{ABIInput, ABIItem, EventInfo, GenerateAbiPropertiesType, JSONB_TUPLE_TYPE},
+    database::postgres::generate::{
+        generate_indexer_contract_schema_name, GenerateTablesForIndexerSqlError,
+    },
+    helpers::camel_to_snake,
+    indexer::Indexer,
+    manifest::contract::{ColumnType, Contract, NumericColumn},
+};
+
+fn generate_columns_with_data_types(inputs: &[ABIInput]) -> Vec<String> {
+    ABIInput::generate_abi_name_properties(
+        inputs,
+        &GenerateAbiPropertiesType::PostgresColumnsNamesOnly,
+        None,
+    )
+    .into_iter()
+    .map(|m| format!("`{}` {}", m.value, solidity_type_to_clickhouse_type(&m.abi_type)))
+    .collect()
+}
+
+fn computed_column_clickhouse_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        ColumnType::Numeric => "Nullable(Decimal256(18))",
+        ColumnType::Text => "Nullable(String)",
+        ColumnType::Bool => "Nullable(Bool)",
+    }
+}
+
+/// Events are keyed on the log identity so a block range indexed twice collapses into a single
+/// row when ClickHouse merges the parts
+fn generate_event_table_sql(
+    event_info: &EventInfo,
+    contract: &Contract,
+    database_name: &str,
+) -> Vec<String> {
+    let table_name = format!("{}.{}", database_name, camel_to_snake(&event_info.name));
+    info!("Creating clickhouse table if not exists: {}", table_name);
+
+    let event_columns = if event_info.inputs.is_empty() {
+        "".to_string()
+    } else {
+        generate_columns_with_data_types(&event_info.inputs).join(", ") + ","
+    };
+
+    let mut statements = vec![format!(
+        "CREATE TABLE IF NOT EXISTS {} (\
+            `contract_address` FixedString(42), \
+            {} \
+            `tx_hash` FixedString(66), \
+            `block_number` UInt64, \
+            `block_hash` FixedString(66), \
+            `network` LowCardinality(String), \
+            `tx_index` UInt64, \
+            `log_index` UInt256, \
+            `input` String, \
+            `block_timestamp` Nullable(DateTime('UTC'))\
+        ) ENGINE = ReplacingMergeTree \
+        ORDER BY (network, block_number, tx_hash, log_index)",
+        table_name, event_columns
+    )];
+
+    // computed columns can be added to the yaml after the table exists
+    statements.extend(
+        contract.computed_columns(&event_info.name, event_info.signature()).iter().map(|column| {
+            format!(
+                "ALTER TABLE {} ADD COLUMN IF NOT EXISTS `{}` {}",
+                table_name,
+                camel_to_snake(&column.name),
+                computed_column_clickhouse_type(column.type_)
+            )
+        }),
+    );
+
+    statements
+}
+
+/// The highest block wins when the parts merge and reads take the max so a checkpoint only
+/// ever moves forward
+fn generate_internal_event_table_sql(event_name: &str, database_name: &str) -> String {
+    format!(
+        "CREATE TABLE IF NOT EXISTS rindexer_internal.{}_{} (\
+            `network` String, \
+            `last_synced_block` UInt64\
+        ) ENGINE = ReplacingMergeTree(last_synced_block) \
+        ORDER BY network",
+        database_name,
+        camel_to_snake(event_name)
+    )
+}
+
+pub fn generate_tables_for_indexer_clickhouse(
+    project_path: &Path,
+    indexer: &Indexer,
+    disable_event_tables: bool,
+) -> Result<Vec<String>, GenerateTablesForIndexerSqlError> {
+    let mut statements = vec!["CREATE DATABASE IF NOT EXISTS rindexer_internal".to_string()];
+
+    for contract in &indexer.contracts {
+        let contract_name = contract.before_modify_name_if_filter_readonly();
+        let event_names = ABIItem::read_abi_events(project_path, contract)?;
+        let versioned_events =
+            ABIItem::read_versioned_events(project_path, contract, &event_names)?;
+        let database_name = generate_indexer_contract_schema_name(&indexer.name, &contract_name);
+
+        if !disable_event_tables {
+            statements.push(format!("CREATE DATABASE IF NOT EXISTS {}", database_name));
+
+            // only versions with a different layout to the current ABI need their own table
+            let versioned_table_events = versioned_events
+                .iter()
+                .filter(|versioned_event| {
+                    versioned_event.event_name == versioned_event.table_event_name
+                })
+                .map(|versioned_event| versioned_event.table_event_info());
+            for event_info in event_names.iter().cloned().chain(versioned_table_events) {
+                statements.extend(generate_event_table_sql(&event_info, contract, &database_name));
+            }
+        }
+
+        let internal_event_names = event_names
+            .iter()
+            .map(|event_info| event_info.name.as_str())
+            .chain(versioned_events.iter().map(|versioned| versioned.event_name.as_str()));
+        for event_name in internal_event_names {
+            statements.push(generate_internal_event_table_sql(event_name, &database_name));
+        }
+    }
+
+    Ok(statements)
+}
+
+pub fn drop_tables_for_indexer_clickhouse(indexer: &Indexer) -> Vec<String> {
+    let mut statements = vec![];
+
+    for contract in &indexer.contracts {
+        let contract_name = contract.before_modify_name_if_filter_readonly();
+        let database_name = generate_indexer_contract_schema_name(&indexer.name, &contract_name);
+        statements.push(format!("DROP DATABASE IF EXISTS {}", database_name));
+    }
+
+    statements.push("DROP DATABASE IF EXISTS rindexer_internal".to_string());
+
+    statements
+}
+
+#[allow(clippy::manual_strip)]
+pub fn solidity_type_to_clickhouse_type(abi_type: &str) -> String {
+    if let Some(numeric_column) = NumericColumn::from_abi_type(abi_type) {
+        return match numeric_column {
+            NumericColumn::Numeric => {
+                solidity_type_to_clickhouse_type(abi_type.split(':').next().unwrap_or(abi_type))
+            }
+            NumericColumn::Text | NumericColumn::Bytea => "String".to_string(),
+            NumericColumn::Decimal(decimals) => format!("Decimal256({})", decimals),
+        };
+    }
+
+    let is_array = abi_type.ends_with("[]");
+    let base_type = abi_type.trim_end_matches("[]");
+
+    let clickhouse_type = match base_type {
+        "address" => "FixedString(42)".to_string(),
+        "bool" => "Bool".to_string(),
+        "string" | JSONB_TUPLE_TYPE => "String".to_string(),
+        t if t.starts_with("bytes") => "String".to_string(),
+        t if t.starts_with("int") || t.starts_with("uint") => {
+            // ClickHouse only has 8, 16, 32, 64, 128 and 256 bit integers so round up
+            let (prefix, size): (&str, usize) = if t.starts_with("int") {
+                ("Int", t[3..].parse().expect("Invalid intN type"))
+            } else {
+                ("UInt", t[4..].parse().expect("Invalid uintN type"))
+            };
+
+            let size = match size {
+                8 => 8,
+                16 => 16,
+                24 | 32 => 32,
+                40 | 48 | 56 | 64 => 64,
+                72 | 80 | 88 | 96 | 104 | 112 | 120 | 128 => 128,
+                136 | 144 | 152 | 160 | 168 | 176 | 184 | 192 | 200 | 208 | 216 | 224 | 232 |
+                240 | 248 | 256 => 256,
+                _ => panic!("Unsupported {}N size: {}", prefix.to_lowercase(), size),
+            };
+            format!("{}{}", prefix, size)
+        }
+        _ => panic!("Unsupported type: {}", base_type),
+    };
+
+    if is_array {
+        format!("Array({})", clickhouse_type)
+    } else {
+        clickhouse_type
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solidity_type_to_clickhouse_type() {
+        assert_eq!(solidity_type_to_clickhouse_type("address"), "FixedString(42)");
+        assert_eq!(solidity_type_to_clickhouse_type("address[]"), "Array(FixedString(42))");
+        assert_eq!(solidity_type_to_clickhouse_type("bytes32"), "String");
+        assert_eq!(solidity_type_to_clickhouse_type("uint24"), "UInt32");
+        assert_eq!(solidity_type_to_clickhouse_type("int128"), "Int128");
+        assert_eq!(solidity_type_to_clickhouse_type("uint256[]"), "Array(UInt256)");
+        assert_eq!(solidity_type_to_clickhouse_type(JSONB_TUPLE_TYPE), "String");
+        assert_eq!(solidity_type_to_clickhouse_type("uint256:numeric"), "UInt256");
+        assert_eq!(solidity_type_to_clickhouse_type("int64:bytea"), "String");
+        assert_eq!(solidity_type_to_clickhouse_type("uint256:decimal6"), "Decimal256(6)");
+    }
+}
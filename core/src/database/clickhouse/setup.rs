@@ -0,0 +1,59 @@
+use std::path::Path;
+
+use tracing::info;
+
+use crate::{
+    database::{
+        clickhouse::{
+            client::{ClickhouseClient, ClickhouseConnectionError, ClickhouseError},
+            generate::{
+                drop_tables_for_indexer_clickhouse, generate_tables_for_indexer_clickhouse,
+            },
+        },
+        postgres::generate::GenerateTablesForIndexerSqlError,
+    },
+    manifest::core::Manifest,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SetupClickhouseError {
+    #[error("{0}")]
+    ClickhouseConnection(#[from] ClickhouseConnectionError),
+
+    #[error("{0}")]
+    ClickhouseError(#[from] ClickhouseError),
+
+    #[error("Error creating clickhouse tables for indexer: {0}")]
+    GeneratingTables(#[from] GenerateTablesForIndexerSqlError),
+}
+
+pub async fn setup_clickhouse(
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<ClickhouseClient, SetupClickhouseError> {
+    info!("Setting up clickhouse");
+    let client = ClickhouseClient::new().await?;
+    let indexer = manifest.to_indexer();
+
+    if manifest.storage.clickhouse_drop_each_run() {
+        info!(
+            "`drop_each_run` enabled so dropping all clickhouse data for {} before starting",
+            &manifest.name
+        );
+        client.execute_all(&drop_tables_for_indexer_clickhouse(&indexer)).await?;
+        info!("Dropped all clickhouse data for {}", manifest.name);
+    }
+
+    let disable_event_tables = manifest.storage.clickhouse_disable_create_tables();
+    if !disable_event_tables {
+        info!("Creating clickhouse tables for {}", manifest.name);
+    } else {
+        info!("Creating internal rindexer clickhouse tables for {}", manifest.name);
+    }
+    let statements =
+        generate_tables_for_indexer_clickhouse(project_path, &indexer, disable_event_tables)?;
+    client.execute_all(&statements).await?;
+    info!("Created clickhouse tables for {}", manifest.name);
+
+    Ok(client)
+}
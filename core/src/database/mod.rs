@@ -1 +1,3 @@
+pub mod clickhouse;
 pub mod postgres;
+pub mod sqlite;
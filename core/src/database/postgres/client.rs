@@ -1,4 +1,4 @@
-use std::{env, future::Future, time::Duration};
+use std::{collections::HashMap, env, future::Future, time::Duration};
 
 use bb8::{Pool, RunError};
 use bb8_postgres::PostgresConnectionManager;
@@ -16,7 +16,8 @@ use tokio_postgres::{
 use tracing::{debug, error};
 
 use crate::database::postgres::{
-    generate::generate_event_table_columns_names_sql, sql_type_wrapper::EthereumSqlTypeWrapper,
+    generate::generate_event_table_columns_names_sql, partitions::create_missing_partitions,
+    sql_type_wrapper::EthereumSqlTypeWrapper,
 };
 
 pub fn connection_string() -> Result<String, env::VarError> {
@@ -282,11 +283,9 @@ impl PostgresClient {
         column_types: &[PgType],
         data: &[Vec<EthereumSqlTypeWrapper>],
     ) -> Result<(), BulkInsertPostgresError> {
-        let stmt = format!(
-            "COPY {} ({}) FROM STDIN WITH (FORMAT binary)",
-            table_name,
-            generate_event_table_columns_names_sql(column_names),
-        );
+        create_missing_partitions(self, table_name, column_names, data).await?;
+
+        let stmt = bulk_copy_statement(table_name, column_names);
 
         debug!("Bulk insert statement: {}", stmt);
 
@@ -311,14 +310,12 @@ impl PostgresClient {
         Ok(())
     }
 
-    pub async fn bulk_insert<'a>(
+    pub async fn bulk_insert(
         &self,
         table_name: &str,
         column_names: &[String],
-        bulk_data: &'a [Vec<EthereumSqlTypeWrapper>],
+        bulk_data: &[Vec<EthereumSqlTypeWrapper>],
     ) -> Result<u64, PostgresError> {
-        let total_columns = column_names.len();
-
         // Good for debugging
         // for (i, row) in bulk_data.iter().enumerate() {
         //     for (j, param) in row.iter().enumerate() {
@@ -333,27 +330,9 @@ impl PostgresClient {
         //     }
         // }
 
-        let mut query = format!(
-            "INSERT INTO {} ({}) VALUES ",
-            table_name,
-            generate_event_table_columns_names_sql(column_names),
-        );
-        let mut params: Vec<&'a (dyn ToSql + Sync + 'a)> = Vec::new();
-
-        for (i, row) in bulk_data.iter().enumerate() {
-            if i > 0 {
-                query.push(',');
-            }
-            let mut placeholders = vec![];
-            for j in 0..total_columns {
-                placeholders.push(format!("${}", i * total_columns + j + 1));
-            }
-            query.push_str(&format!("({})", placeholders.join(",")));
+        create_missing_partitions(self, table_name, column_names, bulk_data).await?;
 
-            for param in row {
-                params.push(param as &'a (dyn ToSql + Sync + 'a));
-            }
-        }
+        let (query, params) = bulk_insert_statement(table_name, column_names, bulk_data);
 
         // Good for debugging
         // info!("query: {:?}", query);
@@ -392,4 +371,105 @@ impl PostgresClient {
                 .map_err(|e| e.to_string())
         }
     }
+
+    /// Inserts the rows the same way as `insert_bulk` and then runs the statements in the same
+    /// transaction, so anything maintained from the rows such as derived tables can never drift
+    /// from them
+    pub async fn insert_bulk_with_statements(
+        &self,
+        table_name: &str,
+        columns: &[String],
+        postgres_bulk_data: &[Vec<EthereumSqlTypeWrapper>],
+        statements: &[(&str, Vec<EthereumSqlTypeWrapper>)],
+    ) -> Result<(), BulkInsertPostgresError> {
+        if postgres_bulk_data.is_empty() && statements.is_empty() {
+            return Ok(());
+        }
+
+        create_missing_partitions(self, table_name, columns, postgres_bulk_data).await?;
+
+        let mut conn = self.pool.get().await.map_err(PostgresError::ConnectionPoolError)?;
+        let transaction = conn.transaction().await?;
+
+        if postgres_bulk_data.len() > 100 {
+            let column_types: Vec<PgType> =
+                postgres_bulk_data[0].iter().map(|param| param.to_type()).collect();
+
+            let sink = transaction.copy_in(&bulk_copy_statement(table_name, columns)).await?;
+            let writer = BinaryCopyInWriter::new(sink, &column_types);
+            pin_mut!(writer);
+
+            for row in postgres_bulk_data {
+                let row: Vec<&(dyn ToSql + Sync)> =
+                    row.iter().map(|param| param as &(dyn ToSql + Sync)).collect();
+                writer.as_mut().write(&row).await?;
+            }
+
+            writer.finish().await?;
+        } else if !postgres_bulk_data.is_empty() {
+            let (query, params) = bulk_insert_statement(table_name, columns, postgres_bulk_data);
+            transaction.execute(&query, &params).await?;
+        }
+
+        // the same statement is normally run for many events so only prepare it once
+        let mut prepared: HashMap<&str, Statement> = HashMap::new();
+        for (query, params) in statements {
+            let statement = match prepared.get(query) {
+                Some(statement) => statement.clone(),
+                None => {
+                    let statement = transaction.prepare(query).await?;
+                    prepared.insert(query, statement.clone());
+                    statement
+                }
+            };
+
+            let params: Vec<&(dyn ToSql + Sync)> =
+                params.iter().map(|param| param as &(dyn ToSql + Sync)).collect();
+            transaction.execute(&statement, &params).await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
+
+fn bulk_copy_statement(table_name: &str, column_names: &[String]) -> String {
+    format!(
+        "COPY {} ({}) FROM STDIN WITH (FORMAT binary)",
+        table_name,
+        generate_event_table_columns_names_sql(column_names),
+    )
+}
+
+fn bulk_insert_statement<'a>(
+    table_name: &str,
+    column_names: &[String],
+    bulk_data: &'a [Vec<EthereumSqlTypeWrapper>],
+) -> (String, Vec<&'a (dyn ToSql + Sync + 'a)>) {
+    let total_columns = column_names.len();
+
+    let mut query = format!(
+        "INSERT INTO {} ({}) VALUES ",
+        table_name,
+        generate_event_table_columns_names_sql(column_names),
+    );
+    let mut params: Vec<&'a (dyn ToSql + Sync + 'a)> = Vec::new();
+
+    for (i, row) in bulk_data.iter().enumerate() {
+        if i > 0 {
+            query.push(',');
+        }
+        let mut placeholders = vec![];
+        for j in 0..total_columns {
+            placeholders.push(format!("${}", i * total_columns + j + 1));
+        }
+        query.push_str(&format!("({})", placeholders.join(",")));
+
+        for param in row {
+            params.push(param as &'a (dyn ToSql + Sync + 'a));
+        }
+    }
+
+    (query, params)
 }
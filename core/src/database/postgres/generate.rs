@@ -3,10 +3,14 @@ use std::path::Path;
 use tracing::{error, info};
 
 use crate::{
-    abi::{ABIInput, ABIItem, EventInfo, GenerateAbiPropertiesType, ParamTypeError, ReadAbiError},
+    abi::{
+        versioned_event_name, ABIInput, ABIItem, EventInfo, GenerateAbiPropertiesType,
+        ParamTypeError, ReadAbiError, JSONB_TUPLE_TYPE,
+    },
+    database::postgres::partitions::PartitionedTables,
     helpers::camel_to_snake,
-    indexer::Indexer,
-    manifest::contract::Contract,
+    indexer::{Indexer, RAW_LOGS_EVENT_NAME},
+    manifest::contract::{Contract, NumericColumn},
     types::code::Code,
 };
 
@@ -42,9 +46,10 @@ pub fn generate_column_names_only_with_base_properties(inputs: &[ABIInput]) -> V
 
 fn generate_event_table_sql_with_comments(
     abi_inputs: &[EventInfo],
-    contract_name: &str,
+    contract: &Contract,
     schema_name: &str,
     apply_full_name_comment_for_events: Vec<String>,
+    partitioned_tables: &PartitionedTables,
 ) -> String {
     abi_inputs
         .iter()
@@ -57,9 +62,30 @@ fn generate_event_table_sql_with_comments(
                 generate_columns_with_data_types(&event_info.inputs).join(", ") + ","
             };
 
+            // the partition column has to be part of the primary key of a partitioned table
+            let partitioning = partitioned_tables.get(&table_name);
+            let (rindexer_id_column, partition_columns, partition_clause) = match partitioning {
+                Some(partitioning) => {
+                    let block_timestamp_column = match partitioning.column() {
+                        "block_timestamp" => ", block_timestamp TIMESTAMPTZ NOT NULL",
+                        _ => "",
+                    };
+                    (
+                        "rindexer_id SERIAL NOT NULL",
+                        format!(
+                            "{}, PRIMARY KEY (rindexer_id, {})",
+                            block_timestamp_column,
+                            partitioning.column()
+                        ),
+                        partitioning.create_table_clause(),
+                    )
+                }
+                None => ("rindexer_id SERIAL PRIMARY KEY NOT NULL", "".to_string(), "".to_string()),
+            };
+
             let create_table_sql = format!(
                 "CREATE TABLE IF NOT EXISTS {} (\
-                rindexer_id SERIAL PRIMARY KEY NOT NULL, \
+                {}, \
                 contract_address CHAR(66) NOT NULL, \
                 {} \
                 tx_hash CHAR(66) NOT NULL, \
@@ -69,9 +95,38 @@ fn generate_event_table_sql_with_comments(
                 tx_index NUMERIC NOT NULL, \
                 log_index VARCHAR(78) NOT NULL, \
                 input TEXT\
-            );",
-                table_name, event_columns
+                {}\
+            ){};",
+                table_name, rindexer_id_column, event_columns, partition_columns, partition_clause
             );
+            let create_table_sql = match partitioning
+                .and_then(|partitioning| partitioning.after_create_table_sql(&table_name))
+            {
+                Some(after_create_table_sql) => {
+                    format!("{}\n{}", create_table_sql, after_create_table_sql)
+                }
+                None => create_table_sql,
+            };
+
+            // computed columns can be added to the yaml after the table exists
+            let computed_columns_sql = contract
+                .computed_columns(&event_info.name, event_info.signature())
+                .iter()
+                .map(|column| {
+                    format!(
+                        "ALTER TABLE {} ADD COLUMN IF NOT EXISTS \"{}\" {};",
+                        table_name,
+                        camel_to_snake(&column.name),
+                        column.type_.postgres_type()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let create_table_sql = if computed_columns_sql.is_empty() {
+                create_table_sql
+            } else {
+                format!("{}\n{}", create_table_sql, computed_columns_sql)
+            };
 
             if !apply_full_name_comment_for_events.contains(&event_info.name) {
                 return create_table_sql;
@@ -80,7 +135,7 @@ fn generate_event_table_sql_with_comments(
             // smart comments needed to avoid clashing of order by graphql names
             let table_comment = format!(
                 "COMMENT ON TABLE {} IS E'@name {}{}';",
-                table_name, contract_name, event_info.name
+                table_name, contract.name, event_info.name
             );
 
             format!("{}\n{}", create_table_sql, table_comment)
@@ -89,16 +144,193 @@ fn generate_event_table_sql_with_comments(
         .join("\n")
 }
 
+fn generate_derived_tables_sql(
+    contract: &Contract,
+    schema_name: &str,
+    apply_full_name_comment_for_tables: &[String],
+) -> String {
+    contract
+        .tables
+        .iter()
+        .flatten()
+        .map(|table| {
+            let table_name = generate_table_full_name(schema_name, &table.name);
+            info!("Creating table if not exists: {}", table_name);
+            let key_columns = table
+                .keys
+                .iter()
+                .map(|key| {
+                    format!(
+                        "\"{}\" {} NOT NULL",
+                        camel_to_snake(&key.name),
+                        key.type_.postgres_type()
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let key_names = table
+                .keys
+                .iter()
+                .map(|key| format!("\"{}\"", camel_to_snake(&key.name)))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            let mut sql = format!(
+                "CREATE TABLE IF NOT EXISTS {} (\
+                network VARCHAR(50) NOT NULL, \
+                {}, \
+                last_updated_block NUMERIC NOT NULL, \
+                PRIMARY KEY (network, {})\
+            );",
+                table_name, key_columns, key_names
+            );
+
+            // columns can be added to the yaml after the table exists
+            for column in &table.columns {
+                sql.push_str(&format!(
+                    "\nALTER TABLE {} ADD COLUMN IF NOT EXISTS \"{}\" {};",
+                    table_name,
+                    camel_to_snake(&column.name),
+                    column.type_.postgres_type()
+                ));
+            }
+
+            // smart comments needed to avoid clashing of graphql names
+            if apply_full_name_comment_for_tables.contains(&table.name) {
+                sql.push_str(&format!(
+                    "\nCOMMENT ON TABLE {} IS E'@name {}{}';",
+                    table_name, contract.name, table.name
+                ));
+            }
+
+            sql
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn generate_rollup_tables_sql(
+    contract: &Contract,
+    schema_name: &str,
+    apply_full_name_comment_for_tables: &[String],
+) -> String {
+    contract
+        .rollups
+        .iter()
+        .flatten()
+        .flat_map(|rollup| rollup.intervals.iter().map(move |interval| (rollup, *interval)))
+        .map(|(rollup, interval)| {
+            let rollup_table_name = rollup.table_name(interval);
+            let table_name = generate_table_full_name(schema_name, &rollup_table_name);
+            info!("Creating table if not exists: {}", table_name);
+            let key_columns = rollup
+                .keys()
+                .iter()
+                .map(|key| {
+                    format!(
+                        "\"{}\" {} NOT NULL, ",
+                        camel_to_snake(&key.name),
+                        key.type_.postgres_type()
+                    )
+                })
+                .collect::<String>();
+            let key_names = rollup
+                .keys()
+                .iter()
+                .map(|key| format!("\"{}\", ", camel_to_snake(&key.name)))
+                .collect::<String>();
+
+            // the first and last positions decide the first and last values so late data can
+            // still be rolled up correctly
+            let mut sql = format!(
+                "CREATE TABLE IF NOT EXISTS {} (\
+                network VARCHAR(50) NOT NULL, \
+                {}\
+                bucket TIMESTAMPTZ NOT NULL, \
+                first_block_number NUMERIC NOT NULL, \
+                first_log_index NUMERIC NOT NULL, \
+                last_block_number NUMERIC NOT NULL, \
+                last_log_index NUMERIC NOT NULL, \
+                PRIMARY KEY (network, {}bucket)\
+            );",
+                table_name, key_columns, key_names
+            );
+
+            // metrics can be added to the yaml after the table exists
+            for metric in &rollup.metrics {
+                sql.push_str(&format!(
+                    "\nALTER TABLE {} ADD COLUMN IF NOT EXISTS \"{}\" NUMERIC;",
+                    table_name,
+                    camel_to_snake(&metric.name)
+                ));
+            }
+
+            // smart comments needed to avoid clashing of graphql names
+            if apply_full_name_comment_for_tables.contains(&rollup_table_name) {
+                sql.push_str(&format!(
+                    "\nCOMMENT ON TABLE {} IS E'@name {}{}';",
+                    table_name, contract.name, rollup_table_name
+                ));
+            }
+
+            sql
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The events already applied to the derived tables and rollups of a contract, a batch which is
+/// retried skips them so the sums and counts are not added twice
+fn generate_applied_updates_table_sql(schema_name: &str) -> String {
+    let table_name = generate_applied_updates_table_name(schema_name);
+    info!("Creating table if not exists: {}", table_name);
+
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\
+            target TEXT NOT NULL, \
+            network VARCHAR(50) NOT NULL, \
+            block_number NUMERIC NOT NULL, \
+            log_index NUMERIC NOT NULL, \
+            PRIMARY KEY (target, network, block_number, log_index)\
+        );",
+        table_name
+    )
+}
+
+fn generate_raw_logs_table_sql(schema_name: &str) -> String {
+    let table_name = format!("{}.{}", schema_name, camel_to_snake(RAW_LOGS_EVENT_NAME));
+    info!("Creating table if not exists: {}", table_name);
+
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\
+            rindexer_id SERIAL PRIMARY KEY NOT NULL, \
+            contract_address CHAR(66) NOT NULL, \
+            topic_0 CHAR(66), \
+            topic_1 CHAR(66), \
+            topic_2 CHAR(66), \
+            topic_3 CHAR(66), \
+            data TEXT NOT NULL, \
+            tx_hash CHAR(66) NOT NULL, \
+            block_number NUMERIC NOT NULL, \
+            block_hash CHAR(66) NOT NULL, \
+            network VARCHAR(50) NOT NULL, \
+            tx_index NUMERIC NOT NULL, \
+            log_index VARCHAR(78) NOT NULL\
+        );",
+        table_name
+    )
+}
+
 fn generate_internal_event_table_sql(
-    abi_inputs: &[EventInfo],
+    event_names: &[&str],
     schema_name: &str,
     networks: Vec<&str>,
 ) -> String {
-    abi_inputs.iter().map(|event_info| {
+    event_names.iter().map(|event_name| {
         let table_name = format!(
             "rindexer_internal.{}_{}",
             schema_name,
-            camel_to_snake(&event_info.name)
+            camel_to_snake(event_name)
         );
 
         let create_table_query = format!(
@@ -143,8 +375,7 @@ fn find_clashing_event_names(
         }
 
         let other_abi_items = ABIItem::read_abi_items(project_path, other_contract)?;
-        let other_event_names =
-            ABIItem::extract_event_names_and_signatures_from_abi(other_abi_items)?;
+        let other_event_names = ABIItem::extract_contract_events(other_contract, other_abi_items)?;
 
         for event_name in current_event_names {
             if other_event_names.iter().any(|e| e.name == event_name.name) &&
@@ -158,10 +389,39 @@ fn find_clashing_event_names(
     Ok(clashing_events)
 }
 
+/// If the same table name is used by other contracts the table should be exposed differently on
+/// graphql to avoid clashing of graphql namings
+fn find_clashing_table_names(
+    current_contract: &Contract,
+    other_contracts: &[Contract],
+) -> Vec<String> {
+    derived_table_names(current_contract)
+        .into_iter()
+        .filter(|table_name| {
+            other_contracts.iter().any(|other_contract| {
+                other_contract.name != current_contract.name &&
+                    derived_table_names(other_contract).contains(table_name)
+            })
+        })
+        .collect()
+}
+
+/// The names of the tables and rollup tables maintained for a contract
+fn derived_table_names(contract: &Contract) -> Vec<String> {
+    let mut table_names: Vec<String> =
+        contract.tables.iter().flatten().map(|table| table.name.clone()).collect();
+    for rollup in contract.rollups.iter().flatten() {
+        table_names.extend(rollup.intervals.iter().map(|interval| rollup.table_name(*interval)));
+    }
+
+    table_names
+}
+
 pub fn generate_tables_for_indexer_sql(
     project_path: &Path,
     indexer: &Indexer,
     disable_event_tables: bool,
+    partitioned_tables: &PartitionedTables,
 ) -> Result<Code, GenerateTablesForIndexerSqlError> {
     let mut sql = "CREATE SCHEMA IF NOT EXISTS rindexer_internal;".to_string();
 
@@ -215,8 +475,9 @@ pub fn generate_tables_for_indexer_sql(
     ));
     for contract in &indexer.contracts {
         let contract_name = contract.before_modify_name_if_filter_readonly();
-        let abi_items = ABIItem::read_abi_items(project_path, contract)?;
-        let event_names = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?;
+        let event_names = ABIItem::read_abi_events(project_path, contract)?;
+        let versioned_events =
+            ABIItem::read_versioned_events(project_path, contract, &event_names)?;
         let schema_name = generate_indexer_contract_schema_name(&indexer.name, &contract_name);
         let networks: Vec<&str> = contract.details.iter().map(|d| d.network.as_str()).collect();
 
@@ -233,13 +494,66 @@ pub fn generate_tables_for_indexer_sql(
 
             sql.push_str(&generate_event_table_sql_with_comments(
                 &event_names,
-                &contract.name,
+                contract,
                 &schema_name,
                 event_matching_name_on_other,
+                partitioned_tables,
             ));
+
+            // only versions with a different layout to the current ABI need their own table
+            let versioned_table_events: Vec<EventInfo> = versioned_events
+                .iter()
+                .filter(|versioned_event| {
+                    versioned_event.event_name == versioned_event.table_event_name
+                })
+                .map(|versioned_event| versioned_event.table_event_info())
+                .collect();
+            sql.push_str(&generate_event_table_sql_with_comments(
+                &versioned_table_events,
+                contract,
+                &schema_name,
+                vec![],
+                partitioned_tables,
+            ));
+
+            if contract.raw_logs_enabled() {
+                sql.push_str(&generate_raw_logs_table_sql(&schema_name));
+            }
+
+            let table_matching_name_on_other =
+                find_clashing_table_names(contract, &indexer.contracts);
+            sql.push_str(&generate_derived_tables_sql(
+                contract,
+                &schema_name,
+                &table_matching_name_on_other,
+            ));
+            sql.push_str(&generate_rollup_tables_sql(
+                contract,
+                &schema_name,
+                &table_matching_name_on_other,
+            ));
+            if contract.tables.as_ref().is_some_and(|tables| !tables.is_empty()) ||
+                contract.rollups.as_ref().is_some_and(|rollups| !rollups.is_empty())
+            {
+                sql.push_str(&generate_applied_updates_table_sql(&schema_name));
+            }
+        }
+
+        let mut internal_event_names: Vec<&str> =
+            event_names.iter().map(|event_info| event_info.name.as_str()).collect();
+        internal_event_names.extend(
+            versioned_events.iter().map(|versioned_event| versioned_event.event_name.as_str()),
+        );
+        if contract.raw_logs_enabled() {
+            internal_event_names.push(RAW_LOGS_EVENT_NAME);
         }
+
         // we still need to create the internal tables for the contract
-        sql.push_str(&generate_internal_event_table_sql(&event_names, &schema_name, networks));
+        sql.push_str(&generate_internal_event_table_sql(
+            &internal_event_names,
+            &schema_name,
+            networks,
+        ));
     }
 
     sql.push_str(&format!(
@@ -252,6 +566,16 @@ pub fn generate_tables_for_indexer_sql(
         indexer_name = camel_to_snake(&indexer.name)
     ));
 
+    sql.push_str(&format!(
+        r#"
+        CREATE TABLE IF NOT EXISTS rindexer_internal.{indexer_name}_applied_schema (
+            key INT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+    "#,
+        indexer_name = camel_to_snake(&indexer.name)
+    ));
+
     sql.push_str(&format!(
         r#"
         CREATE TABLE IF NOT EXISTS rindexer_internal.{indexer_name}_last_known_indexes_dropping_sql (
@@ -274,6 +598,14 @@ pub fn generate_event_table_full_name(
     format!("{}.{}", schema_name, camel_to_snake(event_name))
 }
 
+pub fn generate_table_full_name(schema_name: &str, table_name: &str) -> String {
+    format!("{}.{}", schema_name, camel_to_snake(table_name))
+}
+
+pub fn generate_applied_updates_table_name(schema_name: &str) -> String {
+    format!("rindexer_internal.{}_applied_updates", schema_name)
+}
+
 pub fn generate_event_table_columns_names_sql(column_names: &[String]) -> String {
     column_names.iter().map(|name| format!("\"{}\"", name)).collect::<Vec<String>>().join(", ")
 }
@@ -288,6 +620,13 @@ pub fn drop_tables_for_indexer_sql(project_path: &Path, indexer: &Indexer) -> Co
         camel_to_snake(&indexer.name)
     );
     sql.push_str(format!("DROP TABLE IF EXISTS rindexer_internal.{}_last_known_relationship_dropping_sql CASCADE;", camel_to_snake(&indexer.name)).as_str());
+    sql.push_str(
+        format!(
+            "DROP TABLE IF EXISTS rindexer_internal.{}_applied_schema CASCADE;",
+            camel_to_snake(&indexer.name)
+        )
+        .as_str(),
+    );
 
     for contract in &indexer.contracts {
         let contract_name = contract.before_modify_name_if_filter_readonly();
@@ -295,10 +634,10 @@ pub fn drop_tables_for_indexer_sql(project_path: &Path, indexer: &Indexer) -> Co
         sql.push_str(format!("DROP SCHEMA IF EXISTS {} CASCADE;", schema_name).as_str());
 
         // drop last synced blocks for contracts
-        let abi_items = ABIItem::read_abi_items(project_path, contract);
-        if let Ok(abi_items) = abi_items {
-            for abi_item in abi_items.iter() {
-                let table_name = format!("{}_{}", schema_name, camel_to_snake(&abi_item.name));
+        let events = ABIItem::read_abi_events(project_path, contract);
+        if let Ok(events) = events {
+            for event in events.iter() {
+                let table_name = format!("{}_{}", schema_name, camel_to_snake(&event.name));
                 sql.push_str(
                     format!("DROP TABLE IF EXISTS rindexer_internal.{} CASCADE;", table_name)
                         .as_str(),
@@ -310,6 +649,41 @@ pub fn drop_tables_for_indexer_sql(project_path: &Path, indexer: &Indexer) -> Co
                 contract.name
             );
         }
+
+        for (index, abi_version) in contract.abi_versions.iter().flatten().enumerate() {
+            let events = ABIItem::read_abi_version_items(project_path, contract, abi_version)
+                .ok()
+                .and_then(|abi_items| ABIItem::extract_contract_events(contract, abi_items).ok());
+            if let Some(events) = events {
+                for event in events.iter() {
+                    let table_name = format!(
+                        "{}_{}",
+                        schema_name,
+                        camel_to_snake(&versioned_event_name(&event.name, index))
+                    );
+                    sql.push_str(
+                        format!("DROP TABLE IF EXISTS rindexer_internal.{} CASCADE;", table_name)
+                            .as_str(),
+                    );
+                }
+            }
+        }
+
+        sql.push_str(&format!(
+            "DROP TABLE IF EXISTS {} CASCADE;",
+            generate_applied_updates_table_name(&schema_name)
+        ));
+
+        if contract.raw_logs_enabled() {
+            sql.push_str(
+                format!(
+                    "DROP TABLE IF EXISTS rindexer_internal.{}_{} CASCADE;",
+                    schema_name,
+                    camel_to_snake(RAW_LOGS_EVENT_NAME)
+                )
+                .as_str(),
+            );
+        }
     }
 
     Code::new(sql)
@@ -317,6 +691,10 @@ pub fn drop_tables_for_indexer_sql(project_path: &Path, indexer: &Indexer) -> Co
 
 #[allow(clippy::manual_strip)]
 pub fn solidity_type_to_db_type(abi_type: &str) -> String {
+    if let Some(numeric_column) = NumericColumn::from_abi_type(abi_type) {
+        return numeric_column.postgres_type();
+    }
+
     let is_array = abi_type.ends_with("[]");
     let base_type = abi_type.trim_end_matches("[]");
 
@@ -324,6 +702,7 @@ pub fn solidity_type_to_db_type(abi_type: &str) -> String {
         "address" => "CHAR(42)",
         "bool" => "BOOLEAN",
         "string" => "TEXT",
+        JSONB_TUPLE_TYPE => "JSONB",
         t if t.starts_with("bytes") => "BYTEA",
         t if t.starts_with("int") || t.starts_with("uint") => {
             // Handling fixed-size integers (intN and uintN where N can be 8 to 256 in steps of 8)
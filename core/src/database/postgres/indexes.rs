@@ -4,17 +4,244 @@ use futures::future::join_all;
 use tracing::info;
 
 use crate::{
-    abi::{get_abi_item_with_db_map, ABIItem, GetAbiItemWithDbMapError, ReadAbiError},
-    database::postgres::client::{PostgresClient, PostgresConnectionError, PostgresError},
+    abi::{
+        get_abi_item_with_db_map, ABIInput, ABIItem, EventInfo, GetAbiItemWithDbMapError,
+        ReadAbiError,
+    },
+    database::postgres::{
+        client::{PostgresClient, PostgresConnectionError, PostgresError},
+        generate::generate_column_names_only_with_base_properties,
+        partitions::PartitionedTables,
+    },
     helpers::camel_to_snake,
-    manifest::{contract::Contract, storage::PostgresIndexes},
+    manifest::{
+        contract::Contract,
+        storage::{EventIndex, IndexMethod, IndexOrder, PostgresIndexes},
+    },
     types::code::Code,
 };
 
+#[derive(thiserror::Error, Debug)]
+pub enum InvalidEventIndexError {
+    #[error("{0}")]
+    ParameterNotFound(#[from] GetAbiItemWithDbMapError),
+
+    #[error("{0}")]
+    Invalid(&'static str),
+}
+
+/// An index on an event table with its columns and predicate mapped to the database columns
+#[derive(Debug, Clone)]
+pub struct EventIndexDefinition {
+    columns_sql: Vec<String>,
+    column_names: Vec<String>,
+    method: IndexMethod,
+    unique: bool,
+    where_: Option<String>,
+}
+
+impl EventIndexDefinition {
+    fn column(column_name: &str) -> Self {
+        Self {
+            columns_sql: vec![format!("\"{}\"", column_name)],
+            column_names: vec![column_name.to_string()],
+            method: IndexMethod::Btree,
+            unique: false,
+            where_: None,
+        }
+    }
+
+    pub fn method(&self) -> IndexMethod {
+        self.method
+    }
+
+    pub fn unique(&self) -> bool {
+        self.unique
+    }
+
+    pub fn columns_sql(&self) -> String {
+        self.columns_sql.join(", ")
+    }
+
+    pub fn where_sql(&self) -> String {
+        self.where_.as_ref().map_or("".to_string(), |where_| format!(" WHERE {}", where_))
+    }
+
+    /// The name of the index on the table, the schema is not part of it
+    pub fn index_name(&self, table_name: &str) -> String {
+        let mut index_name = format!(
+            "idx_{table_name}_{db_table_columns}",
+            db_table_columns = self.column_names.join("_"),
+        );
+
+        // the same columns can be indexed in different ways so they need different names
+        if self.method != IndexMethod::Btree {
+            index_name.push_str(&format!("_{}", self.method.name()));
+        }
+        if self.unique {
+            index_name.push_str("_unique");
+        }
+        if self.where_.is_some() {
+            index_name.push_str("_partial");
+        }
+
+        index_name
+    }
+}
+
+/// Maps an event input name to its column, the injected columns such as `block_number` can be
+/// used as well as the ABI inputs
+fn resolve_index_column(
+    events: &[EventInfo],
+    event_name: &str,
+    name: &str,
+    partition_column: Option<&str>,
+) -> Result<(String, Option<ABIInput>), GetAbiItemWithDbMapError> {
+    if generate_column_names_only_with_base_properties(&[]).iter().any(|column| column == name) ||
+        partition_column == Some(name)
+    {
+        return Ok((name.to_string(), None));
+    }
+
+    let abi_parameter =
+        get_abi_item_with_db_map(events, event_name, &name.split('.').collect::<Vec<&str>>())?;
+    Ok((abi_parameter.db_column_name, Some(abi_parameter.abi_item)))
+}
+
+/// Expressions and predicates reference event inputs as `{name}` so they can be checked against
+/// the ABI and mapped to their columns
+fn resolve_index_expression(
+    events: &[EventInfo],
+    event_name: &str,
+    expression: &str,
+    partition_column: Option<&str>,
+) -> Result<String, InvalidEventIndexError> {
+    let mut sql = String::new();
+    let mut rest = expression;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or(InvalidEventIndexError::Invalid("an expression has a { which is not closed"))? +
+            start;
+        let (column, _) =
+            resolve_index_column(events, event_name, &rest[start + 1..end], partition_column)?;
+
+        sql.push_str(&rest[..start]);
+        sql.push_str(&format!("\"{}\"", column));
+        rest = &rest[end + 1..];
+    }
+    sql.push_str(rest);
+
+    Ok(sql)
+}
+
+/// Works out the sql for an index defined on an event, `partition_column` is the column the
+/// table is partitioned on if it is partitioned
+pub fn resolve_event_index(
+    events: &[EventInfo],
+    event_name: &str,
+    index: &EventIndex,
+    partition_column: Option<&str>,
+) -> Result<EventIndexDefinition, InvalidEventIndexError> {
+    let method = index.method.unwrap_or_default();
+    let unique = index.unique.unwrap_or_default();
+
+    let index_columns =
+        index.event_input_names.iter().map(|name| (Some(name.as_str()), None, None)).chain(
+            index.columns.iter().flatten().map(|column| {
+                (column.event_input_name.as_deref(), column.expression.as_deref(), column.order)
+            }),
+        );
+
+    let mut columns_sql = vec![];
+    let mut column_names = vec![];
+    for (event_input_name, expression, order) in index_columns {
+        let (column_sql, column_name) = match (event_input_name, expression) {
+            (Some(event_input_name), None) => {
+                let (column, abi_item) =
+                    resolve_index_column(events, event_name, event_input_name, partition_column)?;
+                if method == IndexMethod::Gin &&
+                    !abi_item.is_some_and(|abi_item| {
+                        abi_item.type_.ends_with(']') || abi_item.is_jsonb()
+                    })
+                {
+                    return Err(InvalidEventIndexError::Invalid(
+                        "gin indexes can only be used on array or JSONB event inputs or expressions",
+                    ));
+                }
+                (format!("\"{}\"", column), column)
+            }
+            (None, Some(expression)) => {
+                let sql =
+                    resolve_index_expression(events, event_name, expression, partition_column)?;
+                (format!("({})", sql), index_name_part(&sql))
+            }
+            _ => {
+                return Err(InvalidEventIndexError::Invalid(
+                    "each column must have either an event_input_name or an expression",
+                ))
+            }
+        };
+
+        let column_sql = match order {
+            Some(_) if method != IndexMethod::Btree => {
+                return Err(InvalidEventIndexError::Invalid("only btree indexes can be ordered"))
+            }
+            Some(IndexOrder::Asc) => format!("{} ASC", column_sql),
+            Some(IndexOrder::Desc) => format!("{} DESC", column_sql),
+            None => column_sql,
+        };
+
+        columns_sql.push(column_sql);
+        column_names.push(column_name);
+    }
+
+    if columns_sql.is_empty() {
+        return Err(InvalidEventIndexError::Invalid("an index must have at least one column"));
+    }
+
+    if unique && method != IndexMethod::Btree {
+        return Err(InvalidEventIndexError::Invalid("only btree indexes can be unique"));
+    }
+
+    if method == IndexMethod::Hash && columns_sql.len() > 1 {
+        return Err(InvalidEventIndexError::Invalid("hash indexes can only have one column"));
+    }
+
+    if let Some(partition_column) = partition_column {
+        if unique && !column_names.iter().any(|name| name == partition_column) {
+            return Err(InvalidEventIndexError::Invalid(
+                "unique indexes on partitioned tables must include the partition column",
+            ));
+        }
+    }
+
+    let where_ = index
+        .where_
+        .as_ref()
+        .map(|where_| resolve_index_expression(events, event_name, where_, partition_column))
+        .transpose()?;
+
+    Ok(EventIndexDefinition { columns_sql, column_names, method, unique, where_ })
+}
+
+/// Expressions can not go in the index name as they are so anything which is not a letter or
+/// number is turned into an underscore
+fn index_name_part(expression: &str) -> String {
+    expression
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
 #[derive(Debug, Clone)]
 pub struct PostgresIndexResult {
     db_table_name: String,
-    db_table_columns: Vec<String>,
+    definition: EventIndexDefinition,
+    // partitioned tables and hypertables can not build indexes concurrently
+    concurrently: bool,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -37,12 +264,16 @@ impl PostgresIndexResult {
         // CONCURRENTLY is used to avoid locking the table for writes
         Code::new(format!(
             r#"
-                CREATE INDEX CONCURRENTLY {index_name}
-                ON {db_table_name} ({db_table_columns});
+                CREATE {unique}INDEX {concurrently}{index_name}
+                ON {db_table_name} USING {method} ({db_table_columns}){where_};
             "#,
+            unique = if self.definition.unique() { "UNIQUE " } else { "" },
+            concurrently = self.concurrently_sql(),
             index_name = self.index_name(),
             db_table_name = self.db_table_name,
-            db_table_columns = self.db_table_columns.join(", "),
+            method = self.definition.method().name(),
+            db_table_columns = self.definition.columns_sql(),
+            where_ = self.definition.where_sql(),
         ))
     }
 
@@ -55,7 +286,8 @@ impl PostgresIndexResult {
 
         Code::new(format!(
             // CONCURRENTLY is used to avoid locking the table for writes
-            "DROP INDEX CONCURRENTLY IF EXISTS {}.{};",
+            "DROP INDEX {}IF EXISTS {}.{};",
+            self.concurrently_sql(),
             // get schema else drop won't work
             self.db_table_name.split('.').next().unwrap_or_else(|| panic!(
                 "Failed to split and then get schema for table: {}",
@@ -65,15 +297,18 @@ impl PostgresIndexResult {
         ))
     }
 
+    fn concurrently_sql(&self) -> &'static str {
+        if self.concurrently {
+            "CONCURRENTLY "
+        } else {
+            ""
+        }
+    }
+
     pub fn index_name(&self) -> String {
-        format!(
-            "idx_{db_table_name}_{db_table_columns}",
-            db_table_name = self.db_table_name.split('.').last().unwrap_or_else(|| panic!(
-                "Failed to split and then get schema for table: {}",
-                self.db_table_name
-            )),
-            db_table_columns = self.db_table_columns.join("_"),
-        )
+        self.definition.index_name(self.db_table_name.split('.').last().unwrap_or_else(|| {
+            panic!("Failed to split and then get schema for table: {}", self.db_table_name)
+        }))
     }
 
     pub async fn apply_indexes(
@@ -177,7 +412,7 @@ pub enum PrepareIndexesError {
     PostgresConnectionError(#[from] PostgresConnectionError),
 
     #[error("{0}")]
-    GetAbiParameterError(#[from] GetAbiItemWithDbMapError),
+    InvalidEventIndex(#[from] InvalidEventIndexError),
 
     #[error("Contract {0} not found in `contracts` make sure it is defined")]
     ContractMissing(String),
@@ -192,43 +427,42 @@ pub enum PrepareIndexesError {
     SaveIndexesError(#[from] PostgresError),
 }
 
-pub async fn prepare_indexes(
+/// Resolves the indexes in the yaml to the event tables they are on, `partition_column` gives the
+/// column a table is partitioned on if it is partitioned
+pub fn resolve_indexes(
     project_path: &Path,
     manifest_name: &str,
-    postgres_indexes: &PostgresIndexes,
+    indexes: &PostgresIndexes,
     contracts: &[Contract],
-) -> Result<Vec<PostgresIndexResult>, PrepareIndexesError> {
-    let mut index_results: Vec<PostgresIndexResult> = vec![];
-    let mut dropping_sql: Vec<Code> = vec![];
-    let client = Arc::new(PostgresClient::new().await?);
+    partition_column: impl Fn(&str) -> Option<&'static str>,
+) -> Result<Vec<(String, EventIndexDefinition)>, PrepareIndexesError> {
+    let mut resolved_indexes: Vec<(String, EventIndexDefinition)> = vec![];
 
     // global first
-    if let Some(global_injected_parameters) = &postgres_indexes.global_injected_parameters {
+    if let Some(global_injected_parameters) = &indexes.global_injected_parameters {
         for contract in contracts {
-            let abi_items = ABIItem::read_abi_items(project_path, contract)?;
+            let events = ABIItem::read_abi_events(project_path, contract)?;
 
-            for abi_item in abi_items {
+            for event in events {
                 let db_table_name = format!(
                     "{}_{}.{}",
                     camel_to_snake(manifest_name),
                     camel_to_snake(&contract.name),
-                    camel_to_snake(&abi_item.name)
+                    camel_to_snake(&event.name)
                 );
 
                 for global_parameter_column_name in global_injected_parameters {
-                    let index_result = PostgresIndexResult {
-                        db_table_name: db_table_name.clone(),
-                        db_table_columns: vec![global_parameter_column_name.clone()],
-                    };
-                    dropping_sql.push(index_result.drop_index_sql());
-                    index_results.push(index_result);
+                    resolved_indexes.push((
+                        db_table_name.clone(),
+                        EventIndexDefinition::column(global_parameter_column_name),
+                    ));
                 }
             }
         }
     }
 
     // then contracts
-    if let Some(contract_events_indexes) = &postgres_indexes.contracts {
+    if let Some(contract_events_indexes) = &indexes.contracts {
         for contract_event_indexes in contract_events_indexes.iter() {
             let contract = contracts.iter().find(|c| c.name == contract_event_indexes.name);
 
@@ -239,24 +473,22 @@ pub async fn prepare_indexes(
                     ));
                 }
                 Some(contract) => {
-                    let abi_items = ABIItem::read_abi_items(project_path, contract)?;
+                    let events = ABIItem::read_abi_events(project_path, contract)?;
 
                     if let Some(injected_parameters) = &contract_event_indexes.injected_parameters {
-                        for abi_item in &abi_items {
+                        for event in &events {
                             let db_table_name = format!(
                                 "{}_{}.{}",
                                 camel_to_snake(manifest_name),
                                 camel_to_snake(&contract.name),
-                                camel_to_snake(&abi_item.name)
+                                camel_to_snake(&event.name)
                             );
 
                             for injected_parameter in injected_parameters {
-                                let index_result = PostgresIndexResult {
-                                    db_table_name: db_table_name.clone(),
-                                    db_table_columns: vec![injected_parameter.clone()],
-                                };
-                                dropping_sql.push(index_result.drop_index_sql());
-                                index_results.push(index_result);
+                                resolved_indexes.push((
+                                    db_table_name.clone(),
+                                    EventIndexDefinition::column(injected_parameter),
+                                ));
                             }
                         }
                     }
@@ -271,32 +503,21 @@ pub async fn prepare_indexes(
 
                         if let Some(injected_parameters) = &event_indexes.injected_parameters {
                             for injected_parameter in injected_parameters {
-                                let index_result = PostgresIndexResult {
-                                    db_table_name: db_table_name.clone(),
-                                    db_table_columns: vec![injected_parameter.clone()],
-                                };
-                                dropping_sql.push(index_result.drop_index_sql());
-                                index_results.push(index_result);
+                                resolved_indexes.push((
+                                    db_table_name.clone(),
+                                    EventIndexDefinition::column(injected_parameter),
+                                ));
                             }
                         }
 
                         for index in &event_indexes.indexes {
-                            let mut db_table_columns = vec![];
-                            for parameter in &index.event_input_names {
-                                let abi_parameter = get_abi_item_with_db_map(
-                                    &abi_items,
-                                    &event_indexes.name,
-                                    &parameter.split('.').collect::<Vec<&str>>(),
-                                )?;
-                                db_table_columns.push(abi_parameter.db_column_name);
-                            }
-
-                            let index_result = PostgresIndexResult {
-                                db_table_name: db_table_name.clone(),
-                                db_table_columns,
-                            };
-                            dropping_sql.push(index_result.drop_index_sql());
-                            index_results.push(index_result);
+                            let definition = resolve_event_index(
+                                &events,
+                                &event_indexes.name,
+                                index,
+                                partition_column(&db_table_name),
+                            )?;
+                            resolved_indexes.push((db_table_name.clone(), definition));
                         }
                     }
                 }
@@ -304,6 +525,34 @@ pub async fn prepare_indexes(
         }
     }
 
+    Ok(resolved_indexes)
+}
+
+pub async fn prepare_indexes(
+    project_path: &Path,
+    manifest_name: &str,
+    postgres_indexes: &PostgresIndexes,
+    partitioned_tables: &PartitionedTables,
+    contracts: &[Contract],
+) -> Result<Vec<PostgresIndexResult>, PrepareIndexesError> {
+    let mut index_results: Vec<PostgresIndexResult> = vec![];
+    let mut dropping_sql: Vec<Code> = vec![];
+    let client = Arc::new(PostgresClient::new().await?);
+
+    let resolved_indexes =
+        resolve_indexes(project_path, manifest_name, postgres_indexes, contracts, |table_name| {
+            partitioned_tables.get(table_name).map(|partitioning| partitioning.column())
+        })?;
+    for (db_table_name, definition) in resolved_indexes {
+        let index_result = PostgresIndexResult {
+            concurrently: partitioned_tables.get(&db_table_name).is_none(),
+            db_table_name,
+            definition,
+        };
+        dropping_sql.push(index_result.drop_index_sql());
+        index_results.push(index_result);
+    }
+
     let indexes_dropping_sql_json = serde_json::to_string(
         &dropping_sql.iter().map(|code| code.as_str()).collect::<Vec<&str>>(),
     )?;
@@ -322,3 +571,89 @@ pub async fn prepare_indexes(
 
     Ok(index_results)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::storage::EventIndexColumn;
+
+    fn transfer_events() -> Vec<EventInfo> {
+        let input = |name: &str, type_: &str| ABIInput {
+            indexed: None,
+            name: name.to_string(),
+            type_: type_.to_string(),
+            components: None,
+        };
+        let item = ABIItem {
+            inputs: vec![input("from", "address"), input("value", "uint256")],
+            name: "Transfer".to_string(),
+            type_: "event".to_string(),
+        };
+
+        vec![EventInfo::new(item, "Transfer(address,uint256)".to_string())]
+    }
+
+    fn index(method: Option<IndexMethod>) -> EventIndex {
+        EventIndex { event_input_names: vec![], columns: None, method, unique: None, where_: None }
+    }
+
+    #[test]
+    fn test_apply_index_sql() {
+        let events = transfer_events();
+        let mut index = index(None);
+        index.unique = Some(true);
+        index.where_ = Some("{value} <> '0'".to_string());
+        index.columns = Some(vec![
+            EventIndexColumn {
+                event_input_name: Some("block_number".to_string()),
+                expression: None,
+                order: Some(IndexOrder::Desc),
+            },
+            EventIndexColumn {
+                event_input_name: None,
+                expression: Some("lower({from})".to_string()),
+                order: None,
+            },
+        ]);
+
+        let index_result = PostgresIndexResult {
+            db_table_name: "indexer_token.transfer".to_string(),
+            definition: resolve_event_index(&events, "Transfer", &index, Some("block_number"))
+                .unwrap(),
+            concurrently: false,
+        };
+        assert_eq!(
+            index_result.index_name(),
+            "idx_transfer_block_number_lower_from_unique_partial"
+        );
+        assert_eq!(
+            index_result.apply_index_sql().as_str().trim(),
+            "CREATE UNIQUE INDEX idx_transfer_block_number_lower_from_unique_partial\n                \
+            ON indexer_token.transfer USING btree (\"block_number\" DESC, (lower(\"from\"))) \
+            WHERE \"value\" <> '0';"
+        );
+    }
+
+    #[test]
+    fn test_invalid_indexes() {
+        let events = transfer_events();
+        let resolve = |index: &EventIndex| resolve_event_index(&events, "Transfer", index, None);
+
+        let mut brin = index(Some(IndexMethod::Brin));
+        brin.event_input_names = vec!["block_number".to_string()];
+        assert!(resolve(&brin).is_ok());
+
+        brin.unique = Some(true);
+        assert!(resolve(&brin).is_err());
+
+        let mut gin = index(Some(IndexMethod::Gin));
+        gin.event_input_names = vec!["from".to_string()];
+        assert!(resolve(&gin).is_err());
+
+        let mut missing = index(None);
+        missing.event_input_names = vec!["to".to_string()];
+        assert!(resolve(&missing).is_err());
+
+        assert!(resolve(&index(None)).is_err());
+    }
+}
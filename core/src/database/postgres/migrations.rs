@@ -0,0 +1,532 @@
+use std::{fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    abi::{ABIInput, ABIItem, EventInfo, GenerateAbiPropertiesType},
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        generate::{
+            generate_indexer_contract_schema_name, generate_table_full_name,
+            solidity_type_to_db_type, GenerateTablesForIndexerSqlError,
+        },
+        partitions::PartitionedTables,
+    },
+    helpers::camel_to_snake,
+    indexer::{Indexer, RAW_LOGS_EVENT_NAME},
+    manifest::contract::Contract,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColumnSchema {
+    pub name: String,
+
+    pub data_type: String,
+
+    pub not_null: bool,
+}
+
+impl ColumnSchema {
+    fn new(name: &str, data_type: &str, not_null: bool) -> Self {
+        Self { name: name.to_string(), data_type: data_type.to_string(), not_null }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TableSchema {
+    pub name: String,
+
+    pub columns: Vec<ColumnSchema>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partitioning: Option<String>,
+}
+
+impl TableSchema {
+    fn column(&self, name: &str) -> Option<&ColumnSchema> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+}
+
+fn event_table_schema(
+    table_name: String,
+    event_info: &EventInfo,
+    contract: &Contract,
+    partitioned_tables: &PartitionedTables,
+) -> TableSchema {
+    let partitioning = partitioned_tables.get(&table_name);
+    let mut columns = vec![
+        ColumnSchema::new("rindexer_id", "SERIAL", true),
+        ColumnSchema::new("contract_address", "CHAR(66)", true),
+    ];
+    columns.extend(
+        ABIInput::generate_abi_name_properties(
+            &event_info.inputs,
+            &GenerateAbiPropertiesType::PostgresColumnsNamesOnly,
+            None,
+        )
+        .into_iter()
+        .map(|property| {
+            ColumnSchema::new(&property.value, &solidity_type_to_db_type(&property.abi_type), false)
+        }),
+    );
+    columns.extend([
+        ColumnSchema::new("tx_hash", "CHAR(66)", true),
+        ColumnSchema::new("block_number", "NUMERIC", true),
+        ColumnSchema::new("block_hash", "CHAR(66)", true),
+        ColumnSchema::new("network", "VARCHAR(50)", true),
+        ColumnSchema::new("tx_index", "NUMERIC", true),
+        ColumnSchema::new("log_index", "VARCHAR(78)", true),
+        ColumnSchema::new("input", "TEXT", false),
+    ]);
+    if partitioning.is_some_and(|partitioning| partitioning.column() == "block_timestamp") {
+        columns.push(ColumnSchema::new("block_timestamp", "TIMESTAMPTZ", true));
+    }
+    columns.extend(contract.computed_columns(&event_info.name, event_info.signature()).iter().map(
+        |column| {
+            ColumnSchema::new(&camel_to_snake(&column.name), column.type_.postgres_type(), false)
+        },
+    ));
+
+    TableSchema {
+        name: table_name,
+        columns,
+        partitioning: partitioning.map(|partitioning| partitioning.to_string()),
+    }
+}
+
+fn raw_logs_table_schema(schema_name: &str) -> TableSchema {
+    TableSchema {
+        name: generate_table_full_name(schema_name, RAW_LOGS_EVENT_NAME),
+        columns: vec![
+            ColumnSchema::new("rindexer_id", "SERIAL", true),
+            ColumnSchema::new("contract_address", "CHAR(66)", true),
+            ColumnSchema::new("topic_0", "CHAR(66)", false),
+            ColumnSchema::new("topic_1", "CHAR(66)", false),
+            ColumnSchema::new("topic_2", "CHAR(66)", false),
+            ColumnSchema::new("topic_3", "CHAR(66)", false),
+            ColumnSchema::new("data", "TEXT", true),
+            ColumnSchema::new("tx_hash", "CHAR(66)", true),
+            ColumnSchema::new("block_number", "NUMERIC", true),
+            ColumnSchema::new("block_hash", "CHAR(66)", true),
+            ColumnSchema::new("network", "VARCHAR(50)", true),
+            ColumnSchema::new("tx_index", "NUMERIC", true),
+            ColumnSchema::new("log_index", "VARCHAR(78)", true),
+        ],
+        partitioning: None,
+    }
+}
+
+fn derived_table_schemas(contract: &Contract, schema_name: &str) -> Vec<TableSchema> {
+    let mut tables: Vec<TableSchema> = contract
+        .tables
+        .iter()
+        .flatten()
+        .map(|table| {
+            let mut columns = vec![ColumnSchema::new("network", "VARCHAR(50)", true)];
+            columns.extend(table.keys.iter().map(|key| {
+                ColumnSchema::new(&camel_to_snake(&key.name), key.type_.postgres_type(), true)
+            }));
+            columns.push(ColumnSchema::new("last_updated_block", "NUMERIC", true));
+            columns.extend(table.columns.iter().map(|column| {
+                ColumnSchema::new(
+                    &camel_to_snake(&column.name),
+                    column.type_.postgres_type(),
+                    false,
+                )
+            }));
+
+            TableSchema {
+                name: generate_table_full_name(schema_name, &table.name),
+                columns,
+                partitioning: None,
+            }
+        })
+        .collect();
+
+    for rollup in contract.rollups.iter().flatten() {
+        for interval in &rollup.intervals {
+            let mut columns = vec![ColumnSchema::new("network", "VARCHAR(50)", true)];
+            columns.extend(rollup.keys().iter().map(|key| {
+                ColumnSchema::new(&camel_to_snake(&key.name), key.type_.postgres_type(), true)
+            }));
+            columns.extend([
+                ColumnSchema::new("bucket", "TIMESTAMPTZ", true),
+                ColumnSchema::new("first_block_number", "NUMERIC", true),
+                ColumnSchema::new("first_log_index", "NUMERIC", true),
+                ColumnSchema::new("last_block_number", "NUMERIC", true),
+                ColumnSchema::new("last_log_index", "NUMERIC", true),
+            ]);
+            columns.extend(
+                rollup.metrics.iter().map(|metric| {
+                    ColumnSchema::new(&camel_to_snake(&metric.name), "NUMERIC", false)
+                }),
+            );
+
+            tables.push(TableSchema {
+                name: generate_table_full_name(schema_name, &rollup.table_name(*interval)),
+                columns,
+                partitioning: None,
+            });
+        }
+    }
+
+    tables
+}
+
+/// The event, raw logs and derived tables rindexer creates for the indexer, this mirrors
+/// `generate_tables_for_indexer_sql` so it can be compared with the schema applied last time
+pub fn generate_indexer_schema(
+    project_path: &Path,
+    indexer: &Indexer,
+    partitioned_tables: &PartitionedTables,
+) -> Result<Vec<TableSchema>, GenerateTablesForIndexerSqlError> {
+    let mut tables = vec![];
+
+    for contract in &indexer.contracts {
+        let contract_name = contract.before_modify_name_if_filter_readonly();
+        let event_names = ABIItem::read_abi_events(project_path, contract)?;
+        let versioned_events =
+            ABIItem::read_versioned_events(project_path, contract, &event_names)?;
+        let schema_name = generate_indexer_contract_schema_name(&indexer.name, &contract_name);
+
+        for event_info in &event_names {
+            tables.push(event_table_schema(
+                generate_table_full_name(&schema_name, &event_info.name),
+                event_info,
+                contract,
+                partitioned_tables,
+            ));
+        }
+
+        for versioned_event in versioned_events.iter().filter(|versioned_event| {
+            versioned_event.event_name == versioned_event.table_event_name
+        }) {
+            let event_info = versioned_event.table_event_info();
+            tables.push(event_table_schema(
+                generate_table_full_name(&schema_name, &event_info.name),
+                &event_info,
+                contract,
+                partitioned_tables,
+            ));
+        }
+
+        if contract.raw_logs_enabled() {
+            tables.push(raw_logs_table_schema(&schema_name));
+        }
+
+        tables.extend(derived_table_schemas(contract, &schema_name));
+    }
+
+    Ok(tables)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SchemaChange {
+    CreateTable { table: String },
+    AddColumn { table: String, column: ColumnSchema },
+    ChangeColumnType { table: String, column: String, from: String, to: String },
+    RemoveColumn { table: String, column: ColumnSchema },
+    RemoveTable { table: String },
+    ChangePartitioning { table: String, from: Option<String>, to: Option<String> },
+}
+
+impl SchemaChange {
+    /// Changes which can not be applied without losing data or breaking the inserts, the
+    /// indexer refuses to start until they are resolved
+    pub fn is_blocking(&self) -> bool {
+        match self {
+            SchemaChange::CreateTable { .. } | SchemaChange::RemoveTable { .. } => false,
+            SchemaChange::AddColumn { column, .. } | SchemaChange::RemoveColumn { column, .. } => {
+                column.not_null
+            }
+            SchemaChange::ChangeColumnType { .. } | SchemaChange::ChangePartitioning { .. } => true,
+        }
+    }
+
+    /// New tables are created by the normal table setup so only new columns need sql
+    fn sql(&self) -> Option<String> {
+        match self {
+            SchemaChange::AddColumn { table, column } if !column.not_null => Some(format!(
+                "ALTER TABLE {} ADD COLUMN IF NOT EXISTS \"{}\" {};",
+                table, column.name, column.data_type
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaChange::CreateTable { table } => write!(f, "+ create table {}", table),
+            SchemaChange::AddColumn { table, column } if column.not_null => write!(
+                f,
+                "! can not add required column {}.{} {} to existing rows",
+                table, column.name, column.data_type
+            ),
+            SchemaChange::AddColumn { table, column } => {
+                write!(f, "+ add column {}.{} {}", table, column.name, column.data_type)
+            }
+            SchemaChange::ChangeColumnType { table, column, from, to } => {
+                write!(f, "! can not change column {}.{} from {} to {}", table, column, from, to)
+            }
+            SchemaChange::RemoveColumn { table, column } if column.not_null => write!(
+                f,
+                "! can not remove required column {}.{} as inserts would fail",
+                table, column.name
+            ),
+            SchemaChange::RemoveColumn { table, column } => write!(
+                f,
+                "- column {}.{} is no longer used and is left in place",
+                table, column.name
+            ),
+            SchemaChange::RemoveTable { table } => {
+                write!(f, "- table {} is no longer used and is left in place", table)
+            }
+            SchemaChange::ChangePartitioning { table, from, to } => write!(
+                f,
+                "! can not change table {} from {} to {}",
+                table,
+                from.as_deref().unwrap_or("not partitioned"),
+                to.as_deref().unwrap_or("not partitioned")
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MigrationPlan {
+    pub changes: Vec<SchemaChange>,
+}
+
+impl MigrationPlan {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.changes.iter().any(|change| change.is_blocking())
+    }
+
+    pub fn sql(&self) -> String {
+        self.changes.iter().filter_map(|change| change.sql()).collect::<Vec<_>>().join("\n")
+    }
+}
+
+impl fmt::Display for MigrationPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return write!(f, "The database schema is up to date");
+        }
+
+        let changes =
+            self.changes.iter().map(|change| change.to_string()).collect::<Vec<_>>().join("\n");
+        write!(f, "{}", changes)
+    }
+}
+
+/// Compares the schema applied last time with the schema the manifest and ABIs need now
+pub fn plan_migration(applied: &[TableSchema], expected: &[TableSchema]) -> MigrationPlan {
+    let mut changes = vec![];
+
+    for table in expected {
+        let applied_table = match applied.iter().find(|applied| applied.name == table.name) {
+            Some(applied_table) => applied_table,
+            None => {
+                changes.push(SchemaChange::CreateTable { table: table.name.clone() });
+                continue;
+            }
+        };
+
+        // an existing table can not be moved in or out of partitions
+        if applied_table.partitioning != table.partitioning {
+            changes.push(SchemaChange::ChangePartitioning {
+                table: table.name.clone(),
+                from: applied_table.partitioning.clone(),
+                to: table.partitioning.clone(),
+            });
+        }
+
+        for column in &table.columns {
+            match applied_table.column(&column.name) {
+                None => changes.push(SchemaChange::AddColumn {
+                    table: table.name.clone(),
+                    column: column.clone(),
+                }),
+                Some(applied_column) if applied_column.data_type != column.data_type => changes
+                    .push(SchemaChange::ChangeColumnType {
+                        table: table.name.clone(),
+                        column: column.name.clone(),
+                        from: applied_column.data_type.clone(),
+                        to: column.data_type.clone(),
+                    }),
+                Some(_) => {}
+            }
+        }
+
+        for applied_column in &applied_table.columns {
+            if table.column(&applied_column.name).is_none() {
+                changes.push(SchemaChange::RemoveColumn {
+                    table: table.name.clone(),
+                    column: applied_column.clone(),
+                });
+            }
+        }
+    }
+
+    for applied_table in applied {
+        if !expected.iter().any(|table| table.name == applied_table.name) {
+            changes.push(SchemaChange::RemoveTable { table: applied_table.name.clone() });
+        }
+    }
+
+    MigrationPlan { changes }
+}
+
+fn applied_schema_table_name(indexer_name: &str) -> String {
+    format!("rindexer_internal.{}_applied_schema", camel_to_snake(indexer_name))
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum MigrationError {
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("Could not read the applied schema: {0}")]
+    InvalidAppliedSchema(#[from] serde_json::Error),
+
+    #[error("{0}")]
+    GeneratingSchema(#[from] GenerateTablesForIndexerSqlError),
+
+    #[error("The database schema can not be migrated safely, resolve these changes by hand or delete the data with `rindexer delete` and resync:\n{0}")]
+    UnsafeMigration(MigrationPlan),
+}
+
+/// The schema stored the last time tables were set up, `None` if it has never been stored
+pub async fn read_applied_schema(
+    client: &PostgresClient,
+    indexer_name: &str,
+) -> Result<Option<Vec<TableSchema>>, MigrationError> {
+    let table_name = applied_schema_table_name(indexer_name);
+    let exists =
+        client.query_one(&format!("SELECT to_regclass('{}') IS NOT NULL", table_name), &[]).await?;
+    if !exists.get::<_, bool>(0) {
+        return Ok(None);
+    }
+
+    let row = client
+        .query_one_or_none(&format!("SELECT value FROM {} WHERE key = 1", table_name), &[])
+        .await?;
+
+    match row {
+        Some(row) => {
+            let value: &str = row.get(0);
+            Ok(Some(serde_json::from_str(value)?))
+        }
+        None => Ok(None),
+    }
+}
+
+pub async fn save_applied_schema(
+    client: &PostgresClient,
+    indexer_name: &str,
+    schema: &[TableSchema],
+) -> Result<(), MigrationError> {
+    let schema_json = serde_json::to_string(schema)?;
+    client
+        .execute(
+            &format!(
+                "INSERT INTO {} (key, value) VALUES (1, $1) ON CONFLICT (key) DO UPDATE SET value = $1",
+                applied_schema_table_name(indexer_name)
+            ),
+            &[&schema_json],
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Works out the migration needed for the current manifest and ABIs, a database which has
+/// never stored its schema is assumed to match the manifest
+pub async fn plan_indexer_migration(
+    client: &PostgresClient,
+    project_path: &Path,
+    indexer: &Indexer,
+    partitioned_tables: &PartitionedTables,
+) -> Result<(MigrationPlan, Vec<TableSchema>), MigrationError> {
+    let expected = generate_indexer_schema(project_path, indexer, partitioned_tables)?;
+    let plan = match read_applied_schema(client, &indexer.name).await? {
+        Some(applied) => plan_migration(&applied, &expected),
+        None => MigrationPlan::default(),
+    };
+
+    Ok((plan, expected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(columns: Vec<ColumnSchema>) -> TableSchema {
+        TableSchema { name: "indexer_token.transfer".to_string(), columns, partitioning: None }
+    }
+
+    #[test]
+    fn test_plan_migration_safe_changes() {
+        let applied = vec![
+            table(vec![ColumnSchema::new("from", "CHAR(42)", false)]),
+            TableSchema {
+                name: "indexer_token.approval".to_string(),
+                columns: vec![],
+                partitioning: None,
+            },
+        ];
+        let expected = vec![
+            table(vec![
+                ColumnSchema::new("from", "CHAR(42)", false),
+                ColumnSchema::new("value", "VARCHAR(78)", false),
+            ]),
+            TableSchema {
+                name: "indexer_token.holders".to_string(),
+                columns: vec![],
+                partitioning: None,
+            },
+        ];
+
+        let plan = plan_migration(&applied, &expected);
+        assert!(!plan.is_blocked());
+        assert_eq!(
+            plan.to_string(),
+            "+ add column indexer_token.transfer.value VARCHAR(78)\n\
+            + create table indexer_token.holders\n\
+            - table indexer_token.approval is no longer used and is left in place"
+        );
+        assert_eq!(
+            plan.sql(),
+            "ALTER TABLE indexer_token.transfer ADD COLUMN IF NOT EXISTS \"value\" VARCHAR(78);"
+        );
+    }
+
+    #[test]
+    fn test_plan_migration_blocking_changes() {
+        let applied = vec![table(vec![
+            ColumnSchema::new("value", "NUMERIC", false),
+            ColumnSchema::new("holder", "TEXT", true),
+        ])];
+        let mut expected = vec![table(vec![ColumnSchema::new("value", "VARCHAR(78)", false)])];
+        expected[0].partitioning = Some("partitioned every 1000000 blocks".to_string());
+
+        let plan = plan_migration(&applied, &expected);
+        assert!(plan.is_blocked());
+        assert_eq!(plan.changes.len(), 3);
+        assert_eq!(
+            plan.changes[0].to_string(),
+            "! can not change table indexer_token.transfer from not partitioned to partitioned \
+            every 1000000 blocks"
+        );
+        assert!(plan.sql().is_empty());
+
+        assert!(plan_migration(&expected, &expected).is_empty());
+    }
+}
@@ -1,6 +1,8 @@
 pub mod client;
 pub mod generate;
 pub mod indexes;
+pub mod migrations;
+pub mod partitions;
 pub mod relationship;
 pub mod setup;
 pub mod sql_type_wrapper;
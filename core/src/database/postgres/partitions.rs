@@ -0,0 +1,409 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use once_cell::sync::Lazy;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::{
+    database::postgres::{
+        client::{PostgresClient, PostgresError},
+        generate::generate_event_table_full_name,
+        sql_type_wrapper::EthereumSqlTypeWrapper,
+    },
+    manifest::{
+        contract::RollupInterval,
+        core::Manifest,
+        storage::{ContractEventsPartitions, EventPartition, PartitionBy},
+    },
+};
+
+/// How an event table is partitioned once it is known if TimescaleDB is installed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TablePartitioning {
+    BlockRange { blocks: u64 },
+    Time { interval: RollupInterval },
+    Hypertable { interval: RollupInterval },
+}
+
+impl TablePartitioning {
+    fn new(partition: &EventPartition, timescale_available: bool) -> Self {
+        let interval = partition.interval.unwrap_or(RollupInterval::Month);
+        match partition.by {
+            PartitionBy::BlockNumber => {
+                TablePartitioning::BlockRange { blocks: partition.blocks.unwrap_or(1_000_000) }
+            }
+            PartitionBy::Timestamp if partition.timescale.unwrap_or_default() => {
+                if timescale_available {
+                    TablePartitioning::Hypertable { interval }
+                } else {
+                    warn!(
+                        "TimescaleDB is not installed so {} is partitioned by postgres instead of being a hypertable",
+                        partition.name
+                    );
+                    TablePartitioning::Time { interval }
+                }
+            }
+            PartitionBy::Timestamp => TablePartitioning::Time { interval },
+        }
+    }
+
+    /// The column the table is partitioned on, it has to be part of the primary key
+    pub fn column(&self) -> &'static str {
+        match self {
+            TablePartitioning::BlockRange { .. } => "block_number",
+            TablePartitioning::Time { .. } | TablePartitioning::Hypertable { .. } => {
+                "block_timestamp"
+            }
+        }
+    }
+
+    /// Appended to the create table, hypertables are plain tables converted after they exist
+    pub fn create_table_clause(&self) -> String {
+        match self {
+            TablePartitioning::Hypertable { .. } => "".to_string(),
+            _ => format!(" PARTITION BY RANGE ({})", self.column()),
+        }
+    }
+
+    /// Run after the create table, TimescaleDB creates the chunks of a hypertable by itself
+    pub fn after_create_table_sql(&self, table_name: &str) -> Option<String> {
+        match self {
+            TablePartitioning::Hypertable { interval } => Some(format!(
+                "SELECT create_hypertable('{}', 'block_timestamp', chunk_time_interval => INTERVAL '1 {}', if_not_exists => TRUE);",
+                table_name,
+                interval.name()
+            )),
+            _ => None,
+        }
+    }
+
+    /// The start of the partition a value of the partition column falls in, seconds for time
+    fn partition_start(&self, value: &EthereumSqlTypeWrapper) -> Option<i64> {
+        match (self, value) {
+            (TablePartitioning::BlockRange { blocks }, EthereumSqlTypeWrapper::U64(block)) => {
+                Some((block.as_u64() / blocks * blocks) as i64)
+            }
+            (TablePartitioning::Time { interval }, EthereumSqlTypeWrapper::DateTime(timestamp)) => {
+                Some(interval.bucket_start(timestamp.timestamp() as u64).timestamp())
+            }
+            _ => None,
+        }
+    }
+
+    /// The start of a partition from the suffix of its name
+    fn parse_partition_suffix(&self, suffix: &str) -> Option<i64> {
+        match self {
+            TablePartitioning::BlockRange { .. } => suffix.parse().ok(),
+            TablePartitioning::Time { .. } | TablePartitioning::Hypertable { .. } => {
+                NaiveDateTime::parse_from_str(suffix, "%Y%m%d%H%M")
+                    .ok()
+                    .map(|start| start.and_utc().timestamp())
+            }
+        }
+    }
+
+    /// The exclusive end of the partition which starts at start
+    fn partition_end(&self, start: i64) -> i64 {
+        match self {
+            TablePartitioning::BlockRange { blocks } => start + *blocks as i64,
+            TablePartitioning::Time { interval } | TablePartitioning::Hypertable { interval } => {
+                let start = DateTime::from_timestamp(start, 0).unwrap_or_default();
+                interval.next_bucket_start(start).timestamp()
+            }
+        }
+    }
+
+    fn partition_name(&self, table_name: &str, start: i64) -> String {
+        let suffix = match self {
+            TablePartitioning::BlockRange { .. } => start.to_string(),
+            TablePartitioning::Time { .. } | TablePartitioning::Hypertable { .. } => {
+                DateTime::from_timestamp(start, 0)
+                    .unwrap_or_default()
+                    .format("%Y%m%d%H%M")
+                    .to_string()
+            }
+        };
+        format!("{}_p{}", table_name, suffix)
+    }
+
+    fn create_partition_sql(&self, table_name: &str, start: i64) -> String {
+        let (from, to) = match self {
+            TablePartitioning::BlockRange { .. } => {
+                (start.to_string(), self.partition_end(start).to_string())
+            }
+            TablePartitioning::Time { interval } | TablePartitioning::Hypertable { interval } => {
+                let start = DateTime::from_timestamp(start, 0).unwrap_or_default();
+                (
+                    format!("'{}'", start.to_rfc3339()),
+                    format!("'{}'", interval.next_bucket_start(start).to_rfc3339()),
+                )
+            }
+        };
+        let partition_name = self.partition_name(table_name, start);
+
+        // partitions are hidden from graphql which only sees the partitioned table
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} PARTITION OF {} FOR VALUES FROM ({}) TO ({});\
+            COMMENT ON TABLE {} IS E'@omit';",
+            partition_name, table_name, from, to, partition_name
+        )
+    }
+}
+
+impl fmt::Display for TablePartitioning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TablePartitioning::BlockRange { blocks } => {
+                write!(f, "partitioned every {} blocks", blocks)
+            }
+            TablePartitioning::Time { interval } => {
+                write!(f, "partitioned every {}", interval.name())
+            }
+            TablePartitioning::Hypertable { interval } => {
+                write!(f, "hypertable with a chunk every {}", interval.name())
+            }
+        }
+    }
+}
+
+/// The partitioning of every partitioned event table keyed by the full table name
+#[derive(Debug, Clone, Default)]
+pub struct PartitionedTables {
+    tables: HashMap<String, TablePartitioning>,
+}
+
+impl PartitionedTables {
+    pub fn new(
+        indexer_name: &str,
+        partitions: &[ContractEventsPartitions],
+        timescale_available: bool,
+    ) -> Self {
+        let tables = partitions
+            .iter()
+            .flat_map(|contract| {
+                contract.events.iter().map(move |event| {
+                    (
+                        generate_event_table_full_name(indexer_name, &contract.name, &event.name),
+                        TablePartitioning::new(event, timescale_available),
+                    )
+                })
+            })
+            .collect();
+
+        Self { tables }
+    }
+
+    /// Checks if TimescaleDB is installed when any table wants to be a hypertable
+    pub async fn load(client: &PostgresClient, manifest: &Manifest) -> Result<Self, PostgresError> {
+        let partitions = manifest.storage.postgres_partitions();
+        let wants_timescale = partitions
+            .iter()
+            .flat_map(|contract| &contract.events)
+            .any(|event| event.timescale.unwrap_or_default());
+
+        let timescale_available = if wants_timescale {
+            client
+                .query_one(
+                    "SELECT EXISTS (SELECT 1 FROM pg_extension WHERE extname = 'timescaledb')",
+                    &[],
+                )
+                .await?
+                .get::<_, bool>(0)
+        } else {
+            false
+        };
+
+        Ok(Self::new(&manifest.name, partitions, timescale_available))
+    }
+
+    pub fn get(&self, table_name: &str) -> Option<TablePartitioning> {
+        self.tables.get(table_name).copied()
+    }
+}
+
+struct PostgresPartitions {
+    partitioning: TablePartitioning,
+    created: Mutex<HashSet<i64>>,
+}
+
+// inserts go through many clients, including the ones generated for rust projects, so the
+// tables postgres partitions are shared by everything in the process
+static POSTGRES_PARTITIONS: Lazy<RwLock<HashMap<String, Arc<PostgresPartitions>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Remembers the tables postgres partitions so their partitions can be created as indexing
+/// reaches them
+pub fn register_partitioned_tables(partitioned_tables: &PartitionedTables) {
+    let mut registered = POSTGRES_PARTITIONS.write().unwrap();
+    for (table_name, partitioning) in &partitioned_tables.tables {
+        if matches!(partitioning, TablePartitioning::Hypertable { .. }) {
+            continue;
+        }
+
+        info!("Event table {} is {}", table_name, partitioning);
+        registered.insert(
+            table_name.clone(),
+            Arc::new(PostgresPartitions {
+                partitioning: *partitioning,
+                created: Mutex::new(HashSet::new()),
+            }),
+        );
+    }
+}
+
+/// Creates the partitions the rows are about to be inserted into if they do not exist yet
+pub async fn create_missing_partitions(
+    client: &PostgresClient,
+    table_name: &str,
+    column_names: &[String],
+    rows: &[Vec<EthereumSqlTypeWrapper>],
+) -> Result<(), PostgresError> {
+    let partitions = match POSTGRES_PARTITIONS.read().unwrap().get(table_name) {
+        Some(partitions) => Arc::clone(partitions),
+        None => return Ok(()),
+    };
+
+    let column_index =
+        match column_names.iter().position(|name| name == partitions.partitioning.column()) {
+            Some(column_index) => column_index,
+            None => return Ok(()),
+        };
+
+    let starts: HashSet<i64> = rows
+        .iter()
+        .filter_map(|row| row.get(column_index))
+        .filter_map(|value| partitions.partitioning.partition_start(value))
+        .collect();
+
+    // held while creating so two inserts can not race to create the same partition
+    let mut created = partitions.created.lock().await;
+    for start in starts {
+        if created.contains(&start) {
+            continue;
+        }
+
+        client
+            .batch_execute(&partitions.partitioning.create_partition_sql(table_name, start))
+            .await?;
+        created.insert(start);
+    }
+
+    Ok(())
+}
+
+/// Drops the partitions of a table which only hold rows from before the cutoff, which is a block
+/// number or seconds for time partitions, returning how many were dropped
+pub async fn drop_partitions_before(
+    client: &PostgresClient,
+    table_name: &str,
+    partitioning: TablePartitioning,
+    cutoff: i64,
+) -> Result<usize, PostgresError> {
+    // TimescaleDB tracks the chunks of a hypertable itself
+    if matches!(partitioning, TablePartitioning::Hypertable { .. }) {
+        let dropped = client
+            .query(
+                &format!(
+                    "SELECT drop_chunks('{}', older_than => to_timestamp({}))",
+                    table_name, cutoff
+                ),
+                &[],
+            )
+            .await?;
+        return Ok(dropped.len());
+    }
+
+    let (schema_name, name) = table_name.split_once('.').unwrap_or(("public", table_name));
+    let rows = client
+        .query(
+            "SELECT child.relname FROM pg_inherits \
+            JOIN pg_class parent ON pg_inherits.inhparent = parent.oid \
+            JOIN pg_class child ON pg_inherits.inhrelid = child.oid \
+            JOIN pg_namespace ON parent.relnamespace = pg_namespace.oid \
+            WHERE pg_namespace.nspname = $1 AND parent.relname = $2",
+            &[&schema_name, &name],
+        )
+        .await?;
+
+    let prefix = format!("{}_p", name);
+    let expired: Vec<i64> = rows
+        .iter()
+        .filter_map(|row| {
+            partitioning.parse_partition_suffix(row.get::<_, &str>(0).strip_prefix(&prefix)?)
+        })
+        .filter(|start| partitioning.partition_end(*start) <= cutoff)
+        .collect();
+
+    let registered = POSTGRES_PARTITIONS.read().unwrap().get(table_name).map(Arc::clone);
+    for start in &expired {
+        client
+            .batch_execute(&format!(
+                "DROP TABLE IF EXISTS {};",
+                partitioning.partition_name(table_name, *start)
+            ))
+            .await?;
+
+        // a partition dropped here has to be created again if rows for it are ever inserted
+        if let Some(registered) = &registered {
+            registered.created.lock().await.remove(start);
+        }
+    }
+
+    Ok(expired.len())
+}
+
+/// The time partition column value for a block timestamp
+pub fn block_timestamp_value(block_timestamp: u64) -> EthereumSqlTypeWrapper {
+    EthereumSqlTypeWrapper::DateTime(
+        DateTime::<Utc>::from_timestamp(block_timestamp as i64, 0).unwrap_or_default(),
+    )
+}
+
+/// The block timestamp column as written to csv
+pub fn block_timestamp_csv_value(block_timestamp: u64) -> String {
+    DateTime::<Utc>::from_timestamp(block_timestamp as i64, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U64;
+
+    use super::*;
+
+    #[test]
+    fn test_create_partition_sql() {
+        let block_range = TablePartitioning::BlockRange { blocks: 1_000_000 };
+        let start = block_range.partition_start(&EthereumSqlTypeWrapper::U64(U64::from(2_500_000)));
+        assert_eq!(start, Some(2_000_000));
+        assert_eq!(
+            block_range.create_partition_sql("indexer_token.transfer", 2_000_000),
+            "CREATE TABLE IF NOT EXISTS indexer_token.transfer_p2000000 PARTITION OF \
+            indexer_token.transfer FOR VALUES FROM (2000000) TO (3000000);\
+            COMMENT ON TABLE indexer_token.transfer_p2000000 IS E'@omit';"
+        );
+
+        // Wednesday 2024-01-17 13:45:30 UTC
+        let time = TablePartitioning::Time { interval: RollupInterval::Month };
+        let start = time.partition_start(&block_timestamp_value(1705499130)).unwrap();
+        assert_eq!(
+            time.create_partition_sql("indexer_token.transfer", start),
+            "CREATE TABLE IF NOT EXISTS indexer_token.transfer_p202401010000 PARTITION OF \
+            indexer_token.transfer FOR VALUES FROM ('2024-01-01T00:00:00+00:00') TO \
+            ('2024-02-01T00:00:00+00:00');\
+            COMMENT ON TABLE indexer_token.transfer_p202401010000 IS E'@omit';"
+        );
+
+        // retention reads the range of a partition back from its name
+        assert_eq!(time.parse_partition_suffix("202401010000"), Some(start));
+        assert_eq!(time.partition_end(start), 1706745600);
+        assert_eq!(block_range.parse_partition_suffix("2000000"), Some(2_000_000));
+        assert_eq!(block_range.partition_end(2_000_000), 3_000_000);
+    }
+}
@@ -76,15 +76,6 @@ pub enum ApplyAllRelationships {
 }
 
 impl Relationship {
-    pub fn has_cross_contract_dependency(relationships: &[Relationship]) -> bool {
-        for relationship in relationships {
-            if relationship.linked_to.contract_name != relationship.contract_name {
-                return true;
-            }
-        }
-        false
-    }
-
     fn apply_foreign_key_construct_sql(&self) -> Code {
         Code::new(format!(
             r#"
@@ -353,14 +344,14 @@ pub async fn drop_last_known_relationships(
     Ok(())
 }
 
-pub async fn create_relationships(
+/// Resolves the relationships in the yaml to the event tables and columns they link
+pub fn resolve_relationships(
     project_path: &Path,
     manifest_name: &str,
     contracts: &[Contract],
     foreign_keys: &[ForeignKeys],
 ) -> Result<Vec<Relationship>, CreateRelationshipError> {
     let mut relationships = vec![];
-    let mut dropping_sql: Vec<Code> = vec![];
     for foreign_key in foreign_keys {
         let contract = contracts.iter().find(|c| c.name == foreign_key.contract_name);
 
@@ -372,13 +363,13 @@ pub async fn create_relationships(
                 )));
             }
             Some(contract) => {
-                let abi_items = ABIItem::read_abi_items(project_path, contract)?;
+                let events = ABIItem::read_abi_events(project_path, contract)?;
 
                 for linked_key in &foreign_key.foreign_keys {
                     let parameter_mapping =
                         foreign_key.event_input_name.split('.').collect::<Vec<&str>>();
                     let abi_parameter = get_abi_item_with_db_map(
-                        &abi_items,
+                        &events,
                         &foreign_key.event_name,
                         &parameter_mapping,
                     )?;
@@ -393,12 +384,12 @@ pub async fn create_relationships(
                             ))
                         })?;
 
-                    let linked_abi_items =
-                        ABIItem::read_abi_items(project_path, linked_key_contract)?;
+                    let linked_events =
+                        ABIItem::read_abi_events(project_path, linked_key_contract)?;
                     let linked_parameter_mapping =
                         linked_key.event_input_name.split('.').collect::<Vec<&str>>();
                     let linked_abi_parameter = get_abi_item_with_db_map(
-                        &linked_abi_items,
+                        &linked_events,
                         &linked_key.event_name,
                         &linked_parameter_mapping,
                     )?;
@@ -440,14 +431,30 @@ pub async fn create_relationships(
                         },
                     };
 
-                    let sql = relationship.drop_sql().await?;
-                    dropping_sql.extend(sql);
                     relationships.push(relationship);
                 }
             }
         }
     }
 
+    Ok(relationships)
+}
+
+pub async fn create_relationships(
+    project_path: &Path,
+    manifest_name: &str,
+    contracts: &[Contract],
+    foreign_keys: &[ForeignKeys],
+) -> Result<Vec<Relationship>, CreateRelationshipError> {
+    let relationships =
+        resolve_relationships(project_path, manifest_name, contracts, foreign_keys)?;
+
+    let mut dropping_sql: Vec<Code> = vec![];
+    for relationship in &relationships {
+        let sql = relationship.drop_sql().await?;
+        dropping_sql.extend(sql);
+    }
+
     let relationships_dropping_sql_json = serde_json::to_string(
         &dropping_sql.iter().map(|code| code.as_str()).collect::<Vec<&str>>(),
     )?;
@@ -6,6 +6,8 @@ use crate::{
     database::postgres::{
         client::{PostgresClient, PostgresConnectionError, PostgresError},
         generate::{generate_tables_for_indexer_sql, GenerateTablesForIndexerSqlError},
+        migrations::{plan_indexer_migration, save_applied_schema, MigrationError, MigrationPlan},
+        partitions::{register_partitioned_tables, PartitionedTables},
     },
     drop_tables_for_indexer_sql,
     manifest::core::Manifest,
@@ -21,6 +23,9 @@ pub enum SetupPostgresError {
 
     #[error("Error creating tables for indexer: {0}")]
     GeneratingTables(#[from] GenerateTablesForIndexerSqlError),
+
+    #[error("{0}")]
+    Migration(#[from] MigrationError),
 }
 
 pub async fn setup_postgres(
@@ -30,8 +35,6 @@ pub async fn setup_postgres(
     info!("Setting up postgres");
     let client = PostgresClient::new().await?;
 
-    let disable_event_tables = manifest.storage.postgres_disable_create_tables();
-
     if manifest.storage.postgres_drop_each_run() {
         info!(
             "`drop_each_run` enabled so dropping all data for {} before starting",
@@ -42,23 +45,67 @@ pub async fn setup_postgres(
         info!("Dropped all data for {}", manifest.name);
     }
 
+    migrate_postgres(&client, project_path, manifest).await?;
+
+    Ok(client)
+}
+
+/// Creates any missing tables and applies the safe schema changes since the last run, refusing
+/// to touch the database if any change is unsafe
+pub async fn migrate_postgres(
+    client: &PostgresClient,
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<MigrationPlan, SetupPostgresError> {
+    let indexer = manifest.to_indexer();
+    let disable_event_tables = manifest.storage.postgres_disable_create_tables();
+    let partitioned_tables = PartitionedTables::load(client, manifest).await?;
+
+    // tables which do not exist are created below so the plan only has to change existing tables
+    let mut migration = None;
     if !disable_event_tables {
+        let (plan, schema) =
+            plan_indexer_migration(client, project_path, &indexer, &partitioned_tables).await?;
+        if plan.is_blocked() {
+            return Err(MigrationError::UnsafeMigration(plan).into());
+        }
+        if !plan.is_empty() {
+            info!("Migrating database schema for {}:\n{}", manifest.name, plan);
+        }
+        migration = Some((plan, schema));
+
         info!("Creating tables for {}", manifest.name);
     } else {
         info!("Creating internal rindexer tables for {}", manifest.name);
     }
     let sql = generate_tables_for_indexer_sql(
         project_path,
-        &manifest.to_indexer(),
+        &indexer,
         disable_event_tables,
+        &partitioned_tables,
     )?;
     debug!("{}", sql);
     client.batch_execute(sql.as_str()).await?;
+
+    let plan = match migration {
+        Some((plan, schema)) => {
+            let migration_sql = plan.sql();
+            if !migration_sql.is_empty() {
+                debug!("{}", migration_sql);
+                client.batch_execute(&migration_sql).await?;
+            }
+            save_applied_schema(client, &indexer.name, &schema).await?;
+            plan
+        }
+        None => MigrationPlan::default(),
+    };
+
     if !disable_event_tables {
+        register_partitioned_tables(&partitioned_tables);
         info!("Created tables for {}", manifest.name);
     } else {
         info!("Created internal rindexer tables for {}", manifest.name);
     }
 
-    Ok(client)
+    Ok(plan)
 }
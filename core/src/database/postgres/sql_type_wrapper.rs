@@ -13,7 +13,11 @@ use serde_json::{json, Value};
 use tokio_postgres::types::{to_sql_checked, IsNull, ToSql, Type as PgType};
 use tracing::error;
 
-use crate::{abi::ABIInput, event::callback_registry::TxInformation};
+use crate::{
+    abi::{ABIInput, JSONB_TUPLE_TYPE},
+    event::callback_registry::TxInformation,
+    manifest::contract::NumericColumn,
+};
 
 #[derive(Debug, Clone)]
 pub enum EthereumSqlTypeWrapper {
@@ -106,6 +110,12 @@ pub enum EthereumSqlTypeWrapper {
     DateTime(DateTime<Utc>),
 
     JSONB(Value),
+
+    // Decimal string, empty is NULL
+    NumericNullable(String),
+
+    // Integer string stored divided by 10^decimals, the event data keeps the integer
+    NumericScaled(String, u32),
 }
 
 impl EthereumSqlTypeWrapper {
@@ -200,6 +210,8 @@ impl EthereumSqlTypeWrapper {
             EthereumSqlTypeWrapper::DateTime(_) => "DateTime",
 
             EthereumSqlTypeWrapper::JSONB(_) => "JSONB",
+            EthereumSqlTypeWrapper::NumericNullable(_) => "NumericNullable",
+            EthereumSqlTypeWrapper::NumericScaled(..) => "NumericScaled",
         }
     }
 
@@ -304,6 +316,8 @@ impl EthereumSqlTypeWrapper {
             EthereumSqlTypeWrapper::DateTime(_) => PgType::TIMESTAMPTZ,
 
             EthereumSqlTypeWrapper::JSONB(_) => PgType::JSONB,
+            EthereumSqlTypeWrapper::NumericNullable(_) |
+            EthereumSqlTypeWrapper::NumericScaled(..) => PgType::NUMERIC,
         }
     }
 
@@ -371,6 +385,56 @@ impl EthereumSqlTypeWrapper {
         Ok(IsNull::No)
     }
 
+    /// Writes a decimal string such as `-1234.5` in the postgres binary NUMERIC format which is
+    /// base 10000 digit groups with a weight for where the decimal point is
+    fn write_decimal_string_to_postgres(
+        value: &str,
+        out: &mut BytesMut,
+    ) -> Result<IsNull, Box<dyn std::error::Error + Sync + Send>> {
+        let (is_negative, value) = match value.strip_prefix('-') {
+            Some(value) => (true, value),
+            None => (false, value),
+        };
+        let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+        if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(format!("Invalid numeric value: {}", value).into());
+        }
+
+        let whole = whole.trim_start_matches('0');
+        let whole = format!("{}{}", "0".repeat((4 - whole.len() % 4) % 4), whole);
+        let padded_fraction = format!("{}{}", fraction, "0".repeat((4 - fraction.len() % 4) % 4));
+
+        let mut groups: Vec<i16> = whole
+            .as_bytes()
+            .chunks(4)
+            .chain(padded_fraction.as_bytes().chunks(4))
+            .map(|chunk| std::str::from_utf8(chunk).map(|c| c.parse::<i16>()))
+            .collect::<Result<Result<_, _>, _>>()??;
+        let mut weight = (whole.len() / 4) as i16 - 1;
+
+        while groups.first() == Some(&0) {
+            groups.remove(0);
+            weight -= 1;
+        }
+        while groups.last() == Some(&0) {
+            groups.pop();
+        }
+        if groups.is_empty() {
+            weight = 0;
+        }
+
+        out.put_i16(groups.len() as i16); // ndigits
+        out.put_i16(weight); // weight
+        out.put_i16(if is_negative && !groups.is_empty() { 0x4000 } else { 0x0000 }); // sign
+        out.put_i16(fraction.len() as i16); // dscale
+
+        for group in groups {
+            out.put_i16(group);
+        }
+
+        Ok(IsNull::No)
+    }
+
     fn serialize_numeric_array<T>(
         values: &[T],
         out: &mut BytesMut,
@@ -795,6 +859,16 @@ impl ToSql for EthereumSqlTypeWrapper {
             }
             EthereumSqlTypeWrapper::DateTime(value) => value.to_sql(ty, out),
             EthereumSqlTypeWrapper::JSONB(value) => value.to_sql(ty, out),
+            EthereumSqlTypeWrapper::NumericNullable(value) => {
+                if value.is_empty() {
+                    return Ok(IsNull::Yes);
+                }
+
+                Self::write_decimal_string_to_postgres(value, out)
+            }
+            EthereumSqlTypeWrapper::NumericScaled(value, decimals) => {
+                Self::write_decimal_string_to_postgres(&scale_decimal_string(value, *decimals), out)
+            }
         }
     }
 
@@ -828,6 +902,7 @@ pub fn solidity_type_to_ethereum_sql_type_wrapper(
         } else {
             EthereumSqlTypeWrapper::Bool(false)
         }),
+        JSONB_TUPLE_TYPE => Some(EthereumSqlTypeWrapper::JSONB(Value::Null)),
         t if t.starts_with("bytes") => Some(if is_array {
             EthereumSqlTypeWrapper::VecBytes(Vec::new())
         } else {
@@ -950,6 +1025,12 @@ pub fn map_log_params_to_ethereum_wrapper(
     for (index, param) in params.iter().enumerate() {
         if let Some(abi_input) = abi_inputs.get(index) {
             match &param.value {
+                _ if abi_input.is_jsonb() => {
+                    wrappers.push(EthereumSqlTypeWrapper::JSONB(map_log_token_to_json(
+                        abi_input,
+                        &param.value,
+                    )));
+                }
                 Token::Tuple(tuple) => {
                     wrappers.extend(process_tuple(
                         abi_input
@@ -971,6 +1052,30 @@ pub fn map_log_params_to_ethereum_wrapper(
     wrappers
 }
 
+/// Builds the JSON document of a tuple stored as JSONB, components are keyed by their ABI name
+pub fn map_log_token_to_json(abi_input: &ABIInput, token: &Token) -> Value {
+    match token {
+        Token::Tuple(tokens) => Value::Object(
+            abi_input
+                .components
+                .iter()
+                .flatten()
+                .zip(tokens)
+                .map(|(component, token)| {
+                    (component.name.clone(), map_log_token_to_json(component, token))
+                })
+                .collect(),
+        ),
+        // the components of an array of tuples describe each item
+        Token::FixedArray(tokens) | Token::Array(tokens) if abi_input.components.is_some() => {
+            Value::Array(
+                tokens.iter().map(|token| map_log_token_to_json(abi_input, token)).collect(),
+            )
+        }
+        _ => ethereum_wrapper_to_json(&map_log_token_to_ethereum_wrapper(abi_input, token)),
+    }
+}
+
 fn process_tuple(abi_inputs: &[ABIInput], tokens: &[Token]) -> Vec<EthereumSqlTypeWrapper> {
     let mut wrappers = vec![];
 
@@ -1044,10 +1149,53 @@ fn convert_int(value: &Int, target_type: &EthereumSqlTypeWrapper) -> EthereumSql
     }
 }
 
+/// Moves the decimal point of an integer string left by the decimals, `-1500` with 3 decimals is
+/// `-1.500`
+pub fn scale_decimal_string(value: &str, decimals: u32) -> String {
+    if decimals == 0 {
+        return value.to_string();
+    }
+
+    let (sign, digits) = match value.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", value),
+    };
+    let decimals = decimals as usize;
+    let digits = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - decimals);
+    format!("{}{}.{}", sign, whole, fraction)
+}
+
+/// The value of an int input the contract stores in an overridden numeric column
+fn map_int_to_numeric_column(
+    abi_input: &ABIInput,
+    numeric_column: NumericColumn,
+    value: &Int,
+) -> EthereumSqlTypeWrapper {
+    let is_signed = abi_input.type_.starts_with("int");
+    let decimal = if is_signed { I256::from_raw(*value).to_string() } else { value.to_string() };
+
+    match numeric_column {
+        NumericColumn::Numeric => EthereumSqlTypeWrapper::NumericNullable(decimal),
+        NumericColumn::Text => EthereumSqlTypeWrapper::String(decimal),
+        NumericColumn::Bytea if is_signed => {
+            EthereumSqlTypeWrapper::I256Bytes(I256::from_raw(*value))
+        }
+        NumericColumn::Bytea => EthereumSqlTypeWrapper::U256Bytes(*value),
+        NumericColumn::Decimal(decimals) => {
+            EthereumSqlTypeWrapper::NumericScaled(decimal, decimals)
+        }
+    }
+}
+
 fn map_dynamic_int_to_ethereum_sql_type_wrapper(
     abi_input: &ABIInput,
     value: &Int,
 ) -> EthereumSqlTypeWrapper {
+    if let Some(numeric_column) = abi_input.numeric_column() {
+        return map_int_to_numeric_column(abi_input, numeric_column, value);
+    }
+
     let sql_type_wrapper = solidity_type_to_ethereum_sql_type_wrapper(&abi_input.type_);
     if let Some(target_type) = sql_type_wrapper {
         convert_int(value, &target_type)
@@ -1326,6 +1474,100 @@ fn count_components(components: &[ABIInput]) -> usize {
         .sum()
 }
 
+pub fn ethereum_wrapper_to_json(wrapper: &EthereumSqlTypeWrapper) -> Value {
+    match wrapper {
+        EthereumSqlTypeWrapper::U64(u) => json!(u),
+        EthereumSqlTypeWrapper::VecU64(u64s) => json!(u64s),
+        EthereumSqlTypeWrapper::I64(i) => json!(i),
+        EthereumSqlTypeWrapper::VecI64(i64s) => json!(i64s),
+        EthereumSqlTypeWrapper::U128(u) => json!(u.to_string()),
+        EthereumSqlTypeWrapper::VecU128(u128s) => {
+            json!(u128s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::I128(i) => json!(i.to_string()),
+        EthereumSqlTypeWrapper::VecI128(i128s) => {
+            json!(i128s.iter().map(|i| i.to_string()).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::U256(u) |
+        EthereumSqlTypeWrapper::U256Bytes(u) |
+        EthereumSqlTypeWrapper::U256Nullable(u) |
+        EthereumSqlTypeWrapper::U256BytesNullable(u) => {
+            json!(u.to_string())
+        }
+        EthereumSqlTypeWrapper::VecU256(u256s) | EthereumSqlTypeWrapper::VecU256Bytes(u256s) => {
+            json!(u256s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::I256(i) |
+        EthereumSqlTypeWrapper::I256Bytes(i) |
+        EthereumSqlTypeWrapper::I256Nullable(i) |
+        EthereumSqlTypeWrapper::I256BytesNullable(i) => {
+            json!(i.to_string())
+        }
+        EthereumSqlTypeWrapper::VecI256(i256s) | EthereumSqlTypeWrapper::VecI256Bytes(i256s) => {
+            json!(i256s.iter().map(|i| i.to_string()).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::U512(u) => json!(u.to_string()),
+        EthereumSqlTypeWrapper::VecU512(u512s) => {
+            json!(u512s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::H128(h) => json!(h),
+        EthereumSqlTypeWrapper::VecH128(h128s) => json!(h128s),
+        EthereumSqlTypeWrapper::H160(h) => json!(h),
+        EthereumSqlTypeWrapper::VecH160(h160s) => json!(h160s),
+        EthereumSqlTypeWrapper::H256(h) | EthereumSqlTypeWrapper::H256Bytes(h) => {
+            json!(h)
+        }
+        EthereumSqlTypeWrapper::VecH256(h256s) | EthereumSqlTypeWrapper::VecH256Bytes(h256s) => {
+            json!(h256s)
+        }
+        EthereumSqlTypeWrapper::H512(h) => json!(h),
+        EthereumSqlTypeWrapper::VecH512(h512s) => json!(h512s),
+        EthereumSqlTypeWrapper::Address(address) |
+        EthereumSqlTypeWrapper::AddressBytes(address) |
+        EthereumSqlTypeWrapper::AddressBytesNullable(address) |
+        EthereumSqlTypeWrapper::AddressNullable(address) => json!(address),
+        EthereumSqlTypeWrapper::VecAddress(addresses) |
+        EthereumSqlTypeWrapper::VecAddressBytes(addresses) => json!(addresses),
+        EthereumSqlTypeWrapper::Bool(b) => json!(b),
+        EthereumSqlTypeWrapper::VecBool(bools) => json!(bools),
+        EthereumSqlTypeWrapper::U32(u) => json!(u),
+        EthereumSqlTypeWrapper::VecU32(u32s) => json!(u32s),
+        EthereumSqlTypeWrapper::I32(i) => json!(i),
+        EthereumSqlTypeWrapper::VecI32(i32s) => json!(i32s),
+        EthereumSqlTypeWrapper::U16(u) => json!(u),
+        EthereumSqlTypeWrapper::VecU16(u16s) => json!(u16s),
+        EthereumSqlTypeWrapper::I16(i) => json!(i),
+        EthereumSqlTypeWrapper::VecI16(i16s) => json!(i16s),
+        EthereumSqlTypeWrapper::U8(u) => json!(u),
+        EthereumSqlTypeWrapper::VecU8(u8s) => json!(u8s),
+        EthereumSqlTypeWrapper::I8(i) => json!(i),
+        EthereumSqlTypeWrapper::VecI8(i8s) => json!(i8s),
+        EthereumSqlTypeWrapper::String(s) |
+        EthereumSqlTypeWrapper::StringNullable(s) |
+        EthereumSqlTypeWrapper::StringVarchar(s) |
+        EthereumSqlTypeWrapper::StringVarcharNullable(s) |
+        EthereumSqlTypeWrapper::StringChar(s) |
+        EthereumSqlTypeWrapper::StringCharNullable(s) => json!(s),
+        EthereumSqlTypeWrapper::VecString(strings) |
+        EthereumSqlTypeWrapper::VecStringVarchar(strings) |
+        EthereumSqlTypeWrapper::VecStringChar(strings) => json!(strings),
+        EthereumSqlTypeWrapper::Bytes(bytes) | EthereumSqlTypeWrapper::BytesNullable(bytes) => {
+            json!(hex::encode(bytes))
+        }
+        EthereumSqlTypeWrapper::VecBytes(bytes) => {
+            json!(bytes.iter().map(hex::encode).collect::<Vec<_>>())
+        }
+        EthereumSqlTypeWrapper::DateTime(date_time) => {
+            json!(date_time.to_rfc3339())
+        }
+        EthereumSqlTypeWrapper::JSONB(json) => json.clone(),
+        EthereumSqlTypeWrapper::NumericNullable(s) |
+        EthereumSqlTypeWrapper::NumericScaled(s, _) => {
+            json!(s)
+        }
+    }
+}
+
 pub fn map_ethereum_wrapper_to_json(
     abi_inputs: &[ABIInput],
     wrappers: &[EthereumSqlTypeWrapper],
@@ -1358,93 +1600,7 @@ pub fn map_ethereum_wrapper_to_json(
                 }
                 current_wrapper_index = total_properties;
             } else {
-                let value = match wrapper {
-                    EthereumSqlTypeWrapper::U64(u) => json!(u),
-                    EthereumSqlTypeWrapper::VecU64(u64s) => json!(u64s),
-                    EthereumSqlTypeWrapper::I64(i) => json!(i),
-                    EthereumSqlTypeWrapper::VecI64(i64s) => json!(i64s),
-                    EthereumSqlTypeWrapper::U128(u) => json!(u.to_string()),
-                    EthereumSqlTypeWrapper::VecU128(u128s) => {
-                        json!(u128s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
-                    }
-                    EthereumSqlTypeWrapper::I128(i) => json!(i.to_string()),
-                    EthereumSqlTypeWrapper::VecI128(i128s) => {
-                        json!(i128s.iter().map(|i| i.to_string()).collect::<Vec<_>>())
-                    }
-                    EthereumSqlTypeWrapper::U256(u) |
-                    EthereumSqlTypeWrapper::U256Bytes(u) |
-                    EthereumSqlTypeWrapper::U256Nullable(u) |
-                    EthereumSqlTypeWrapper::U256BytesNullable(u) => {
-                        json!(u.to_string())
-                    }
-                    EthereumSqlTypeWrapper::VecU256(u256s) |
-                    EthereumSqlTypeWrapper::VecU256Bytes(u256s) => {
-                        json!(u256s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
-                    }
-                    EthereumSqlTypeWrapper::I256(i) |
-                    EthereumSqlTypeWrapper::I256Bytes(i) |
-                    EthereumSqlTypeWrapper::I256Nullable(i) |
-                    EthereumSqlTypeWrapper::I256BytesNullable(i) => {
-                        json!(i.to_string())
-                    }
-                    EthereumSqlTypeWrapper::VecI256(i256s) |
-                    EthereumSqlTypeWrapper::VecI256Bytes(i256s) => {
-                        json!(i256s.iter().map(|i| i.to_string()).collect::<Vec<_>>())
-                    }
-                    EthereumSqlTypeWrapper::U512(u) => json!(u.to_string()),
-                    EthereumSqlTypeWrapper::VecU512(u512s) => {
-                        json!(u512s.iter().map(|u| u.to_string()).collect::<Vec<_>>())
-                    }
-                    EthereumSqlTypeWrapper::H128(h) => json!(h),
-                    EthereumSqlTypeWrapper::VecH128(h128s) => json!(h128s),
-                    EthereumSqlTypeWrapper::H160(h) => json!(h),
-                    EthereumSqlTypeWrapper::VecH160(h160s) => json!(h160s),
-                    EthereumSqlTypeWrapper::H256(h) | EthereumSqlTypeWrapper::H256Bytes(h) => {
-                        json!(h)
-                    }
-                    EthereumSqlTypeWrapper::VecH256(h256s) |
-                    EthereumSqlTypeWrapper::VecH256Bytes(h256s) => json!(h256s),
-                    EthereumSqlTypeWrapper::H512(h) => json!(h),
-                    EthereumSqlTypeWrapper::VecH512(h512s) => json!(h512s),
-                    EthereumSqlTypeWrapper::Address(address) |
-                    EthereumSqlTypeWrapper::AddressBytes(address) |
-                    EthereumSqlTypeWrapper::AddressBytesNullable(address) |
-                    EthereumSqlTypeWrapper::AddressNullable(address) => json!(address),
-                    EthereumSqlTypeWrapper::VecAddress(addresses) |
-                    EthereumSqlTypeWrapper::VecAddressBytes(addresses) => json!(addresses),
-                    EthereumSqlTypeWrapper::Bool(b) => json!(b),
-                    EthereumSqlTypeWrapper::VecBool(bools) => json!(bools),
-                    EthereumSqlTypeWrapper::U32(u) => json!(u),
-                    EthereumSqlTypeWrapper::VecU32(u32s) => json!(u32s),
-                    EthereumSqlTypeWrapper::I32(i) => json!(i),
-                    EthereumSqlTypeWrapper::VecI32(i32s) => json!(i32s),
-                    EthereumSqlTypeWrapper::U16(u) => json!(u),
-                    EthereumSqlTypeWrapper::VecU16(u16s) => json!(u16s),
-                    EthereumSqlTypeWrapper::I16(i) => json!(i),
-                    EthereumSqlTypeWrapper::VecI16(i16s) => json!(i16s),
-                    EthereumSqlTypeWrapper::U8(u) => json!(u),
-                    EthereumSqlTypeWrapper::VecU8(u8s) => json!(u8s),
-                    EthereumSqlTypeWrapper::I8(i) => json!(i),
-                    EthereumSqlTypeWrapper::VecI8(i8s) => json!(i8s),
-                    EthereumSqlTypeWrapper::String(s) |
-                    EthereumSqlTypeWrapper::StringNullable(s) |
-                    EthereumSqlTypeWrapper::StringVarchar(s) |
-                    EthereumSqlTypeWrapper::StringVarcharNullable(s) |
-                    EthereumSqlTypeWrapper::StringChar(s) |
-                    EthereumSqlTypeWrapper::StringCharNullable(s) => json!(s),
-                    EthereumSqlTypeWrapper::VecString(strings) |
-                    EthereumSqlTypeWrapper::VecStringVarchar(strings) |
-                    EthereumSqlTypeWrapper::VecStringChar(strings) => json!(strings),
-                    EthereumSqlTypeWrapper::Bytes(bytes) |
-                    EthereumSqlTypeWrapper::BytesNullable(bytes) => json!(hex::encode(bytes)),
-                    EthereumSqlTypeWrapper::VecBytes(bytes) => {
-                        json!(bytes.iter().map(hex::encode).collect::<Vec<_>>())
-                    }
-                    EthereumSqlTypeWrapper::DateTime(date_time) => {
-                        json!(date_time.to_rfc3339())
-                    }
-                    EthereumSqlTypeWrapper::JSONB(json) => json.clone(),
-                };
+                let value = ethereum_wrapper_to_json(wrapper);
                 result.insert(abi_input.name.clone(), value);
                 wrappers_index_processed.push(current_wrapper_index);
                 current_wrapper_index += 1;
@@ -1464,3 +1620,38 @@ pub fn map_ethereum_wrapper_to_json(
 
     Value::Object(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_int_inputs_in_numeric_columns() {
+        let input = |type_: &str| ABIInput {
+            indexed: Some(false),
+            name: "value".to_string(),
+            type_: type_.to_string(),
+            components: None,
+        };
+        let value = U256::from(1_500_000);
+
+        match map_dynamic_int_to_ethereum_sql_type_wrapper(&input("uint256:decimal6"), &value) {
+            EthereumSqlTypeWrapper::NumericScaled(value, decimals) => {
+                assert_eq!(scale_decimal_string(&value, decimals), "1.500000");
+            }
+            wrapper => panic!("unexpected wrapper {:?}", wrapper),
+        }
+        assert!(matches!(
+            map_dynamic_int_to_ethereum_sql_type_wrapper(&input("int256:text"), &(U256::MAX - 1)),
+            EthereumSqlTypeWrapper::String(value) if value == "-2"
+        ));
+        assert!(matches!(
+            map_dynamic_int_to_ethereum_sql_type_wrapper(&input("uint8:bytea"), &value),
+            EthereumSqlTypeWrapper::U256Bytes(_)
+        ));
+
+        assert_eq!(scale_decimal_string("-15", 3), "-0.015");
+        assert_eq!(scale_decimal_string("0", 2), "0.00");
+        assert_eq!(scale_decimal_string("123", 0), "123");
+    }
+}
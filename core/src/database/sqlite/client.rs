@@ -0,0 +1,219 @@
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rusqlite::{params_from_iter, types::Value, Connection};
+use tokio::task::JoinError;
+use tracing::debug;
+
+use crate::database::postgres::sql_type_wrapper::{
+    ethereum_wrapper_to_json, scale_decimal_string, EthereumSqlTypeWrapper,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SqliteConnectionError {
+    #[error("Could not create the directory for the sqlite database: {0}")]
+    CouldNotCreateDirectory(#[from] std::io::Error),
+
+    #[error("Can not open the sqlite database please make sure the path is correct: {0}")]
+    CanNotOpenSqlite(#[from] rusqlite::Error),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SqliteError {
+    #[error("SQLite error: {0}")]
+    SqliteError(#[from] rusqlite::Error),
+
+    #[error("SQLite task failed: {0}")]
+    TaskFailed(#[from] JoinError),
+}
+
+/// An embedded SQLite database, the connection is blocking so every call runs on the blocking
+/// thread pool
+#[derive(Clone)]
+pub struct SqliteClient {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl SqliteClient {
+    pub fn new(path: &Path) -> Result<Self, SqliteConnectionError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let connection = Connection::open(path)?;
+        // WAL lets the database be read while the indexer is writing to it
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.pragma_update(None, "synchronous", "NORMAL")?;
+        connection.busy_timeout(Duration::from_secs(30))?;
+
+        Ok(Self { connection: Arc::new(Mutex::new(connection)) })
+    }
+
+    async fn with_connection<T, F>(&self, f: F) -> Result<T, SqliteError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, rusqlite::Error> + Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+        let result = tokio::task::spawn_blocking(move || {
+            // a panic while holding the lock does not leave the connection in a bad state
+            let mut connection = connection.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut connection)
+        })
+        .await??;
+
+        Ok(result)
+    }
+
+    pub async fn execute_batch(&self, sql: &str) -> Result<(), SqliteError> {
+        debug!("{}", sql);
+        let sql = sql.to_string();
+        self.with_connection(move |connection| connection.execute_batch(&sql)).await
+    }
+
+    pub async fn execute(&self, sql: &str, params: Vec<Value>) -> Result<usize, SqliteError> {
+        let sql = sql.to_string();
+        self.with_connection(move |connection| connection.execute(&sql, params_from_iter(params)))
+            .await
+    }
+
+    pub async fn query(
+        &self,
+        sql: &str,
+        params: Vec<Value>,
+    ) -> Result<Vec<Vec<Value>>, SqliteError> {
+        let sql = sql.to_string();
+        self.with_connection(move |connection| {
+            let mut statement = connection.prepare(&sql)?;
+            let column_count = statement.column_count();
+            let rows = statement.query_map(params_from_iter(params), |row| {
+                (0..column_count).map(|index| row.get::<_, Value>(index)).collect()
+            })?;
+
+            rows.collect()
+        })
+        .await
+    }
+
+    /// Inserts all the rows in a single transaction so a batch is either fully written or not
+    /// written at all
+    pub async fn bulk_insert(
+        &self,
+        table_name: &str,
+        column_names: &[String],
+        bulk_data: &[Vec<EthereumSqlTypeWrapper>],
+    ) -> Result<usize, SqliteError> {
+        if bulk_data.is_empty() {
+            return Ok(0);
+        }
+
+        let sql = format!(
+            "INSERT INTO {} ({}) VALUES ({})",
+            table_name,
+            column_names.iter().map(|name| format!("\"{}\"", name)).collect::<Vec<_>>().join(", "),
+            vec!["?"; column_names.len()].join(", ")
+        );
+        let rows: Vec<Vec<Value>> = bulk_data
+            .iter()
+            .map(|row| row.iter().map(ethereum_wrapper_to_sqlite_value).collect())
+            .collect();
+
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            {
+                let mut statement = transaction.prepare_cached(&sql)?;
+                for row in &rows {
+                    statement.execute(params_from_iter(row))?;
+                }
+            }
+            transaction.commit()?;
+
+            Ok(rows.len())
+        })
+        .await
+    }
+}
+
+fn json_to_sqlite_value(value: serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Integer(b as i64),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            Some(i) => Value::Integer(i),
+            None => Value::Text(number.to_string()),
+        },
+        serde_json::Value::String(s) => Value::Text(s),
+        // arrays and tuples are stored as JSON so they can be read with the sqlite json functions
+        value => Value::Text(value.to_string()),
+    }
+}
+
+/// Integers which can be larger than an i64 are stored as text to keep their full value and the
+/// nullable values are stored as NULL when empty like they are in postgres
+pub fn ethereum_wrapper_to_sqlite_value(wrapper: &EthereumSqlTypeWrapper) -> Value {
+    match wrapper {
+        EthereumSqlTypeWrapper::U256Nullable(u) | EthereumSqlTypeWrapper::U256BytesNullable(u)
+            if u.is_zero() =>
+        {
+            Value::Null
+        }
+        EthereumSqlTypeWrapper::I256Nullable(i) | EthereumSqlTypeWrapper::I256BytesNullable(i)
+            if i.is_zero() =>
+        {
+            Value::Null
+        }
+        EthereumSqlTypeWrapper::AddressNullable(address) |
+        EthereumSqlTypeWrapper::AddressBytesNullable(address)
+            if address.is_zero() =>
+        {
+            Value::Null
+        }
+        EthereumSqlTypeWrapper::StringNullable(s) |
+        EthereumSqlTypeWrapper::StringVarcharNullable(s) |
+        EthereumSqlTypeWrapper::StringCharNullable(s) |
+        EthereumSqlTypeWrapper::NumericNullable(s)
+            if s.is_empty() =>
+        {
+            Value::Null
+        }
+        EthereumSqlTypeWrapper::BytesNullable(bytes) if bytes.is_empty() => Value::Null,
+        // ethers serializes these as hex strings
+        EthereumSqlTypeWrapper::U64(u) => match i64::try_from(u.as_u64()) {
+            Ok(i) => Value::Integer(i),
+            Err(_) => Value::Text(u.to_string()),
+        },
+        EthereumSqlTypeWrapper::VecU64(u64s) => Value::Text(
+            serde_json::json!(u64s.iter().map(|u| u.as_u64()).collect::<Vec<_>>()).to_string(),
+        ),
+        EthereumSqlTypeWrapper::U256Bytes(u) | EthereumSqlTypeWrapper::U256BytesNullable(u) => {
+            let mut bytes = [0u8; 32];
+            u.to_big_endian(&mut bytes);
+            Value::Blob(bytes.to_vec())
+        }
+        EthereumSqlTypeWrapper::I256Bytes(i) | EthereumSqlTypeWrapper::I256BytesNullable(i) => {
+            let mut bytes = [0u8; 32];
+            i.into_raw().to_big_endian(&mut bytes);
+            Value::Blob(bytes.to_vec())
+        }
+        EthereumSqlTypeWrapper::H256Bytes(h) => Value::Blob(h.as_bytes().to_vec()),
+        EthereumSqlTypeWrapper::AddressBytes(address) |
+        EthereumSqlTypeWrapper::AddressBytesNullable(address) => {
+            Value::Blob(address.as_bytes().to_vec())
+        }
+        EthereumSqlTypeWrapper::Bytes(bytes) | EthereumSqlTypeWrapper::BytesNullable(bytes) => {
+            Value::Blob(bytes.to_vec())
+        }
+        // the format the sqlite date and time functions understand
+        EthereumSqlTypeWrapper::DateTime(date_time) => {
+            Value::Text(date_time.format("%Y-%m-%d %H:%M:%S").to_string())
+        }
+        EthereumSqlTypeWrapper::NumericScaled(s, decimals) => {
+            Value::Text(scale_decimal_string(s, *decimals))
+        }
+        _ => json_to_sqlite_value(ethereum_wrapper_to_json(wrapper)),
+    }
+}
@@ -0,0 +1,262 @@
+use std::path::Path;
+
+use tracing::{error, info};
+
+use crate::{
+    abi::{ABIInput, ABIItem, EventInfo, GenerateAbiPropertiesType, JSONB_TUPLE_TYPE},
+    database::postgres::generate::{
+        generate_indexer_contract_schema_name, GenerateTablesForIndexerSqlError,
+    },
+    helpers::camel_to_snake,
+    indexer::Indexer,
+    manifest::contract::{ColumnType, Contract, NumericColumn},
+};
+
+/// SQLite has no schemas so the postgres schema is folded into the table name
+pub fn sqlite_table_name(postgres_table_name: &str) -> String {
+    postgres_table_name.replace('.', "_")
+}
+
+pub fn generate_internal_event_table_name_sqlite(
+    indexer_name: &str,
+    contract_name: &str,
+    event_name: &str,
+) -> String {
+    format!(
+        "rindexer_internal_{}_{}",
+        generate_indexer_contract_schema_name(indexer_name, contract_name),
+        camel_to_snake(event_name)
+    )
+}
+
+/// A computed column which is added to an event table if it does not have it yet
+pub struct SqliteComputedColumn {
+    pub table_name: String,
+    pub column_name: String,
+    pub column_type: &'static str,
+}
+
+fn generate_columns_with_data_types(inputs: &[ABIInput]) -> Vec<String> {
+    ABIInput::generate_abi_name_properties(
+        inputs,
+        &GenerateAbiPropertiesType::PostgresColumnsNamesOnly,
+        None,
+    )
+    .into_iter()
+    .map(|m| format!("\"{}\" {}", m.value, solidity_type_to_sqlite_type(&m.abi_type)))
+    .collect()
+}
+
+fn computed_column_sqlite_type(column_type: ColumnType) -> &'static str {
+    match column_type {
+        // numeric values are kept as text so they do not lose precision
+        ColumnType::Numeric | ColumnType::Text => "TEXT",
+        ColumnType::Bool => "INTEGER",
+    }
+}
+
+fn generate_event_table_sql(event_info: &EventInfo, table_name: &str) -> String {
+    info!("Creating sqlite table if not exists: {}", table_name);
+
+    let event_columns = if event_info.inputs.is_empty() {
+        "".to_string()
+    } else {
+        generate_columns_with_data_types(&event_info.inputs).join(", ") + ","
+    };
+
+    format!(
+        "CREATE TABLE IF NOT EXISTS {} (\
+            rindexer_id INTEGER PRIMARY KEY AUTOINCREMENT, \
+            contract_address TEXT NOT NULL, \
+            {} \
+            tx_hash TEXT NOT NULL, \
+            block_number INTEGER NOT NULL, \
+            block_hash TEXT NOT NULL, \
+            network TEXT NOT NULL, \
+            tx_index INTEGER NOT NULL, \
+            log_index TEXT NOT NULL, \
+            input TEXT, \
+            block_timestamp TEXT\
+        );",
+        table_name, event_columns
+    )
+}
+
+fn generate_internal_event_table_sql(table_name: &str, networks: &[&str]) -> String {
+    let mut sql = format!(
+        "CREATE TABLE IF NOT EXISTS {} (network TEXT PRIMARY KEY, last_synced_block INTEGER);",
+        table_name
+    );
+
+    for network in networks {
+        sql.push_str(&format!(
+            "\nINSERT OR IGNORE INTO {} (network, last_synced_block) VALUES ('{}', 0);",
+            table_name, network
+        ));
+    }
+
+    sql
+}
+
+fn event_table_names(
+    project_path: &Path,
+    indexer_name: &str,
+    contract: &Contract,
+) -> Result<(Vec<EventInfo>, Vec<String>), GenerateTablesForIndexerSqlError> {
+    let contract_name = contract.before_modify_name_if_filter_readonly();
+    let event_names = ABIItem::read_abi_events(project_path, contract)?;
+    let versioned_events = ABIItem::read_versioned_events(project_path, contract, &event_names)?;
+
+    let internal_table_names = event_names
+        .iter()
+        .map(|event_info| event_info.name.as_str())
+        .chain(versioned_events.iter().map(|versioned| versioned.event_name.as_str()))
+        .map(|event_name| {
+            generate_internal_event_table_name_sqlite(indexer_name, &contract_name, event_name)
+        })
+        .collect();
+
+    // only versions with a different layout to the current ABI need their own table
+    let versioned_table_events = versioned_events
+        .iter()
+        .filter(|versioned_event| versioned_event.event_name == versioned_event.table_event_name)
+        .map(|versioned_event| versioned_event.table_event_info());
+    let table_events = event_names.iter().cloned().chain(versioned_table_events).collect();
+
+    Ok((table_events, internal_table_names))
+}
+
+pub fn generate_tables_for_indexer_sqlite(
+    project_path: &Path,
+    indexer: &Indexer,
+    disable_event_tables: bool,
+) -> Result<(Vec<String>, Vec<SqliteComputedColumn>), GenerateTablesForIndexerSqlError> {
+    let mut statements = vec![];
+    let mut computed_columns = vec![];
+
+    for contract in &indexer.contracts {
+        let contract_name = contract.before_modify_name_if_filter_readonly();
+        let schema_name = generate_indexer_contract_schema_name(&indexer.name, &contract_name);
+        let (table_events, internal_table_names) =
+            event_table_names(project_path, &indexer.name, contract)?;
+
+        if !disable_event_tables {
+            for event_info in table_events {
+                let table_name = format!("{}_{}", schema_name, camel_to_snake(&event_info.name));
+                statements.push(generate_event_table_sql(&event_info, &table_name));
+
+                // computed columns can be added to the yaml after the table exists
+                computed_columns.extend(
+                    contract.computed_columns(&event_info.name, event_info.signature()).iter().map(
+                        |column| SqliteComputedColumn {
+                            table_name: table_name.clone(),
+                            column_name: camel_to_snake(&column.name),
+                            column_type: computed_column_sqlite_type(column.type_),
+                        },
+                    ),
+                );
+            }
+        }
+
+        let networks: Vec<&str> = contract.details.iter().map(|d| d.network.as_str()).collect();
+        for internal_table_name in internal_table_names {
+            statements.push(generate_internal_event_table_sql(&internal_table_name, &networks));
+        }
+    }
+
+    Ok((statements, computed_columns))
+}
+
+pub fn drop_tables_for_indexer_sqlite(project_path: &Path, indexer: &Indexer) -> Vec<String> {
+    let mut statements = vec![];
+
+    for contract in &indexer.contracts {
+        let contract_name = contract.before_modify_name_if_filter_readonly();
+        let schema_name = generate_indexer_contract_schema_name(&indexer.name, &contract_name);
+
+        match event_table_names(project_path, &indexer.name, contract) {
+            Ok((table_events, internal_table_names)) => {
+                for event_info in table_events {
+                    statements.push(format!(
+                        "DROP TABLE IF EXISTS {}_{};",
+                        schema_name,
+                        camel_to_snake(&event_info.name)
+                    ));
+                }
+                for internal_table_name in internal_table_names {
+                    statements.push(format!("DROP TABLE IF EXISTS {};", internal_table_name));
+                }
+            }
+            Err(_) => {
+                error!(
+                    "Could not read ABI items for contract moving on clearing the other data up: {}",
+                    contract.name
+                );
+            }
+        }
+    }
+
+    statements
+}
+
+/// SQLite integers are 64 bit signed so anything which may not fit is stored as text
+#[allow(clippy::manual_strip)]
+pub fn solidity_type_to_sqlite_type(abi_type: &str) -> &'static str {
+    if let Some(numeric_column) = NumericColumn::from_abi_type(abi_type) {
+        return match numeric_column {
+            NumericColumn::Bytea => "BLOB",
+            NumericColumn::Numeric | NumericColumn::Text | NumericColumn::Decimal(_) => "TEXT",
+        };
+    }
+
+    // arrays are stored as JSON
+    if abi_type.ends_with("[]") {
+        return "TEXT";
+    }
+
+    match abi_type {
+        "address" | "string" | JSONB_TUPLE_TYPE => "TEXT",
+        "bool" => "INTEGER",
+        t if t.starts_with("bytes") => "BLOB",
+        t if t.starts_with("int") => match t[3..].parse::<usize>() {
+            Ok(size) if size <= 64 => "INTEGER",
+            Ok(_) => "TEXT",
+            Err(_) => panic!("Invalid intN type"),
+        },
+        t if t.starts_with("uint") => match t[4..].parse::<usize>() {
+            Ok(size) if size < 64 => "INTEGER",
+            Ok(_) => "TEXT",
+            Err(_) => panic!("Invalid uintN type"),
+        },
+        _ => panic!("Unsupported type: {}", abi_type),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_solidity_type_to_sqlite_type() {
+        assert_eq!(solidity_type_to_sqlite_type("address"), "TEXT");
+        assert_eq!(solidity_type_to_sqlite_type("address[]"), "TEXT");
+        assert_eq!(solidity_type_to_sqlite_type("bool"), "INTEGER");
+        assert_eq!(solidity_type_to_sqlite_type("bytes32"), "BLOB");
+        assert_eq!(solidity_type_to_sqlite_type("int64"), "INTEGER");
+        assert_eq!(solidity_type_to_sqlite_type("uint56"), "INTEGER");
+        assert_eq!(solidity_type_to_sqlite_type("uint64"), "TEXT");
+        assert_eq!(solidity_type_to_sqlite_type("uint256"), "TEXT");
+        assert_eq!(solidity_type_to_sqlite_type(JSONB_TUPLE_TYPE), "TEXT");
+        assert_eq!(solidity_type_to_sqlite_type("int64:bytea"), "BLOB");
+        assert_eq!(solidity_type_to_sqlite_type("uint256:decimal6"), "TEXT");
+    }
+
+    #[test]
+    fn test_sqlite_table_name() {
+        assert_eq!(sqlite_table_name("indexer_token.transfer"), "indexer_token_transfer");
+        assert_eq!(
+            generate_internal_event_table_name_sqlite("Indexer", "Token", "Transfer"),
+            "rindexer_internal_indexer_token_transfer"
+        );
+    }
+}
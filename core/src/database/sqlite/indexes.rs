@@ -0,0 +1,181 @@
+use std::path::Path;
+
+use rusqlite::types::Value;
+use tracing::info;
+
+use crate::{
+    database::{
+        postgres::{
+            indexes::{resolve_indexes, PrepareIndexesError},
+            relationship::{resolve_relationships, CreateRelationshipError, Relationship},
+        },
+        sqlite::{
+            client::{SqliteClient, SqliteError},
+            generate::sqlite_table_name,
+        },
+    },
+    helpers::camel_to_snake,
+    manifest::{
+        core::Manifest,
+        storage::{IndexMethod, SqliteDetails},
+    },
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum PrepareSqliteIndexesError {
+    #[error("{0}")]
+    SqliteError(#[from] SqliteError),
+
+    #[error("Could not prepare indexes: {0}")]
+    PrepareIndexesError(#[from] PrepareIndexesError),
+
+    #[error("Yaml relationship error: {0}")]
+    RelationshipError(#[from] CreateRelationshipError),
+
+    #[error("SQLite only supports btree indexes - index {0} uses {1}")]
+    UnsupportedIndexMethod(String, &'static str),
+}
+
+/// An index or relationship trigger which is created once the historic sync is complete
+#[derive(Debug, Clone)]
+pub struct SqliteIndexResult {
+    db_table_name: String,
+    name: String,
+    sql: String,
+}
+
+impl SqliteIndexResult {
+    pub async fn apply_indexes(
+        client: &SqliteClient,
+        indexes: Vec<SqliteIndexResult>,
+    ) -> Result<(), SqliteError> {
+        for index in indexes {
+            info!(
+                "Applying sqlite index after historic resync complete: table - {} index - {}",
+                index.db_table_name, index.name
+            );
+            client.execute_batch(&index.sql).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// SQLite can not add a foreign key to an existing table so a relationship is enforced with a
+/// trigger on insert, the linked column gets a unique index like the postgres constraint
+fn relationship_sqlite_indexes(relationship: &Relationship) -> Vec<SqliteIndexResult> {
+    let db_table_name = sqlite_table_name(&relationship.db_table_name);
+    let linked_db_table_name = sqlite_table_name(&relationship.linked_to.db_table_name);
+    let linked_db_table_column = &relationship.linked_to.db_table_column;
+
+    let unique_name = format!("unique_{}_{}", linked_db_table_name, linked_db_table_column);
+    let index_name = format!("idx_{}_{}", db_table_name, relationship.db_table_column);
+    let foreign_key_name = format!("fk_{}_{}", linked_db_table_name, linked_db_table_column);
+
+    vec![
+        SqliteIndexResult {
+            sql: format!(
+                "CREATE UNIQUE INDEX IF NOT EXISTS {} ON {} (\"{}\");",
+                unique_name, linked_db_table_name, linked_db_table_column
+            ),
+            db_table_name: linked_db_table_name.clone(),
+            name: unique_name,
+        },
+        SqliteIndexResult {
+            sql: format!(
+                "CREATE INDEX IF NOT EXISTS {} ON {} (\"{}\");",
+                index_name, db_table_name, relationship.db_table_column
+            ),
+            db_table_name: db_table_name.clone(),
+            name: index_name,
+        },
+        SqliteIndexResult {
+            sql: format!(
+                "CREATE TRIGGER IF NOT EXISTS {name} BEFORE INSERT ON {db_table_name} \
+                FOR EACH ROW WHEN NEW.\"{db_table_column}\" IS NOT NULL AND NOT EXISTS (\
+                    SELECT 1 FROM {linked_db_table_name} \
+                    WHERE \"{linked_db_table_column}\" = NEW.\"{db_table_column}\"\
+                ) \
+                BEGIN SELECT RAISE(ABORT, 'FOREIGN KEY constraint failed - {name}'); END;",
+                name = foreign_key_name,
+                db_table_name = db_table_name,
+                db_table_column = relationship.db_table_column,
+                linked_db_table_name = linked_db_table_name,
+                linked_db_table_column = linked_db_table_column,
+            ),
+            db_table_name,
+            name: foreign_key_name,
+        },
+    ]
+}
+
+/// Drops the indexes and relationship triggers rindexer created on the event tables so the
+/// historic sync is quicker, the ones in the yaml are returned to be applied once it is done
+pub async fn prepare_sqlite_indexes(
+    client: &SqliteClient,
+    project_path: &Path,
+    manifest: &Manifest,
+    sqlite: &SqliteDetails,
+) -> Result<(Vec<Relationship>, Vec<SqliteIndexResult>), PrepareSqliteIndexesError> {
+    let mut relationships: Vec<Relationship> = vec![];
+    let mut index_results: Vec<SqliteIndexResult> = vec![];
+
+    if let Some(foreign_keys) = &sqlite.relationships {
+        relationships =
+            resolve_relationships(project_path, &manifest.name, &manifest.contracts, foreign_keys)?;
+        index_results.extend(relationships.iter().flat_map(relationship_sqlite_indexes));
+    }
+
+    if let Some(indexes) = &sqlite.indexes {
+        let resolved_indexes =
+            resolve_indexes(project_path, &manifest.name, indexes, &manifest.contracts, |_| None)?;
+        for (db_table_name, definition) in resolved_indexes {
+            let db_table_name = sqlite_table_name(&db_table_name);
+            let name = definition.index_name(&db_table_name);
+            if definition.method() != IndexMethod::Btree {
+                return Err(PrepareSqliteIndexesError::UnsupportedIndexMethod(
+                    name,
+                    definition.method().name(),
+                ));
+            }
+
+            index_results.push(SqliteIndexResult {
+                sql: format!(
+                    "CREATE {}INDEX IF NOT EXISTS {} ON {} ({}){};",
+                    if definition.unique() { "UNIQUE " } else { "" },
+                    name,
+                    db_table_name,
+                    definition.columns_sql(),
+                    definition.where_sql()
+                ),
+                db_table_name,
+                name,
+            });
+        }
+    }
+
+    // anything rindexer created on the tables of the indexer is dropped so indexes removed from
+    // the yaml do not stay around
+    let table_prefix = format!("{}_", camel_to_snake(&manifest.name));
+    let existing = client
+        .query(
+            "SELECT type, name, tbl_name FROM sqlite_master \
+            WHERE type IN ('index', 'trigger') AND sql IS NOT NULL \
+            AND (name LIKE 'idx\\_%' ESCAPE '\\' OR name LIKE 'unique\\_%' ESCAPE '\\' \
+                OR name LIKE 'fk\\_%' ESCAPE '\\') \
+            AND substr(tbl_name, 1, length(?1)) = ?1",
+            vec![Value::Text(table_prefix)],
+        )
+        .await?;
+    for row in existing {
+        if let [Value::Text(type_), Value::Text(name), Value::Text(table_name)] = row.as_slice() {
+            info!(
+                "Dropping sqlite {} for historic resync: table - {} {} - {}",
+                type_, table_name, type_, name
+            );
+            client.execute_batch(&format!("DROP {} IF EXISTS {};", type_, name)).await?;
+        }
+    }
+
+    Ok((relationships, index_results))
+}
@@ -0,0 +1,4 @@
+pub mod client;
+pub mod generate;
+pub mod indexes;
+pub mod setup;
@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use rusqlite::types::Value;
+use tracing::info;
+
+use crate::{
+    database::{
+        postgres::generate::GenerateTablesForIndexerSqlError,
+        sqlite::{
+            client::{SqliteClient, SqliteConnectionError, SqliteError},
+            generate::{drop_tables_for_indexer_sqlite, generate_tables_for_indexer_sqlite},
+        },
+    },
+    manifest::core::Manifest,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum SetupSqliteError {
+    #[error("{0}")]
+    SqliteConnection(#[from] SqliteConnectionError),
+
+    #[error("{0}")]
+    SqliteError(#[from] SqliteError),
+
+    #[error("Error creating sqlite tables for indexer: {0}")]
+    GeneratingTables(#[from] GenerateTablesForIndexerSqlError),
+}
+
+pub async fn setup_sqlite(
+    project_path: &Path,
+    manifest: &Manifest,
+) -> Result<SqliteClient, SetupSqliteError> {
+    info!("Setting up sqlite");
+    let client = SqliteClient::new(&manifest.storage.sqlite_path(project_path))?;
+    let indexer = manifest.to_indexer();
+
+    if manifest.storage.sqlite_drop_each_run() {
+        info!(
+            "`drop_each_run` enabled so dropping all sqlite data for {} before starting",
+            &manifest.name
+        );
+        client
+            .execute_batch(&drop_tables_for_indexer_sqlite(project_path, &indexer).join("\n"))
+            .await?;
+        info!("Dropped all sqlite data for {}", manifest.name);
+    }
+
+    let disable_event_tables = manifest.storage.sqlite_disable_create_tables();
+    if !disable_event_tables {
+        info!("Creating sqlite tables for {}", manifest.name);
+    } else {
+        info!("Creating internal rindexer sqlite tables for {}", manifest.name);
+    }
+    let (statements, computed_columns) =
+        generate_tables_for_indexer_sqlite(project_path, &indexer, disable_event_tables)?;
+    client.execute_batch(&statements.join("\n")).await?;
+
+    // sqlite has no ADD COLUMN IF NOT EXISTS so check the table first
+    for computed_column in computed_columns {
+        let exists = !client
+            .query(
+                "SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2",
+                vec![
+                    Value::Text(computed_column.table_name.clone()),
+                    Value::Text(computed_column.column_name.clone()),
+                ],
+            )
+            .await?
+            .is_empty();
+        if !exists {
+            client
+                .execute_batch(&format!(
+                    "ALTER TABLE {} ADD COLUMN \"{}\" {};",
+                    computed_column.table_name,
+                    computed_column.column_name,
+                    computed_column.column_type
+                ))
+                .await?;
+        }
+    }
+    info!("Created sqlite tables for {}", manifest.name);
+
+    Ok(client)
+}
@@ -138,7 +138,9 @@ impl EventCallbackRegistry {
         self.events.push(event);
     }
 
-    pub async fn trigger_event(&self, id: &String, data: Vec<EventResult>) {
+    /// Runs the handler until it succeeds, returns `false` when it never did because rindexer is
+    /// shutting down or the event is not registered
+    pub async fn trigger_event(&self, id: &String, data: Vec<EventResult>) -> bool {
         let mut attempts = 0;
         let mut delay = Duration::from_millis(100);
 
@@ -148,7 +150,7 @@ impl EventCallbackRegistry {
             loop {
                 if !is_running() {
                     info!("Detected shutdown, stopping event trigger");
-                    break;
+                    return false;
                 }
 
                 match (event_information.callback)(data.clone()).await {
@@ -157,12 +159,12 @@ impl EventCallbackRegistry {
                             "Event processing succeeded for id: {} - topic_id: {}",
                             id, event_information.topic_id
                         );
-                        break;
+                        return true;
                     }
                     Err(e) => {
                         if !is_running() {
                             info!("Detected shutdown, stopping event trigger");
-                            break;
+                            return false;
                         }
                         attempts += 1;
                         error!(
@@ -179,6 +181,8 @@ impl EventCallbackRegistry {
         } else {
             error!("EventCallbackRegistry: No event found for id: {}", id);
         }
+
+        false
     }
 
     pub fn complete(&self) -> Arc<Self> {
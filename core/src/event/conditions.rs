@@ -1,91 +1,1362 @@
-use ethers::prelude::U64;
+use std::{cmp::Ordering, fmt};
+
+use ethers::prelude::U256;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{Map, Value};
+use tracing::debug;
 
-fn get_nested_value(data: &Value, path: &str) -> Option<Value> {
-    let keys: Vec<&str> = path.split('.').collect();
-    let mut current = data;
-    for key in keys {
-        match current.get(key) {
-            Some(value) => current = value,
-            None => return None,
-        }
-    }
-    Some(current.clone())
-}
-
-#[allow(clippy::manual_strip)]
-fn evaluate_condition(value: &Value, condition: &str) -> bool {
-    if condition.contains("||") ||
-        condition.contains("&&") ||
-        condition.contains('>') ||
-        condition.contains('<') ||
-        condition.contains('=')
-    {
-        let parts: Vec<&str> = condition.split("||").collect();
-        for part in parts {
-            let subparts: Vec<&str> = part.split("&&").collect();
-            let mut and_result = true;
-            for subpart in subparts {
-                let (op, comp) = if subpart.starts_with(">=") {
-                    (">=", &subpart[2..])
-                } else if subpart.starts_with("<=") {
-                    ("<=", &subpart[2..])
-                } else if subpart.starts_with(">") {
-                    (">", &subpart[1..])
-                } else if subpart.starts_with("<") {
-                    ("<", &subpart[1..])
-                } else if subpart.starts_with("=") {
-                    ("=", &subpart[1..])
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum ConditionError {
+    #[error("Unexpected character '{0}'")]
+    UnexpectedCharacter(char),
+
+    #[error("String is missing its closing quote")]
+    UnterminatedString,
+
+    #[error("$ must be followed by a field name")]
+    EmptyFieldReference,
+
+    #[error("Unexpected '{0}'")]
+    UnexpectedToken(String),
+
+    #[error("Condition ended unexpectedly")]
+    UnexpectedEnd,
+
+    #[error("Unknown function {0}")]
+    UnknownFunction(String),
+
+    #[error("Function {0} expects {1} arguments but got {2}")]
+    WrongArgumentCount(String, usize, usize),
+
+    #[error("{0} is not a number")]
+    InvalidNumber(String),
+
+    #[error("A comparison can not be used as a value")]
+    ExpectedValue,
+
+    #[error("Condition must be a string, number or boolean")]
+    InvalidConditionValue,
+
+    #[error("Values combined with &&, || or ! must be compared, for example value > 0")]
+    MissingComparison,
+}
+
+/// The most decimal places a number keeps, divisions are rounded down to this precision
+const MAX_SCALE: u32 = 18;
+
+fn pow10(exponent: u32) -> Option<U256> {
+    U256::from(10).checked_pow(U256::from(exponent))
+}
+
+/// A signed decimal wide enough to hold any uint256 or int256 value, the value is
+/// `magnitude / 10^scale`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Number {
+    negative: bool,
+    magnitude: U256,
+    scale: u32,
+}
+
+impl Number {
+    fn new(negative: bool, magnitude: U256, scale: u32) -> Self {
+        let mut magnitude = magnitude;
+        let mut scale = scale;
+        while scale > MAX_SCALE {
+            magnitude /= 10;
+            scale -= 1;
+        }
+        // trailing zeros are removed so equal values are stored the same way
+        while scale > 0 && (magnitude % 10).is_zero() {
+            magnitude /= 10;
+            scale -= 1;
+        }
+
+        Self { negative: negative && !magnitude.is_zero(), magnitude, scale }
+    }
+
+    fn integer(negative: bool, magnitude: U256) -> Self {
+        Self::new(negative, magnitude, 0)
+    }
+
+    /// Parses decimal (`-15`, `1.5`, `5e18`) and hex (`0x1f`) numbers
+    fn parse(value: &str) -> Option<Self> {
+        let (negative, digits) = match value.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, value),
+        };
+
+        if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+            if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                return None;
+            }
+            return Some(Self::integer(negative, U256::from_str_radix(hex, 16).ok()?));
+        }
+
+        let (mantissa, exponent) = match digits.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<u32>().ok()?),
+            None => (digits, 0),
+        };
+        let (whole, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if !fraction.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let magnitude = parse_decimal(&format!("{}{}", whole, fraction))?;
+        let scale = fraction.len() as u32;
+        if exponent >= scale {
+            Some(Self::integer(negative, magnitude.checked_mul(pow10(exponent - scale)?)?))
+        } else {
+            Some(Self::new(negative, magnitude, scale - exponent))
+        }
+    }
+
+    /// Both magnitudes at the same scale, `None` if scaling one of them up overflows
+    fn aligned(self, other: Self) -> Option<(U256, U256, u32)> {
+        let scale = self.scale.max(other.scale);
+        Some((
+            self.magnitude.checked_mul(pow10(scale - self.scale)?)?,
+            other.magnitude.checked_mul(pow10(scale - other.scale)?)?,
+            scale,
+        ))
+    }
+
+    fn cmp_magnitude(self, other: Self) -> Ordering {
+        match self.aligned(other) {
+            Some((a, b, _)) => a.cmp(&b),
+            // only the number with the smaller scale is scaled up so it must be the larger one
+            None if self.scale < other.scale => Ordering::Greater,
+            None => Ordering::Less,
+        }
+    }
+
+    fn negate(self) -> Self {
+        Self::new(!self.negative, self.magnitude, self.scale)
+    }
+
+    fn abs(self) -> Self {
+        Self::new(false, self.magnitude, self.scale)
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        if self.negative == other.negative {
+            return Some(Self::new(self.negative, a.checked_add(b)?, scale));
+        }
+
+        if a >= b {
+            Some(Self::new(self.negative, a - b, scale))
+        } else {
+            Some(Self::new(other.negative, b - a, scale))
+        }
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        self.checked_add(other.negate())
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        Some(Self::new(
+            self.negative != other.negative,
+            self.magnitude.checked_mul(other.magnitude)?,
+            self.scale + other.scale,
+        ))
+    }
+
+    fn checked_div(self, other: Self) -> Option<Self> {
+        if other.magnitude.is_zero() {
+            return None;
+        }
+
+        // keep as many decimal places as fit in 256 bits
+        (0..=MAX_SCALE).rev().find_map(|precision| {
+            let numerator = self.magnitude.checked_mul(pow10(other.scale + precision)?)?;
+            Some(Self::new(
+                self.negative != other.negative,
+                numerator / other.magnitude,
+                self.scale + precision,
+            ))
+        })
+    }
+
+    fn checked_rem(self, other: Self) -> Option<Self> {
+        let (a, b, scale) = self.aligned(other)?;
+        Some(Self::new(self.negative, a.checked_rem(b)?, scale))
+    }
+
+    fn checked_pow(self, exponent: Self) -> Option<Self> {
+        if exponent.negative || exponent.scale > 0 || exponent.magnitude > U256::from(u32::MAX) {
+            return None;
+        }
+
+        let exponent = exponent.magnitude.as_u32();
+        Some(Self::new(
+            self.negative && exponent % 2 == 1,
+            self.magnitude.checked_pow(U256::from(exponent))?,
+            self.scale.checked_mul(exponent)?,
+        ))
+    }
+}
+
+impl Ord for Number {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmp_magnitude(*other),
+            (true, true) => other.cmp_magnitude(*self),
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+
+        let digits = self.magnitude.to_string();
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}", digits);
+        }
+
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}.{}", whole, fraction)
+    }
+}
+
+fn parse_decimal(digits: &str) -> Option<U256> {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    U256::from_dec_str(digits).ok()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ConditionValue {
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    List(Vec<ConditionValue>),
+}
+
+impl ConditionValue {
+    fn from_json(value: &Value) -> Self {
+        match value {
+            Value::Null => ConditionValue::Null,
+            Value::Bool(b) => ConditionValue::Bool(*b),
+            Value::Number(n) => {
+                if let Some(u) = n.as_u64() {
+                    ConditionValue::Number(Number::integer(false, U256::from(u)))
+                } else if let Some(i) = n.as_i64() {
+                    ConditionValue::Number(Number::integer(i < 0, U256::from(i.unsigned_abs())))
                 } else {
-                    ("", subpart)
-                };
+                    ConditionValue::String(n.to_string())
+                }
+            }
+            Value::String(s) => ConditionValue::String(s.clone()),
+            Value::Array(values) => {
+                ConditionValue::List(values.iter().map(ConditionValue::from_json).collect())
+            }
+            Value::Object(_) => ConditionValue::String(value.to_string()),
+        }
+    }
 
-                and_result &= match op {
-                    ">=" => {
-                        U64::from_str_radix(value.as_str().unwrap_or("0"), 10).unwrap_or_default() >=
-                            U64::from_str_radix(comp, 10).unwrap_or_default()
+    /// Event values are mostly strings so numbers are parsed on demand
+    fn as_number(&self) -> Option<Number> {
+        match self {
+            ConditionValue::Number(n) => Some(*n),
+            ConditionValue::String(s) => Number::parse(s),
+            _ => None,
+        }
+    }
+
+    fn as_text(&self) -> Result<String, String> {
+        match self {
+            ConditionValue::Null => Ok(String::new()),
+            ConditionValue::Bool(b) => Ok(b.to_string()),
+            ConditionValue::Number(n) => Ok(n.to_string()),
+            ConditionValue::String(s) => Ok(s.clone()),
+            ConditionValue::List(_) => Err("a list can not be used as text".to_string()),
+        }
+    }
+
+    fn equals(&self, other: &ConditionValue) -> bool {
+        match (self, other) {
+            (ConditionValue::Null, ConditionValue::Null) => true,
+            (ConditionValue::Bool(a), ConditionValue::Bool(b)) => a == b,
+            (ConditionValue::Bool(b), ConditionValue::String(s)) |
+            (ConditionValue::String(s), ConditionValue::Bool(b)) => {
+                s.eq_ignore_ascii_case(&b.to_string())
+            }
+            (ConditionValue::List(a), ConditionValue::List(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.equals(b))
+            }
+            _ => {
+                if let (Some(a), Some(b)) = (self.as_number(), other.as_number()) {
+                    return a == b;
+                }
+
+                match (self, other) {
+                    // addresses and hashes are compared case-insensitively so checksummed
+                    // addresses match the lowercase event values
+                    (ConditionValue::String(a), ConditionValue::String(b))
+                        if a.starts_with("0x") && b.starts_with("0x") =>
+                    {
+                        a.eq_ignore_ascii_case(b)
                     }
-                    "<=" => {
-                        U64::from_str_radix(value.as_str().unwrap_or("0"), 10).unwrap_or_default() <=
-                            U64::from_str_radix(comp, 10).unwrap_or_default()
+                    (ConditionValue::String(a), ConditionValue::String(b)) => a == b,
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    String(String),
+    Field(String),
+    Operator(&'static str),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => write!(f, "{}", word),
+            Token::String(s) => write!(f, "'{}'", s),
+            Token::Field(field) => write!(f, "${}", field),
+            Token::Operator(operator) => write!(f, "{}", operator),
+        }
+    }
+}
+
+// two character operators must come first so `>=` is not read as `>` then `=`
+const OPERATORS: [&str; 21] = [
+    "||", "&&", "!=", "==", ">=", "<=", "=", ">", "<", "!", "+", "-", "*", "/", "%", "^", "(", ")",
+    "[", "]", ",",
+];
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ConditionError> {
+    let mut tokens = vec![];
+    let mut rest = input;
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = &rest[c.len_utf8()..];
+        } else if c == '\'' || c == '"' {
+            let end = rest[1..].find(c).ok_or(ConditionError::UnterminatedString)?;
+            tokens.push(Token::String(rest[1..end + 1].to_string()));
+            rest = &rest[end + 2..];
+        } else if c == '$' {
+            let end = rest[1..].find(|c| !is_word_char(c)).map_or(rest.len(), |end| end + 1);
+            if end == 1 {
+                return Err(ConditionError::EmptyFieldReference);
+            }
+            tokens.push(Token::Field(rest[1..end].to_string()));
+            rest = &rest[end..];
+        } else if is_word_char(c) {
+            let end = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+            tokens.push(Token::Word(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else {
+            let operator = OPERATORS
+                .iter()
+                .find(|operator| rest.starts_with(**operator))
+                .ok_or(ConditionError::UnexpectedCharacter(c))?;
+            tokens.push(Token::Operator(operator));
+            rest = &rest[operator.len()..];
+        }
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CompareOperator {
+    Equal,
+    NotEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    LessThan,
+    LessThanOrEqual,
+    In,
+    NotIn,
+}
+
+impl CompareOperator {
+    fn is_ordering(&self) -> bool {
+        matches!(
+            self,
+            CompareOperator::GreaterThan |
+                CompareOperator::GreaterThanOrEqual |
+                CompareOperator::LessThan |
+                CompareOperator::LessThanOrEqual
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArithmeticOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    Power,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    Lower,
+    Upper,
+    Len,
+    Abs,
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lower" => Some(Function::Lower),
+            "upper" => Some(Function::Upper),
+            "len" => Some(Function::Len),
+            "abs" => Some(Function::Abs),
+            "contains" => Some(Function::Contains),
+            "starts_with" => Some(Function::StartsWith),
+            "ends_with" => Some(Function::EndsWith),
+            _ => None,
+        }
+    }
+
+    fn arguments(&self) -> usize {
+        match self {
+            Function::Lower | Function::Upper | Function::Len | Function::Abs => 1,
+            Function::Contains | Function::StartsWith | Function::EndsWith => 2,
+        }
+    }
+
+    fn returns_bool(&self) -> bool {
+        matches!(self, Function::Contains | Function::StartsWith | Function::EndsWith)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expression {
+    /// The value of the field the condition is defined under
+    Subject,
+    Field(String),
+    Literal(ConditionValue),
+    List(Vec<Expression>),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Compare(CompareOperator, Box<Expression>, Box<Expression>),
+    Arithmetic(ArithmeticOperator, Box<Expression>, Box<Expression>),
+    Negate(Box<Expression>),
+    Call(Function, Vec<Expression>),
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    /// The field the condition is defined under, writing its name is the same as leaving it out
+    /// so `>= 10 && value <= 20` works for `value`
+    subject: Option<&'a str>,
+    /// Computed expressions have no subject so bare words are read as fields instead of text
+    bare_words_are_fields: bool,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Result<Token, ConditionError> {
+        let token = self.tokens.get(self.position).cloned().ok_or(ConditionError::UnexpectedEnd)?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, operator: &str) -> bool {
+        if matches!(self.peek(), Some(Token::Operator(found)) if *found == operator) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, operator: &str) -> Result<(), ConditionError> {
+        match self.next()? {
+            Token::Operator(found) if found == operator => Ok(()),
+            token => Err(ConditionError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    fn compare_operator(&mut self) -> Option<CompareOperator> {
+        let operator = match self.peek()? {
+            Token::Operator("=") | Token::Operator("==") => CompareOperator::Equal,
+            Token::Operator("!=") => CompareOperator::NotEqual,
+            Token::Operator(">") => CompareOperator::GreaterThan,
+            Token::Operator(">=") => CompareOperator::GreaterThanOrEqual,
+            Token::Operator("<") => CompareOperator::LessThan,
+            Token::Operator("<=") => CompareOperator::LessThanOrEqual,
+            Token::Word(word) if word == "in" => CompareOperator::In,
+            Token::Word(word)
+                if word == "not" &&
+                    matches!(self.tokens.get(self.position + 1), Some(Token::Word(w)) if w == "in") =>
+            {
+                self.position += 1;
+                CompareOperator::NotIn
+            }
+            _ => return None,
+        };
+        self.position += 1;
+        Some(operator)
+    }
+
+    fn parse_or(&mut self) -> Result<Expression, ConditionError> {
+        let mut left = self.parse_and()?;
+        while self.eat("||") {
+            left = Expression::Or(Box::new(left), Box::new(self.parse_and()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_and(&mut self) -> Result<Expression, ConditionError> {
+        let mut left = self.parse_not()?;
+        while self.eat("&&") {
+            left = Expression::And(Box::new(left), Box::new(self.parse_not()?));
+        }
+        Ok(left)
+    }
+
+    fn parse_not(&mut self) -> Result<Expression, ConditionError> {
+        if self.eat("!") {
+            return Ok(Expression::Not(Box::new(self.parse_not()?)));
+        }
+        self.parse_comparison()
+    }
+
+    fn parse_comparison(&mut self) -> Result<Expression, ConditionError> {
+        // a comparison without a left hand side is made against the subject, e.g. `>= 1000`
+        if let Some(operator) = self.compare_operator() {
+            let right = self.parse_additive()?;
+            return Ok(Expression::Compare(
+                operator,
+                Box::new(Expression::Subject),
+                Box::new(right),
+            ));
+        }
+
+        let left = self.parse_additive()?;
+        match self.compare_operator() {
+            Some(operator) => {
+                let right = self.parse_additive()?;
+                Ok(Expression::Compare(operator, Box::new(left), Box::new(right)))
+            }
+            None => Ok(left),
+        }
+    }
+
+    fn parse_additive(&mut self) -> Result<Expression, ConditionError> {
+        let mut left = self.parse_multiplicative()?;
+        loop {
+            let operator = if self.eat("+") {
+                ArithmeticOperator::Add
+            } else if self.eat("-") {
+                ArithmeticOperator::Subtract
+            } else {
+                return Ok(left);
+            };
+            left = Expression::Arithmetic(
+                operator,
+                Box::new(left),
+                Box::new(self.parse_multiplicative()?),
+            );
+        }
+    }
+
+    fn parse_multiplicative(&mut self) -> Result<Expression, ConditionError> {
+        let mut left = self.parse_unary()?;
+        loop {
+            let operator = if self.eat("*") {
+                ArithmeticOperator::Multiply
+            } else if self.eat("/") {
+                ArithmeticOperator::Divide
+            } else if self.eat("%") {
+                ArithmeticOperator::Remainder
+            } else {
+                return Ok(left);
+            };
+            left = Expression::Arithmetic(operator, Box::new(left), Box::new(self.parse_unary()?));
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expression, ConditionError> {
+        if self.eat("-") {
+            return Ok(Expression::Negate(Box::new(self.parse_unary()?)));
+        }
+        self.parse_power()
+    }
+
+    fn parse_power(&mut self) -> Result<Expression, ConditionError> {
+        let base = self.parse_primary()?;
+        if self.eat("^") {
+            // right associative so `2^3^2` is `2^(3^2)`
+            let exponent = self.parse_unary()?;
+            return Ok(Expression::Arithmetic(
+                ArithmeticOperator::Power,
+                Box::new(base),
+                Box::new(exponent),
+            ));
+        }
+        Ok(base)
+    }
+
+    fn parse_primary(&mut self) -> Result<Expression, ConditionError> {
+        match self.next()? {
+            Token::Operator("(") => {
+                let expression = self.parse_or()?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            Token::Operator("[") => {
+                let mut items = vec![];
+                if !self.eat("]") {
+                    loop {
+                        items.push(self.parse_additive()?);
+                        if self.eat("]") {
+                            break;
+                        }
+                        self.expect(",")?;
                     }
-                    ">" => {
-                        U64::from_str_radix(value.as_str().unwrap_or("0"), 10).unwrap_or_default() >
-                            U64::from_str_radix(comp, 10).unwrap_or_default()
+                }
+                Ok(Expression::List(items))
+            }
+            Token::Field(field) => Ok(Expression::Field(field)),
+            Token::String(s) => Ok(Expression::Literal(ConditionValue::String(s))),
+            Token::Word(word) => {
+                if self.eat("(") {
+                    return self.parse_call(word);
+                }
+
+                if self.subject == Some(word.as_str()) {
+                    return Ok(Expression::Subject);
+                }
+
+                Ok(Expression::Literal(match word.as_str() {
+                    "true" => ConditionValue::Bool(true),
+                    "false" => ConditionValue::Bool(false),
+                    "null" => ConditionValue::Null,
+                    _ => match Number::parse(&word) {
+                        Some(number) => ConditionValue::Number(number),
+                        None if self.bare_words_are_fields => {
+                            return Ok(Expression::Field(word));
+                        }
+                        None => ConditionValue::String(word),
+                    },
+                }))
+            }
+            token => Err(ConditionError::UnexpectedToken(token.to_string())),
+        }
+    }
+
+    fn parse_call(&mut self, name: String) -> Result<Expression, ConditionError> {
+        let function = Function::from_name(&name)
+            .ok_or_else(|| ConditionError::UnknownFunction(name.clone()))?;
+
+        let mut arguments = vec![];
+        if !self.eat(")") {
+            loop {
+                arguments.push(self.parse_additive()?);
+                if self.eat(")") {
+                    break;
+                }
+                self.expect(",")?;
+            }
+        }
+
+        if arguments.len() != function.arguments() {
+            return Err(ConditionError::WrongArgumentCount(
+                name,
+                function.arguments(),
+                arguments.len(),
+            ));
+        }
+
+        Ok(Expression::Call(function, arguments))
+    }
+}
+
+fn is_bool_expression(expression: &Expression) -> bool {
+    match expression {
+        Expression::Not(_) |
+        Expression::And(_, _) |
+        Expression::Or(_, _) |
+        Expression::Compare(_, _, _) => true,
+        Expression::Call(function, _) => function.returns_bool(),
+        _ => false,
+    }
+}
+
+/// Bare values in a boolean position are compared to the subject so `a || b` means the field is
+/// `a` or `b`, this also checks literal operands that can never work at runtime
+fn resolve(expression: Expression, as_bool: bool) -> Result<Expression, ConditionError> {
+    match expression {
+        Expression::Not(_) | Expression::And(_, _) | Expression::Or(_, _) if !as_bool => {
+            Err(ConditionError::ExpectedValue)
+        }
+        Expression::Not(inner) => Ok(Expression::Not(Box::new(resolve(*inner, true)?))),
+        Expression::And(left, right) => {
+            Ok(Expression::And(Box::new(resolve(*left, true)?), Box::new(resolve(*right, true)?)))
+        }
+        Expression::Or(left, right) => {
+            Ok(Expression::Or(Box::new(resolve(*left, true)?), Box::new(resolve(*right, true)?)))
+        }
+        Expression::Compare(_, _, _) if !as_bool => Err(ConditionError::ExpectedValue),
+        Expression::Compare(operator, left, right) => {
+            let left = resolve(*left, false)?;
+            let right = resolve(*right, false)?;
+            if operator.is_ordering() {
+                expect_numeric_literal(&left)?;
+                expect_numeric_literal(&right)?;
+            }
+            Ok(Expression::Compare(operator, Box::new(left), Box::new(right)))
+        }
+        expression if as_bool && !is_bool_expression(&expression) => Ok(Expression::Compare(
+            CompareOperator::Equal,
+            Box::new(Expression::Subject),
+            Box::new(resolve(expression, false)?),
+        )),
+        Expression::Arithmetic(operator, left, right) => {
+            let left = resolve(*left, false)?;
+            let right = resolve(*right, false)?;
+            expect_numeric_literal(&left)?;
+            expect_numeric_literal(&right)?;
+            Ok(Expression::Arithmetic(operator, Box::new(left), Box::new(right)))
+        }
+        Expression::Negate(inner) => {
+            let inner = resolve(*inner, false)?;
+            expect_numeric_literal(&inner)?;
+            Ok(Expression::Negate(Box::new(inner)))
+        }
+        Expression::List(items) => Ok(Expression::List(
+            items.into_iter().map(|item| resolve(item, false)).collect::<Result<_, _>>()?,
+        )),
+        Expression::Call(function, arguments) => Ok(Expression::Call(
+            function,
+            arguments
+                .into_iter()
+                .map(|argument| resolve(argument, false))
+                .collect::<Result<_, _>>()?,
+        )),
+        expression => Ok(expression),
+    }
+}
+
+fn expect_numeric_literal(expression: &Expression) -> Result<(), ConditionError> {
+    match expression {
+        Expression::Literal(ConditionValue::Number(_)) => Ok(()),
+        Expression::Literal(value) if value.as_number().is_none() => {
+            Err(ConditionError::InvalidNumber(value.as_text().unwrap_or_default()))
+        }
+        Expression::List(_) => Err(ConditionError::InvalidNumber("list".to_string())),
+        _ => Ok(()),
+    }
+}
+
+fn get_nested_value<'a>(data: &'a Value, path: &str) -> Option<&'a Value> {
+    let mut current = data;
+    for key in path.split('.') {
+        current = match current {
+            Value::Array(values) => values.get(key.parse::<usize>().ok()?)?,
+            _ => current.get(key)?,
+        };
+    }
+    Some(current)
+}
+
+struct EvaluationContext<'a> {
+    subject: &'a Value,
+    event_data: &'a Value,
+}
+
+fn as_number(value: ConditionValue) -> Result<Number, String> {
+    value.as_number().ok_or_else(|| format!("{:?} is not a number", value))
+}
+
+fn evaluate(
+    expression: &Expression,
+    context: &EvaluationContext,
+) -> Result<ConditionValue, String> {
+    Ok(match expression {
+        Expression::Subject => ConditionValue::from_json(context.subject),
+        Expression::Field(path) => get_nested_value(context.event_data, path)
+            .map(ConditionValue::from_json)
+            .unwrap_or(ConditionValue::Null),
+        Expression::Literal(value) => value.clone(),
+        Expression::List(items) => ConditionValue::List(
+            items.iter().map(|item| evaluate(item, context)).collect::<Result<_, _>>()?,
+        ),
+        Expression::Not(inner) => ConditionValue::Bool(!evaluate_bool(inner, context)?),
+        Expression::And(left, right) => {
+            ConditionValue::Bool(evaluate_bool(left, context)? && evaluate_bool(right, context)?)
+        }
+        Expression::Or(left, right) => {
+            ConditionValue::Bool(evaluate_bool(left, context)? || evaluate_bool(right, context)?)
+        }
+        Expression::Compare(operator, left, right) => {
+            let left = evaluate(left, context)?;
+            let right = evaluate(right, context)?;
+            ConditionValue::Bool(match operator {
+                CompareOperator::Equal => left.equals(&right),
+                CompareOperator::NotEqual => !left.equals(&right),
+                CompareOperator::In | CompareOperator::NotIn => {
+                    let found = match &right {
+                        ConditionValue::List(items) => items.iter().any(|item| left.equals(item)),
+                        _ => return Err(format!("{:?} is not a list", right)),
+                    };
+                    found == (*operator == CompareOperator::In)
+                }
+                _ => {
+                    let ordering = as_number(left)?.cmp(&as_number(right)?);
+                    match operator {
+                        CompareOperator::GreaterThan => ordering == Ordering::Greater,
+                        CompareOperator::GreaterThanOrEqual => ordering != Ordering::Less,
+                        CompareOperator::LessThan => ordering == Ordering::Less,
+                        _ => ordering != Ordering::Greater,
                     }
-                    "<" => {
-                        U64::from_str_radix(value.as_str().unwrap_or("0"), 10).unwrap_or_default() <
-                            U64::from_str_radix(comp, 10).unwrap_or_default()
+                }
+            })
+        }
+        Expression::Arithmetic(operator, left, right) => {
+            let left = as_number(evaluate(left, context)?)?;
+            let right = as_number(evaluate(right, context)?)?;
+            let result = match operator {
+                ArithmeticOperator::Add => left.checked_add(right),
+                ArithmeticOperator::Subtract => left.checked_sub(right),
+                ArithmeticOperator::Multiply => left.checked_mul(right),
+                ArithmeticOperator::Divide => left.checked_div(right),
+                ArithmeticOperator::Remainder => left.checked_rem(right),
+                ArithmeticOperator::Power => left.checked_pow(right),
+            };
+            ConditionValue::Number(
+                result.ok_or_else(|| format!("{} {:?} {} overflowed", left, operator, right))?,
+            )
+        }
+        Expression::Negate(inner) => {
+            ConditionValue::Number(as_number(evaluate(inner, context)?)?.negate())
+        }
+        Expression::Call(function, arguments) => {
+            let arguments = arguments
+                .iter()
+                .map(|argument| evaluate(argument, context))
+                .collect::<Result<Vec<_>, _>>()?;
+            match function {
+                Function::Lower => ConditionValue::String(arguments[0].as_text()?.to_lowercase()),
+                Function::Upper => ConditionValue::String(arguments[0].as_text()?.to_uppercase()),
+                Function::Len => {
+                    let length = match &arguments[0] {
+                        ConditionValue::List(items) => items.len(),
+                        value => value.as_text()?.chars().count(),
+                    };
+                    ConditionValue::Number(Number::integer(false, U256::from(length)))
+                }
+                Function::Abs => ConditionValue::Number(as_number(arguments[0].clone())?.abs()),
+                Function::Contains => ConditionValue::Bool(match &arguments[0] {
+                    ConditionValue::List(items) => {
+                        items.iter().any(|item| item.equals(&arguments[1]))
                     }
-                    "=" => value == &Value::String(comp.to_string()),
-                    "" => value == &Value::String(subpart.to_string()),
-                    _ => false,
-                };
+                    value => value.as_text()?.contains(&arguments[1].as_text()?),
+                }),
+                Function::StartsWith => ConditionValue::Bool(
+                    arguments[0].as_text()?.starts_with(&arguments[1].as_text()?),
+                ),
+                Function::EndsWith => ConditionValue::Bool(
+                    arguments[0].as_text()?.ends_with(&arguments[1].as_text()?),
+                ),
+            }
+        }
+    })
+}
+
+fn evaluate_bool(expression: &Expression, context: &EvaluationContext) -> Result<bool, String> {
+    match evaluate(expression, context)? {
+        ConditionValue::Bool(b) => Ok(b),
+        value => Err(format!("{:?} is not a boolean", value)),
+    }
+}
+
+fn referenced_fields<'a>(expression: &'a Expression, fields: &mut Vec<&'a str>) {
+    match expression {
+        Expression::Field(path) => fields.push(path),
+        Expression::List(items) | Expression::Call(_, items) => {
+            items.iter().for_each(|item| referenced_fields(item, fields))
+        }
+        Expression::Not(inner) | Expression::Negate(inner) => referenced_fields(inner, fields),
+        Expression::And(left, right) |
+        Expression::Or(left, right) |
+        Expression::Compare(_, left, right) |
+        Expression::Arithmetic(_, left, right) => {
+            referenced_fields(left, fields);
+            referenced_fields(right, fields);
+        }
+        Expression::Subject | Expression::Literal(_) => {}
+    }
+}
+
+fn parse_expression(input: &str, subject: Option<&str>) -> Result<Expression, ConditionError> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+        subject,
+        bare_words_are_fields: subject.is_none(),
+    };
+    let expression = parser.parse_or()?;
+    if let Some(token) = parser.peek() {
+        return Err(ConditionError::UnexpectedToken(token.to_string()));
+    }
+    Ok(expression)
+}
+
+fn uses_subject(expression: &Expression) -> bool {
+    match expression {
+        Expression::Subject => true,
+        Expression::List(items) | Expression::Call(_, items) => items.iter().any(uses_subject),
+        Expression::Not(inner) | Expression::Negate(inner) => uses_subject(inner),
+        Expression::And(left, right) |
+        Expression::Or(left, right) |
+        Expression::Compare(_, left, right) |
+        Expression::Arithmetic(_, left, right) => uses_subject(left) || uses_subject(right),
+        Expression::Field(_) | Expression::Literal(_) => false,
+    }
+}
+
+/// Conditions without any operators are compared as plain text so values like `hello world`
+/// keep matching exactly
+fn is_plain_text(condition: &str) -> bool {
+    !condition.contains(|c| "|&<>=!$()[]'\"".contains(c))
+}
+
+/// A parsed condition for a single field of the event data, parsing happens when the manifest is
+/// loaded so invalid conditions fail fast
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    field: String,
+    expression: Expression,
+}
+
+impl Condition {
+    pub fn parse(field: &str, condition: &Value) -> Result<Self, ConditionError> {
+        let condition = match condition {
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            Value::Bool(b) => b.to_string(),
+            _ => return Err(ConditionError::InvalidConditionValue),
+        };
+
+        let expression = if is_plain_text(&condition) {
+            Expression::Compare(
+                CompareOperator::Equal,
+                Box::new(Expression::Subject),
+                Box::new(Expression::Literal(ConditionValue::String(condition))),
+            )
+        } else {
+            resolve(parse_expression(&condition, Some(field))?, true)?
+        };
+
+        Ok(Self { field: field.to_string(), expression })
+    }
+
+    /// The field the condition is defined under and any fields it references with `$`
+    pub fn fields(&self) -> Vec<&str> {
+        let mut fields = vec![self.field.as_str()];
+        referenced_fields(&self.expression, &mut fields);
+        fields
+    }
+
+    pub fn matches(&self, event_data: &Value) -> bool {
+        let subject = match get_nested_value(event_data, &self.field) {
+            Some(subject) => subject,
+            None => return false,
+        };
+
+        match evaluate_bool(&self.expression, &EvaluationContext { subject, event_data }) {
+            Ok(matches) => matches,
+            Err(e) => {
+                debug!("Condition for {} could not be evaluated: {}", self.field, e);
+                false
             }
-            if and_result {
-                return true;
+        }
+    }
+}
+
+pub fn parse_conditions(
+    conditions: &[Map<String, Value>],
+) -> Result<Vec<Condition>, (String, ConditionError)> {
+    conditions
+        .iter()
+        .flat_map(|condition| condition.iter())
+        .map(|(field, value)| Condition::parse(field, value).map_err(|e| (field.clone(), e)))
+        .collect()
+}
+
+pub fn filter_event_data_by_conditions(event_data: &Value, conditions: &[Condition]) -> bool {
+    conditions.iter().all(|condition| condition.matches(event_data))
+}
+
+/// The conditions of a stream event or chat message parsed when the manifest is loaded, any
+/// error is kept so manifest validation can report it with the event it belongs to
+#[derive(Debug, Clone)]
+pub struct Conditions {
+    raw: Vec<Map<String, Value>>,
+    parsed: Result<Vec<Condition>, (String, ConditionError)>,
+}
+
+impl Conditions {
+    pub fn new(raw: Vec<Map<String, Value>>) -> Self {
+        let parsed = parse_conditions(&raw);
+        Self { raw, parsed }
+    }
+
+    pub fn parsed(&self) -> Result<&[Condition], (String, ConditionError)> {
+        self.parsed.as_deref().map_err(Clone::clone)
+    }
+
+    /// Invalid conditions never match, they are rejected when the manifest is validated
+    pub fn matches(&self, event_data: &Value) -> bool {
+        match &self.parsed {
+            Ok(conditions) => filter_event_data_by_conditions(event_data, conditions),
+            Err((field, e)) => {
+                debug!("Condition for {} is invalid: {}", field, e);
+                false
             }
         }
-        false
-    } else {
-        value == &Value::String(condition.to_string())
     }
 }
 
-pub fn filter_event_data_by_conditions(
+impl Serialize for Conditions {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.raw.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Conditions {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Vec::<Map<String, Value>>::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// Whether the event matches the storage conditions of its contract, events without any are stored
+pub fn is_stored_by_conditions(
+    storage_conditions: Option<&[Condition]>,
     event_data: &Value,
-    conditions: &Vec<Map<String, Value>>,
 ) -> bool {
-    for condition in conditions {
-        for (key, value) in condition {
-            if let Some(event_value) = get_nested_value(event_data, key) {
-                if !evaluate_condition(&event_value, value.as_str().unwrap_or("")) {
-                    return false;
-                }
-            } else {
-                return false;
+    storage_conditions
+        .is_none_or(|conditions| filter_event_data_by_conditions(event_data, conditions))
+}
+
+/// An expression which works out a value from the event data, bare words are read as event
+/// fields so `value / 10^18` divides the `value` field
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueExpression {
+    expression: Expression,
+}
+
+impl ValueExpression {
+    pub fn parse(expression: &str) -> Result<Self, ConditionError> {
+        let expression = parse_expression(expression, None)?;
+        let as_bool = is_bool_expression(&expression);
+        let expression = resolve(expression, as_bool)?;
+        if uses_subject(&expression) {
+            return Err(ConditionError::MissingComparison);
+        }
+
+        Ok(Self { expression })
+    }
+
+    /// The fields the expression reads from the event data
+    pub fn fields(&self) -> Vec<&str> {
+        let mut fields = vec![];
+        referenced_fields(&self.expression, &mut fields);
+        fields
+    }
+
+    fn value(&self, event_data: &Value) -> Option<ConditionValue> {
+        let context = EvaluationContext { subject: &Value::Null, event_data };
+        match evaluate(&self.expression, &context) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                debug!("Expression could not be evaluated: {}", e);
+                None
             }
         }
     }
-    true
+
+    /// The value as a decimal string, `None` if it is not a number or can not be worked out for
+    /// this event such as dividing by zero
+    pub fn evaluate_number(&self, event_data: &Value) -> Option<String> {
+        self.value(event_data)?.as_number().map(|number| number.to_string())
+    }
+
+    pub fn evaluate_text(&self, event_data: &Value) -> Option<String> {
+        match self.value(event_data)? {
+            ConditionValue::Null => None,
+            value => value.as_text().ok(),
+        }
+    }
+
+    pub fn evaluate_bool(&self, event_data: &Value) -> bool {
+        self.value(event_data).is_some_and(|value| value.equals(&ConditionValue::Bool(true)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{Address, H256, U64};
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        abi::ABIInput,
+        database::postgres::sql_type_wrapper::{
+            map_ethereum_wrapper_to_json, EthereumSqlTypeWrapper,
+        },
+        event::callback_registry::TxInformation,
+        manifest::contract::Contract,
+    };
+
+    fn matches(event_data: &Value, field: &str, condition: &str) -> bool {
+        Condition::parse(field, &json!(condition)).unwrap().matches(event_data)
+    }
+
+    #[test]
+    fn test_legacy_conditions_still_match() {
+        let event_data = json!({ "value": "1500", "name": "hello world", "from": "0xabc" });
+
+        assert!(matches(&event_data, "value", ">=1000 && <=2000"));
+        assert!(matches(&event_data, "value", "<1000 || =1500"));
+        assert!(matches(&event_data, "value", ">=1000 && value <=2000"));
+        assert!(!matches(&event_data, "value", ">2000"));
+        assert!(matches(&event_data, "value", "1500"));
+        assert!(matches(&event_data, "name", "hello world"));
+        assert!(matches(&event_data, "from", "0xabc || 0xdef"));
+        assert!(!matches(&event_data, "missing", "1500"));
+    }
+
+    #[test]
+    fn test_uint256_values_do_not_overflow() {
+        let event_data = json!({ "value": "100000000000000000000000", "delta": "-5" });
+
+        assert!(matches(&event_data, "value", "> 99999999999999999999999"));
+        assert!(matches(&event_data, "value", "= 1e23"));
+        assert!(matches(&event_data, "delta", "< 0 && abs($delta) == 5"));
+        assert!(matches(&event_data, "value", "$value - 1e23 == 0"));
+    }
+
+    #[test]
+    fn test_expressions_across_fields() {
+        let event_data = json!({
+            "from": "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "to": "0x0000000000000000000000000000000000000001",
+            "amount0": "10",
+            "amount1": "-3",
+            "transaction_information": { "network": "ethereum" }
+        });
+
+        assert!(matches(&event_data, "from", "!= $to"));
+        assert!(matches(&event_data, "from", "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"));
+        assert!(matches(&event_data, "amount0", "$amount0 + $amount1 > 5 && ($amount1 < 0)"));
+        assert!(matches(&event_data, "to", "not in ['0xdead', $from]"));
+        assert!(matches(
+            &event_data,
+            "transaction_information.network",
+            "in [ethereum, base] && starts_with(lower($from), '0x5aae')"
+        ));
+        assert!(!matches(&event_data, "amount0", "!(>= 10)"));
+    }
+
+    #[test]
+    fn test_invalid_conditions_fail_to_parse() {
+        let parse = |condition: &str| Condition::parse("value", &json!(condition)).unwrap_err();
+
+        assert_eq!(parse(">= 1OOO"), ConditionError::InvalidNumber("1OOO".to_string()));
+        assert_eq!(
+            parse("lowr($from) == 'a'"),
+            ConditionError::UnknownFunction("lowr".to_string())
+        );
+        assert_eq!(parse("(>= 1"), ConditionError::UnexpectedEnd);
+        assert_eq!(parse("= 'abc"), ConditionError::UnterminatedString);
+        assert_eq!(parse("$a + (> 1) == 2"), ConditionError::ExpectedValue);
+        assert!(Condition::parse("value", &json!({ "a": 1 })).is_err());
+    }
+
+    #[test]
+    fn test_conditions_are_parsed_when_they_are_loaded() {
+        let conditions: Conditions =
+            serde_yaml::from_str("- value: \">= 1000\"\n- from: \"!= '0xdead'\"").unwrap();
+        assert_eq!(conditions.parsed().unwrap().len(), 2);
+        assert!(conditions.matches(&json!({ "value": "1500", "from": "0xbeef" })));
+        assert!(!conditions.matches(&json!({ "value": "1500", "from": "0xdead" })));
+        assert!(!conditions.matches(&json!({ "value": "500", "from": "0xbeef" })));
+        assert_eq!(
+            serde_json::to_value(&conditions).unwrap(),
+            json!([{ "value": ">= 1000" }, { "from": "!= '0xdead'" }])
+        );
+
+        let invalid: Conditions = serde_yaml::from_str("- value: \">= 1OOO\"").unwrap();
+        assert_eq!(
+            invalid.parsed().unwrap_err(),
+            ("value".to_string(), ConditionError::InvalidNumber("1OOO".to_string()))
+        );
+        assert!(!invalid.matches(&json!({ "value": "1500" })));
+    }
+
+    #[test]
+    fn test_decimal_numbers() {
+        let event_data = json!({ "value": "1500000000000000000", "price": "2.5" });
+
+        assert!(matches(&event_data, "value", "$value / 10^18 == 1.5"));
+        assert!(matches(&event_data, "price", "> 2.25 && < 3"));
+        assert!(matches(&event_data, "price", "$price * 2 == 5"));
+        assert!(matches(&event_data, "value", "1 / 3 < 0.34"));
+        assert!(!matches(&event_data, "value", "$value / 0 > 0"));
+    }
+
+    #[test]
+    fn test_value_expressions() {
+        let event_data = json!({
+            "value": "1500000000000000000",
+            "from": "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "amount0": "-4",
+            "price": "0.25"
+        });
+        let parse = |expression: &str| ValueExpression::parse(expression).unwrap();
+
+        assert_eq!(parse("value / 10^18").evaluate_number(&event_data), Some("1.5".to_string()));
+        assert_eq!(
+            parse("lower(from)").evaluate_text(&event_data),
+            Some("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed".to_string())
+        );
+        assert_eq!(parse("amount0 * price").evaluate_number(&event_data), Some("-1".to_string()));
+        assert!(parse("value > 10^18 && amount0 < 0").evaluate_bool(&event_data));
+        assert_eq!(parse("value / (amount0 + 4)").evaluate_number(&event_data), None);
+        assert_eq!(parse("missing").evaluate_text(&event_data), None);
+        assert_eq!(
+            ValueExpression::parse("amount0 && price").unwrap_err(),
+            ConditionError::MissingComparison
+        );
+        assert_eq!(ValueExpression::parse("lower(from)").unwrap().fields(), vec!["from"]);
+    }
+
+    #[test]
+    fn test_condition_fields() {
+        let condition = Condition::parse("from", &json!("!= $to && $data.value > 1")).unwrap();
+
+        assert_eq!(condition.fields(), vec!["from", "to", "data.value"]);
+    }
+
+    fn transfer_rows(contract: &Contract) -> Vec<(String, bool)> {
+        let input = |name: &str, type_: &str| ABIInput {
+            indexed: Some(false),
+            name: name.to_string(),
+            type_: type_.to_string(),
+            components: None,
+        };
+        let inputs =
+            vec![input("from", "address"), input("to", "address"), input("value", "uint256")];
+        let conditions = contract
+            .storage_conditions("Transfer", "Transfer(address,address,uint256)")
+            .map(|conditions| parse_conditions(conditions).unwrap());
+
+        [(1, 2, 500u64), (1, 3, 1500), (0, 2, 2500), (2, 1, 1000)]
+            .into_iter()
+            .map(|(from, to, value)| {
+                let event_data = map_ethereum_wrapper_to_json(
+                    &inputs,
+                    &[
+                        EthereumSqlTypeWrapper::Address(Address::from_low_u64_be(from)),
+                        EthereumSqlTypeWrapper::Address(Address::from_low_u64_be(to)),
+                        EthereumSqlTypeWrapper::U256(U256::from(value)),
+                    ],
+                    &TxInformation {
+                        network: "ethereum".to_string(),
+                        address: Address::from_low_u64_be(10),
+                        block_hash: H256::zero(),
+                        block_number: U64::from(100),
+                        block_timestamp: None,
+                        transaction_hash: H256::zero(),
+                        log_index: U256::zero(),
+                        transaction_index: U64::zero(),
+                        input: "0x".to_string(),
+                    },
+                    false,
+                );
+                let stored = is_stored_by_conditions(conditions.as_deref(), &event_data);
+                (event_data["value"].as_str().unwrap().to_string(), stored)
+            })
+            .collect()
+    }
+
+    fn contract(conditions: &str) -> Contract {
+        serde_yaml::from_str(&format!(
+            "name: Token\ndetails: []\nabi: ./abis/Token.abi.json\nconditions:{}",
+            conditions
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_storage_conditions_only_store_matching_rows() {
+        let contract = contract(
+            "\n  - event_name: Transfer\n    conditions:\n      \
+             - value: \">= 1000\"\n      \
+             - from: \"!= '0x0000000000000000000000000000000000000000'\"",
+        );
+
+        assert_eq!(
+            transfer_rows(&contract),
+            [
+                ("500".to_string(), false),
+                ("1500".to_string(), true),
+                ("2500".to_string(), false),
+                ("1000".to_string(), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_storage_conditions_can_use_the_event_signature() {
+        let contract = contract(
+            "\n  - event_name: Transfer(address,address,uint256)\n    conditions:\n      \
+             - value: \"< 1000 || $to == '0x0000000000000000000000000000000000000003'\"",
+        );
+
+        let stored: Vec<bool> = transfer_rows(&contract).into_iter().map(|(_, s)| s).collect();
+        assert_eq!(stored, [true, true, false, false]);
+    }
+
+    #[test]
+    fn test_events_without_storage_conditions_store_every_row() {
+        let contract =
+            contract("\n  - event_name: Approval\n    conditions:\n      - value: \"> 0\"");
+
+        assert!(transfer_rows(&contract).iter().all(|(_, stored)| *stored));
+    }
 }
@@ -10,8 +10,7 @@ use crate::{
         BuildRindexerFilterError, RindexerEventFilter,
     },
     indexer::IndexingEventsProgressState,
-    manifest::storage::CsvDetails,
-    PostgresClient,
+    storage::StorageBackend,
 };
 
 pub struct EventProcessingConfig {
@@ -28,9 +27,8 @@ pub struct EventProcessingConfig {
     pub semaphore: Arc<Semaphore>,
     pub registry: Arc<EventCallbackRegistry>,
     pub progress: Arc<Mutex<IndexingEventsProgressState>>,
-    pub database: Option<Arc<PostgresClient>>,
-    pub csv_details: Option<CsvDetails>,
-    pub stream_last_synced_block_file_path: Option<String>,
+    /// The storage backends which store the event, their checkpoint moves once a batch is stored
+    pub storage_backends: Vec<Arc<dyn StorageBackend>>,
     pub index_event_in_order: bool,
     pub live_indexing: bool,
     pub indexing_distance_from_head: U64,
@@ -47,7 +45,7 @@ impl EventProcessingConfig {
         )
     }
 
-    pub async fn trigger_event(&self, fn_data: Vec<EventResult>) {
-        self.registry.trigger_event(&self.id, fn_data).await;
+    pub async fn trigger_event(&self, fn_data: Vec<EventResult>) -> bool {
+        self.registry.trigger_event(&self.id, fn_data).await
     }
 }
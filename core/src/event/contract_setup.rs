@@ -90,6 +90,56 @@ impl ContractInformation {
             reorg_safe_distance: contract.reorg_safe_distance.unwrap_or_default(),
         })
     }
+
+    /// Restricts each network to only index within the block range, networks with no blocks
+    /// left to index are removed and `None` is returned if no networks are left
+    pub fn restrict_to_block_range(
+        &self,
+        start_block: Option<U64>,
+        end_block: Option<U64>,
+    ) -> Option<ContractInformation> {
+        let details: Vec<NetworkContract> = self
+            .details
+            .iter()
+            .filter_map(|network_contract| {
+                // no start block means only indexing from the latest block
+                let network_start_block = match network_contract.start_block {
+                    Some(network_start_block) => network_start_block,
+                    None => {
+                        return if end_block.is_none() {
+                            Some(network_contract.clone())
+                        } else {
+                            None
+                        };
+                    }
+                };
+
+                let start_block = start_block.map_or(network_start_block, |start_block| {
+                    std::cmp::max(start_block, network_start_block)
+                });
+                let end_block = match (network_contract.end_block, end_block) {
+                    (Some(a), Some(b)) => Some(std::cmp::min(a, b)),
+                    (a, b) => a.or(b),
+                };
+
+                if end_block.is_some_and(|end_block| start_block > end_block) {
+                    return None;
+                }
+
+                let mut network_contract = network_contract.clone();
+                network_contract.id = generate_random_id(10);
+                network_contract.start_block = Some(start_block);
+                network_contract.end_block = end_block;
+                Some(network_contract)
+            })
+            .collect();
+
+        if details.is_empty() {
+            return None;
+        }
+
+        Some(ContractInformation { details, ..self.clone() })
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
@@ -137,3 +187,121 @@ impl IndexingContractSetup {
         matches!(self, IndexingContractSetup::Filter(_))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderMap;
+
+    use super::*;
+    use crate::{event::callback_registry::noop_decoder, provider::create_client};
+
+    fn contract_information(blocks: &[(Option<u64>, Option<u64>)]) -> ContractInformation {
+        let provider =
+            create_client("http://localhost:8545", None, None, HeaderMap::new()).unwrap();
+        ContractInformation {
+            name: "Pool".to_string(),
+            details: blocks
+                .iter()
+                .enumerate()
+                .map(|(index, (start_block, end_block))| NetworkContract {
+                    id: generate_random_id(10),
+                    network: format!("network{}", index),
+                    include_tx_data: None,
+                    indexing_contract_setup: IndexingContractSetup::Address(AddressDetails {
+                        address: ValueOrArray::Value(Address::zero()),
+                        indexed_filters: None,
+                    }),
+                    cached_provider: Arc::clone(&provider),
+                    decoder: noop_decoder(),
+                    start_block: start_block.map(U64::from),
+                    end_block: end_block.map(U64::from),
+                    disable_logs_bloom_checks: false,
+                })
+                .collect(),
+            abi: StringOrArray::Single("Pool.abi.json".to_string()),
+            reorg_safe_distance: false,
+        }
+    }
+
+    fn block_ranges(contract_information: &ContractInformation) -> Vec<(&str, u64, Option<u64>)> {
+        contract_information
+            .details
+            .iter()
+            .map(|network_contract| {
+                (
+                    network_contract.network.as_str(),
+                    network_contract.start_block.unwrap().as_u64(),
+                    network_contract.end_block.map(|block| block.as_u64()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_networks_are_clipped_to_the_block_range() {
+        let contract_information = contract_information(&[
+            (Some(50), None),
+            (Some(150), Some(160)),
+            (Some(100), Some(120)),
+            (Some(200), None),
+        ]);
+
+        let restricted = contract_information
+            .restrict_to_block_range(Some(100.into()), Some(199.into()))
+            .unwrap();
+
+        assert_eq!(
+            block_ranges(&restricted),
+            [
+                ("network0", 100, Some(199)),
+                ("network1", 150, Some(160)),
+                ("network2", 100, Some(120))
+            ]
+        );
+        assert!(restricted
+            .details
+            .iter()
+            .zip(&contract_information.details)
+            .all(|(restricted, original)| restricted.id != original.id));
+    }
+
+    #[test]
+    fn test_the_range_boundaries_are_inclusive() {
+        let contract_information =
+            contract_information(&[(Some(199), None), (Some(0), Some(100)), (Some(0), Some(99))]);
+
+        let restricted = contract_information
+            .restrict_to_block_range(Some(100.into()), Some(199.into()))
+            .unwrap();
+
+        assert_eq!(
+            block_ranges(&restricted),
+            [("network0", 199, Some(199)), ("network1", 100, Some(100))]
+        );
+    }
+
+    #[test]
+    fn test_the_current_abi_range_has_no_end_block() {
+        let contract_information =
+            contract_information(&[(Some(50), None), (Some(400), Some(500))]);
+
+        let restricted =
+            contract_information.restrict_to_block_range(Some(300.into()), None).unwrap();
+
+        assert_eq!(
+            block_ranges(&restricted),
+            [("network0", 300, None), ("network1", 400, Some(500))]
+        );
+    }
+
+    #[test]
+    fn test_networks_indexing_from_the_latest_block_only_use_the_current_abi() {
+        let contract_information = contract_information(&[(None, None)]);
+
+        assert!(contract_information.restrict_to_block_range(None, Some(199.into())).is_none());
+        let restricted =
+            contract_information.restrict_to_block_range(Some(200.into()), None).unwrap();
+        assert_eq!(restricted.details.len(), 1);
+        assert_eq!(restricted.details[0].start_block, None);
+    }
+}
@@ -1,4 +1,4 @@
-use ethers::abi::Hash;
+use ethers::{abi::Hash, utils::keccak256};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -9,3 +9,15 @@ pub struct EventMessage {
     pub event_signature_hash: Hash,
     pub network: String,
 }
+
+impl EventMessage {
+    /// Stream and chat events can be selected by the event name or by the full event signature
+    /// for example `Transfer(address,address,uint256)` to pick a single overloaded event
+    pub fn matches_event_name(&self, event_name: &str) -> bool {
+        if event_name.contains('(') {
+            Hash::from(keccak256(event_name)) == self.event_signature_hash
+        } else {
+            event_name == self.event_name
+        }
+    }
+}
@@ -10,4 +10,7 @@ mod message;
 pub use message::EventMessage;
 
 mod conditions;
-pub use conditions::filter_event_data_by_conditions;
+pub use conditions::{
+    filter_event_data_by_conditions, is_stored_by_conditions, parse_conditions, Condition,
+    ConditionError, Conditions, ValueExpression,
+};
@@ -4,7 +4,7 @@ use ethers::{
     types::ValueOrArray,
 };
 
-use crate::event::contract_setup::IndexingContractSetup;
+use crate::{event::contract_setup::IndexingContractSetup, indexer::RAW_LOGS_TOPIC_ID};
 
 #[derive(thiserror::Error, Debug)]
 pub enum BuildRindexerFilterError {
@@ -17,6 +17,15 @@ pub struct RindexerEventFilter {
     filter: Filter,
 }
 
+fn apply_topic_id(filter: Filter, topic_id: &H256) -> Filter {
+    // raw logs want every log for the address so no topic0 is applied
+    if *topic_id == RAW_LOGS_TOPIC_ID {
+        filter
+    } else {
+        filter.topic0(*topic_id)
+    }
+}
+
 impl RindexerEventFilter {
     fn from_filter(filter: Filter) -> Self {
         if filter.get_to_block().is_none() {
@@ -45,29 +54,32 @@ impl RindexerEventFilter {
                         {
                             return Ok(RindexerEventFilter::from_filter(
                                 index_filters.extend_filter_indexed(
-                                    Filter::new()
-                                        .address(address_details.address.clone())
-                                        .topic0(*topic_id)
-                                        .from_block(current_block)
-                                        .to_block(next_block),
+                                    apply_topic_id(
+                                        Filter::new().address(address_details.address.clone()),
+                                        topic_id,
+                                    )
+                                    .from_block(current_block)
+                                    .to_block(next_block),
                                 ),
                             ));
                         }
 
                         Ok(RindexerEventFilter::from_filter(
-                            Filter::new()
-                                .address(address_details.address.clone())
-                                .topic0(*topic_id)
-                                .from_block(current_block)
-                                .to_block(next_block),
+                            apply_topic_id(
+                                Filter::new().address(address_details.address.clone()),
+                                topic_id,
+                            )
+                            .from_block(current_block)
+                            .to_block(next_block),
                         ))
                     }
                     None => Ok(RindexerEventFilter::from_filter(
-                        Filter::new()
-                            .address(address_details.address.clone())
-                            .topic0(*topic_id)
-                            .from_block(current_block)
-                            .to_block(next_block),
+                        apply_topic_id(
+                            Filter::new().address(address_details.address.clone()),
+                            topic_id,
+                        )
+                        .from_block(current_block)
+                        .to_block(next_block),
                     )),
                 }
             }
@@ -131,3 +143,30 @@ impl RindexerEventFilter {
         &self.filter
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::contract_setup::AddressDetails;
+
+    fn topic0(topic_id: &H256) -> Option<ValueOrArray<Option<H256>>> {
+        let indexing_contract_setup = IndexingContractSetup::Address(AddressDetails {
+            address: ValueOrArray::Value(Address::from_low_u64_be(1)),
+            indexed_filters: None,
+        });
+
+        RindexerEventFilter::new(topic_id, "Transfer", &indexing_contract_setup, 1.into(), 2.into())
+            .unwrap()
+            .raw_filter()
+            .topics[0]
+            .clone()
+    }
+
+    #[test]
+    fn test_raw_logs_do_not_filter_on_topic0() {
+        let topic_id = H256::from_low_u64_be(10);
+
+        assert_eq!(topic0(&topic_id), Some(ValueOrArray::Value(Some(topic_id))));
+        assert_eq!(topic0(&RAW_LOGS_TOPIC_ID), None);
+    }
+}
@@ -74,8 +74,8 @@ fn write_global(
     Ok(())
 }
 
-fn write_database(output: &Path) -> Result<(), WriteGlobalError> {
-    let database_code = generate_database_code();
+fn write_database(output: &Path, storage: &Storage) -> Result<(), WriteGlobalError> {
+    let database_code = generate_database_code(storage);
     write_file(&generate_file_location(output, "database"), database_code.as_str())?;
 
     Ok(())
@@ -179,8 +179,8 @@ pub fn generate_rindexer_typings(
                 write_global(&output, global, &manifest.networks)?;
             }
 
-            if manifest.storage.postgres_enabled() {
-                write_database(&output)?;
+            if manifest.storage.postgres_enabled() || manifest.storage.sqlite_enabled() {
+                write_database(&output, &manifest.storage)?;
             }
 
             write_indexer_events(project_path, &output, manifest.to_indexer(), &manifest.storage)?;
@@ -443,6 +443,7 @@ serde = {{ version = "1.0.194", features = ["derive"] }}
                             indexing_details: if enable_indexer {
                                 Some(IndexingDetails {
                                     registry: register_all_handlers(&manifest_path).await,
+                                    storage_backends: vec![],
                                 })
                             } else {
                                 None
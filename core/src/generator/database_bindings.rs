@@ -1,12 +1,7 @@
-use crate::types::code::Code;
-
-pub fn generate_database_code() -> Code {
-    Code::new(
-        r#"
-    use std::sync::Arc;
-    use rindexer::PostgresClient;
-    use tokio::sync::OnceCell;
+use crate::{manifest::storage::Storage, types::code::Code};
 
+fn generate_postgres_code() -> &'static str {
+    r#"
     static POSTGRES_CLIENT: OnceCell<Arc<PostgresClient>> = OnceCell::const_new();
 
     pub async fn get_or_init_postgres_client() -> Arc<PostgresClient> {
@@ -18,6 +13,45 @@ pub fn generate_database_code() -> Code {
             .clone()
     }
     "#
-        .to_string(),
+}
+
+fn generate_sqlite_code(path: &str) -> String {
+    format!(
+        r#"
+    static SQLITE_CLIENT: OnceCell<Arc<SqliteClient>> = OnceCell::const_new();
+
+    pub async fn get_or_init_sqlite_client() -> Arc<SqliteClient> {{
+        SQLITE_CLIENT
+            .get_or_init(|| async {{
+                Arc::new(
+                    SqliteClient::new(std::path::Path::new({path:?}))
+                        .expect("Failed to open SQLite"),
+                )
+            }})
+            .await
+            .clone()
+    }}
+    "#,
+        path = path
     )
 }
+
+pub fn generate_database_code(storage: &Storage) -> Code {
+    let mut code = r#"
+    use std::sync::Arc;
+    use tokio::sync::OnceCell;
+    "#
+    .to_string();
+
+    if storage.postgres_enabled() {
+        code.push_str("use rindexer::PostgresClient;");
+        code.push_str(generate_postgres_code());
+    }
+
+    if let Some(sqlite) = storage.sqlite.as_ref().filter(|sqlite| sqlite.enabled) {
+        code.push_str("use rindexer::SqliteClient;");
+        code.push_str(&generate_sqlite_code(&sqlite.path));
+    }
+
+    Code::new(code)
+}
@@ -1,15 +1,17 @@
 use std::path::{Path, PathBuf};
 
 use ethers::types::ValueOrArray;
-use serde_json::Value;
 
 use crate::{
     abi::{
         ABIInput, ABIItem, CreateCsvFileForEvent, EventInfo, GenerateAbiPropertiesType,
         ParamTypeError, ReadAbiError,
     },
-    database::postgres::generate::{
-        generate_column_names_only_with_base_properties, generate_event_table_full_name,
+    database::{
+        postgres::generate::{
+            generate_column_names_only_with_base_properties, generate_event_table_full_name,
+        },
+        sqlite::generate::sqlite_table_name,
     },
     helpers::{camel_to_snake, camel_to_snake_advanced, to_pascal_case},
     manifest::{
@@ -39,8 +41,8 @@ pub enum GenerateStructsError {
     #[error("Could not read ABI JSON: {0}")]
     CouldNotReadAbiJson(#[from] serde_json::Error),
 
-    #[error("Invalid ABI JSON format")]
-    InvalidAbiJsonFormat,
+    #[error("{0}")]
+    ParamTypeError(#[from] ParamTypeError),
 
     #[error("{0}")]
     ParseAbiError(#[from] ParseAbiError),
@@ -53,18 +55,17 @@ fn generate_structs(
     // TODO - this could be shared with `get_abi_items`
     let abi_str = contract.parse_abi(project_path)?;
 
-    let abi_json: Value = serde_json::from_str(&abi_str)?;
+    let abi_items: Vec<ABIItem> = serde_json::from_str(&abi_str)?;
 
     let mut structs = Code::blank();
 
-    for item in abi_json.as_array().ok_or(GenerateStructsError::InvalidAbiJsonFormat)?.iter() {
-        if item["type"] == "event" {
-            let event_name = item["name"].as_str().unwrap_or_default();
-            let struct_result = format!("{}Result", event_name);
-            let struct_data = format!("{}Data", event_name);
+    // overloaded events are numbered the same as the ethers abigen event structs
+    for event_info in ABIItem::extract_event_names_and_signatures_from_abi(abi_items)? {
+        let struct_result = event_info.struct_result();
+        let struct_data = event_info.struct_data();
 
-            structs.push_str(&Code::new(format!(
-                r#"
+        structs.push_str(&Code::new(format!(
+            r#"
                     pub type {struct_data} = {abigen_mod_name}::{pascal_event_name}Filter;
 
                     #[derive(Debug, Clone)]
@@ -73,12 +74,11 @@ fn generate_structs(
                         pub tx_information: TxInformation
                     }}
                 "#,
-                struct_result = struct_result,
-                struct_data = struct_data,
-                abigen_mod_name = abigen_contract_mod_name(contract),
-                pascal_event_name = to_pascal_case(event_name)
-            )));
-        }
+            struct_result = struct_result,
+            struct_data = struct_data,
+            abigen_mod_name = abigen_contract_mod_name(contract),
+            pascal_event_name = to_pascal_case(&event_info.name)
+        )));
     }
 
     Ok(structs)
@@ -291,6 +291,7 @@ fn generate_event_callback_structs_code(
                         callback: {lower_name}_handler(closure),
                         context: Arc::new(EventContext {{
                             {database}
+                            {sqlite}
                             {csv}
                             extensions: Arc::new(extensions),
                         }}),
@@ -332,6 +333,11 @@ fn generate_event_callback_structs_code(
             } else {
                 ""
             },
+            sqlite = if storage.sqlite_enabled() {
+                "sqlite: get_or_init_sqlite_client().await,"
+            } else {
+                ""
+            },
             csv = if csv_enabled { r#"csv: Arc::new(csv),"# } else { "" },
             csv_generator = csv_generator,
             event_callback_events_len =
@@ -511,9 +517,11 @@ fn generate_event_bindings_code(
             }},
             provider::JsonRpcCachedProvider,
             {postgres_client_import}
+            {sqlite_client_import}
         }};
         use super::super::super::super::typings::networks::get_provider_cache_for_network;
         {postgres_import}
+        {sqlite_import}
 
         {structs}
 
@@ -526,6 +534,7 @@ fn generate_event_bindings_code(
 
         pub struct EventContext<TExtensions> where TExtensions: Send + Sync {{
             {event_context_database}
+            {event_context_sqlite}
             {event_context_csv}
             pub extensions: Arc<TExtensions>,
         }}
@@ -641,6 +650,12 @@ fn generate_event_bindings_code(
             ""
         },
         postgres_client_import = if storage.postgres_enabled() { "PostgresClient," } else { "" },
+        sqlite_import = if storage.sqlite_enabled() {
+            "use super::super::super::super::typings::database::get_or_init_sqlite_client;"
+        } else {
+            ""
+        },
+        sqlite_client_import = if storage.sqlite_enabled() { "SqliteClient," } else { "" },
         csv_import = if storage.csv_enabled() { "AsyncCsvAppender," } else { "" },
         abigen_mod_name = abigen_contract_mod_name(contract),
         abigen_file_name = abigen_contract_file_name(contract),
@@ -649,6 +664,8 @@ fn generate_event_bindings_code(
         event_type_name = &event_type_name,
         event_context_database =
             if storage.postgres_enabled() { "pub database: Arc<PostgresClient>," } else { "" },
+        event_context_sqlite =
+            if storage.sqlite_enabled() { "pub sqlite: Arc<SqliteClient>," } else { "" },
         event_context_csv =
             if storage.csv_enabled() { "pub csv: Arc<AsyncCsvAppender>," } else { "" },
         event_callback_structs =
@@ -692,7 +709,7 @@ pub fn generate_event_bindings(
     storage: &Storage,
 ) -> Result<Code, GenerateEventBindingsError> {
     let abi_items = ABIItem::get_abi_items(project_path, contract, is_filter)?;
-    let event_names = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?;
+    let event_names = ABIItem::extract_contract_events(contract, abi_items)?;
 
     generate_event_bindings_code(project_path, indexer_name, contract, storage, event_names)
         .map_err(GenerateEventBindingsError::GenerateEventBindingCode)
@@ -715,7 +732,7 @@ pub fn generate_event_handlers(
     storage: &Storage,
 ) -> Result<Code, GenerateEventHandlersError> {
     let abi_items = ABIItem::get_abi_items(project_path, contract, is_filter)?;
-    let event_names = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?;
+    let event_names = ABIItem::extract_contract_events(contract, abi_items)?;
 
     let mut imports = String::new();
     imports.push_str(
@@ -811,11 +828,11 @@ pub fn generate_event_handlers(
             }
         }
 
-        let mut postgres_write = String::new();
+        let mut data = String::new();
 
         // this checks storage enabled as well
-        if !storage.postgres_disable_create_tables() {
-            let mut data =
+        if !storage.postgres_disable_create_tables() || !storage.sqlite_disable_create_tables() {
+            data =
                 "vec![EthereumSqlTypeWrapper::Address(result.tx_information.address),".to_string();
 
             for item in &abi_name_properties {
@@ -853,7 +870,56 @@ pub fn generate_event_handlers(
                 "EthereumSqlTypeWrapper::String(result.tx_information.input.to_string()),",
             );
             data.push_str("];");
+        }
+
+        let columns_names = generate_column_names_only_with_base_properties(&event.inputs)
+            .iter()
+            .map(|item| format!("\"{}\".to_string()", item))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        let mut sqlite_write = String::new();
 
+        // this checks storage enabled as well
+        if !storage.sqlite_disable_create_tables() {
+            sqlite_write = format!(
+                r#"
+                    let mut sqlite_bulk_data: Vec<Vec<EthereumSqlTypeWrapper>> = vec![];
+                    for result in results.iter() {{
+                        let data = {data};
+                        sqlite_bulk_data.push(data);
+                    }}
+
+                    let result = context
+                        .sqlite
+                        .bulk_insert(
+                            "{table_name}",
+                            &[{columns_names}],
+                            &sqlite_bulk_data,
+                        )
+                        .await;
+
+                    if let Err(e) = result {{
+                        rindexer_error!("{event_type_name}::{handler_name} inserting sqlite data: {{:?}}", e);
+                        return Err(e.to_string());
+                    }}
+                "#,
+                table_name = sqlite_table_name(&generate_event_table_full_name(
+                    indexer_name,
+                    &contract.name,
+                    &event.name
+                )),
+                handler_name = event.name,
+                event_type_name = event_type_name,
+                columns_names = columns_names,
+                data = data,
+            );
+        }
+
+        let mut postgres_write = String::new();
+
+        // this checks storage enabled as well
+        if !storage.postgres_disable_create_tables() {
             postgres_write = format!(
                 r#"
                     let mut postgres_bulk_data: Vec<Vec<EthereumSqlTypeWrapper>> = vec![];
@@ -910,11 +976,7 @@ pub fn generate_event_handlers(
                     generate_event_table_full_name(indexer_name, &contract.name, &event.name),
                 handler_name = event.name,
                 event_type_name = event_type_name,
-                columns_names = generate_column_names_only_with_base_properties(&event.inputs)
-                    .iter()
-                    .map(|item| format!("\"{}\".to_string()", item))
-                    .collect::<Vec<String>>()
-                    .join(", "),
+                columns_names = columns_names,
                 data = data,
                 csv_write = csv_write,
                 csv_bulk_data = if storage.csv_enabled() {
@@ -950,6 +1012,7 @@ pub fn generate_event_handlers(
                             }}
 
                             {csv_write}
+                            {sqlite_write}
                             {postgres_write}
 
                             rindexer_info!(
@@ -972,6 +1035,7 @@ pub fn generate_event_handlers(
             event_type_name = event_type_name,
             contract_name = contract.name,
             csv_write = if !postgres_write.is_empty() { String::new() } else { csv_write },
+            sqlite_write = sqlite_write,
             postgres_write = postgres_write,
         );
 
@@ -0,0 +1,88 @@
+use serde_json::Value;
+
+use crate::{
+    database::postgres::sql_type_wrapper::EthereumSqlTypeWrapper,
+    event::{ConditionError, ValueExpression},
+    helpers::camel_to_snake,
+    manifest::contract::{ColumnType, ComputedColumn},
+};
+
+/// The computed columns of an event with their expressions parsed once on startup
+#[derive(Clone, Default)]
+pub struct ComputedColumns {
+    columns: Vec<(ComputedColumn, ValueExpression)>,
+}
+
+impl ComputedColumns {
+    pub fn parse(columns: &[ComputedColumn]) -> Result<Self, (String, ConditionError)> {
+        let columns = columns
+            .iter()
+            .map(|column| {
+                ValueExpression::parse(&column.expression)
+                    .map(|expression| (column.clone(), expression))
+                    .map_err(|e| (column.name.clone(), e))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { columns })
+    }
+
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|(column, _)| camel_to_snake(&column.name)).collect()
+    }
+
+    pub fn column_types(&self) -> Vec<(String, ColumnType)> {
+        self.columns
+            .iter()
+            .map(|(column, _)| (camel_to_snake(&column.name), column.type_))
+            .collect()
+    }
+
+    /// Works out the values in order and adds them to the event data, so later columns,
+    /// conditions, streams and chat can all use them, returning the postgres and csv values
+    pub fn compute(&self, event_data: &mut Value) -> Vec<(EthereumSqlTypeWrapper, String)> {
+        self.columns
+            .iter()
+            .map(|(column, expression)| {
+                let (json, postgres, csv) = evaluate(expression, column.type_, event_data);
+
+                if let Value::Object(event_data) = event_data {
+                    event_data.insert(column.name.clone(), json);
+                }
+
+                (postgres, csv)
+            })
+            .collect()
+    }
+}
+
+/// Works out an expression as the given column type returning the json, postgres and csv values,
+/// a value which can not be worked out for this event is stored as null
+pub fn evaluate(
+    expression: &ValueExpression,
+    type_: ColumnType,
+    event_data: &Value,
+) -> (Value, EthereumSqlTypeWrapper, String) {
+    match type_ {
+        ColumnType::Numeric => {
+            let value = expression.evaluate_number(event_data);
+            (
+                value.clone().map_or(Value::Null, Value::String),
+                EthereumSqlTypeWrapper::NumericNullable(value.clone().unwrap_or_default()),
+                value.unwrap_or_default(),
+            )
+        }
+        ColumnType::Text => {
+            let value = expression.evaluate_text(event_data);
+            (
+                value.clone().map_or(Value::Null, Value::String),
+                EthereumSqlTypeWrapper::StringNullable(value.clone().unwrap_or_default()),
+                value.unwrap_or_default(),
+            )
+        }
+        ColumnType::Bool => {
+            let value = expression.evaluate_bool(event_data);
+            (Value::Bool(value), EthereumSqlTypeWrapper::Bool(value), value.to_string())
+        }
+    }
+}
@@ -5,8 +5,8 @@ use std::{
 
 use crate::{
     database::postgres::relationship::Relationship,
-    event::{config::EventProcessingConfig, contract_setup::ContractEventMapping},
-    manifest::{contract::DependencyEventTree, core::Manifest},
+    event::contract_setup::ContractEventMapping,
+    manifest::{contract::DependencyEventTree, core::Manifest, storage::ForeignKeys},
 };
 
 #[derive(Debug, Clone)]
@@ -57,298 +57,334 @@ pub struct ContractEventDependencies {
     pub event_dependencies: EventDependencies,
 }
 
-#[derive(thiserror::Error, Debug)]
-pub enum ContractEventDependenciesMapFromRelationshipsError {
-    #[error("Cross contract relationships are need manually mapping in the dependency_events, https://rindexer.xyz/docs/start-building/yaml-config/contracts#dependency_events")]
-    CrossContractRelationshipsNotDefinedInDependencyEvents,
-}
-
 impl ContractEventDependencies {
-    pub fn map_from_relationships(
-        relationships: &[Relationship],
-    ) -> Result<Vec<ContractEventDependencies>, ContractEventDependenciesMapFromRelationshipsError>
-    {
-        if Relationship::has_cross_contract_dependency(relationships) {
-            return Err(ContractEventDependenciesMapFromRelationshipsError::CrossContractRelationshipsNotDefinedInDependencyEvents);
-        }
-
-        Ok(ContractEventDependencies::map_all_dependencies(relationships))
-    }
+    pub fn parse(manifest: &Manifest) -> Vec<ContractEventDependencies> {
+        let mut dependencies: Vec<ContractEventDependencies> = vec![];
+        for contract in &manifest.contracts {
+            if let Some(dependency) = contract.dependency_events.clone() {
+                let dependency_event_tree = contract.convert_dependency_event_tree_yaml(dependency);
+                let dependency_tree =
+                    EventsDependencyTree::from_dependency_event_tree(&dependency_event_tree);
 
-    fn map_all_dependencies(relationships: &[Relationship]) -> Vec<ContractEventDependencies> {
-        let relationships_map =
-            ContractEventDependencies::generate_relationships_map(relationships);
-        let mut result_map = HashMap::new();
-        let mut visited = HashSet::new();
-
-        for event in relationships_map.keys() {
-            let tree = ContractEventDependencies::build_dependency_tree(
-                event,
-                &relationships_map,
-                &mut visited,
-            );
-            let dependency_events = ContractEventDependencies::collect_dependency_events(&tree);
-
-            result_map
-                .entry(event.contract_name.clone())
-                .and_modify(|e: &mut EventDependencies| {
-                    e.tree = Arc::new(ContractEventDependencies::merge_trees(&e.tree, &tree));
-                    e.dependency_events.extend(dependency_events.clone());
-                })
-                .or_insert(EventDependencies { tree: Arc::clone(&tree), dependency_events });
+                dependencies.push(ContractEventDependencies {
+                    contract_name: contract.name.clone(),
+                    event_dependencies: EventDependencies {
+                        tree: Arc::new(dependency_tree),
+                        dependency_events: dependency_event_tree.collect_dependency_events(),
+                    },
+                });
+            }
         }
 
-        result_map
-            .into_iter()
-            .map(|(contract_name, event_dependencies)| ContractEventDependencies {
-                contract_name,
-                event_dependencies,
-            })
-            .collect()
+        dependencies
     }
+}
 
-    fn merge_trees(
-        tree1: &EventsDependencyTree,
-        tree2: &EventsDependencyTree,
-    ) -> EventsDependencyTree {
-        let mut contract_events = tree1.contract_events.clone();
-        contract_events.extend(tree2.contract_events.clone());
-        contract_events.sort_by(|a, b| a.event_name.cmp(&b.event_name));
-        contract_events.dedup();
-
-        EventsDependencyTree {
-            contract_events,
-            then: if tree1.then.is_none() && tree2.then.is_none() {
-                Box::new(None)
-            } else {
-                Box::new(Some(Arc::new(ContractEventDependencies::merge_trees(
-                    tree1.then.as_ref().as_ref().unwrap_or(&Arc::new(EventsDependencyTree {
-                        contract_events: vec![],
-                        then: Box::new(None),
-                    })),
-                    tree2.then.as_ref().as_ref().unwrap_or(&Arc::new(EventsDependencyTree {
-                        contract_events: vec![],
-                        then: Box::new(None),
-                    })),
-                ))))
-            },
-        }
-    }
+#[derive(thiserror::Error, Debug)]
+pub enum EventDependencyGraphError {
+    #[error("Dependency events contain a cycle between: {0}")]
+    CycleDetected(String),
+}
 
-    fn build_dependency_tree(
-        event: &ContractEventMapping,
-        relationships_map: &HashMap<ContractEventMapping, Vec<ContractEventMapping>>,
-        visited: &mut HashSet<ContractEventMapping>,
-    ) -> Arc<EventsDependencyTree> {
-        if visited.contains(event) {
-            return Arc::new(EventsDependencyTree { contract_events: vec![], then: Box::new(None) });
-        }
+/// A directed acyclic graph of event dependencies across every contract. Each event waits on
+/// all of its parents to finish indexing before it starts, so an event can depend on events in
+/// as many other contracts as it needs.
+#[derive(Debug, Clone, Default)]
+pub struct EventDependencyGraph {
+    // kept in insertion order so scheduling is deterministic
+    events: Vec<ContractEventMapping>,
+    parents: HashMap<ContractEventMapping, HashSet<ContractEventMapping>>,
+}
 
-        visited.insert(event.clone());
+impl EventDependencyGraph {
+    pub fn from_contract_dependencies(dependencies: &[ContractEventDependencies]) -> Self {
+        let mut graph = EventDependencyGraph::default();
 
-        let contract_events = vec![event.clone()];
-        let mut next_tree: Option<Arc<EventsDependencyTree>> = None;
+        for dependency in dependencies {
+            let mut parents: Vec<ContractEventMapping> = vec![];
+            let mut current = Some(Arc::clone(&dependency.event_dependencies.tree));
 
-        if let Some(linked_events) = relationships_map.get(event) {
-            for linked_event in linked_events {
-                let tree = ContractEventDependencies::build_dependency_tree(
-                    linked_event,
-                    relationships_map,
-                    visited,
-                );
-                match next_tree {
-                    None => {
-                        next_tree = Some(tree);
-                    }
-                    Some(next_tree_value) => {
-                        next_tree = Some(Arc::new(ContractEventDependencies::merge_trees(
-                            &next_tree_value,
-                            &tree,
-                        )));
+            while let Some(tree) = current {
+                for event in &tree.contract_events {
+                    graph.add_event(event.clone());
+                    for parent in &parents {
+                        graph.add_dependency(parent.clone(), event.clone());
                     }
                 }
+
+                if !tree.contract_events.is_empty() {
+                    parents = tree.contract_events.clone();
+                }
+                current = tree.then.as_ref().clone();
             }
         }
 
-        Arc::new(EventsDependencyTree { contract_events, then: Box::new(next_tree) })
+        graph
     }
 
-    fn generate_relationships_map(
-        relationships: &[Relationship],
-    ) -> HashMap<ContractEventMapping, Vec<ContractEventMapping>> {
-        let mut relationships_map = HashMap::new();
+    pub fn from_relationships(relationships: &[Relationship]) -> Self {
+        let mut graph = EventDependencyGraph::default();
 
         for relationship in relationships {
-            let event = ContractEventMapping {
-                contract_name: relationship.contract_name.clone(),
-                event_name: relationship.event.clone(),
-            };
+            // the linked table holds the unique key, so it has to be written first
+            graph.add_dependency(
+                ContractEventMapping {
+                    contract_name: relationship.linked_to.contract_name.clone(),
+                    event_name: relationship.linked_to.event.clone(),
+                },
+                ContractEventMapping {
+                    contract_name: relationship.contract_name.clone(),
+                    event_name: relationship.event.clone(),
+                },
+            );
+        }
 
-            let linked_event = ContractEventMapping {
-                contract_name: relationship.linked_to.contract_name.clone(),
-                event_name: relationship.linked_to.event.clone(),
-            };
+        graph
+    }
 
-            relationships_map.entry(linked_event).or_insert_with(Vec::new).push(event);
+    pub fn from_foreign_keys(foreign_keys: &[ForeignKeys]) -> Self {
+        let mut graph = EventDependencyGraph::default();
+
+        for foreign_key in foreign_keys {
+            for linked_to in &foreign_key.foreign_keys {
+                graph.add_dependency(
+                    ContractEventMapping {
+                        contract_name: linked_to.contract_name.clone(),
+                        event_name: linked_to.event_name.clone(),
+                    },
+                    ContractEventMapping {
+                        contract_name: foreign_key.contract_name.clone(),
+                        event_name: foreign_key.event_name.clone(),
+                    },
+                );
+            }
         }
 
-        relationships_map
+        graph
+    }
+
+    /// Builds the graph from the `dependency_events` of every contract in the manifest.
+    pub fn parse(manifest: &Manifest) -> Self {
+        EventDependencyGraph::from_contract_dependencies(&ContractEventDependencies::parse(
+            manifest,
+        ))
     }
 
-    fn collect_dependency_events(tree: &EventsDependencyTree) -> Vec<ContractEventMapping> {
-        let mut events = tree.contract_events.clone();
-        if let Some(ref then_tree) = *tree.then {
-            events.extend(ContractEventDependencies::collect_dependency_events(then_tree));
+    pub fn add_event(&mut self, event: ContractEventMapping) {
+        if !self.parents.contains_key(&event) {
+            self.parents.insert(event.clone(), HashSet::new());
+            self.events.push(event);
         }
-        events
     }
-}
 
-#[derive(Debug)]
-pub struct DependencyStatus {
-    pub has_dependency_in_own_contract: bool,
-    pub dependencies_in_other_contracts: Vec<String>,
-}
+    pub fn add_dependency(&mut self, parent: ContractEventMapping, child: ContractEventMapping) {
+        self.add_event(parent.clone());
+        self.add_event(child.clone());
+        if parent != child {
+            self.parents.entry(child).or_default().insert(parent);
+        }
+    }
 
-impl DependencyStatus {
-    pub fn has_dependency_in_other_contracts_multiple_times(&self) -> bool {
-        self.dependencies_in_other_contracts.len() > 1
+    pub fn merge(&mut self, other: &EventDependencyGraph) {
+        for event in &other.events {
+            self.add_event(event.clone());
+            for parent in other.parents_of(event) {
+                self.add_dependency(parent, event.clone());
+            }
+        }
     }
 
-    pub fn has_dependencies(&self) -> bool {
-        self.has_dependency_in_own_contract || !self.dependencies_in_other_contracts.is_empty()
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
     }
 
-    pub fn get_first_dependencies_in_other_contracts(&self) -> Option<String> {
-        self.dependencies_in_other_contracts.first().cloned()
+    pub fn events(&self) -> &[ContractEventMapping] {
+        &self.events
     }
-}
 
-impl ContractEventDependencies {
-    pub fn parse(manifest: &Manifest) -> Vec<ContractEventDependencies> {
-        let mut dependencies: Vec<ContractEventDependencies> = vec![];
-        for contract in &manifest.contracts {
-            if let Some(dependency) = contract.dependency_events.clone() {
-                let dependency_event_tree = contract.convert_dependency_event_tree_yaml(dependency);
-                let dependency_tree =
-                    EventsDependencyTree::from_dependency_event_tree(&dependency_event_tree);
+    pub fn contains(&self, contract_name: &str, event_name: &str) -> bool {
+        self.parents.contains_key(&EventDependencyGraph::mapping_for(contract_name, event_name))
+    }
 
-                dependencies.push(ContractEventDependencies {
-                    contract_name: contract.name.clone(),
-                    event_dependencies: EventDependencies {
-                        tree: Arc::new(dependency_tree),
-                        dependency_events: dependency_event_tree.collect_dependency_events(),
-                    },
-                });
+    pub fn parents_of(&self, event: &ContractEventMapping) -> Vec<ContractEventMapping> {
+        // return in insertion order so it is stable between runs
+        self.parents
+            .get(event)
+            .map(|parents| self.events.iter().filter(|e| parents.contains(e)).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Filter contracts are registered as `{name}Filter` but dependencies always reference the
+    /// name defined in the yaml.
+    pub fn mapping_for(contract_name: &str, event_name: &str) -> ContractEventMapping {
+        ContractEventMapping {
+            contract_name: contract_name
+                .strip_suffix("Filter")
+                .unwrap_or(contract_name)
+                .to_string(),
+            event_name: event_name.to_string(),
+        }
+    }
+
+    /// Orders the events so every parent comes before its children, failing if the graph has a
+    /// cycle as those events could never start.
+    pub fn topological_order(
+        &self,
+    ) -> Result<Vec<ContractEventMapping>, EventDependencyGraphError> {
+        let mut remaining_parents: HashMap<&ContractEventMapping, usize> =
+            self.events.iter().map(|e| (e, self.parents[e].len())).collect();
+        let mut ordered: Vec<ContractEventMapping> = Vec::with_capacity(self.events.len());
+        let mut ready: Vec<&ContractEventMapping> =
+            self.events.iter().filter(|e| remaining_parents[e] == 0).collect();
+
+        while !ready.is_empty() {
+            let mut next_ready = vec![];
+            for event in ready {
+                ordered.push(event.clone());
+                for child in self.events.iter().filter(|c| self.parents[*c].contains(event)) {
+                    let count = remaining_parents.get_mut(child).expect("child must be in graph");
+                    *count -= 1;
+                    if *count == 0 {
+                        next_ready.push(child);
+                    }
+                }
             }
+            ready = next_ready;
         }
 
-        dependencies
+        if ordered.len() != self.events.len() {
+            let cycle = self
+                .events
+                .iter()
+                .filter(|e| remaining_parents[e] > 0)
+                .map(|e| format!("{}.{}", e.contract_name, e.event_name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(EventDependencyGraphError::CycleDetected(cycle));
+        }
+
+        Ok(ordered)
     }
 
-    pub fn dependencies_status(
-        contract_name: &str,
-        event_name: &str,
-        dependencies: &[ContractEventDependencies],
-    ) -> DependencyStatus {
-        let has_dependency_in_own_contract =
-            dependencies.iter().find(|d| d.contract_name == contract_name).map_or(false, |deps| {
-                deps.event_dependencies.has_dependency(&ContractEventMapping {
-                    contract_name: deps.contract_name.clone(),
-                    event_name: event_name.to_string(),
-                })
-            });
-
-        let dependencies_in_other_contracts: Vec<String> = dependencies
-            .iter()
-            .filter_map(|d| {
-                if d.contract_name != contract_name {
-                    let has_dependency =
-                        d.event_dependencies.has_dependency(&ContractEventMapping {
-                            contract_name: contract_name.to_string(),
-                            event_name: event_name.to_string(),
-                        });
-
-                    if has_dependency {
-                        return Some(d.contract_name.to_string());
-                    }
+    /// Splits the events into groups which share no dependencies, each in topological order, so
+    /// unrelated groups can be indexed independently of each other.
+    pub fn independent_groups(
+        &self,
+    ) -> Result<Vec<Vec<ContractEventMapping>>, EventDependencyGraphError> {
+        let ordered = self.topological_order()?;
 
-                    // check if it's a filter event
-                    let has_dependency =
-                        d.event_dependencies.has_dependency(&ContractEventMapping {
-                            // TODO - this is a hacky way to check if it's a filter event
-                            contract_name: contract_name.to_string().replace("Filter", ""),
-                            event_name: event_name.to_string(),
-                        });
-                    if has_dependency {
-                        return Some(d.contract_name.to_string());
-                    }
+        let mut group_ids: HashMap<&ContractEventMapping, usize> = HashMap::new();
+        let mut groups: Vec<Vec<ContractEventMapping>> = vec![];
+
+        for event in &self.events {
+            if group_ids.contains_key(event) {
+                continue;
+            }
+
+            let group_id = groups.len();
+            groups.push(vec![]);
+
+            let mut stack = vec![event];
+            while let Some(current) = stack.pop() {
+                if group_ids.insert(current, group_id).is_some() {
+                    continue;
+                }
+                stack.extend(self.parents[current].iter());
+                for child in self.events.iter().filter(|c| self.parents[*c].contains(current)) {
+                    stack.push(child);
                 }
-                None
-            })
-            .collect();
+            }
+        }
+
+        for event in ordered {
+            let group_id = group_ids[&event];
+            groups[group_id].push(event);
+        }
 
-        DependencyStatus { has_dependency_in_own_contract, dependencies_in_other_contracts }
+        Ok(groups)
     }
 }
 
-pub struct ContractEventsDependenciesConfig {
-    pub contract_name: String,
-    pub event_dependencies: EventDependencies,
-    pub events_config: Vec<Arc<EventProcessingConfig>>,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-impl ContractEventsDependenciesConfig {
-    fn add_event_config(&mut self, config: Arc<EventProcessingConfig>) {
-        self.events_config.push(config);
+    fn event(contract_name: &str, event_name: &str) -> ContractEventMapping {
+        ContractEventMapping {
+            contract_name: contract_name.to_string(),
+            event_name: event_name.to_string(),
+        }
     }
 
-    pub fn add_to_event_or_new_entry(
-        dependency_event_processing_configs: &mut Vec<ContractEventsDependenciesConfig>,
-        event_processing_config: Arc<EventProcessingConfig>,
-        dependencies: &[ContractEventDependencies],
-    ) {
-        match dependency_event_processing_configs
-            .iter_mut()
-            .find(|c| c.contract_name == event_processing_config.contract_name)
-        {
-            Some(contract_events_config) => {
-                contract_events_config.add_event_config(event_processing_config)
-            }
-            None => {
-                dependency_event_processing_configs.push(ContractEventsDependenciesConfig {
-                    contract_name: event_processing_config.contract_name.clone(),
-                    event_dependencies: dependencies
-                        .iter()
-                        .find(|d| d.contract_name == event_processing_config.contract_name)
-                        .expect("Failed to find contract dependencies")
-                        .event_dependencies
-                        .clone(),
-                    events_config: vec![event_processing_config],
-                });
-            }
-        }
+    #[test]
+    fn test_event_depending_on_multiple_contracts() {
+        let mut graph = EventDependencyGraph::default();
+        graph.add_dependency(event("TokenA", "Transfer"), event("Pool", "Swap"));
+        graph.add_dependency(event("TokenB", "Transfer"), event("Pool", "Swap"));
+        graph.add_dependency(event("Pool", "Swap"), event("Router", "Routed"));
+
+        let ordered = graph.topological_order().unwrap();
+        let position = |e: &ContractEventMapping| ordered.iter().position(|o| o == e).unwrap();
+
+        assert_eq!(ordered.len(), 4);
+        assert!(position(&event("TokenA", "Transfer")) < position(&event("Pool", "Swap")));
+        assert!(position(&event("TokenB", "Transfer")) < position(&event("Pool", "Swap")));
+        assert!(position(&event("Pool", "Swap")) < position(&event("Router", "Routed")));
+        assert_eq!(
+            graph.parents_of(&event("Pool", "Swap")),
+            vec![event("TokenA", "Transfer"), event("TokenB", "Transfer")]
+        );
     }
 
-    pub fn add_to_event_or_panic(
-        contract_name: &str,
-        dependency_event_processing_configs: &mut [ContractEventsDependenciesConfig],
-        event_processing_config: Arc<EventProcessingConfig>,
-    ) {
-        match dependency_event_processing_configs
-            .iter_mut()
-            .find(|c| c.contract_name == contract_name)
-        {
-            Some(contract_events_config) => {
-                contract_events_config.add_event_config(event_processing_config)
-            }
-            None => {
-                panic!("Contract events config not found for {} dependency event processing config make sure it registered - trying to add to it - contract {} - event {}",
-                       contract_name,
-                       event_processing_config.contract_name,
-                       event_processing_config.event_name
-                );
+    #[test]
+    fn test_mapping_for_only_strips_filter_suffix() {
+        assert_eq!(
+            EventDependencyGraph::mapping_for("TokenFilter", "Transfer"),
+            event("Token", "Transfer")
+        );
+        assert_eq!(
+            EventDependencyGraph::mapping_for("FilterRegistry", "Registered"),
+            event("FilterRegistry", "Registered")
+        );
+    }
+
+    #[test]
+    fn test_cycle_detected() {
+        let mut graph = EventDependencyGraph::default();
+        graph.add_dependency(event("A", "One"), event("B", "Two"));
+        graph.add_dependency(event("B", "Two"), event("C", "Three"));
+        graph.add_dependency(event("C", "Three"), event("A", "One"));
+        graph.add_dependency(event("D", "Four"), event("A", "One"));
+
+        match graph.topological_order() {
+            Err(EventDependencyGraphError::CycleDetected(events)) => {
+                assert_eq!(events, "A.One, B.Two, C.Three");
             }
+            Ok(_) => panic!("expected a cycle"),
         }
     }
+
+    #[test]
+    fn test_independent_groups() {
+        let mut graph = EventDependencyGraph::default();
+        graph.add_dependency(event("A", "One"), event("B", "Two"));
+        graph.add_dependency(event("C", "Three"), event("D", "Four"));
+        graph.add_dependency(event("E", "Five"), event("B", "Two"));
+
+        let groups = graph.independent_groups().unwrap();
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].len(), 3);
+        assert_eq!(groups[0].last(), Some(&event("B", "Two")));
+        assert_eq!(groups[1], vec![event("C", "Three"), event("D", "Four")]);
+    }
+
+    #[test]
+    fn test_mapping_for_filter_contract() {
+        let mut graph = EventDependencyGraph::default();
+        graph.add_event(event("Token", "Transfer"));
+
+        assert!(graph.contains("TokenFilter", "Transfer"));
+        assert!(!graph.contains("Token", "Approval"));
+    }
 }
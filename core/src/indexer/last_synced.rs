@@ -1,7 +1,6 @@
 use std::{path::Path, sync::Arc};
 
 use ethers::prelude::U64;
-use rust_decimal::Decimal;
 use tokio::{
     fs,
     fs::File,
@@ -11,12 +10,11 @@ use tracing::error;
 
 use crate::{
     event::config::EventProcessingConfig,
-    helpers::{camel_to_snake, get_full_path},
-    manifest::{storage::CsvDetails, stream::StreamsConfig},
-    EthereumSqlTypeWrapper, PostgresClient,
+    manifest::stream::StreamsConfig,
+    storage::{StorageBackend, StorageBackendError, StorageEvent},
 };
 
-async fn get_last_synced_block_number_file(
+pub async fn get_last_synced_block_number_file(
     full_path: &Path,
     contract_name: &str,
     network: &str,
@@ -67,107 +65,141 @@ fn build_last_synced_block_number_file(
 
 pub struct SyncConfig<'a> {
     pub project_path: &'a Path,
-    pub database: &'a Option<Arc<PostgresClient>>,
-    pub csv_details: &'a Option<CsvDetails>,
+    /// The storage backends which store the event
+    pub storage_backends: &'a [Arc<dyn StorageBackend>],
     pub stream_details: &'a Option<&'a StreamsConfig>,
-    pub contract_csv_enabled: bool,
-    pub indexer_name: &'a str,
-    pub contract_name: &'a str,
-    pub event_name: &'a str,
+    pub event: &'a StorageEvent,
     pub network: &'a str,
 }
 
-pub async fn get_last_synced_block_number(config: SyncConfig<'_>) -> Option<U64> {
-    // Check CSV file for last seen block as no database enabled
-    if config.database.is_none() && config.contract_csv_enabled {
-        if let Some(csv_details) = config.csv_details {
-            return if let Ok(result) = get_last_synced_block_number_file(
-                &get_full_path(config.project_path, &csv_details.path).unwrap_or_else(|_| {
-                    panic!("failed to get full path {}", config.project_path.display())
-                }),
-                config.contract_name,
-                config.network,
-                config.event_name,
-            )
-            .await
-            {
-                if let Some(value) = result {
-                    if value.is_zero() {
-                        return None;
-                    }
-                }
+/// The block indexing resumes from, the lowest one of the storage and every stream sink so a sink
+/// which fell behind catches up
+pub async fn get_last_synced_block_number(
+    config: &SyncConfig<'_>,
+) -> Result<Option<U64>, StorageBackendError> {
+    let storage_last_synced_block =
+        get_storage_last_synced_block_number(config.storage_backends, config.event, config.network)
+            .await?;
+
+    let stream_details = match config.stream_details {
+        Some(stream_details) => *stream_details,
+        None => return Ok(storage_last_synced_block),
+    };
+
+    let streams_last_synced_block =
+        get_streams_last_synced_block_number(config, stream_details, storage_last_synced_block)
+            .await;
 
-                result
-            } else {
-                error!("Error fetching last synced block from CSV");
-                None
-            }
-        }
+    if config.storage_backends.is_empty() {
+        Ok(streams_last_synced_block)
+    } else {
+        Ok(storage_last_synced_block
+            .zip(streams_last_synced_block)
+            .map(|(storage, streams)| storage.min(streams)))
     }
+}
 
-    // Then check streams if no csv or database to find out last synced block
-    if config.database.is_none() && !config.contract_csv_enabled && config.stream_details.is_some()
+/// The lowest last synced block of the stream sinks, a sink without one yet starts where the
+/// streams were before each sink kept its own or else where the storage is
+async fn get_streams_last_synced_block_number(
+    config: &SyncConfig<'_>,
+    stream_details: &StreamsConfig,
+    storage_last_synced_block: Option<U64>,
+) -> Option<U64> {
+    let previous_last_synced_block = match get_last_synced_block_number_file(
+        &config.project_path.join(stream_details.get_streams_last_synced_block_path()),
+        &config.event.contract_name,
+        config.network,
+        &config.event.event_name,
+    )
+    .await
     {
-        let stream_details = config.stream_details.as_ref().unwrap();
-
-        // create the path if it does not exist
-        stream_details
-            .create_full_streams_last_synced_block_path(config.project_path, config.contract_name)
-            .await;
-
-        return if let Ok(result) = get_last_synced_block_number_file(
-            &config
-                .project_path
-                .join(stream_details.get_streams_last_synced_block_path())
-                .canonicalize()
-                .expect("Failed to canonicalize path"),
-            config.contract_name,
+        Ok(result) => result.filter(|value| !value.is_zero()),
+        Err(e) => {
+            error!("Error fetching last synced block from stream: {:?}", e);
+            None
+        }
+    };
+    let fallback_last_synced_block = previous_last_synced_block.or(storage_last_synced_block);
+
+    let mut last_synced_block: Option<U64> = None;
+    for sink_name in stream_details.sink_names() {
+        let sink_path = StreamsConfig::sink_last_synced_block_path(config.project_path, &sink_name);
+        let sink_last_synced_block = match get_last_synced_block_number_file(
+            &sink_path,
+            &config.event.contract_name,
             config.network,
-            config.event_name,
+            &config.event.event_name,
         )
         .await
         {
-            if let Some(value) = result {
-                if value.is_zero() {
-                    return None;
+            Ok(Some(value)) if !value.is_zero() => Some(value),
+            Ok(_) => {
+                // written so the sink does not send the blocks before it again
+                if let Some(fallback) = fallback_last_synced_block {
+                    if let Err(e) = update_sink_last_synced_block_number_file(
+                        &sink_path,
+                        &config.event.contract_name,
+                        config.network,
+                        &config.event.event_name,
+                        fallback,
+                    )
+                    .await
+                    {
+                        error!("Error updating last synced block of stream {}: {:?}", sink_name, e);
+                    }
                 }
+                fallback_last_synced_block
             }
+            Err(e) => {
+                error!("Error fetching last synced block of stream {}: {:?}", sink_name, e);
+                return None;
+            }
+        };
 
-            result
-        } else {
-            error!("Error fetching last synced block from stream");
-            None
+        match sink_last_synced_block {
+            Some(value) => {
+                last_synced_block = Some(last_synced_block.map_or(value, |last| last.min(value)));
+            }
+            None => return None,
         }
     }
 
-    // Query database for last synced block
-    if let Some(database) = config.database {
-        let query = format!(
-            "SELECT last_synced_block FROM rindexer_internal.{}_{}_{} WHERE network = $1",
-            camel_to_snake(config.indexer_name),
-            camel_to_snake(config.contract_name),
-            camel_to_snake(config.event_name)
-        );
-
-        match database.query_one(&query, &[&config.network]).await {
-            Ok(row) => {
-                let result: Decimal = row.get("last_synced_block");
-                let parsed = U64::from_dec_str(&result.to_string())
-                    .expect("Failed to parse last_synced_block");
-                if parsed.is_zero() {
-                    None
-                } else {
-                    Some(parsed)
-                }
-            }
-            Err(e) => {
-                error!("Error fetching last synced block: {:?}", e);
-                None
+    last_synced_block
+}
+
+/// Writes the last synced block of a stream sink, creating its folder the first time
+pub async fn update_sink_last_synced_block_number_file(
+    sink_path: &Path,
+    contract_name: &str,
+    network: &str,
+    event_name: &str,
+    to_block: U64,
+) -> Result<(), UpdateLastSyncedBlockNumberFile> {
+    fs::create_dir_all(sink_path.join(contract_name).join("last-synced-blocks")).await?;
+
+    update_last_synced_block_number_file(sink_path, contract_name, network, event_name, to_block)
+        .await
+}
+
+/// The lowest last synced block of the storage backends, `None` when there are none or any of
+/// them has not synced the network yet
+pub async fn get_storage_last_synced_block_number(
+    storage_backends: &[Arc<dyn StorageBackend>],
+    event: &StorageEvent,
+    network: &str,
+) -> Result<Option<U64>, StorageBackendError> {
+    let mut last_synced_block: Option<U64> = None;
+    for storage_backend in storage_backends {
+        match storage_backend.last_synced_block(event, network).await? {
+            Some(value) => {
+                last_synced_block = Some(last_synced_block.map_or(value, |last| last.min(value)));
             }
+            None => return Ok(None),
         }
-    } else {
-        None
     }
+
+    Ok(last_synced_block)
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -179,25 +211,19 @@ pub enum UpdateLastSyncedBlockNumberFile {
     ParseError(String, String),
 }
 
-async fn update_last_synced_block_number_for_file(
-    config: &Arc<EventProcessingConfig>,
+/// Writes the last synced block to the file if it is higher than the one already there
+pub async fn update_last_synced_block_number_file(
     full_path: &Path,
+    contract_name: &str,
+    network: &str,
+    event_name: &str,
     to_block: U64,
 ) -> Result<(), UpdateLastSyncedBlockNumberFile> {
-    let file_path = build_last_synced_block_number_file(
-        full_path,
-        &config.contract_name,
-        &config.network_contract.network,
-        &config.event_name,
-    );
-
-    let last_block = get_last_synced_block_number_file(
-        full_path,
-        &config.contract_name,
-        &config.network_contract.network,
-        &config.event_name,
-    )
-    .await?;
+    let file_path =
+        build_last_synced_block_number_file(full_path, contract_name, network, event_name);
+
+    let last_block =
+        get_last_synced_block_number_file(full_path, contract_name, network, event_name).await?;
 
     let to_block_higher_then_last_block =
         if let Some(last_block_value) = last_block { to_block > last_block_value } else { true };
@@ -231,61 +257,145 @@ pub fn update_progress_and_last_synced_task(
             error!("Error updating last synced block: {:?}", e);
         }
 
-        if let Some(database) = &config.database {
-            let result = database
-                .execute(
-                    &format!(
-                        "UPDATE rindexer_internal.{}_{}_{} SET last_synced_block = $1 WHERE network = $2 AND $1 > last_synced_block",
-                        camel_to_snake(&config.indexer_name),
-                        camel_to_snake(&config.contract_name),
-                        camel_to_snake(&config.event_name)
-                    ),
-                    &[
-                        &EthereumSqlTypeWrapper::U64(to_block),
-                        &config.network_contract.network,
-                    ],
-                )
-                .await;
-
-            if let Err(e) = result {
-                error!("Error updating last synced block: {:?}", e);
-            }
-        } else if let Some(csv_details) = &config.csv_details {
-            if let Err(e) = update_last_synced_block_number_for_file(
-                &config,
-                &get_full_path(&config.project_path, &csv_details.path).unwrap_or_else(|_| {
-                    panic!("failed to get full path {}", config.project_path.display())
-                }),
-                to_block,
-            )
-            .await
-            {
-                error!(
-                    "Error updating last synced block to CSV - path - {} error - {:?}",
-                    csv_details.path, e
-                );
-            }
-        } else if let Some(stream_last_synced_block_file_path) =
-            &config.stream_last_synced_block_file_path
-        {
-            if let Err(e) = update_last_synced_block_number_for_file(
-                &config,
-                &config
-                    .project_path
-                    .join(stream_last_synced_block_file_path)
-                    .canonicalize()
-                    .expect("Failed to canonicalize path"),
-                to_block,
-            )
-            .await
-            {
-                error!(
-                    "Error updating last synced block to stream - path - {} error - {:?}",
-                    stream_last_synced_block_file_path, e
-                );
-            }
-        }
+        advance_storage_backends(
+            &config.storage_backends,
+            &StorageEvent::new(&config.indexer_name, &config.contract_name, &config.event_name),
+            &config.network_contract.network,
+            to_block,
+        )
+        .await;
 
         on_complete();
     });
 }
+
+/// Moves the checkpoint of every storage backend forward once the handlers stored a batch, one
+/// which fails does not stop the others
+pub async fn advance_storage_backends(
+    storage_backends: &[Arc<dyn StorageBackend>],
+    event: &StorageEvent,
+    network: &str,
+    to_block: U64,
+) {
+    for storage_backend in storage_backends {
+        if let Err(e) = storage_backend.advance_checkpoint(event, network, to_block).await {
+            error!(
+                "Error updating last synced block in {} storage backend: {:?}",
+                storage_backend.name(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::storage::CsvStorageBackend;
+
+    fn streams() -> StreamsConfig {
+        serde_yaml::from_str(
+            r#"
+webhooks:
+  - endpoint: https://one.example.com
+    shared_secret: secret
+    networks: [ethereum]
+    events: [{ event_name: Transfer }]
+  - endpoint: https://two.example.com
+    shared_secret: secret
+    networks: [ethereum]
+    events: [{ event_name: Transfer }]
+"#,
+        )
+        .unwrap()
+    }
+
+    async fn write_block(path: &Path, block: u64) {
+        update_sink_last_synced_block_number_file(
+            path,
+            "Token",
+            "ethereum",
+            "Transfer",
+            U64::from(block),
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn read_block(path: &Path) -> Option<U64> {
+        get_last_synced_block_number_file(path, "Token", "ethereum", "Transfer").await.unwrap()
+    }
+
+    /// Resumes with the csv storage at `storage_block` and the sinks at `sink_blocks`
+    async fn resume_block(
+        project_path: &Path,
+        storage_block: Option<u64>,
+        sink_blocks: &[Option<u64>],
+    ) -> Option<U64> {
+        let streams = streams();
+        let csv_path = project_path.join("generated_csv");
+        let mut storage_backends: Vec<Arc<dyn StorageBackend>> = vec![];
+        if let Some(block) = storage_block {
+            fs::create_dir_all(&csv_path).await.unwrap();
+            write_block(&csv_path, block).await;
+            storage_backends.push(Arc::new(CsvStorageBackend::new(&csv_path)));
+        }
+        for (sink_name, block) in streams.sink_names().iter().zip(sink_blocks) {
+            if let Some(block) = block {
+                let sink_path = StreamsConfig::sink_last_synced_block_path(project_path, sink_name);
+                write_block(&sink_path, *block).await;
+            }
+        }
+
+        get_last_synced_block_number(&SyncConfig {
+            project_path,
+            storage_backends: &storage_backends,
+            stream_details: &Some(&streams),
+            event: &StorageEvent::new("test", "Token", "Transfer"),
+            network: "ethereum",
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resumes_from_the_sink_which_fell_furthest_behind() {
+        let project = tempdir().unwrap();
+
+        let block = resume_block(project.path(), Some(200), &[Some(150), Some(180)]).await;
+
+        assert_eq!(block, Some(U64::from(150)));
+    }
+
+    #[tokio::test]
+    async fn test_resumes_from_the_storage_when_it_is_behind_the_sinks() {
+        let project = tempdir().unwrap();
+
+        let block = resume_block(project.path(), Some(100), &[Some(150), Some(180)]).await;
+
+        assert_eq!(block, Some(U64::from(100)));
+    }
+
+    #[tokio::test]
+    async fn test_a_new_sink_starts_where_the_storage_is() {
+        let project = tempdir().unwrap();
+
+        let block = resume_block(project.path(), Some(200), &[Some(250), None]).await;
+
+        assert_eq!(block, Some(U64::from(200)));
+        let new_sink_path =
+            StreamsConfig::sink_last_synced_block_path(project.path(), &streams().sink_names()[1]);
+        assert_eq!(read_block(&new_sink_path).await, Some(U64::from(200)));
+    }
+
+    #[tokio::test]
+    async fn test_streams_without_storage_resume_from_the_lowest_sink() {
+        let project = tempdir().unwrap();
+
+        let block = resume_block(project.path(), None, &[Some(300), Some(250)]).await;
+
+        assert_eq!(block, Some(U64::from(250)));
+    }
+}
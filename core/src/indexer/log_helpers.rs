@@ -8,7 +8,13 @@ use ethers::{
     utils::keccak256,
 };
 
-use crate::helpers::u256_to_i256;
+use crate::{
+    abi::ABIInput,
+    database::postgres::sql_type_wrapper::{map_log_token_to_json, scale_decimal_string},
+    helpers::u256_to_i256,
+    indexer::RAW_LOGS_TOPIC_ID,
+    manifest::contract::NumericColumn,
+};
 
 pub fn parse_log(event: &Event, log: &Log) -> Option<ParsedLog> {
     let raw_log = RawLog { topics: log.topics.clone(), data: log.data.to_vec() };
@@ -58,10 +64,31 @@ fn map_token_to_raw_values(token: &Token) -> Vec<String> {
     }
 }
 
-pub fn map_log_params_to_raw_values(params: &[LogParam]) -> Vec<String> {
+/// Int inputs with an overridden numeric column are written the way postgres stores them
+fn map_int_token_to_csv_value(numeric_column: NumericColumn, token: &Token) -> String {
+    let value = map_token_to_raw_values(token).concat();
+    match (numeric_column, token) {
+        (NumericColumn::Decimal(decimals), _) => scale_decimal_string(&value, decimals),
+        (NumericColumn::Bytea, Token::Int(int) | Token::Uint(int)) => {
+            let mut bytes = [0u8; 32];
+            int.to_big_endian(&mut bytes);
+            format!("0x{}", hex::encode(bytes))
+        }
+        _ => value,
+    }
+}
+
+pub fn map_log_params_to_raw_values(inputs: &[ABIInput], params: &[LogParam]) -> Vec<String> {
     let mut raw_values = vec![];
-    for param in params {
-        raw_values.extend(map_token_to_raw_values(&param.value));
+    for (input, param) in inputs.iter().zip(params) {
+        // tuples stored as JSONB are a single JSON value
+        if input.is_jsonb() {
+            raw_values.push(map_log_token_to_json(input, &param.value).to_string());
+        } else if let Some(numeric_column) = input.numeric_column() {
+            raw_values.push(map_int_token_to_csv_value(numeric_column, &param.value));
+        } else {
+            raw_values.extend(map_token_to_raw_values(&param.value));
+        }
     }
     raw_values
 }
@@ -117,7 +144,8 @@ pub fn is_relevant_block(
                 }
             }
 
-            if !topic_in_bloom(*topic_id, logs_bloom) {
+            // raw logs are not filtered by topic so only the address can be checked
+            if *topic_id != RAW_LOGS_TOPIC_ID && !topic_in_bloom(*topic_id, logs_bloom) {
                 return false;
             }
 
@@ -125,3 +153,30 @@ pub fn is_relevant_block(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_logs_only_check_the_address_in_the_logs_bloom() {
+        let address = Address::from_low_u64_be(1);
+        let contract_address = Some(ValueOrArray::Value(address));
+        let latest_block =
+            |logs_bloom: Bloom| Block { logs_bloom: Some(logs_bloom), ..Default::default() };
+        let address_bloom = FilteredParams::address_filter(&contract_address)[0].unwrap();
+        let topic_id = H256::from_low_u64_be(10);
+
+        assert!(is_relevant_block(
+            &contract_address,
+            &RAW_LOGS_TOPIC_ID,
+            &latest_block(address_bloom)
+        ));
+        assert!(!is_relevant_block(&contract_address, &topic_id, &latest_block(address_bloom)));
+        assert!(!is_relevant_block(
+            &Some(ValueOrArray::Value(Address::from_low_u64_be(2))),
+            &RAW_LOGS_TOPIC_ID,
+            &latest_block(address_bloom)
+        ));
+    }
+}
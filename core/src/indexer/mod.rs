@@ -6,16 +6,30 @@ use serde::{Deserialize, Serialize};
 mod log_helpers;
 pub mod native_transfer;
 pub use log_helpers::parse_topic;
+mod computed;
 mod dependency;
-pub use dependency::ContractEventDependenciesMapFromRelationshipsError;
+pub use dependency::EventDependencyGraphError;
 mod fetch_logs;
 mod last_synced;
+pub use last_synced::{
+    get_last_synced_block_number_file, update_last_synced_block_number_file,
+    update_sink_last_synced_block_number_file, UpdateLastSyncedBlockNumberFile,
+};
 pub mod no_code;
+mod raw_logs;
+pub use raw_logs::{RAW_LOGS_EVENT_NAME, RAW_LOGS_TOPIC_ID};
 mod reorg;
+pub mod retention;
+mod rollups;
+pub use rollups::Rollups;
 pub mod start;
+mod tables;
+pub use tables::TableUpdates;
 pub mod task_tracker;
 
-pub use dependency::{ContractEventDependencies, EventDependencies, EventsDependencyTree};
+pub use dependency::{
+    ContractEventDependencies, EventDependencies, EventDependencyGraph, EventsDependencyTree,
+};
 
 use crate::manifest::contract::Contract;
 
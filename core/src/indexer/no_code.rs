@@ -1,27 +1,50 @@
 use std::{
+    collections::{HashMap, HashSet},
     io,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
 
 use colored::Colorize;
-use ethers::abi::{Abi, Contract as EthersContract, Event};
+use ethers::{
+    abi::{Abi, Contract as EthersContract, Event},
+    types::{U256, U64},
+};
+use futures::future::try_join_all;
 use serde_json::Value;
-use tokio_postgres::types::Type as PgType;
 use tracing::{debug, error, info, warn};
 
 use crate::{
-    abi::{ABIItem, CreateCsvFileForEvent, EventInfo, ParamTypeError, ReadAbiError},
+    abi::{
+        create_csv_file_for_event_name, ABIItem, CreateCsvFileForEvent, EventInfo, ParamTypeError,
+        ReadAbiError,
+    },
     chat::ChatClients,
-    database::postgres::{
-        client::PostgresClient,
-        generate::{
-            generate_column_names_only_with_base_properties, generate_event_table_full_name,
+    database::{
+        clickhouse::{
+            client::ClickhouseClient,
+            setup::{setup_clickhouse, SetupClickhouseError},
         },
-        setup::{setup_postgres, SetupPostgresError},
-        sql_type_wrapper::{
-            map_ethereum_wrapper_to_json, map_log_params_to_ethereum_wrapper,
-            EthereumSqlTypeWrapper,
+        postgres::{
+            client::PostgresClient,
+            generate::{
+                generate_column_names_only_with_base_properties, generate_event_table_full_name,
+                generate_indexer_contract_schema_name,
+            },
+            partitions::{block_timestamp_csv_value, block_timestamp_value},
+            setup::{setup_postgres, SetupPostgresError},
+            sql_type_wrapper::{
+                map_ethereum_wrapper_to_json, map_log_params_to_ethereum_wrapper,
+                EthereumSqlTypeWrapper,
+            },
+        },
+        sqlite::{
+            client::SqliteClient,
+            generate::sqlite_table_name,
+            setup::{setup_sqlite, SetupSqliteError},
         },
     },
     event::{
@@ -30,18 +53,39 @@ use crate::{
             EventCallbackType, TxInformation,
         },
         contract_setup::{ContractInformation, CreateContractInformationError},
-        EventMessage,
+        is_stored_by_conditions, parse_conditions, Condition, ConditionError, EventMessage,
     },
     generate_random_id,
-    indexer::log_helpers::{map_log_params_to_raw_values, parse_log},
+    indexer::{
+        computed::ComputedColumns,
+        log_helpers::{map_log_params_to_raw_values, parse_log},
+        raw_logs::{
+            raw_logs_callback, raw_logs_column_names, RawLogsCallbackParams, RAW_LOGS_EVENT_NAME,
+            RAW_LOGS_TOPIC_ID,
+        },
+        rollups::Rollups,
+        tables::TableUpdates,
+    },
     manifest::{
         contract::ParseAbiError,
         core::Manifest,
+        storage::PartitionBy,
         yaml::{read_manifest, ReadManifestError},
     },
-    provider::{CreateNetworkProvider, RetryClientError},
+    provider::{CreateNetworkProvider, JsonRpcCachedProvider, RetryClientError},
     setup_info_logger,
-    streams::StreamsClients,
+    simple_file_formatters::{
+        csv::CsvFormat,
+        jsonl::JsonlFormat,
+        parquet::{parquet_schema_for_event, ParquetFormat},
+        rolling::{RollingFileError, RollingFileUpload, RollingFiles},
+        s3::{S3Client, S3Error},
+    },
+    storage::{
+        ClickhouseStorageBackend, CsvStorageBackend, PostgresStorageBackend, SqliteStorageBackend,
+        StorageBackend, StorageBatch, StorageEvent, StorageRow,
+    },
+    streams::{StreamError, StreamsClients},
     AsyncCsvAppender, FutureExt, IndexingDetails, StartDetails, StartNoCodeDetails,
 };
 
@@ -56,6 +100,15 @@ pub enum SetupNoCodeError {
     #[error("Could not setup postgres: {0}")]
     SetupPostgresError(#[from] SetupPostgresError),
 
+    #[error("Could not setup clickhouse: {0}")]
+    SetupClickhouseError(#[from] SetupClickhouseError),
+
+    #[error("Could not setup sqlite: {0}")]
+    SetupSqliteError(#[from] SetupSqliteError),
+
+    #[error("Could not setup s3: {0}")]
+    SetupS3Error(#[from] S3Error),
+
     #[error("{0}")]
     RetryClientError(#[from] RetryClientError),
 
@@ -85,6 +138,22 @@ pub async fn setup_no_code(
                 postgres = Some(Arc::new(setup_postgres(project_path, &manifest).await?));
             }
 
+            let mut clickhouse: Option<Arc<ClickhouseClient>> = None;
+            if manifest.storage.clickhouse_enabled() {
+                clickhouse = Some(Arc::new(setup_clickhouse(project_path, &manifest).await?));
+            }
+
+            let mut sqlite: Option<Arc<SqliteClient>> = None;
+            if manifest.storage.sqlite_enabled() {
+                sqlite = Some(Arc::new(setup_sqlite(project_path, &manifest).await?));
+            }
+
+            let mut s3: Option<Arc<S3Client>> = None;
+            if let Some(s3_details) = manifest.storage.s3.as_ref().filter(|details| details.enabled)
+            {
+                s3 = Some(Arc::new(S3Client::new(s3_details).await?));
+            }
+
             if !details.indexing_details.enabled {
                 return Ok(StartDetails {
                     manifest_path: details.manifest_path,
@@ -103,8 +172,16 @@ pub async fn setup_no_code(
                     .join(", ")
             );
 
-            let events =
-                process_events(project_path, &mut manifest, postgres, &network_providers).await?;
+            let (events, storage_backends) = process_events(
+                project_path,
+                &mut manifest,
+                postgres,
+                clickhouse,
+                sqlite,
+                s3,
+                &network_providers,
+            )
+            .await?;
 
             let registry = EventCallbackRegistry { events };
             info!(
@@ -119,7 +196,7 @@ pub async fn setup_no_code(
 
             Ok(StartDetails {
                 manifest_path: details.manifest_path,
-                indexing_details: Some(IndexingDetails { registry }),
+                indexing_details: Some(IndexingDetails { registry, storage_backends }),
                 graphql_details: details.graphql_details,
             })
         }
@@ -134,12 +211,18 @@ struct NoCodeCallbackParams {
     contract_name: String,
     event: Event,
     index_event_in_order: bool,
-    csv: Option<Arc<AsyncCsvAppender>>,
-    postgres: Option<Arc<PostgresClient>>,
-    postgres_event_table_name: String,
-    postgres_column_names: Vec<String>,
+    storage_event: StorageEvent,
+    storage_backends: Vec<Arc<dyn StorageBackend>>,
+    column_names: Vec<String>,
+    /// If the rows need the block timestamp which not every rpc returns with the logs
+    fetch_block_timestamps: bool,
     streams_clients: Arc<Option<StreamsClients>>,
     chat_clients: Arc<Option<ChatClients>>,
+    decode_failures: Arc<AtomicU64>,
+    storage_conditions: Option<Vec<Condition>>,
+    computed_columns: ComputedColumns,
+    store_block_timestamp: bool,
+    providers: HashMap<String, Arc<JsonRpcCachedProvider>>,
 }
 
 fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
@@ -170,24 +253,94 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
 
             let network = results.first().unwrap().tx_information.network.clone();
 
+            // rollups, time partitioned tables and daily files need the block timestamp which not
+            // every rpc returns with the logs
+            let mut block_timestamps: HashMap<U64, U256> = HashMap::new();
+            if params.fetch_block_timestamps {
+                let missing_timestamps: HashSet<U64> = results
+                    .iter()
+                    .filter(|result| result.tx_information.block_timestamp.is_none())
+                    .map(|result| result.tx_information.block_number)
+                    .collect();
+
+                if !missing_timestamps.is_empty() {
+                    let provider = match params.providers.get(&network) {
+                        Some(provider) => provider,
+                        None => {
+                            let error_message =
+                                format!("No provider found for network {}", network);
+                            error!("{}", error_message);
+                            return Err(error_message);
+                        }
+                    };
+
+                    let timestamps = try_join_all(missing_timestamps.into_iter().map(
+                        |block_number| async move {
+                            provider
+                                .get_block_timestamp(block_number)
+                                .await
+                                .map(|timestamp| (block_number, timestamp))
+                        },
+                    ))
+                    .await;
+
+                    match timestamps {
+                        Ok(timestamps) => {
+                            for (block_number, timestamp) in timestamps {
+                                match timestamp {
+                                    Some(timestamp) => {
+                                        block_timestamps.insert(block_number, timestamp);
+                                    }
+                                    None => {
+                                        let error_message = format!(
+                                            "Block {} not found when getting its timestamp",
+                                            block_number
+                                        );
+                                        error!("{}", error_message);
+                                        return Err(error_message);
+                                    }
+                                }
+                            }
+                        }
+                        Err(e) => {
+                            error!(
+                                "{}::{} - Error getting block timestamps: {}",
+                                params.contract_name, params.event_info.name, e
+                            );
+                            return Err(e.to_string());
+                        }
+                    }
+                }
+            }
+
             let mut indexed_count = 0;
-            let mut postgres_bulk_data: Vec<Vec<EthereumSqlTypeWrapper>> = Vec::new();
-            let mut postgres_bulk_column_types: Vec<PgType> = Vec::new();
-            let mut csv_bulk_data: Vec<Vec<String>> = Vec::new();
+            let mut filtered_count = 0;
+            let mut rows: Vec<StorageRow> = Vec::new();
 
             // stream and chat info
             let mut event_message_data: Vec<Value> = Vec::new();
 
             // Collect owned results to avoid lifetime issues
+            let mut decode_failures = 0;
             let owned_results: Vec<_> = results
                 .iter()
                 .filter_map(|result| {
-                    let log = parse_log(&params.event, &result.log)?;
+                    let log = match parse_log(&params.event, &result.log) {
+                        Some(log) => log,
+                        None => {
+                            decode_failures += 1;
+                            return None;
+                        }
+                    };
 
                     let address = result.tx_information.address;
                     let transaction_hash = result.tx_information.transaction_hash;
                     let block_number = result.tx_information.block_number;
                     let block_hash = result.tx_information.block_hash;
+                    let block_timestamp = result
+                        .tx_information
+                        .block_timestamp
+                        .or_else(|| block_timestamps.get(&block_number).copied());
                     let network = result.tx_information.network.to_string();
                     let transaction_index = result.tx_information.transaction_index;
                     let log_index = result.tx_information.log_index;
@@ -215,6 +368,7 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
                         transaction_index,
                         block_number,
                         block_hash,
+                        block_timestamp,
                         network,
                         contract_address,
                         event_parameters,
@@ -224,6 +378,21 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
                 })
                 .collect();
 
+            if decode_failures > 0 {
+                let total_decode_failures =
+                    params.decode_failures.fetch_add(decode_failures, Ordering::Relaxed) +
+                        decode_failures;
+                warn!(
+                    "{}::{} - {} - {} logs did not match the ABI event ({} total) {}",
+                    params.contract_name,
+                    params.event_info.name,
+                    "DECODE_MISMATCH".yellow(),
+                    decode_failures,
+                    total_decode_failures,
+                    format!("- blocks: {} - {} - network: {}", from_block, to_block, network)
+                );
+            }
+
             for (
                 log_params,
                 address,
@@ -232,6 +401,7 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
                 transaction_index,
                 block_number,
                 block_hash,
+                block_timestamp,
                 network,
                 contract_address,
                 event_parameters,
@@ -239,100 +409,96 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
                 input,
             ) in owned_results
             {
-                if params.streams_clients.is_some() || params.chat_clients.is_some() {
-                    let event_result = map_ethereum_wrapper_to_json(
-                        &params.event_info.inputs,
-                        &event_parameters,
-                        &TxInformation {
-                            network: network.clone(),
-                            address,
-                            block_hash,
-                            block_number,
-                            transaction_hash,
-                            block_timestamp: None,
-                            log_index,
-                            transaction_index,
-                            input,
-                        },
-                        false,
-                    );
-                    event_message_data.push(event_result);
-                }
+                let mut event_result = map_ethereum_wrapper_to_json(
+                    &params.event_info.inputs,
+                    &event_parameters,
+                    &TxInformation {
+                        network: network.clone(),
+                        address,
+                        block_hash,
+                        block_number,
+                        transaction_hash,
+                        block_timestamp,
+                        log_index,
+                        transaction_index,
+                        input: input.clone(),
+                    },
+                    false,
+                );
+                let computed_values = params.computed_columns.compute(&mut event_result);
 
-                let mut all_params: Vec<EthereumSqlTypeWrapper> = vec![contract_address];
-                all_params.extend(event_parameters);
-                all_params.extend(end_global_parameters);
+                // storage conditions only decide what is persisted, streams and chat still get
+                // every event and apply their own conditions
+                let persist =
+                    is_stored_by_conditions(params.storage_conditions.as_deref(), &event_result);
 
-                // Set column types dynamically based on first result
-                if postgres_bulk_column_types.is_empty() {
-                    postgres_bulk_column_types =
-                        all_params.iter().map(|param| param.to_type()).collect();
+                if params.streams_clients.is_some() || params.chat_clients.is_some() {
+                    event_message_data.push(event_result.clone());
                 }
 
-                postgres_bulk_data.push(all_params);
-
-                if params.csv.is_some() {
-                    let mut csv_data: Vec<String> = vec![format!("{:?}", address)];
-
-                    let raw_values = map_log_params_to_raw_values(&log_params);
-
-                    for param in raw_values {
-                        csv_data.push(param);
-                    }
-
-                    csv_data.push(format!("{:?}", transaction_hash));
-                    csv_data.push(format!("{:?}", block_number));
-                    csv_data.push(format!("{:?}", block_hash));
-                    csv_data.push(network);
+                if !persist {
+                    filtered_count += 1;
+                    continue;
+                }
 
-                    csv_bulk_data.push(csv_data);
+                let mut values: Vec<EthereumSqlTypeWrapper> = vec![contract_address];
+                values.extend(event_parameters);
+                values.extend(end_global_parameters);
+                if params.store_block_timestamp {
+                    values
+                        .push(block_timestamp_value(block_timestamp.unwrap_or_default().as_u64()));
                 }
+                values.extend(computed_values.iter().map(|(postgres, _)| postgres.clone()));
+
+                let mut text_values: Vec<String> = vec![format!("{:?}", address)];
+                text_values
+                    .extend(map_log_params_to_raw_values(&params.event_info.inputs, &log_params));
+                text_values.push(format!("{:?}", transaction_hash));
+                text_values.push(format!("{:?}", block_number));
+                text_values.push(format!("{:?}", block_hash));
+                text_values.push(network);
+                text_values.push(transaction_index.to_string());
+                text_values.push(log_index.to_string());
+                text_values.push(input);
+                if params.store_block_timestamp {
+                    text_values.push(block_timestamp_csv_value(
+                        block_timestamp.unwrap_or_default().as_u64(),
+                    ));
+                }
+                text_values.extend(computed_values.into_iter().map(|(_, csv)| csv));
+
+                rows.push(StorageRow {
+                    block_number,
+                    block_timestamp,
+                    log_index,
+                    values,
+                    text_values,
+                    event_data: event_result,
+                });
 
                 indexed_count += 1;
             }
 
-            if let Some(postgres) = &params.postgres {
-                let bulk_data_length = postgres_bulk_data.len();
-                if bulk_data_length > 0 {
-                    // anything over 100 events is considered bulk and goes the COPY route
-                    if bulk_data_length > 100 {
-                        if let Err(e) = postgres
-                            .bulk_insert_via_copy(
-                                &params.postgres_event_table_name,
-                                &params.postgres_column_names,
-                                &postgres_bulk_column_types,
-                                &postgres_bulk_data,
-                            )
-                            .await
-                        {
-                            error!(
-                                "{}::{} - Error performing bulk insert: {}",
-                                params.contract_name, params.event_info.name, e
-                            );
-                            return Err(e.to_string());
-                        }
-                    } else if let Err(e) = postgres
-                        .bulk_insert(
-                            &params.postgres_event_table_name,
-                            &params.postgres_column_names,
-                            &postgres_bulk_data,
-                        )
-                        .await
-                    {
-                        error!(
-                            "{}::{} - Error performing bulk insert: {}",
-                            params.contract_name, params.event_info.name, e
-                        );
-                        return Err(e.to_string());
-                    }
-                }
-            }
-
-            if let Some(csv) = &params.csv {
-                if !csv_bulk_data.is_empty() {
-                    if let Err(e) = csv.append_bulk(csv_bulk_data).await {
-                        return Err(e.to_string());
-                    }
+            // ranges where every event was filtered out are still written so the files move
+            // forward
+            for storage_backend in &params.storage_backends {
+                let batch = StorageBatch {
+                    event: &params.storage_event,
+                    network: &network,
+                    from_block,
+                    to_block,
+                    column_names: &params.column_names,
+                    rows: &rows,
+                };
+                if let Err(e) = storage_backend.write_batch(batch).await {
+                    error!(
+                        "{}::{} - Error writing to {}: {}",
+                        params.contract_name,
+                        params.event_info.name,
+                        storage_backend.name(),
+                        e
+                    );
+                    return Err(e.to_string());
                 }
             }
 
@@ -350,7 +516,7 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
                 );
 
                 match streams_clients
-                    .stream(stream_id, &event_message, params.index_event_in_order)
+                    .stream(stream_id, &event_message, to_block, params.index_event_in_order)
                     .await
                 {
                     Ok(streamed) => {
@@ -359,7 +525,7 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
                                 "{}::{} - {} - {} events {}",
                                 params.contract_name,
                                 params.event_info.name,
-                                "STREAMED".green(),
+                                "QUEUED TO STREAM".green(),
                                 streamed,
                                 format!(
                                     "- blocks: {} - {} - network: {}",
@@ -417,6 +583,17 @@ fn no_code_callback(params: Arc<NoCodeCallbackParams>) -> EventCallbackType {
                 }
             }
 
+            if filtered_count > 0 {
+                info!(
+                    "{}::{} - {} - {} events did not match the storage conditions {}",
+                    params.contract_name,
+                    params.event_info.name,
+                    "FILTERED".yellow(),
+                    filtered_count,
+                    format!("- blocks: {} - {} - network: {}", from_block, to_block, network)
+                );
+            }
+
             info!(
                 "{}::{} - {} - {} events {}",
                 params.contract_name,
@@ -460,15 +637,73 @@ pub enum ProcessIndexersError {
 
     #[error("{0}")]
     ParseAbiError(#[from] ParseAbiError),
+
+    #[error("Could not set up the rolling files: {0}")]
+    RollingFileError(#[from] RollingFileError),
+
+    #[error("Computed column {0} for event {1} is invalid: {2}")]
+    InvalidComputedColumn(String, String, ConditionError),
+
+    #[error("Storage condition for {0} on event {1} is invalid: {2}")]
+    InvalidStorageCondition(String, String, ConditionError),
+
+    #[error("Table expression {0} for event {1} is invalid: {2}")]
+    InvalidTableExpression(String, String, ConditionError),
+
+    #[error("Rollup expression {0} for event {1} is invalid: {2}")]
+    InvalidRollupExpression(String, String, ConditionError),
+
+    #[error("Could not set up the streams: {0}")]
+    StreamError(Box<StreamError>),
+}
+
+struct NoCodeEvent {
+    event_name: String,
+    event_info: EventInfo,
+    table_event_info: EventInfo,
+    event: Event,
+    start_block: Option<U64>,
+    end_block: Option<U64>,
+}
+
+fn find_abi_event(
+    abi_gen: &EthersContract,
+    contract_name: &str,
+    event_info: &EventInfo,
+) -> Result<Event, ProcessIndexersError> {
+    let events = abi_gen.events.get(&event_info.abi_name).ok_or_else(|| {
+        ProcessIndexersError::EventNameNotFoundInAbi(
+            contract_name.to_string(),
+            event_info.name.clone(),
+        )
+    })?;
+
+    // overloaded events share a name so match on the signature
+    events.iter().find(|event| event.signature() == event_info.topic_id()).cloned().ok_or_else(
+        || {
+            ProcessIndexersError::EventNameNotFoundInAbi(
+                contract_name.to_string(),
+                event_info.name.clone(),
+            )
+        },
+    )
 }
 
 pub async fn process_events(
     project_path: &Path,
     manifest: &mut Manifest,
     postgres: Option<Arc<PostgresClient>>,
+    clickhouse: Option<Arc<ClickhouseClient>>,
+    sqlite: Option<Arc<SqliteClient>>,
+    s3: Option<Arc<S3Client>>,
     network_providers: &[CreateNetworkProvider],
-) -> Result<Vec<EventCallbackRegistryInformation>, ProcessIndexersError> {
+    // the built in storage of every event which takes part in resuming it
+) -> Result<
+    (Vec<EventCallbackRegistryInformation>, Vec<Arc<dyn StorageBackend>>),
+    ProcessIndexersError,
+> {
     let mut events: Vec<EventCallbackRegistryInformation> = vec![];
+    let mut storage_backends: Vec<Arc<dyn StorageBackend>> = vec![];
 
     for contract in &mut manifest.contracts {
         // TODO - this could be shared with `get_abi_items`
@@ -480,59 +715,249 @@ pub async fn process_events(
 
         let is_filter = contract.identify_and_modify_filter();
         let abi_items = ABIItem::get_abi_items(project_path, contract, is_filter)?;
-        let event_names = ABIItem::extract_event_names_and_signatures_from_abi(abi_items)?;
+        let mut event_names = ABIItem::extract_contract_events(contract, abi_items)?;
+        for event_info in event_names.iter_mut() {
+            event_info.apply_storage_types(contract);
+        }
 
+        let versioned_events =
+            ABIItem::read_versioned_events(project_path, contract, &event_names)?;
+
+        let mut no_code_events: Vec<NoCodeEvent> = Vec::new();
         for event_info in event_names {
-            let event_name = event_info.name.clone();
-            let event = &abi_gen
-                .events
-                .iter()
-                .find(|(name, _)| *name == &event_name)
-                .map(|(_, event)| event)
-                .ok_or_else(|| {
-                    ProcessIndexersError::EventNameNotFoundInAbi(
-                        contract.name.clone(),
-                        event_name.clone(),
-                    )
-                })?
-                .first()
-                .ok_or_else(|| {
-                    ProcessIndexersError::EventNameNotFoundInAbi(
-                        contract.name.clone(),
-                        event_name.clone(),
-                    )
-                })?
-                .clone();
+            let event = find_abi_event(&abi_gen, &contract.name, &event_info)?;
+            no_code_events.push(NoCodeEvent {
+                event_name: event_info.name.clone(),
+                table_event_info: event_info.clone(),
+                event_info,
+                event,
+                start_block: contract.current_abi_start_block(),
+                end_block: None,
+            });
+        }
+
+        for versioned_event in versioned_events {
+            let abi_str = contract.parse_abi_version(project_path, &versioned_event.abi_version)?;
+            let abi: Abi = serde_json::from_str(&abi_str)?;
+
+            #[allow(clippy::useless_conversion)]
+            let abi_gen = EthersContract::from(abi);
+
+            let event = find_abi_event(&abi_gen, &contract.name, &versioned_event.event_info)?;
+            no_code_events.push(NoCodeEvent {
+                table_event_info: versioned_event.table_event_info(),
+                event_name: versioned_event.event_name,
+                event_info: versioned_event.event_info,
+                event,
+                start_block: versioned_event.abi_version.start_block,
+                end_block: versioned_event.abi_version.end_block,
+            });
+        }
+
+        // versioned events can share a csv file with the current ABI event
+        let mut csv_appenders: HashMap<String, Arc<AsyncCsvAppender>> = HashMap::new();
+
+        for NoCodeEvent {
+            event_name,
+            event_info,
+            table_event_info,
+            event,
+            start_block,
+            end_block,
+        } in no_code_events
+        {
+            let computed_columns = ComputedColumns::parse(
+                contract.computed_columns(&table_event_info.name, table_event_info.signature()),
+            )
+            .map_err(|(column, e)| {
+                ProcessIndexersError::InvalidComputedColumn(column, event_name.clone(), e)
+            })?;
+
+            let table_updates = TableUpdates::parse(
+                &generate_indexer_contract_schema_name(&manifest.name, &contract.name),
+                &contract.table_events(&table_event_info.name, table_event_info.signature()),
+            )
+            .map_err(|(expression, e)| {
+                ProcessIndexersError::InvalidTableExpression(expression, event_name.clone(), e)
+            })?;
+
+            let rollups = Rollups::parse(
+                &generate_indexer_contract_schema_name(&manifest.name, &contract.name),
+                &contract.event_rollups(&table_event_info.name, table_event_info.signature()),
+            )
+            .map_err(|(expression, e)| {
+                ProcessIndexersError::InvalidRollupExpression(expression, event_name.clone(), e)
+            })?;
 
             let contract_information =
-                ContractInformation::create(contract, network_providers, noop_decoder())?;
+                match ContractInformation::create(contract, network_providers, noop_decoder())?
+                    .restrict_to_block_range(start_block, end_block)
+                {
+                    Some(contract_information) => contract_information,
+                    None => {
+                        warn!(
+                            "{}::{} - no blocks to index for the ABI version - skipping",
+                            contract.name, event_name
+                        );
+                        continue;
+                    }
+                };
 
-            let mut csv: Option<Arc<AsyncCsvAppender>> = None;
-            if contract.generate_csv.unwrap_or(true) && manifest.storage.csv_enabled() {
+            // tables partitioned by time store the block timestamp to partition on
+            let store_block_timestamp = manifest
+                .storage
+                .postgres_event_partition(&contract.name, &table_event_info.name)
+                .is_some_and(|partition| partition.by == PartitionBy::Timestamp);
+
+            let mut column_names =
+                generate_column_names_only_with_base_properties(&event_info.inputs);
+            if store_block_timestamp {
+                column_names.push("block_timestamp".to_string());
+            }
+            column_names.extend(computed_columns.column_names());
+
+            let storage_event = StorageEvent::new(&manifest.name, &contract.name, &event_name);
+            let postgres_event_table_name = generate_event_table_full_name(
+                &manifest.name,
+                &contract.name,
+                &table_event_info.name,
+            );
+            let mut fetch_block_timestamps = !rollups.is_empty() || store_block_timestamp;
+            let mut event_storage_backends: Vec<Arc<dyn StorageBackend>> = vec![];
+
+            if let Some(postgres) = &postgres {
+                event_storage_backends.push(Arc::new(
+                    PostgresStorageBackend::new(Arc::clone(postgres))
+                        .for_event(&storage_event, &postgres_event_table_name)
+                        .with_derived_tables(table_updates, rollups),
+                ));
+            }
+
+            // clickhouse tables share the postgres table and column names
+            if let Some(clickhouse) = &clickhouse {
+                event_storage_backends.push(Arc::new(
+                    ClickhouseStorageBackend::new(Arc::clone(clickhouse))
+                        .for_event(&storage_event, &postgres_event_table_name),
+                ));
+            }
+
+            if let Some(sqlite) = &sqlite {
+                event_storage_backends.push(Arc::new(
+                    SqliteStorageBackend::new(Arc::clone(sqlite))
+                        .for_event(&storage_event, &sqlite_table_name(&postgres_event_table_name)),
+                ));
+            }
+
+            if contract.generate_csv.unwrap_or(true) && manifest.storage.csv_rolls() {
+                let mut headers: Vec<String> = table_event_info.csv_headers_for_event();
+                if store_block_timestamp {
+                    headers.push("block_timestamp".to_string());
+                }
+                headers.extend(computed_columns.column_names());
+
+                let csv_files = RollingFiles::new(
+                    &manifest.storage.csv_path(project_path),
+                    &manifest
+                        .storage
+                        .csv
+                        .as_ref()
+                        .map(|csv| csv.rotation.clone())
+                        .unwrap_or_default(),
+                    &contract.name,
+                    &table_event_info.name,
+                    &event_name,
+                    CsvFormat::new(headers, manifest.storage.csv_disable_create_headers()),
+                    s3.as_ref().map(|client| RollingFileUpload::new(client, &manifest.name)),
+                )
+                .await?;
+
+                fetch_block_timestamps |= csv_files.rotates_daily();
+                event_storage_backends.push(Arc::new(csv_files));
+            } else if contract.generate_csv.unwrap_or(true) && manifest.storage.csv_enabled() {
                 let csv_path =
                     manifest.storage.csv.as_ref().map_or(PathBuf::from("generated_csv"), |c| {
                         PathBuf::from(c.path.strip_prefix("./").unwrap())
                     });
 
-                let headers: Vec<String> = event_info.csv_headers_for_event();
-                let csv_path_str = csv_path.to_str().expect("Failed to convert csv path to string");
-                let csv_path =
-                    event_info.create_csv_file_for_event(project_path, contract, csv_path_str)?;
-                let csv_appender = AsyncCsvAppender::new(&csv_path);
-                if !Path::new(&csv_path).exists() {
-                    csv_appender.append_header(headers).await?;
+                let mut headers: Vec<String> = table_event_info.csv_headers_for_event();
+                if store_block_timestamp {
+                    headers.push("block_timestamp".to_string());
                 }
+                headers.extend(computed_columns.column_names());
+                let csv_path_str = csv_path.to_str().expect("Failed to convert csv path to string");
+                let csv_path = table_event_info.create_csv_file_for_event(
+                    project_path,
+                    contract,
+                    csv_path_str,
+                )?;
+
+                let csv_appender = match csv_appenders.get(&csv_path) {
+                    Some(csv_appender) => Arc::clone(csv_appender),
+                    None => {
+                        let csv_appender = AsyncCsvAppender::new(&csv_path);
+                        if !Path::new(&csv_path).exists() {
+                            csv_appender.append_header(headers).await?;
+                        }
+
+                        let csv_appender = Arc::new(csv_appender);
+                        csv_appenders.insert(csv_path, Arc::clone(&csv_appender));
+                        csv_appender
+                    }
+                };
 
-                csv = Some(Arc::new(csv_appender));
+                event_storage_backends.push(Arc::new(
+                    CsvStorageBackend::new(&manifest.storage.csv_path(project_path))
+                        .for_event(&storage_event, csv_appender),
+                ));
             }
 
-            let postgres_column_names =
-                generate_column_names_only_with_base_properties(&event_info.inputs);
-            let postgres_event_table_name =
-                generate_event_table_full_name(&manifest.name, &contract.name, &event_info.name);
+            if let Some(parquet_details) =
+                manifest.storage.parquet.as_ref().filter(|details| details.enabled)
+            {
+                let schema = parquet_schema_for_event(
+                    &event_info.inputs,
+                    store_block_timestamp,
+                    &computed_columns.column_types(),
+                );
+                let parquet_files = RollingFiles::new(
+                    &manifest.storage.parquet_path(project_path),
+                    &parquet_details.rotation,
+                    &contract.name,
+                    &table_event_info.name,
+                    &event_name,
+                    ParquetFormat::new(schema, parquet_details),
+                    s3.as_ref().map(|client| RollingFileUpload::new(client, &manifest.name)),
+                )
+                .await?;
+
+                fetch_block_timestamps |= parquet_files.rotates_daily();
+                event_storage_backends.push(Arc::new(parquet_files));
+            }
+
+            if let Some(jsonl_details) =
+                manifest.storage.jsonl.as_ref().filter(|details| details.enabled)
+            {
+                let jsonl_files = RollingFiles::new(
+                    &manifest.storage.jsonl_path(project_path),
+                    &jsonl_details.rotation,
+                    &contract.name,
+                    &table_event_info.name,
+                    &event_name,
+                    JsonlFormat::new(jsonl_details.compression.unwrap_or_default()),
+                    s3.as_ref().map(|client| RollingFileUpload::new(client, &manifest.name)),
+                )
+                .await?;
+
+                fetch_block_timestamps |= jsonl_files.rotates_daily();
+                event_storage_backends.push(Arc::new(jsonl_files));
+            }
 
             let streams_client = if let Some(streams) = &contract.streams {
-                Some(StreamsClients::new(streams.clone()).await)
+                Some(
+                    StreamsClients::new(streams.clone(), project_path, &contract.name, &event_name)
+                        .await
+                        .map_err(|e| ProcessIndexersError::StreamError(Box::new(e)))?,
+                )
             } else {
                 None
             };
@@ -543,15 +968,29 @@ pub async fn process_events(
                 None
             };
 
+            let providers = contract_information
+                .details
+                .iter()
+                .map(|details| (details.network.clone(), Arc::clone(&details.cached_provider)))
+                .collect();
+
             let index_event_in_order = contract
                 .index_event_in_order
                 .as_ref()
                 .map_or(false, |vec| vec.contains(&event_info.name));
 
+            let storage_conditions = contract
+                .storage_conditions(&event_info.name, event_info.signature())
+                .map(|conditions| parse_conditions(conditions))
+                .transpose()
+                .map_err(|(field, e)| {
+                    ProcessIndexersError::InvalidStorageCondition(field, event_name.clone(), e)
+                })?;
+
             let event = EventCallbackRegistryInformation {
                 id: generate_random_id(10),
                 indexer_name: manifest.name.clone(),
-                event_name: event_info.name.clone(),
+                event_name,
                 index_event_in_order,
                 topic_id: event_info.topic_id(),
                 contract: contract_information,
@@ -559,20 +998,87 @@ pub async fn process_events(
                     event_info,
                     indexer_name: manifest.name.clone(),
                     contract_name: contract.name.clone(),
-                    event: event.clone(),
+                    event,
                     index_event_in_order,
-                    csv,
-                    postgres: postgres.clone(),
-                    postgres_event_table_name,
-                    postgres_column_names,
+                    storage_event,
+                    storage_backends: event_storage_backends.clone(),
+                    column_names,
+                    fetch_block_timestamps,
                     streams_clients: Arc::new(streams_client),
                     chat_clients: Arc::new(chat_clients),
+                    decode_failures: Arc::new(AtomicU64::new(0)),
+                    storage_conditions,
+                    computed_columns,
+                    store_block_timestamp,
+                    providers,
                 })),
             };
 
             events.push(event);
+            storage_backends.extend(event_storage_backends);
+        }
+
+        if contract.raw_logs_enabled() {
+            let contract_information =
+                ContractInformation::create(contract, network_providers, noop_decoder())?;
+
+            let storage_event =
+                StorageEvent::new(&manifest.name, &contract.name, RAW_LOGS_EVENT_NAME);
+            let mut event_storage_backends: Vec<Arc<dyn StorageBackend>> = vec![];
+            if let Some(postgres) = &postgres {
+                event_storage_backends.push(Arc::new(
+                    PostgresStorageBackend::new(Arc::clone(postgres)).for_event(
+                        &storage_event,
+                        &generate_event_table_full_name(
+                            &manifest.name,
+                            &contract.name,
+                            RAW_LOGS_EVENT_NAME,
+                        ),
+                    ),
+                ));
+            }
+
+            if contract.generate_csv.unwrap_or(true) && manifest.storage.csv_enabled() {
+                let csv_path =
+                    manifest.storage.csv.as_ref().map_or(PathBuf::from("generated_csv"), |c| {
+                        PathBuf::from(c.path.strip_prefix("./").unwrap())
+                    });
+
+                let csv_path_str = csv_path.to_str().expect("Failed to convert csv path to string");
+                let csv_path = create_csv_file_for_event_name(
+                    project_path,
+                    contract,
+                    csv_path_str,
+                    RAW_LOGS_EVENT_NAME,
+                )?;
+                let csv_appender = AsyncCsvAppender::new(&csv_path);
+                if !Path::new(&csv_path).exists() {
+                    csv_appender.append_header(raw_logs_column_names()).await?;
+                }
+
+                event_storage_backends.push(Arc::new(
+                    CsvStorageBackend::new(&manifest.storage.csv_path(project_path))
+                        .for_event(&storage_event, Arc::new(csv_appender)),
+                ));
+            }
+
+            events.push(EventCallbackRegistryInformation {
+                id: generate_random_id(10),
+                indexer_name: manifest.name.clone(),
+                event_name: RAW_LOGS_EVENT_NAME.to_string(),
+                index_event_in_order: false,
+                topic_id: RAW_LOGS_TOPIC_ID,
+                contract: contract_information,
+                callback: raw_logs_callback(Arc::new(RawLogsCallbackParams {
+                    indexer_name: manifest.name.clone(),
+                    contract_name: contract.name.clone(),
+                    storage_event,
+                    storage_backends: event_storage_backends.clone(),
+                })),
+            });
+            storage_backends.extend(event_storage_backends);
         }
     }
 
-    Ok(events)
+    Ok((events, storage_backends))
 }
@@ -7,7 +7,7 @@ use ethers::{
 };
 use futures::future::join_all;
 use tokio::{
-    sync::{Mutex, MutexGuard},
+    sync::{watch, Mutex},
     task::{JoinError, JoinHandle},
     time::Instant,
 };
@@ -15,11 +15,11 @@ use tracing::{debug, error, info};
 
 use crate::{
     event::{
-        callback_registry::EventResult, config::EventProcessingConfig, BuildRindexerFilterError,
-        RindexerEventFilter,
+        callback_registry::EventResult, config::EventProcessingConfig,
+        contract_setup::ContractEventMapping, BuildRindexerFilterError, RindexerEventFilter,
     },
     indexer::{
-        dependency::{ContractEventsDependenciesConfig, EventDependencies},
+        dependency::{EventDependencyGraph, EventDependencyGraphError},
         fetch_logs::{enrich_logs_with_tx_data, fetch_logs_stream, FetchLogsResult},
         last_synced::update_progress_and_last_synced_task,
         log_helpers::is_relevant_block,
@@ -79,29 +79,48 @@ async fn process_event_logs(
 }
 
 #[derive(thiserror::Error, Debug)]
-pub enum ProcessContractsEventsWithDependenciesError {
-    #[error("{0}")]
-    ProcessContractEventsWithDependenciesError(#[from] ProcessContractEventsWithDependenciesError),
+pub enum ProcessEventsWithDependenciesError {
+    #[error("Could not process logs: {0}")]
+    ProcessLogs(#[from] Box<ProviderError>),
+
+    #[error("Could not build filter: {0}")]
+    BuildFilterError(#[from] BuildRindexerFilterError),
 
     #[error("{0}")]
+    EventDependencyGraphError(#[from] EventDependencyGraphError),
+
+    #[error("Event {0} - {1} did not run as a dependency it waits on failed")]
+    DependencyFailed(String, String),
+
+    #[error("Could not run all the logs processes {0}")]
     JoinError(#[from] JoinError),
 }
 
-pub async fn process_contracts_events_with_dependencies(
-    contracts_events_config: Vec<ContractEventsDependenciesConfig>,
-) -> Result<(), ProcessContractsEventsWithDependenciesError> {
-    let mut handles: Vec<JoinHandle<Result<(), ProcessContractEventsWithDependenciesError>>> =
-        Vec::new();
-
-    for contract_events in contracts_events_config {
-        let handle = tokio::spawn(async move {
-            process_contract_events_with_dependencies(
-                contract_events.event_dependencies,
-                Arc::new(contract_events.events_config),
-            )
-            .await
-        });
-        handles.push(handle);
+#[derive(Debug, Clone)]
+pub struct OrderedLiveIndexingDetails {
+    pub filter: RindexerEventFilter,
+    pub last_seen_block_number: U64,
+    pub last_no_new_block_log_time: Instant,
+}
+
+type LiveIndexingEvents = Vec<(Arc<EventProcessingConfig>, RindexerEventFilter)>;
+
+pub async fn process_events_with_dependencies(
+    dependencies: EventDependencyGraph,
+    events_processing_config: Vec<Arc<EventProcessingConfig>>,
+) -> Result<(), ProcessEventsWithDependenciesError> {
+    let dependencies = Arc::new(dependencies);
+    let events_processing_config = Arc::new(events_processing_config);
+
+    let mut handles: Vec<JoinHandle<Result<(), ProcessEventsWithDependenciesError>>> = Vec::new();
+
+    // groups which share no dependencies can be indexed without waiting on each other
+    for group in dependencies.independent_groups()? {
+        let dependencies = Arc::clone(&dependencies);
+        let events_processing_config = Arc::clone(&events_processing_config);
+        handles.push(tokio::spawn(async move {
+            process_dependency_group(group, dependencies, events_processing_config).await
+        }));
     }
 
     let results = join_all(handles).await;
@@ -110,7 +129,7 @@ pub async fn process_contracts_events_with_dependencies(
         match result {
             Ok(inner_result) => inner_result?,
             Err(join_error) => {
-                return Err(ProcessContractsEventsWithDependenciesError::JoinError(join_error))
+                return Err(ProcessEventsWithDependenciesError::JoinError(join_error))
             }
         }
     }
@@ -118,104 +137,109 @@ pub async fn process_contracts_events_with_dependencies(
     Ok(())
 }
 
-#[derive(thiserror::Error, Debug)]
-pub enum ProcessContractEventsWithDependenciesError {
-    #[error("Could not process logs: {0}")]
-    ProcessLogs(#[from] Box<ProviderError>),
-
-    #[error("Could not build filter: {0}")]
-    BuildFilterError(#[from] BuildRindexerFilterError),
-
-    #[error("Event config not found for contract: {0} and event: {1}")]
-    EventConfigNotFound(String, String),
-
-    #[error("Could not run all the logs processes {0}")]
-    JoinError(#[from] JoinError),
-}
-
-#[derive(Debug, Clone)]
-pub struct OrderedLiveIndexingDetails {
-    pub filter: RindexerEventFilter,
-    pub last_seen_block_number: U64,
-    pub last_no_new_block_log_time: Instant,
-}
-
-async fn process_contract_events_with_dependencies(
-    dependencies: EventDependencies,
+async fn process_dependency_group(
+    ordered_events: Vec<ContractEventMapping>,
+    dependencies: Arc<EventDependencyGraph>,
     events_processing_config: Arc<Vec<Arc<EventProcessingConfig>>>,
-) -> Result<(), ProcessContractEventsWithDependenciesError> {
-    let mut stack = vec![dependencies.tree];
-
-    let live_indexing_events =
-        Arc::new(Mutex::new(Vec::<(Arc<EventProcessingConfig>, RindexerEventFilter)>::new()));
-
-    while let Some(current_tree) = stack.pop() {
-        let mut tasks = vec![];
-
-        for dependency in &current_tree.contract_events {
-            let event_processing_config = Arc::clone(&events_processing_config);
-            let dependency = dependency.clone();
-            let live_indexing_events = Arc::clone(&live_indexing_events);
-
-            let task = tokio::spawn(async move {
-                let event_processing_config = event_processing_config
-                    .iter()
-                    .find(|e| {
-                        // TODO - this is a hacky way to check if it's a filter event
-                        (e.contract_name == dependency.contract_name ||
-                            e.contract_name.replace("Filter", "") == dependency.contract_name) &&
-                            e.event_name == dependency.event_name
-                    })
-                    .ok_or(ProcessContractEventsWithDependenciesError::EventConfigNotFound(
+) -> Result<(), ProcessEventsWithDependenciesError> {
+    let live_indexing_events = Arc::new(Mutex::new(LiveIndexingEvents::new()));
+
+    let mut completed: HashMap<ContractEventMapping, watch::Receiver<bool>> = HashMap::new();
+    let mut tasks = vec![];
+
+    // events are spawned in topological order so every parent already has a completion
+    // channel registered by the time its children are spawned
+    for dependency in &ordered_events {
+        let (completed_sender, completed_receiver) = watch::channel(false);
+        let parents: Vec<watch::Receiver<bool>> = dependencies
+            .parents_of(dependency)
+            .iter()
+            .map(|parent| completed[parent].clone())
+            .collect();
+        completed.insert(dependency.clone(), completed_receiver);
+
+        // an event is registered once per network it is indexed on
+        let configs: Vec<Arc<EventProcessingConfig>> = events_processing_config
+            .iter()
+            .filter(|e| {
+                EventDependencyGraph::mapping_for(&e.contract_name, &e.event_name) == *dependency
+            })
+            .cloned()
+            .collect();
+        let dependency = dependency.clone();
+        let live_indexing_events = Arc::clone(&live_indexing_events);
+
+        let task = tokio::spawn(async move {
+            for mut parent in parents {
+                // the sender is dropped without completing if the parent failed
+                if parent.wait_for(|completed| *completed).await.is_err() {
+                    return Err(ProcessEventsWithDependenciesError::DependencyFailed(
                         dependency.contract_name,
                         dependency.event_name,
-                    ))?;
+                    ));
+                }
+            }
 
-                // forces live indexing off as it has to handle it a bit differently
-                process_event_logs(Arc::clone(event_processing_config), true, true).await?;
+            if configs.is_empty() {
+                // nothing left to index for this event in this run (e.g. its end block has
+                // already been reached), so it should not hold up its children
+                debug!(
+                    "{} - {} - No event config found so marking dependency as complete",
+                    dependency.contract_name, dependency.event_name
+                );
+            }
 
-                if event_processing_config.live_indexing {
-                    let rindexer_event_filter = event_processing_config.to_event_filter()?;
-                    live_indexing_events
-                        .lock()
-                        .await
-                        .push((Arc::clone(event_processing_config), rindexer_event_filter));
-                }
+            // forces live indexing off as it has to handle it a bit differently
+            futures::future::try_join_all(
+                configs.iter().map(|config| process_event_logs(Arc::clone(config), true, true)),
+            )
+            .await?;
 
-                Ok::<(), ProcessContractEventsWithDependenciesError>(())
-            });
+            for config in configs.iter().filter(|config| config.live_indexing) {
+                let rindexer_event_filter = config.to_event_filter()?;
+                live_indexing_events.lock().await.push((Arc::clone(config), rindexer_event_filter));
+            }
 
-            tasks.push(task);
-        }
+            let _ = completed_sender.send(true);
 
-        let results = join_all(tasks).await;
-        for result in results {
-            match result {
-                Ok(result) => match result {
-                    Ok(_) => {}
-                    Err(e) => {
-                        error!("Error processing logs due to dependencies error: {:?}", e);
-                        return Err(e);
-                    }
-                },
+            Ok::<(), ProcessEventsWithDependenciesError>(())
+        });
+
+        tasks.push(task);
+    }
+
+    let results = join_all(tasks).await;
+    for result in results {
+        match result {
+            Ok(result) => match result {
+                Ok(_) => {}
                 Err(e) => {
-                    error!("Error processing logs: {:?}", e);
-                    return Err(ProcessContractEventsWithDependenciesError::JoinError(e));
+                    error!("Error processing logs due to dependencies error: {:?}", e);
+                    return Err(e);
                 }
+            },
+            Err(e) => {
+                error!("Error processing logs: {:?}", e);
+                return Err(ProcessEventsWithDependenciesError::JoinError(e));
             }
         }
-
-        // If there are more dependencies to process, push the next level onto the stack
-        if let Some(next_tree) = &*current_tree.then {
-            stack.push(Arc::clone(next_tree));
-        }
     }
 
-    let live_indexing_events = live_indexing_events.lock().await;
+    let mut live_indexing_events = live_indexing_events.lock().await.clone();
     if live_indexing_events.is_empty() {
         return Ok(());
     }
 
+    // live indexing processes each event in turn, so it has to respect the dependency order
+    live_indexing_events.sort_by_key(|(config, _)| {
+        ordered_events
+            .iter()
+            .position(|e| {
+                *e == EventDependencyGraph::mapping_for(&config.contract_name, &config.event_name)
+            })
+            .unwrap_or(usize::MAX)
+    });
+
     live_indexing_for_contract_event_dependencies(&live_indexing_events, true).await;
 
     Ok(())
@@ -223,12 +247,8 @@ async fn process_contract_events_with_dependencies(
 
 // TODO - this is a similar to live_indexing_stream but has to be a bit different we should merge
 // code
-#[allow(clippy::type_complexity)]
-async fn live_indexing_for_contract_event_dependencies<'a>(
-    live_indexing_events: &'a MutexGuard<
-        'a,
-        Vec<(Arc<EventProcessingConfig>, RindexerEventFilter)>,
-    >,
+async fn live_indexing_for_contract_event_dependencies(
+    live_indexing_events: &[(Arc<EventProcessingConfig>, RindexerEventFilter)],
     include_tx_data: bool,
 ) {
     let mut ordering_live_indexing_details_map: HashMap<
@@ -524,8 +544,12 @@ async fn trigger_event(
     to_block: U64,
 ) {
     indexing_event_processing();
-    config.trigger_event(fn_data).await;
-    update_progress_and_last_synced_task(config, to_block, indexing_event_processed);
+    // the blocks are not marked as synced when the handler never stored them
+    if config.trigger_event(fn_data).await {
+        update_progress_and_last_synced_task(config, to_block, indexing_event_processed);
+    } else {
+        indexing_event_processed();
+    }
 }
 
 async fn handle_logs_result(
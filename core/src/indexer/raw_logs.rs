@@ -0,0 +1,243 @@
+use std::sync::Arc;
+
+use colored::Colorize;
+use ethers::types::H256;
+use serde_json::Value;
+use tracing::{debug, error, info};
+
+use crate::{
+    database::postgres::sql_type_wrapper::EthereumSqlTypeWrapper,
+    event::callback_registry::{EventCallbackType, EventResult},
+    storage::{StorageBackend, StorageBatch, StorageEvent, StorageRow},
+    FutureExt,
+};
+
+/// The name raw logs are registered and stored under for a contract
+pub const RAW_LOGS_EVENT_NAME: &str = "RawLogs";
+
+/// Raw logs capture every log emitted by the contract address, a zero topic id means no topic0
+/// filter is applied when fetching the logs
+pub const RAW_LOGS_TOPIC_ID: H256 = H256::zero();
+
+/// Logs can have at most 4 topics (topic0 is the event signature for non-anonymous events)
+const MAX_TOPICS: usize = 4;
+
+pub fn raw_logs_column_names() -> Vec<String> {
+    let mut column_names: Vec<String> = vec!["contract_address".to_string()];
+    column_names.extend((0..MAX_TOPICS).map(|i| format!("topic_{}", i)));
+    column_names.extend(vec![
+        "data".to_string(),
+        "tx_hash".to_string(),
+        "block_number".to_string(),
+        "block_hash".to_string(),
+        "network".to_string(),
+        "tx_index".to_string(),
+        "log_index".to_string(),
+    ]);
+    column_names
+}
+
+#[derive(Clone)]
+pub struct RawLogsCallbackParams {
+    pub indexer_name: String,
+    pub contract_name: String,
+    pub storage_event: StorageEvent,
+    pub storage_backends: Vec<Arc<dyn StorageBackend>>,
+}
+
+/// The topics of the log padded with `None` so every row has a column for each topic
+fn raw_log_topics(result: &EventResult) -> Vec<Option<H256>> {
+    (0..MAX_TOPICS).map(|i| result.log.topics.get(i).copied()).collect()
+}
+
+/// The postgres row of the log in the order of `raw_logs_column_names`
+fn raw_log_postgres_row(result: &EventResult) -> Vec<EthereumSqlTypeWrapper> {
+    let tx_information = &result.tx_information;
+
+    let mut row = vec![EthereumSqlTypeWrapper::Address(tx_information.address)];
+    row.extend(raw_log_topics(result).iter().map(|topic| {
+        EthereumSqlTypeWrapper::StringCharNullable(
+            topic.map(|topic| format!("{:?}", topic)).unwrap_or_default(),
+        )
+    }));
+    row.extend(vec![
+        EthereumSqlTypeWrapper::String(format!("{}", result.log.data)),
+        EthereumSqlTypeWrapper::H256(tx_information.transaction_hash),
+        EthereumSqlTypeWrapper::U64(tx_information.block_number),
+        EthereumSqlTypeWrapper::H256(tx_information.block_hash),
+        EthereumSqlTypeWrapper::String(tx_information.network.clone()),
+        EthereumSqlTypeWrapper::U64(tx_information.transaction_index),
+        EthereumSqlTypeWrapper::U256(tx_information.log_index),
+    ]);
+    row
+}
+
+/// The csv row of the log in the order of `raw_logs_column_names`
+fn raw_log_csv_row(result: &EventResult) -> Vec<String> {
+    let tx_information = &result.tx_information;
+
+    let mut row = vec![format!("{:?}", tx_information.address)];
+    row.extend(
+        raw_log_topics(result)
+            .iter()
+            .map(|topic| topic.map(|t| format!("{:?}", t)).unwrap_or_default()),
+    );
+    row.extend(vec![
+        format!("{}", result.log.data),
+        format!("{:?}", tx_information.transaction_hash),
+        tx_information.block_number.to_string(),
+        format!("{:?}", tx_information.block_hash),
+        tx_information.network.clone(),
+        tx_information.transaction_index.to_string(),
+        tx_information.log_index.to_string(),
+    ]);
+    row
+}
+
+pub fn raw_logs_callback(params: Arc<RawLogsCallbackParams>) -> EventCallbackType {
+    Arc::new(move |results| {
+        let params = Arc::clone(&params);
+
+        async move {
+            let (from_block, to_block, network) = match results.first() {
+                Some(first) => (
+                    first.found_in_request.from_block,
+                    first.found_in_request.to_block,
+                    first.tx_information.network.clone(),
+                ),
+                None => {
+                    debug!(
+                        "{} {}: {} - {}",
+                        params.indexer_name,
+                        params.contract_name,
+                        RAW_LOGS_EVENT_NAME,
+                        "NO EVENTS".red()
+                    );
+                    return Ok(());
+                }
+            };
+
+            let rows: Vec<StorageRow> = results
+                .iter()
+                .map(|result| StorageRow {
+                    block_number: result.tx_information.block_number,
+                    block_timestamp: result.tx_information.block_timestamp,
+                    log_index: result.tx_information.log_index,
+                    values: raw_log_postgres_row(result),
+                    text_values: raw_log_csv_row(result),
+                    event_data: Value::Null,
+                })
+                .collect();
+            let column_names = raw_logs_column_names();
+
+            for storage_backend in &params.storage_backends {
+                let batch = StorageBatch {
+                    event: &params.storage_event,
+                    network: &network,
+                    from_block,
+                    to_block,
+                    column_names: &column_names,
+                    rows: &rows,
+                };
+                if let Err(e) = storage_backend.write_batch(batch).await {
+                    error!(
+                        "{}::{} - Error writing to {}: {}",
+                        params.contract_name,
+                        RAW_LOGS_EVENT_NAME,
+                        storage_backend.name(),
+                        e
+                    );
+                    return Err(e.to_string());
+                }
+            }
+
+            info!(
+                "{}::{} - {} - {} logs {}",
+                params.contract_name,
+                RAW_LOGS_EVENT_NAME,
+                "INDEXED".green(),
+                results.len(),
+                format!("- blocks: {} - {} - network: {}", from_block, to_block, network)
+            );
+
+            Ok(())
+        }
+        .boxed()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{Address, Bytes, Log, U256, U64};
+
+    use super::*;
+    use crate::event::callback_registry::{LogFoundInRequest, TxInformation};
+
+    fn event_result(topics: Vec<H256>) -> EventResult {
+        EventResult {
+            log: Log { topics, data: Bytes::from(vec![0x12, 0x34]), ..Default::default() },
+            decoded_data: Arc::new(()),
+            tx_information: TxInformation {
+                network: "ethereum".to_string(),
+                address: Address::from_low_u64_be(1),
+                block_hash: H256::from_low_u64_be(2),
+                block_number: U64::from(100),
+                block_timestamp: None,
+                transaction_hash: H256::from_low_u64_be(3),
+                log_index: U256::from(7),
+                transaction_index: U64::from(4),
+                input: "0x".to_string(),
+            },
+            found_in_request: LogFoundInRequest { from_block: 100.into(), to_block: 100.into() },
+        }
+    }
+
+    #[test]
+    fn test_csv_rows_have_a_column_for_every_topic() {
+        let topic = H256::from_low_u64_be(10);
+        let row = raw_log_csv_row(&event_result(vec![topic, H256::from_low_u64_be(11)]));
+
+        let columns: Vec<(String, String)> =
+            raw_logs_column_names().into_iter().zip(row.clone()).collect();
+        assert_eq!(row.len(), raw_logs_column_names().len());
+        assert_eq!(
+            columns,
+            [
+                ("contract_address", format!("{:?}", Address::from_low_u64_be(1))),
+                ("topic_0", format!("{:?}", topic)),
+                ("topic_1", format!("{:?}", H256::from_low_u64_be(11))),
+                ("topic_2", String::new()),
+                ("topic_3", String::new()),
+                ("data", "0x1234".to_string()),
+                ("tx_hash", format!("{:?}", H256::from_low_u64_be(3))),
+                ("block_number", "100".to_string()),
+                ("block_hash", format!("{:?}", H256::from_low_u64_be(2))),
+                ("network", "ethereum".to_string()),
+                ("tx_index", "4".to_string()),
+                ("log_index", "7".to_string()),
+            ]
+            .map(|(column, value)| (column.to_string(), value))
+        );
+    }
+
+    #[test]
+    fn test_postgres_rows_match_the_csv_rows() {
+        let result = event_result(vec![H256::from_low_u64_be(10)]);
+        let row = raw_log_postgres_row(&result);
+
+        assert_eq!(row.len(), raw_logs_column_names().len());
+        let topics: Vec<&str> = row[1..=MAX_TOPICS]
+            .iter()
+            .map(|value| match value {
+                EthereumSqlTypeWrapper::StringCharNullable(topic) => topic.as_str(),
+                value => panic!("expected a topic, got {:?}", value),
+            })
+            .collect();
+        assert_eq!(topics, &raw_log_csv_row(&result)[1..=MAX_TOPICS]);
+        assert!(topics[1..].iter().all(|topic| topic.is_empty()));
+        assert!(matches!(&row[5], EthereumSqlTypeWrapper::String(data) if data == "0x1234"));
+        assert!(
+            matches!(row[11], EthereumSqlTypeWrapper::U256(log_index) if log_index == 7.into())
+        );
+    }
+}
@@ -0,0 +1,449 @@
+use std::{collections::HashMap, path::Path, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ethers::{providers::ProviderError, types::U64};
+use tracing::{error, info};
+
+use crate::{
+    abi::{ABIItem, ReadAbiError},
+    database::postgres::{
+        client::{PostgresClient, PostgresError, ToSql},
+        generate::generate_event_table_full_name,
+        partitions::{drop_partitions_before, PartitionedTables, TablePartitioning},
+        sql_type_wrapper::EthereumSqlTypeWrapper,
+    },
+    is_running,
+    manifest::{contract::RetentionPolicy, core::Manifest},
+    provider::{CreateNetworkProvider, JsonRpcCachedProvider, RetryClientError},
+};
+
+/// How long to wait between pruning the retained event tables
+const PRUNE_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Rows are deleted in batches so pruning never holds a lock on a table for long
+const DELETE_BATCH_SIZE: u64 = 10_000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum RetentionError {
+    #[error("Could not read the ABI events: {0}")]
+    ReadAbiError(#[from] ReadAbiError),
+
+    #[error("{0}")]
+    PostgresError(#[from] PostgresError),
+
+    #[error("Could not get the block from the provider: {0}")]
+    ProviderError(#[from] ProviderError),
+
+    #[error("{0}")]
+    RetryClientError(#[from] RetryClientError),
+}
+
+struct RetainedTable {
+    table_name: String,
+    networks: Vec<String>,
+    policy: RetentionPolicy,
+    partitioning: Option<TablePartitioning>,
+}
+
+/// Deletes the rows of event tables which are older than their retention, whole partitions are
+/// dropped when a table is partitioned
+pub struct RetentionPruner {
+    postgres: Arc<PostgresClient>,
+    providers: HashMap<String, Arc<JsonRpcCachedProvider>>,
+    tables: Vec<RetainedTable>,
+}
+
+impl RetentionPruner {
+    pub async fn new(
+        postgres: Arc<PostgresClient>,
+        project_path: &Path,
+        manifest: &Manifest,
+    ) -> Result<Self, RetentionError> {
+        let partitioned_tables = PartitionedTables::load(&postgres, manifest).await?;
+
+        let mut tables = vec![];
+        for contract in manifest.contracts.iter().filter(|c| c.retention.is_some()) {
+            let contract_name = contract.before_modify_name_if_filter_readonly();
+            let networks: Vec<String> =
+                contract.details.iter().map(|d| d.network.clone()).collect();
+
+            let events = ABIItem::read_abi_events(project_path, contract)?;
+            let versioned_events = ABIItem::read_versioned_events(project_path, contract, &events)?;
+            let table_events = events
+                .into_iter()
+                .chain(versioned_events.iter().map(|versioned| versioned.table_event_info()));
+            for event_info in table_events {
+                let policy =
+                    match contract.event_retention(&event_info.name, event_info.signature()) {
+                        Some(policy) => policy,
+                        None => continue,
+                    };
+
+                let table_name = generate_event_table_full_name(
+                    &manifest.name,
+                    &contract_name,
+                    &event_info.name,
+                );
+                // versioned events can share the table of the current ABI event
+                if tables.iter().any(|table: &RetainedTable| table.table_name == table_name) {
+                    continue;
+                }
+
+                tables.push(RetainedTable {
+                    partitioning: partitioned_tables.get(&table_name),
+                    table_name,
+                    networks: networks.clone(),
+                    policy,
+                });
+            }
+        }
+
+        let providers = CreateNetworkProvider::create(manifest)?
+            .into_iter()
+            .map(|provider| (provider.network_name, provider.client))
+            .collect();
+
+        Ok(Self { postgres, providers, tables })
+    }
+
+    pub async fn start(self) {
+        if self.tables.is_empty() {
+            return;
+        }
+
+        info!("Pruning {} event tables with a retention", self.tables.len());
+        while is_running() {
+            // the cutoff of a network is shared by every table with the same retention
+            let mut cutoffs: HashMap<(String, RetentionPolicy), U64> = HashMap::new();
+            for table in &self.tables {
+                match self.prune(table, &mut cutoffs).await {
+                    Ok(0) => {}
+                    Ok(deleted) => {
+                        info!(
+                            "Pruned {} rows older than the retention from {}",
+                            deleted, table.table_name
+                        )
+                    }
+                    Err(e) => error!("Could not prune {}: {}", table.table_name, e),
+                }
+            }
+
+            tokio::time::sleep(PRUNE_INTERVAL).await;
+        }
+    }
+
+    async fn prune(
+        &self,
+        table: &RetainedTable,
+        cutoffs: &mut HashMap<(String, RetentionPolicy), U64>,
+    ) -> Result<u64, RetentionError> {
+        if let (Some(cutoff), Some(partitioning)) = (
+            timestamp_cutoff(table.policy, table.partitioning, Utc::now().timestamp()),
+            table.partitioning,
+        ) {
+            drop_partitions_before(&self.postgres, &table.table_name, partitioning, cutoff).await?;
+
+            return self
+                .delete_in_batches(
+                    &table.table_name,
+                    "block_timestamp < $1",
+                    &[&EthereumSqlTypeWrapper::DateTime(
+                        DateTime::from_timestamp(cutoff, 0).unwrap_or_default(),
+                    )],
+                )
+                .await;
+        }
+
+        let mut network_cutoffs = vec![];
+        for network in &table.networks {
+            let key = (network.clone(), table.policy);
+            let cutoff = match cutoffs.get(&key) {
+                Some(cutoff) => *cutoff,
+                None => match self.providers.get(network) {
+                    Some(provider) => {
+                        let cutoff =
+                            cutoff_block(provider.as_ref(), table.policy, Utc::now().timestamp())
+                                .await?;
+                        *cutoffs.entry(key).or_insert(cutoff)
+                    }
+                    None => continue,
+                },
+            };
+            network_cutoffs.push((network, cutoff));
+        }
+
+        if let (Some(cutoff), Some(partitioning)) = (
+            block_partitions_cutoff(
+                table.partitioning,
+                network_cutoffs.iter().map(|(_, cutoff)| *cutoff),
+            ),
+            table.partitioning,
+        ) {
+            drop_partitions_before(&self.postgres, &table.table_name, partitioning, cutoff).await?;
+        }
+
+        let mut deleted = 0;
+        for (network, cutoff) in network_cutoffs {
+            deleted += self
+                .delete_in_batches(
+                    &table.table_name,
+                    "network = $1 AND block_number < $2",
+                    &[network, &EthereumSqlTypeWrapper::U64(cutoff)],
+                )
+                .await?;
+        }
+
+        Ok(deleted)
+    }
+
+    async fn delete_in_batches(
+        &self,
+        table_name: &str,
+        condition: &str,
+        params: &[&(dyn ToSql + Sync)],
+    ) -> Result<u64, RetentionError> {
+        let sql = delete_batch_sql(table_name, condition);
+
+        let mut deleted = 0;
+        loop {
+            let batch = self.postgres.execute(&sql, params).await?;
+            deleted += batch;
+            if batch < DELETE_BATCH_SIZE {
+                return Ok(deleted);
+            }
+        }
+    }
+}
+
+/// Where the blocks and their timestamps come from when working out a cutoff
+#[async_trait]
+trait BlockSource: Send + Sync {
+    async fn latest_block(&self) -> Result<u64, RetentionError>;
+
+    async fn block_timestamp(&self, block_number: u64) -> Result<u64, RetentionError>;
+}
+
+#[async_trait]
+impl BlockSource for JsonRpcCachedProvider {
+    async fn latest_block(&self) -> Result<u64, RetentionError> {
+        Ok(self.get_block_number().await?.as_u64())
+    }
+
+    async fn block_timestamp(&self, block_number: u64) -> Result<u64, RetentionError> {
+        let timestamp = self.get_block_timestamp(U64::from(block_number)).await?;
+
+        Ok(timestamp.unwrap_or_default().as_u64())
+    }
+}
+
+/// The unix timestamp rows older than `days` are before, it never goes below 0
+fn days_cutoff_timestamp(now: i64, days: u64) -> i64 {
+    let retention = i64::try_from(days.saturating_mul(86_400)).unwrap_or(i64::MAX);
+    now.saturating_sub(retention).max(0)
+}
+
+/// The first block of a network which is kept
+async fn cutoff_block(
+    source: &dyn BlockSource,
+    policy: RetentionPolicy,
+    now: i64,
+) -> Result<U64, RetentionError> {
+    let latest_block = source.latest_block().await?;
+    let cutoff = match policy {
+        RetentionPolicy::Blocks(blocks) => latest_block.saturating_sub(blocks),
+        RetentionPolicy::Days(days) => {
+            let timestamp = days_cutoff_timestamp(now, days) as u64;
+
+            // binary search for the first block at or after the timestamp
+            let (mut low, mut high) = (0, latest_block);
+            while low < high {
+                let middle = low + (high - low) / 2;
+                if source.block_timestamp(middle).await? < timestamp {
+                    low = middle + 1;
+                } else {
+                    high = middle;
+                }
+            }
+            low
+        }
+    };
+
+    Ok(U64::from(cutoff))
+}
+
+/// Tables partitioned by time can be pruned on the block timestamp without the rpc, this is the
+/// timestamp rows are deleted before when the table can be
+fn timestamp_cutoff(
+    policy: RetentionPolicy,
+    partitioning: Option<TablePartitioning>,
+    now: i64,
+) -> Option<i64> {
+    match (policy, partitioning) {
+        (RetentionPolicy::Days(days), Some(partitioning))
+            if partitioning.column() == "block_timestamp" =>
+        {
+            Some(days_cutoff_timestamp(now, days))
+        }
+        _ => None,
+    }
+}
+
+/// A block partition holds the rows of every network so it can only be dropped once it is older
+/// than the retention on all of them
+fn block_partitions_cutoff(
+    partitioning: Option<TablePartitioning>,
+    network_cutoffs: impl Iterator<Item = U64>,
+) -> Option<i64> {
+    match partitioning {
+        Some(TablePartitioning::BlockRange { .. }) => {
+            network_cutoffs.min().map(|cutoff| cutoff.as_u64() as i64)
+        }
+        _ => None,
+    }
+}
+
+fn delete_batch_sql(table_name: &str, condition: &str) -> String {
+    format!(
+        "DELETE FROM {} WHERE rindexer_id IN (SELECT rindexer_id FROM {} WHERE {} LIMIT {})",
+        table_name, table_name, condition, DELETE_BATCH_SIZE
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manifest::contract::RollupInterval;
+
+    const GENESIS_TIMESTAMP: u64 = 1_600_000_000;
+
+    /// A chain with a block every 12 seconds
+    struct MockChain {
+        latest_block: u64,
+    }
+
+    #[async_trait]
+    impl BlockSource for MockChain {
+        async fn latest_block(&self) -> Result<u64, RetentionError> {
+            Ok(self.latest_block)
+        }
+
+        async fn block_timestamp(&self, block_number: u64) -> Result<u64, RetentionError> {
+            Ok(GENESIS_TIMESTAMP + block_number * 12)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cutoff_block_by_blocks() {
+        let chain = MockChain { latest_block: 1_000 };
+
+        let cutoff = cutoff_block(&chain, RetentionPolicy::Blocks(100), 0).await.unwrap();
+        assert_eq!(cutoff, U64::from(900));
+
+        let cutoff = cutoff_block(&chain, RetentionPolicy::Blocks(5_000), 0).await.unwrap();
+        assert_eq!(cutoff, U64::zero());
+    }
+
+    #[tokio::test]
+    async fn test_cutoff_block_by_days() {
+        let chain = MockChain { latest_block: 100_000 };
+        // one day is 7200 blocks
+        let now = (GENESIS_TIMESTAMP + 100_000 * 12) as i64;
+
+        let cutoff = cutoff_block(&chain, RetentionPolicy::Days(1), now).await.unwrap();
+        assert_eq!(cutoff, U64::from(100_000 - 7_200));
+
+        // a second after a block the cutoff moves to the next one
+        let cutoff = cutoff_block(&chain, RetentionPolicy::Days(1), now + 1).await.unwrap();
+        assert_eq!(cutoff, U64::from(100_000 - 7_200 + 1));
+
+        let cutoff = cutoff_block(&chain, RetentionPolicy::Days(365), now).await.unwrap();
+        assert_eq!(cutoff, U64::zero());
+    }
+
+    #[tokio::test]
+    async fn test_cutoff_block_with_days_past_the_epoch_does_not_underflow() {
+        let chain = MockChain { latest_block: 100 };
+
+        let cutoff =
+            cutoff_block(&chain, RetentionPolicy::Days(u64::MAX), 1_700_000_000).await.unwrap();
+        assert_eq!(cutoff, U64::zero());
+        assert_eq!(days_cutoff_timestamp(1_700_000_000, u64::MAX), 0);
+    }
+
+    #[test]
+    fn test_time_partitioned_tables_prune_on_the_timestamp() {
+        let now = 1_700_000_000;
+        let day = RollupInterval::Day;
+
+        assert_eq!(
+            timestamp_cutoff(
+                RetentionPolicy::Days(2),
+                Some(TablePartitioning::Time { interval: day }),
+                now
+            ),
+            Some(now - 2 * 86_400)
+        );
+        assert_eq!(
+            timestamp_cutoff(
+                RetentionPolicy::Days(2),
+                Some(TablePartitioning::Hypertable { interval: day }),
+                now
+            ),
+            Some(now - 2 * 86_400)
+        );
+        assert_eq!(
+            timestamp_cutoff(
+                RetentionPolicy::Blocks(2),
+                Some(TablePartitioning::Time { interval: day }),
+                now
+            ),
+            None
+        );
+        assert_eq!(
+            timestamp_cutoff(
+                RetentionPolicy::Days(2),
+                Some(TablePartitioning::BlockRange { blocks: 1_000 }),
+                now
+            ),
+            None
+        );
+        assert_eq!(timestamp_cutoff(RetentionPolicy::Days(2), None, now), None);
+    }
+
+    #[test]
+    fn test_block_partitions_dropped_before_the_oldest_network_cutoff() {
+        let cutoffs = [U64::from(500), U64::from(300)];
+
+        assert_eq!(
+            block_partitions_cutoff(
+                Some(TablePartitioning::BlockRange { blocks: 100 }),
+                cutoffs.into_iter()
+            ),
+            Some(300)
+        );
+        assert_eq!(
+            block_partitions_cutoff(
+                Some(TablePartitioning::Time { interval: RollupInterval::Day }),
+                cutoffs.into_iter()
+            ),
+            None
+        );
+        assert_eq!(block_partitions_cutoff(None, cutoffs.into_iter()), None);
+        assert_eq!(
+            block_partitions_cutoff(
+                Some(TablePartitioning::BlockRange { blocks: 100 }),
+                std::iter::empty()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_delete_batch_sql() {
+        assert_eq!(
+            delete_batch_sql("indexer_token.transfer", "network = $1 AND block_number < $2"),
+            "DELETE FROM indexer_token.transfer WHERE rindexer_id IN (SELECT rindexer_id FROM indexer_token.transfer WHERE network = $1 AND block_number < $2 LIMIT 10000)"
+        );
+    }
+}
@@ -0,0 +1,398 @@
+use ethers::types::U64;
+use serde_json::Value;
+
+use crate::{
+    database::postgres::{
+        generate::{generate_applied_updates_table_name, generate_table_full_name},
+        sql_type_wrapper::EthereumSqlTypeWrapper,
+    },
+    event::{ConditionError, ValueExpression},
+    helpers::camel_to_snake,
+    indexer::computed::evaluate,
+    manifest::contract::{ColumnType, Rollup, RollupFunction, RollupInterval},
+};
+
+#[derive(Clone)]
+struct RollupUpdate {
+    query: String,
+    interval: RollupInterval,
+    condition: Option<ValueExpression>,
+    keys: Vec<(ColumnType, ValueExpression)>,
+    values: Vec<ValueExpression>,
+}
+
+/// The rollup upserts an event runs with their expressions parsed once on startup
+#[derive(Clone, Default)]
+pub struct Rollups {
+    updates: Vec<RollupUpdate>,
+}
+
+impl Rollups {
+    pub fn parse(schema_name: &str, rollups: &[&Rollup]) -> Result<Self, (String, ConditionError)> {
+        let mut updates = vec![];
+        for rollup in rollups {
+            let parse = |name: &str, expression: &str| {
+                ValueExpression::parse(expression)
+                    .map_err(|e| (format!("{}.{}", rollup.name, name), e))
+            };
+
+            let condition = rollup
+                .condition
+                .as_ref()
+                .map(|condition| parse("condition", condition))
+                .transpose()?;
+
+            let keys: Vec<(ColumnType, ValueExpression)> = rollup
+                .keys()
+                .iter()
+                .map(|key| parse(&key.name, &key.value).map(|expression| (key.type_, expression)))
+                .collect::<Result<_, _>>()?;
+
+            let values: Vec<ValueExpression> = rollup
+                .metrics
+                .iter()
+                .filter(|metric| metric.function != RollupFunction::Count)
+                .map(|metric| parse(&metric.name, metric.value.as_deref().unwrap_or_default()))
+                .collect::<Result<_, _>>()?;
+
+            for interval in &rollup.intervals {
+                updates.push(RollupUpdate {
+                    query: generate_rollup_upsert_sql(schema_name, rollup, *interval),
+                    interval: *interval,
+                    condition: condition.clone(),
+                    keys: keys.clone(),
+                    values: values.clone(),
+                });
+            }
+        }
+
+        Ok(Self { updates })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    /// The upserts to run for an event, rollups whose condition does not match or whose keys can
+    /// not be worked out for this event are skipped
+    pub fn statements(
+        &self,
+        network: &str,
+        block_number: U64,
+        log_index: u64,
+        block_timestamp: u64,
+        event_data: &Value,
+    ) -> Vec<(&str, Vec<EthereumSqlTypeWrapper>)> {
+        self.updates
+            .iter()
+            .filter(|update| {
+                update
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.evaluate_bool(event_data))
+            })
+            .filter_map(|update| {
+                let mut params = vec![
+                    EthereumSqlTypeWrapper::String(network.to_string()),
+                    EthereumSqlTypeWrapper::DateTime(update.interval.bucket_start(block_timestamp)),
+                    EthereumSqlTypeWrapper::U64(block_number),
+                    EthereumSqlTypeWrapper::U64(U64::from(log_index)),
+                ];
+
+                for (type_, expression) in &update.keys {
+                    let (json, postgres, _) = evaluate(expression, *type_, event_data);
+                    if json.is_null() {
+                        return None;
+                    }
+                    params.push(postgres);
+                }
+
+                params.extend(
+                    update
+                        .values
+                        .iter()
+                        .map(|expression| evaluate(expression, ColumnType::Numeric, event_data).1),
+                );
+
+                Some((update.query.as_str(), params))
+            })
+            .collect()
+    }
+}
+
+/// Builds the upsert for a rollup interval, the params are the network, the bucket, the block
+/// number, the log index, the keys in order and then the value of each metric which has one. The
+/// bucket is only changed when the event was not rolled up before so a batch which is retried is
+/// not counted twice.
+fn generate_rollup_upsert_sql(
+    schema_name: &str,
+    rollup: &Rollup,
+    interval: RollupInterval,
+) -> String {
+    let rollup_table_name = rollup.table_name(interval);
+    let table_name = generate_table_full_name(schema_name, &rollup_table_name);
+
+    let mut columns = vec!["network".to_string()];
+    let mut values = vec!["$1".to_string()];
+    let mut conflict_columns = vec!["network".to_string()];
+    let mut updates = vec![];
+    let mut param = 5;
+
+    for key in rollup.keys() {
+        let column = format!("\"{}\"", camel_to_snake(&key.name));
+        values.push(format!("${}::{}", param, key.type_.postgres_type()));
+        columns.push(column.clone());
+        conflict_columns.push(column);
+        param += 1;
+    }
+
+    columns.push("bucket".to_string());
+    values.push("$2::TIMESTAMPTZ".to_string());
+    conflict_columns.push("bucket".to_string());
+
+    let is_earlier = "(EXCLUDED.first_block_number, EXCLUDED.first_log_index) < \
+        (existing.first_block_number, existing.first_log_index)";
+    let is_later = "(EXCLUDED.last_block_number, EXCLUDED.last_log_index) > \
+        (existing.last_block_number, existing.last_log_index)";
+
+    for metric in &rollup.metrics {
+        let column = format!("\"{}\"", camel_to_snake(&metric.name));
+        let value = if metric.function == RollupFunction::Count {
+            "1".to_string()
+        } else {
+            let value = format!("${}::NUMERIC", param);
+            param += 1;
+            value
+        };
+        let update = match metric.function {
+            RollupFunction::Sum => {
+                format!("COALESCE(existing.{column}, 0) + COALESCE(EXCLUDED.{column}, 0)")
+            }
+            RollupFunction::Count => format!("COALESCE(existing.{column}, 0) + 1"),
+            RollupFunction::Min => format!("LEAST(existing.{column}, EXCLUDED.{column})"),
+            RollupFunction::Max => format!("GREATEST(existing.{column}, EXCLUDED.{column})"),
+            RollupFunction::First => {
+                format!("CASE WHEN {is_earlier} THEN EXCLUDED.{column} ELSE existing.{column} END")
+            }
+            RollupFunction::Last => {
+                format!("CASE WHEN {is_later} THEN EXCLUDED.{column} ELSE existing.{column} END")
+            }
+        };
+
+        values.push(value);
+        updates.push(format!("{} = {}", column, update));
+        columns.push(column);
+    }
+
+    for (column, value, condition) in [
+        ("first_block_number", "$3::NUMERIC", is_earlier),
+        ("first_log_index", "$4::NUMERIC", is_earlier),
+        ("last_block_number", "$3::NUMERIC", is_later),
+        ("last_log_index", "$4::NUMERIC", is_later),
+    ] {
+        columns.push(column.to_string());
+        values.push(value.to_string());
+        updates.push(format!(
+            "{column} = CASE WHEN {condition} THEN EXCLUDED.{column} ELSE existing.{column} END"
+        ));
+    }
+
+    format!(
+        "WITH applied AS (INSERT INTO {} (target, network, block_number, log_index) \
+        VALUES ('{}', $1, $3::NUMERIC, $4::NUMERIC) ON CONFLICT DO NOTHING RETURNING 1) \
+        INSERT INTO {} AS existing ({}) SELECT {} FROM applied ON CONFLICT ({}) DO UPDATE SET {}",
+        generate_applied_updates_table_name(schema_name),
+        camel_to_snake(&rollup_table_name),
+        table_name,
+        columns.join(", "),
+        values.join(", "),
+        conflict_columns.join(", "),
+        updates.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        manifest::contract::{RollupKey, RollupMetric},
+        PostgresClient,
+    };
+
+    fn swaps_rollup() -> Rollup {
+        Rollup {
+            name: "swaps".to_string(),
+            event_name: "Swap".to_string(),
+            condition: None,
+            intervals: vec![RollupInterval::Hour, RollupInterval::Day],
+            keys: Some(vec![RollupKey {
+                name: "pool".to_string(),
+                type_: ColumnType::Text,
+                value: "transaction_information.address".to_string(),
+            }]),
+            metrics: vec![
+                RollupMetric {
+                    name: "volume".to_string(),
+                    function: RollupFunction::Sum,
+                    value: Some("abs(amount0)".to_string()),
+                },
+                RollupMetric {
+                    name: "count".to_string(),
+                    function: RollupFunction::Count,
+                    value: None,
+                },
+                RollupMetric {
+                    name: "close".to_string(),
+                    function: RollupFunction::Last,
+                    value: Some("price".to_string()),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_bucket_start() {
+        // Wednesday 2024-01-17 13:45:30 UTC
+        let timestamp = 1705499130;
+        let bucket = |interval: RollupInterval| interval.bucket_start(timestamp).to_rfc3339();
+
+        assert_eq!(bucket(RollupInterval::Minute), "2024-01-17T13:45:00+00:00");
+        assert_eq!(bucket(RollupInterval::Hour), "2024-01-17T13:00:00+00:00");
+        assert_eq!(bucket(RollupInterval::Day), "2024-01-17T00:00:00+00:00");
+        assert_eq!(bucket(RollupInterval::Week), "2024-01-15T00:00:00+00:00");
+        assert_eq!(bucket(RollupInterval::Month), "2024-01-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_rollup_upsert_sql() {
+        let sql = generate_rollup_upsert_sql("indexer_pool", &swaps_rollup(), RollupInterval::Hour);
+
+        assert!(sql.starts_with(
+            "WITH applied AS (INSERT INTO rindexer_internal.indexer_pool_applied_updates (target, \
+            network, block_number, log_index) VALUES ('swaps_hour', $1, $3::NUMERIC, $4::NUMERIC) \
+            ON CONFLICT DO NOTHING RETURNING 1) INSERT INTO indexer_pool.swaps_hour AS existing \
+            (network, \"pool\", bucket, \"volume\", \"count\", \"close\", first_block_number, \
+            first_log_index, last_block_number, last_log_index) SELECT $1, $5::TEXT, \
+            $2::TIMESTAMPTZ, $6::NUMERIC, 1, $7::NUMERIC, $3::NUMERIC, $4::NUMERIC, $3::NUMERIC, \
+            $4::NUMERIC FROM applied ON CONFLICT (network, \"pool\", bucket) DO UPDATE SET"
+        ));
+        assert!(sql.contains("\"count\" = COALESCE(existing.\"count\", 0) + 1"));
+        assert!(sql.contains(
+            "\"close\" = CASE WHEN (EXCLUDED.last_block_number, EXCLUDED.last_log_index) > \
+            (existing.last_block_number, existing.last_log_index) THEN EXCLUDED.\"close\" ELSE \
+            existing.\"close\" END"
+        ));
+    }
+
+    #[test]
+    fn test_statements() {
+        let rollup = swaps_rollup();
+        let rollups = Rollups::parse("indexer_pool", &[&rollup]).unwrap();
+
+        let swap = json!({
+            "amount0": "-250",
+            "price": "1.5",
+            "transaction_information": { "address": "0x00000000000000000000000000000000000000aa" },
+        });
+        let statements = rollups.statements("ethereum", U64::from(10), 3, 1705499130, &swap);
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].1.len(), 7);
+        assert!(matches!(
+            &statements[0].1[5],
+            EthereumSqlTypeWrapper::NumericNullable(value) if value == "250"
+        ));
+        assert!(matches!(
+            &statements[0].1[6],
+            EthereumSqlTypeWrapper::NumericNullable(value) if value == "1.5"
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn test_a_retried_batch_is_not_rolled_up_twice() {
+        let client = PostgresClient::new().await.unwrap();
+        let schema_name = "rindexer_test_rollups";
+        client
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {schema_name} CASCADE; CREATE SCHEMA {schema_name}; \
+                CREATE SCHEMA IF NOT EXISTS rindexer_internal; \
+                DROP TABLE IF EXISTS {applied}; \
+                CREATE TABLE {applied} (target TEXT NOT NULL, network VARCHAR(50) NOT NULL, \
+                block_number NUMERIC NOT NULL, log_index NUMERIC NOT NULL, \
+                PRIMARY KEY (target, network, block_number, log_index)); \
+                CREATE TABLE {schema_name}.swaps_hour (network VARCHAR(50) NOT NULL, \
+                \"pool\" TEXT NOT NULL, bucket TIMESTAMPTZ NOT NULL, \
+                first_block_number NUMERIC NOT NULL, first_log_index NUMERIC NOT NULL, \
+                last_block_number NUMERIC NOT NULL, last_log_index NUMERIC NOT NULL, \
+                \"volume\" NUMERIC, \"count\" NUMERIC, \"close\" NUMERIC, \
+                PRIMARY KEY (network, \"pool\", bucket));",
+                applied = generate_applied_updates_table_name(schema_name)
+            ))
+            .await
+            .unwrap();
+
+        let rollup = Rollup { intervals: vec![RollupInterval::Hour], ..swaps_rollup() };
+        let rollups = Rollups::parse(schema_name, &[&rollup]).unwrap();
+        let swap = |amount0: &str, price: &str| {
+            json!({
+                "amount0": amount0,
+                "price": price,
+                "transaction_information": { "address": "0xaa" },
+            })
+        };
+        let swaps = [
+            (10, 1, swap("-250", "1.5")),
+            (10, 7, swap("100", "1.6")),
+            (12, 0, swap("-50", "1.4")),
+        ];
+        let statements: Vec<(&str, Vec<EthereumSqlTypeWrapper>)> = swaps
+            .iter()
+            .flat_map(|(block_number, log_index, swap)| {
+                rollups.statements(
+                    "ethereum",
+                    U64::from(*block_number),
+                    *log_index,
+                    1705499130,
+                    swap,
+                )
+            })
+            .collect();
+
+        let buckets = || async {
+            client
+                .query(
+                    &format!(
+                        "SELECT \"volume\"::TEXT, \"count\"::TEXT, \"close\"::TEXT, \
+                        last_block_number::TEXT FROM {schema_name}.swaps_hour"
+                    ),
+                    &[],
+                )
+                .await
+                .unwrap()
+                .iter()
+                .map(|row| {
+                    (
+                        row.get::<_, String>(0),
+                        row.get::<_, String>(1),
+                        row.get::<_, String>(2),
+                        row.get::<_, String>(3),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        client.insert_bulk_with_statements("", &[], &[], &statements).await.unwrap();
+        let applied = buckets().await;
+        assert_eq!(
+            applied,
+            vec![("400".to_string(), "3".to_string(), "1.4".to_string(), "12".to_string())]
+        );
+
+        client.insert_bulk_with_statements("", &[], &[], &statements).await.unwrap();
+        assert_eq!(buckets().await, applied);
+
+        client.batch_execute(&format!("DROP SCHEMA {schema_name} CASCADE;")).await.unwrap();
+    }
+}
@@ -10,32 +10,40 @@ use tokio::{
 use tracing::{error, info};
 
 use crate::{
-    database::postgres::client::PostgresConnectionError,
+    database::{
+        clickhouse::client::ClickhouseConnectionError, postgres::client::PostgresConnectionError,
+        sqlite::client::SqliteConnectionError,
+    },
     event::{
         callback_registry::EventCallbackRegistry, config::EventProcessingConfig,
         contract_setup::NetworkContract,
     },
     indexer::{
-        dependency::ContractEventsDependenciesConfig,
+        dependency::EventDependencyGraph,
         last_synced::{get_last_synced_block_number, SyncConfig},
         // native_transfer::NativeTransferIndexer,
         process::{
-            process_contracts_events_with_dependencies, process_event,
-            ProcessContractsEventsWithDependenciesError, ProcessEventError,
+            process_event, process_events_with_dependencies, ProcessEventError,
+            ProcessEventsWithDependenciesError,
         },
         progress::IndexingEventsProgressState,
         reorg::reorg_safe_distance_for_chain,
-        ContractEventDependencies,
     },
     manifest::core::Manifest,
     // provider::CreateNetworkProvider,
+    storage::{
+        ClickhouseStorageBackend, CsvStorageBackend, PostgresStorageBackend, SqliteStorageBackend,
+        StorageBackend, StorageBackendError, StorageEvent,
+    },
+    ClickhouseClient,
     PostgresClient,
+    SqliteClient,
 };
 
 #[derive(thiserror::Error, Debug)]
 pub enum CombinedLogEventProcessingError {
     #[error("{0}")]
-    DependencyError(#[from] ProcessContractsEventsWithDependenciesError),
+    DependencyError(#[from] ProcessEventsWithDependenciesError),
     #[error("{0}")]
     NonBlockingError(#[from] ProcessEventError),
     #[error("{0}")]
@@ -53,6 +61,12 @@ pub enum StartIndexingError {
     #[error("{0}")]
     PostgresConnectionError(#[from] PostgresConnectionError),
 
+    #[error("{0}")]
+    ClickhouseConnectionError(#[from] ClickhouseConnectionError),
+
+    #[error("{0}")]
+    SqliteConnectionError(#[from] SqliteConnectionError),
+
     #[error("Could not get block number from provider: {0}")]
     GetBlockNumberError(#[from] ProviderError),
 
@@ -70,6 +84,9 @@ pub enum StartIndexingError {
 
     #[error("The end block set for {0} is higher than the latest block: {1} - end block: {2}")]
     EndBlockIsHigherThanLatestBlockError(String, U64, U64),
+
+    #[error("Storage backend error: {0}")]
+    StorageBackendError(#[from] StorageBackendError),
 }
 
 pub struct ProcessedNetworkContract {
@@ -80,26 +97,22 @@ pub struct ProcessedNetworkContract {
 pub async fn start_indexing(
     manifest: &Manifest,
     project_path: &Path,
-    dependencies: &[ContractEventDependencies],
+    dependencies: &EventDependencyGraph,
     no_live_indexing_forced: bool,
     registry: Arc<EventCallbackRegistry>,
+    storage_backends: &[Arc<dyn StorageBackend>],
 ) -> Result<Vec<ProcessedNetworkContract>, StartIndexingError> {
     let start = Instant::now();
 
-    let database = initialize_database(manifest).await?;
     let event_progress_state = IndexingEventsProgressState::monitor(&registry.events).await;
 
     // we can bring this into the yaml file later if required
     let semaphore = Arc::new(Semaphore::new(100));
-    // need this to keep track of dependency_events cross contracts and events
-    let mut event_processing_configs: Vec<Arc<EventProcessingConfig>> = vec![];
     // any events which are non-blocking and can be fired in parallel
     let mut non_blocking_process_events = Vec::new();
-    let mut dependency_event_processing_configs: Vec<ContractEventsDependenciesConfig> = Vec::new();
-    // if you are doing advanced dependency events where other contracts depend on the processing of
-    // this contract you will need to apply the dependency after the processing of the other
-    // contract to avoid ordering issues
-    let mut apply_cross_contract_dependency_events_config_after_processing = Vec::new();
+    // any events which are part of the dependency graph are scheduled together so each one
+    // waits on all of its parents across any contract
+    let mut dependency_event_processing_configs: Vec<Arc<EventProcessingConfig>> = Vec::new();
 
     let mut processed_network_contracts: Vec<ProcessedNetworkContract> = Vec::new();
 
@@ -109,19 +122,17 @@ pub async fn start_indexing(
             .iter()
             .find(|c| c.name == event.contract.name)
             .and_then(|c| c.streams.as_ref());
+        let storage_event =
+            StorageEvent::new(&event.indexer_name, &event.contract.name, &event.event_name);
+        let event_storage_backends: Vec<Arc<dyn StorageBackend>> = storage_backends
+            .iter()
+            .filter(|storage_backend| storage_backend.stores(&storage_event))
+            .cloned()
+            .collect();
+        for storage_backend in &event_storage_backends {
+            storage_backend.create_schema(&storage_event).await?;
+        }
         for network_contract in event.contract.details.iter() {
-            let config = SyncConfig {
-                project_path,
-                database: &database,
-                csv_details: &manifest.storage.csv,
-                contract_csv_enabled: manifest.contract_csv_enabled(&event.contract.name),
-                stream_details: &stream_details,
-                indexer_name: &event.indexer_name,
-                contract_name: &event.contract.name,
-                event_name: &event.event_name,
-                network: &network_contract.network,
-            };
-
             let latest_block = network_contract.cached_provider.get_block_number().await?;
 
             if let Some(start_block) = network_contract.start_block {
@@ -146,8 +157,20 @@ pub async fn start_indexing(
                 }
             }
 
-            let last_known_start_block = if network_contract.start_block.is_some() {
-                let last_synced_block = get_last_synced_block_number(config).await;
+            let last_known_start_block = if let Some(start_block) = network_contract.start_block {
+                let last_synced_block = resume_storage_backends(
+                    &SyncConfig {
+                        project_path,
+                        storage_backends: &event_storage_backends,
+                        stream_details: &stream_details,
+                        event: &storage_event,
+                        network: &network_contract.network,
+                    },
+                    start_block,
+                    // versioned events sharing a table only roll back their own blocks
+                    std::cmp::min(network_contract.end_block.unwrap_or(latest_block), latest_block),
+                )
+                .await?;
 
                 if let Some(value) = last_synced_block {
                     let start_from = value + 1;
@@ -168,6 +191,7 @@ pub async fn start_indexing(
             let start_block = last_known_start_block
                 .unwrap_or(network_contract.start_block.unwrap_or(latest_block));
             info!("{} start_block is {}", event.info_log_name(), start_block);
+
             let end_block =
                 std::cmp::min(network_contract.end_block.unwrap_or(latest_block), latest_block);
             if let Some(end_block) = network_contract.end_block {
@@ -204,11 +228,7 @@ pub async fn start_indexing(
                 semaphore: Arc::clone(&semaphore),
                 registry: Arc::clone(&registry),
                 progress: Arc::clone(&event_progress_state),
-                database: database.clone(),
-                csv_details: manifest.storage.csv.clone(),
-                stream_last_synced_block_file_path: stream_details
-                    .as_ref()
-                    .map(|s| s.get_streams_last_synced_block_path()),
+                storage_backends: event_storage_backends.clone(),
                 live_indexing: if no_live_indexing_forced {
                     false
                 } else {
@@ -218,34 +238,11 @@ pub async fn start_indexing(
                 indexing_distance_from_head,
             };
 
-            let dependencies_status = ContractEventDependencies::dependencies_status(
+            if dependencies.contains(
                 &event_processing_config.contract_name,
                 &event_processing_config.event_name,
-                dependencies,
-            );
-
-            if dependencies_status.has_dependency_in_other_contracts_multiple_times() {
-                panic!("Multiple dependencies of the same event on different contracts not supported yet - please raise an issue if you need this feature");
-            }
-
-            if dependencies_status.has_dependencies() {
-                let event_processing_config_arc = Arc::new(event_processing_config);
-                event_processing_configs.push(Arc::clone(&event_processing_config_arc));
-
-                if let Some(dependency_in_other_contract) =
-                    dependencies_status.get_first_dependencies_in_other_contracts()
-                {
-                    apply_cross_contract_dependency_events_config_after_processing
-                        .push((dependency_in_other_contract, event_processing_config_arc));
-
-                    continue;
-                }
-
-                ContractEventsDependenciesConfig::add_to_event_or_new_entry(
-                    &mut dependency_event_processing_configs,
-                    event_processing_config_arc,
-                    dependencies,
-                );
+            ) {
+                dependency_event_processing_configs.push(Arc::new(event_processing_config));
             } else {
                 let process_event = tokio::spawn(process_event(event_processing_config, false));
                 non_blocking_process_events.push(process_event);
@@ -253,18 +250,9 @@ pub async fn start_indexing(
         }
     }
 
-    // apply dependency events config after processing to avoid ordering issues
-    for apply in apply_cross_contract_dependency_events_config_after_processing {
-        let (dependency_in_other_contract, event_processing_config) = apply;
-        ContractEventsDependenciesConfig::add_to_event_or_panic(
-            &dependency_in_other_contract,
-            &mut dependency_event_processing_configs,
-            event_processing_config,
-        );
-    }
-
-    let dependency_handle: JoinHandle<Result<(), ProcessContractsEventsWithDependenciesError>> =
-        tokio::spawn(process_contracts_events_with_dependencies(
+    let dependency_handle: JoinHandle<Result<(), ProcessEventsWithDependenciesError>> =
+        tokio::spawn(process_events_with_dependencies(
+            dependencies.clone(),
             dependency_event_processing_configs,
         ));
 
@@ -318,6 +306,82 @@ pub async fn initialize_database(
     }
 }
 
+pub async fn initialize_clickhouse(
+    manifest: &Manifest,
+) -> Result<Option<Arc<ClickhouseClient>>, StartIndexingError> {
+    if manifest.storage.clickhouse_enabled() {
+        match ClickhouseClient::new().await {
+            Ok(clickhouse) => Ok(Some(Arc::new(clickhouse))),
+            Err(e) => {
+                error!("Error connecting to ClickHouse: {:?}", e);
+                Err(StartIndexingError::ClickhouseConnectionError(e))
+            }
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+pub fn initialize_sqlite(
+    manifest: &Manifest,
+    project_path: &Path,
+) -> Result<Option<Arc<SqliteClient>>, StartIndexingError> {
+    if manifest.storage.sqlite_enabled() {
+        match SqliteClient::new(&manifest.storage.sqlite_path(project_path)) {
+            Ok(sqlite) => Ok(Some(Arc::new(sqlite))),
+            Err(e) => {
+                error!("Error opening SQLite: {:?}", e);
+                Err(StartIndexingError::SqliteConnectionError(e))
+            }
+        }
+    } else {
+        Ok(None)
+    }
+}
+
+/// The built in storage of a rust project as storage backends so it takes part in resuming, the
+/// csv files only keep the last synced block when there is no database
+pub async fn initialize_storage_backends(
+    manifest: &Manifest,
+    project_path: &Path,
+) -> Result<Vec<Arc<dyn StorageBackend>>, StartIndexingError> {
+    let mut storage_backends: Vec<Arc<dyn StorageBackend>> = vec![];
+    if let Some(postgres) = initialize_database(manifest).await? {
+        storage_backends.push(Arc::new(PostgresStorageBackend::new(postgres)));
+    }
+    if let Some(clickhouse) = initialize_clickhouse(manifest).await? {
+        storage_backends.push(Arc::new(ClickhouseStorageBackend::new(clickhouse)));
+    }
+    if let Some(sqlite) = initialize_sqlite(manifest, project_path)? {
+        storage_backends.push(Arc::new(SqliteStorageBackend::new(sqlite)));
+    }
+    if storage_backends.is_empty() && manifest.storage.csv_enabled() {
+        storage_backends
+            .push(Arc::new(CsvStorageBackend::new(&manifest.storage.csv_path(project_path))));
+    }
+
+    Ok(storage_backends)
+}
+
+/// The last synced block indexing resumes after, the lowest one of the storage backends and the
+/// streams. `None` when any of them has not synced the network yet. Anything the backends hold
+/// from the block indexing resumes from is deleted so a backend which got ahead of the others
+/// does not store the blocks twice.
+async fn resume_storage_backends(
+    config: &SyncConfig<'_>,
+    start_block: U64,
+    end_block: U64,
+) -> Result<Option<U64>, StorageBackendError> {
+    let last_synced_block = get_last_synced_block_number(config).await?;
+
+    let resume_from = last_synced_block.map_or(start_block, |value| value + 1);
+    for storage_backend in config.storage_backends {
+        storage_backend.delete_range(config.event, config.network, resume_from, end_block).await?;
+    }
+
+    Ok(last_synced_block)
+}
+
 async fn calculate_safe_block_number(
     reorg_safe_distance: bool,
     network_contract: &NetworkContract,
@@ -340,3 +404,217 @@ async fn calculate_safe_block_number(
     }
     Ok((end_block, indexing_distance_from_head))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use async_trait::async_trait;
+    use ethers::types::U256;
+    use serde_json::Value;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{
+        indexer::last_synced::advance_storage_backends,
+        storage::{StorageBatch, StorageRow},
+    };
+
+    /// Keeps rows by block and records the calls made to it in a log shared by the backends
+    struct MemoryBackend {
+        name: String,
+        checkpoint: Mutex<Option<U64>>,
+        blocks: Mutex<Vec<U64>>,
+        calls: Arc<Mutex<Vec<String>>>,
+        fail_checkpoint: bool,
+    }
+
+    impl MemoryBackend {
+        fn new(name: &str, checkpoint: Option<u64>, calls: &Arc<Mutex<Vec<String>>>) -> Self {
+            MemoryBackend {
+                name: name.to_string(),
+                checkpoint: Mutex::new(checkpoint.map(U64::from)),
+                blocks: Mutex::new(vec![]),
+                calls: Arc::clone(calls),
+                fail_checkpoint: false,
+            }
+        }
+
+        fn record(&self, call: String) {
+            self.calls.lock().unwrap().push(format!("{} {}", self.name, call));
+        }
+    }
+
+    #[async_trait]
+    impl StorageBackend for MemoryBackend {
+        fn name(&self) -> &str {
+            &self.name
+        }
+
+        async fn create_schema(&self, _: &StorageEvent) -> Result<(), StorageBackendError> {
+            Ok(())
+        }
+
+        async fn write_batch(&self, batch: StorageBatch<'_>) -> Result<(), StorageBackendError> {
+            self.record(format!("write {}-{}", batch.from_block, batch.to_block));
+            self.blocks
+                .lock()
+                .unwrap()
+                .extend((batch.from_block.as_u64()..=batch.to_block.as_u64()).map(U64::from));
+            Ok(())
+        }
+
+        async fn last_synced_block(
+            &self,
+            _: &StorageEvent,
+            _: &str,
+        ) -> Result<Option<U64>, StorageBackendError> {
+            self.record("last_synced_block".to_string());
+            Ok(*self.checkpoint.lock().unwrap())
+        }
+
+        async fn advance_checkpoint(
+            &self,
+            _: &StorageEvent,
+            _: &str,
+            to_block: U64,
+        ) -> Result<(), StorageBackendError> {
+            self.record(format!("advance_checkpoint {}", to_block));
+            if self.fail_checkpoint {
+                return Err(StorageBackendError::Custom("down".to_string()));
+            }
+            let mut checkpoint = self.checkpoint.lock().unwrap();
+            if checkpoint.is_none_or(|value| to_block > value) {
+                *checkpoint = Some(to_block);
+            }
+            Ok(())
+        }
+
+        async fn delete_range(
+            &self,
+            _: &StorageEvent,
+            _: &str,
+            from_block: U64,
+            to_block: U64,
+        ) -> Result<(), StorageBackendError> {
+            self.record(format!("delete_range {}-{}", from_block, to_block));
+            self.blocks.lock().unwrap().retain(|block| *block < from_block || *block > to_block);
+            Ok(())
+        }
+    }
+
+    fn event() -> StorageEvent {
+        StorageEvent::new("Indexer", "Token", "Transfer")
+    }
+
+    async fn resume(backends: &[Arc<dyn StorageBackend>]) -> Option<U64> {
+        let project = tempdir().unwrap();
+        resume_storage_backends(
+            &SyncConfig {
+                project_path: project.path(),
+                storage_backends: backends,
+                stream_details: &None,
+                event: &event(),
+                network: "ethereum",
+            },
+            U64::from(100),
+            U64::from(500),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_resumes_after_the_lowest_checkpoint_and_rolls_back_the_backends_ahead_of_it() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let middle = Arc::new(MemoryBackend::new("middle", Some(250), &calls));
+        let behind = Arc::new(MemoryBackend::new("behind", Some(200), &calls));
+        let ahead = Arc::new(MemoryBackend::new("ahead", Some(300), &calls));
+        ahead.blocks.lock().unwrap().extend([U64::from(150), U64::from(250), U64::from(300)]);
+        let backends: Vec<Arc<dyn StorageBackend>> =
+            vec![middle.clone(), behind.clone(), ahead.clone()];
+
+        assert_eq!(resume(&backends).await, Some(U64::from(200)));
+
+        // every checkpoint is read before anything is deleted
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![
+                "middle last_synced_block",
+                "behind last_synced_block",
+                "ahead last_synced_block",
+                "middle delete_range 201-500",
+                "behind delete_range 201-500",
+                "ahead delete_range 201-500",
+            ]
+        );
+        assert_eq!(*ahead.blocks.lock().unwrap(), vec![U64::from(150)]);
+    }
+
+    #[tokio::test]
+    async fn test_resumes_from_the_start_block_when_anything_has_not_synced() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let synced: Arc<dyn StorageBackend> =
+            Arc::new(MemoryBackend::new("synced", Some(300), &calls));
+        let new: Arc<dyn StorageBackend> = Arc::new(MemoryBackend::new("new", None, &calls));
+
+        assert_eq!(resume(&[synced.clone(), new]).await, None);
+        assert!(calls.lock().unwrap().ends_with(&[
+            "synced delete_range 100-500".to_string(),
+            "new delete_range 100-500".to_string()
+        ]));
+
+        assert_eq!(resume(&[synced]).await, Some(U64::from(300)));
+        assert_eq!(resume(&[]).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoints_only_move_forward_after_the_batch_is_written() {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let store = Arc::new(MemoryBackend::new("store", Some(200), &calls));
+        let failing = Arc::new(MemoryBackend {
+            fail_checkpoint: true,
+            ..MemoryBackend::new("failing", Some(200), &calls)
+        });
+        let backends: Vec<Arc<dyn StorageBackend>> = vec![store.clone(), failing.clone()];
+
+        let resumed = resume(&backends).await;
+        assert_eq!(resumed, Some(U64::from(200)));
+
+        let event = event();
+        let rows = [StorageRow {
+            block_number: U64::from(230),
+            block_timestamp: None,
+            log_index: U256::zero(),
+            values: vec![],
+            text_values: vec![],
+            event_data: Value::Null,
+        }];
+        store
+            .write_batch(StorageBatch {
+                event: &event,
+                network: "ethereum",
+                from_block: U64::from(201),
+                to_block: U64::from(260),
+                column_names: &[],
+                rows: &rows,
+            })
+            .await
+            .unwrap();
+        advance_storage_backends(&backends, &event, "ethereum", U64::from(260)).await;
+        advance_storage_backends(&backends, &event, "ethereum", U64::from(240)).await;
+
+        assert_eq!(
+            calls.lock().unwrap()[4..],
+            [
+                "store write 201-260",
+                "store advance_checkpoint 260",
+                "failing advance_checkpoint 260",
+                "store advance_checkpoint 240",
+                "failing advance_checkpoint 240",
+            ]
+        );
+        assert_eq!(*store.checkpoint.lock().unwrap(), Some(U64::from(260)));
+        assert_eq!(*failing.checkpoint.lock().unwrap(), Some(U64::from(200)));
+    }
+}
@@ -0,0 +1,400 @@
+use ethers::types::U64;
+use serde_json::Value;
+
+use crate::{
+    database::postgres::{
+        generate::{generate_applied_updates_table_name, generate_table_full_name},
+        sql_type_wrapper::EthereumSqlTypeWrapper,
+    },
+    event::{ConditionError, ValueExpression},
+    helpers::camel_to_snake,
+    indexer::computed::evaluate,
+    manifest::contract::{ColumnType, Table, TableAction, TableEvent},
+};
+
+#[derive(Clone)]
+struct TableUpdate {
+    query: String,
+    condition: Option<ValueExpression>,
+    keys: Vec<(ColumnType, ValueExpression)>,
+    values: Vec<(ColumnType, ValueExpression)>,
+}
+
+/// The derived table upserts an event runs with their expressions parsed once on startup
+#[derive(Clone, Default)]
+pub struct TableUpdates {
+    updates: Vec<TableUpdate>,
+}
+
+impl TableUpdates {
+    pub fn parse(
+        schema_name: &str,
+        table_events: &[(&Table, &TableEvent)],
+    ) -> Result<Self, (String, ConditionError)> {
+        let updates = table_events
+            .iter()
+            .map(|(table, table_event)| {
+                let parse = |name: &str, expression: &str| {
+                    ValueExpression::parse(expression)
+                        .map_err(|e| (format!("{}.{}", table.name, name), e))
+                };
+
+                let condition = table_event
+                    .condition
+                    .as_ref()
+                    .map(|condition| parse("condition", condition))
+                    .transpose()?;
+
+                let keys = table
+                    .keys
+                    .iter()
+                    .map(|key| {
+                        let expression = table_event.keys.get(&key.name).map_or("", String::as_str);
+                        parse(&key.name, expression).map(|expression| (key.type_, expression))
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                let values = table_event
+                    .operations
+                    .iter()
+                    .filter(|operation| operation.action != TableAction::Increment)
+                    .map(|operation| {
+                        let type_ =
+                            table.column(&operation.column).map_or(ColumnType::Numeric, |column| {
+                                match operation.action {
+                                    TableAction::Set => column.type_,
+                                    _ => ColumnType::Numeric,
+                                }
+                            });
+                        let expression = operation.value.as_deref().unwrap_or_default();
+                        parse(&operation.column, expression).map(|expression| (type_, expression))
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Ok(TableUpdate {
+                    query: generate_upsert_sql(schema_name, table, table_event),
+                    condition,
+                    keys,
+                    values,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { updates })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    /// The upserts to run for an event, updates whose condition does not match or whose keys can
+    /// not be worked out for this event are skipped
+    pub fn statements(
+        &self,
+        network: &str,
+        block_number: U64,
+        log_index: u64,
+        event_data: &Value,
+    ) -> Vec<(&str, Vec<EthereumSqlTypeWrapper>)> {
+        self.updates
+            .iter()
+            .filter(|update| {
+                update
+                    .condition
+                    .as_ref()
+                    .is_none_or(|condition| condition.evaluate_bool(event_data))
+            })
+            .filter_map(|update| {
+                let mut params = vec![
+                    EthereumSqlTypeWrapper::String(network.to_string()),
+                    EthereumSqlTypeWrapper::U64(block_number),
+                    EthereumSqlTypeWrapper::U64(U64::from(log_index)),
+                ];
+
+                for (type_, expression) in &update.keys {
+                    let (json, postgres, _) = evaluate(expression, *type_, event_data);
+                    if json.is_null() {
+                        return None;
+                    }
+                    params.push(postgres);
+                }
+
+                params.extend(
+                    update
+                        .values
+                        .iter()
+                        .map(|(type_, expression)| evaluate(expression, *type_, event_data).1),
+                );
+
+                Some((update.query.as_str(), params))
+            })
+            .collect()
+    }
+}
+
+/// Builds the upsert for a table event, the params are the network, the block number, the log
+/// index, the keys in the order of the table and then the value of each operation which has one.
+/// The row is only changed when the event was not applied to the table before so a batch which
+/// is retried does not add its values again.
+fn generate_upsert_sql(schema_name: &str, table: &Table, table_event: &TableEvent) -> String {
+    let table_name = generate_table_full_name(schema_name, &table.name);
+    // a table can take the same event more than once for example for the sender and the receiver
+    let target = format!(
+        "{}:{}",
+        camel_to_snake(&table.name),
+        table.events.iter().position(|event| std::ptr::eq(event, table_event)).unwrap_or_default()
+    );
+
+    let mut columns = vec!["network".to_string()];
+    let mut values = vec!["$1".to_string()];
+    let mut conflict_columns = vec!["network".to_string()];
+    let mut updates = vec![];
+    let mut param = 4;
+
+    for key in &table.keys {
+        let column = format!("\"{}\"", camel_to_snake(&key.name));
+        values.push(format!("${}::{}", param, key.type_.postgres_type()));
+        columns.push(column.clone());
+        conflict_columns.push(column);
+        param += 1;
+    }
+
+    for operation in &table_event.operations {
+        let column = format!("\"{}\"", camel_to_snake(&operation.column));
+        let type_ = table.column(&operation.column).map_or(ColumnType::Numeric, |c| c.type_);
+        let (value, update) = match operation.action {
+            // set keeps the value from the latest block so events processed out of order can not
+            // overwrite a newer value
+            TableAction::Set => (
+                format!("${}::{}", param, type_.postgres_type()),
+                format!(
+                    "CASE WHEN EXCLUDED.last_updated_block >= existing.last_updated_block \
+                    THEN EXCLUDED.{column} ELSE existing.{column} END"
+                ),
+            ),
+            TableAction::Add => (
+                format!("${}::NUMERIC", param),
+                format!("COALESCE(existing.{column}, 0) + COALESCE(EXCLUDED.{column}, 0)"),
+            ),
+            TableAction::Subtract => (
+                format!("-(${}::NUMERIC)", param),
+                format!("COALESCE(existing.{column}, 0) + COALESCE(EXCLUDED.{column}, 0)"),
+            ),
+            TableAction::Increment => {
+                ("1".to_string(), format!("COALESCE(existing.{column}, 0) + 1"))
+            }
+        };
+        if operation.action != TableAction::Increment {
+            param += 1;
+        }
+
+        values.push(value);
+        updates.push(format!("{} = {}", column, update));
+        columns.push(column);
+    }
+
+    columns.push("last_updated_block".to_string());
+    values.push("$2::NUMERIC".to_string());
+    updates.push(
+        "last_updated_block = GREATEST(existing.last_updated_block, EXCLUDED.last_updated_block)"
+            .to_string(),
+    );
+
+    format!(
+        "WITH applied AS (INSERT INTO {} (target, network, block_number, log_index) \
+        VALUES ('{}', $1, $2::NUMERIC, $3::NUMERIC) ON CONFLICT DO NOTHING RETURNING 1) \
+        INSERT INTO {} AS existing ({}) SELECT {} FROM applied ON CONFLICT ({}) DO UPDATE SET {}",
+        generate_applied_updates_table_name(schema_name),
+        target,
+        table_name,
+        columns.join(", "),
+        values.join(", "),
+        conflict_columns.join(", "),
+        updates.join(", ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        manifest::contract::{TableColumn, TableOperation},
+        PostgresClient,
+    };
+
+    fn holders_table() -> Table {
+        Table {
+            name: "holders".to_string(),
+            keys: vec![TableColumn { name: "holder".to_string(), type_: ColumnType::Text }],
+            columns: vec![
+                TableColumn { name: "balance".to_string(), type_: ColumnType::Numeric },
+                TableColumn { name: "transfers".to_string(), type_: ColumnType::Numeric },
+            ],
+            events: vec![
+                TableEvent {
+                    event_name: "Transfer".to_string(),
+                    condition: Some(
+                        "from != '0x0000000000000000000000000000000000000000'".to_string(),
+                    ),
+                    keys: BTreeMap::from([("holder".to_string(), "from".to_string())]),
+                    operations: vec![
+                        TableOperation {
+                            column: "balance".to_string(),
+                            action: TableAction::Subtract,
+                            value: Some("value".to_string()),
+                        },
+                        TableOperation {
+                            column: "transfers".to_string(),
+                            action: TableAction::Increment,
+                            value: None,
+                        },
+                    ],
+                },
+                TableEvent {
+                    event_name: "Transfer".to_string(),
+                    condition: None,
+                    keys: BTreeMap::from([("holder".to_string(), "to".to_string())]),
+                    operations: vec![TableOperation {
+                        column: "balance".to_string(),
+                        action: TableAction::Add,
+                        value: Some("value".to_string()),
+                    }],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_upsert_sql() {
+        let table = holders_table();
+        assert_eq!(
+            generate_upsert_sql("indexer_token", &table, &table.events[0]),
+            "WITH applied AS (INSERT INTO rindexer_internal.indexer_token_applied_updates (target, \
+            network, block_number, log_index) VALUES ('holders:0', $1, $2::NUMERIC, $3::NUMERIC) \
+            ON CONFLICT DO NOTHING RETURNING 1) INSERT INTO indexer_token.holders AS existing \
+            (network, \"holder\", \"balance\", \"transfers\", last_updated_block) SELECT $1, \
+            $4::TEXT, -($5::NUMERIC), 1, $2::NUMERIC FROM applied ON CONFLICT (network, \"holder\") \
+            DO UPDATE SET \"balance\" = COALESCE(existing.\"balance\", 0) + \
+            COALESCE(EXCLUDED.\"balance\", 0), \"transfers\" = COALESCE(existing.\"transfers\", 0) \
+            + 1, last_updated_block = GREATEST(existing.last_updated_block, \
+            EXCLUDED.last_updated_block)"
+        );
+        assert!(generate_upsert_sql("indexer_token", &table, &table.events[1])
+            .contains("VALUES ('holders:1', $1, $2::NUMERIC, $3::NUMERIC)"));
+    }
+
+    #[test]
+    fn test_statements() {
+        let table = holders_table();
+        let updates = TableUpdates::parse("indexer_token", &[(&table, &table.events[0])]).unwrap();
+
+        let transfer = json!({
+            "from": "0x00000000000000000000000000000000000000aa",
+            "value": "1500",
+        });
+        let statements = updates.statements("ethereum", U64::from(10), 2, &transfer);
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].1.len(), 5);
+        assert!(matches!(
+            &statements[0].1[3],
+            EthereumSqlTypeWrapper::StringNullable(holder)
+                if holder == "0x00000000000000000000000000000000000000aa"
+        ));
+        assert!(matches!(
+            &statements[0].1[4],
+            EthereumSqlTypeWrapper::NumericNullable(value) if value == "1500"
+        ));
+
+        let mint = json!({
+            "from": "0x0000000000000000000000000000000000000000",
+            "value": "1500",
+        });
+        assert!(updates.statements("ethereum", U64::from(10), 2, &mint).is_empty());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn test_a_retried_batch_is_not_applied_twice() {
+        let client = PostgresClient::new().await.unwrap();
+        let schema_name = "rindexer_test_tables";
+        client
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {schema_name} CASCADE; CREATE SCHEMA {schema_name}; \
+                CREATE SCHEMA IF NOT EXISTS rindexer_internal; \
+                DROP TABLE IF EXISTS {applied}; \
+                CREATE TABLE {applied} (target TEXT NOT NULL, network VARCHAR(50) NOT NULL, \
+                block_number NUMERIC NOT NULL, log_index NUMERIC NOT NULL, \
+                PRIMARY KEY (target, network, block_number, log_index)); \
+                CREATE TABLE {schema_name}.holders (network VARCHAR(50) NOT NULL, \
+                \"holder\" TEXT NOT NULL, last_updated_block NUMERIC NOT NULL, \
+                \"balance\" NUMERIC, \"transfers\" NUMERIC, PRIMARY KEY (network, \"holder\"));",
+                applied = generate_applied_updates_table_name(schema_name)
+            ))
+            .await
+            .unwrap();
+
+        let table = holders_table();
+        let updates = TableUpdates::parse(
+            schema_name,
+            &[(&table, &table.events[0]), (&table, &table.events[1])],
+        )
+        .unwrap();
+        let transfers = [
+            (
+                10,
+                0,
+                json!({ "from": "0x0000000000000000000000000000000000000000", "to": "0xaa", "value": "100" }),
+            ),
+            (11, 4, json!({ "from": "0xaa", "to": "0xbb", "value": "30" })),
+            (11, 5, json!({ "from": "0xaa", "to": "0xbb", "value": "20" })),
+        ];
+        let statements: Vec<(&str, Vec<EthereumSqlTypeWrapper>)> = transfers
+            .iter()
+            .flat_map(|(block_number, log_index, transfer)| {
+                updates.statements("ethereum", U64::from(*block_number), *log_index, transfer)
+            })
+            .collect();
+
+        let holders = || async {
+            client
+                .query(
+                    &format!(
+                        "SELECT \"holder\", \"balance\"::TEXT, \"transfers\"::TEXT, \
+                        last_updated_block::TEXT FROM {schema_name}.holders ORDER BY \"holder\""
+                    ),
+                    &[],
+                )
+                .await
+                .unwrap()
+                .iter()
+                .map(|row| {
+                    (
+                        row.get::<_, String>(0),
+                        row.get::<_, String>(1),
+                        row.get::<_, Option<String>>(2),
+                        row.get::<_, String>(3),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        client.insert_bulk_with_statements("", &[], &[], &statements).await.unwrap();
+        let applied = holders().await;
+        assert_eq!(
+            applied,
+            vec![
+                ("0xaa".to_string(), "50".to_string(), Some("2".to_string()), "11".to_string()),
+                ("0xbb".to_string(), "50".to_string(), None, "11".to_string()),
+            ]
+        );
+
+        client.insert_bulk_with_statements("", &[], &[], &statements).await.unwrap();
+        assert_eq!(holders().await, applied);
+
+        client.batch_execute(&format!("DROP SCHEMA {schema_name} CASCADE;")).await.unwrap();
+    }
+}
@@ -8,11 +8,17 @@ mod system_state;
 pub use system_state::{initiate_shutdown, is_running};
 
 mod database;
-pub use database::postgres::{
-    client::{PostgresClient, ToSql},
-    generate::drop_tables_for_indexer_sql,
-    setup::setup_postgres,
-    sql_type_wrapper::EthereumSqlTypeWrapper,
+pub use database::{
+    clickhouse::{client::ClickhouseClient, setup::setup_clickhouse},
+    postgres::{
+        client::{PostgresClient, ToSql},
+        generate::drop_tables_for_indexer_sql,
+        migrations::{plan_indexer_migration, MigrationPlan},
+        partitions::PartitionedTables,
+        setup::{migrate_postgres, setup_postgres},
+        sql_type_wrapper::EthereumSqlTypeWrapper,
+    },
+    sqlite::{client::SqliteClient, setup::setup_sqlite},
 };
 
 mod simple_file_formatters;
@@ -35,7 +41,13 @@ pub mod event;
 pub mod phantom;
 pub mod provider;
 mod start;
+pub mod storage;
 mod streams;
+pub use streams::{
+    redrive_stream_dead_letters, register_stream_sink, stream_dead_letters, stream_outbox_status,
+    OutboxMessage, StreamDeadLetter, StreamError, StreamOutboxError, StreamOutboxStatus,
+    StreamSink, StreamsClients,
+};
 mod types;
 
 // export 3rd party dependencies
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
-use serde_json::{Map, Value};
+
+use crate::event::Conditions;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TelegramConfig {
@@ -14,7 +15,7 @@ pub struct TelegramEvent {
     pub event_name: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub conditions: Option<Vec<Map<String, Value>>>,
+    pub conditions: Option<Conditions>,
 
     pub template_inline: String,
 }
@@ -32,7 +33,7 @@ pub struct DiscordEvent {
     pub event_name: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub conditions: Option<Vec<Map<String, Value>>>,
+    pub conditions: Option<Conditions>,
 
     pub template_inline: String,
 }
@@ -50,7 +51,7 @@ pub struct SlackEvent {
     pub event_name: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub conditions: Option<Vec<Map<String, Value>>>,
+    pub conditions: Option<Conditions>,
 
     pub template_inline: String,
 }
@@ -66,3 +67,35 @@ pub struct ChatConfig {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub slack: Option<Vec<SlackConfig>>,
 }
+
+impl ChatConfig {
+    /// Every message configured across the chat bots with the conditions it is filtered by
+    pub fn event_conditions(&self) -> Vec<(&String, &Conditions)> {
+        let mut event_conditions = vec![];
+        for telegram in self.telegram.iter().flatten() {
+            event_conditions.extend(
+                telegram
+                    .messages
+                    .iter()
+                    .filter_map(|m| m.conditions.as_ref().map(|c| (&m.event_name, c))),
+            );
+        }
+        for discord in self.discord.iter().flatten() {
+            event_conditions.extend(
+                discord
+                    .messages
+                    .iter()
+                    .filter_map(|m| m.conditions.as_ref().map(|c| (&m.event_name, c))),
+            );
+        }
+        for slack in self.slack.iter().flatten() {
+            event_conditions.extend(
+                slack
+                    .messages
+                    .iter()
+                    .filter_map(|m| m.conditions.as_ref().map(|c| (&m.event_name, c))),
+            );
+        }
+        event_conditions
+    }
+}
@@ -1,11 +1,17 @@
-use std::{borrow::Cow, collections::HashSet, fs, path::Path};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+};
 
+use chrono::{DateTime, Datelike, Duration, Months, TimeZone, Utc};
 use ethers::{
     addressbook::Address,
     prelude::{Filter, ValueOrArray, U64},
 };
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::{json, Map, Value};
 
 use super::core::{deserialize_option_u64_from_string, serialize_option_u64_as_string};
 use crate::{
@@ -107,7 +113,7 @@ impl ContractDetails {
                     ),
                     indexed_filters: self.indexed_filters.as_ref().and_then(|f| f.first().cloned()),
                 }),
-            }
+            };
         } else {
             panic!("Contract details must have an address, factory or filter");
         }
@@ -196,6 +202,354 @@ impl DependencyEventTree {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventConditions {
+    pub event_name: String,
+
+    pub conditions: Vec<Map<String, Value>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    Numeric,
+    Text,
+    Bool,
+}
+
+impl ColumnType {
+    pub fn postgres_type(&self) -> &'static str {
+        match self {
+            ColumnType::Numeric => "NUMERIC",
+            ColumnType::Text => "TEXT",
+            ColumnType::Bool => "BOOLEAN",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ComputedColumn {
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub type_: ColumnType,
+
+    pub expression: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventComputedColumns {
+    pub event_name: String,
+
+    pub columns: Vec<ComputedColumn>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TupleStorage {
+    #[default]
+    Flattened,
+    Jsonb,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventTupleInput {
+    pub event_name: String,
+
+    pub input_name: String,
+
+    pub storage: TupleStorage,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NumericType {
+    Numeric,
+    Text,
+    Bytea,
+    Decimal,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NumericInput {
+    pub event_name: String,
+
+    pub input_name: String,
+
+    #[serde(rename = "type")]
+    pub type_: NumericType,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decimals: Option<u32>,
+}
+
+/// The column an int input is stored in when the default mapping for its size is overridden
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumericColumn {
+    Numeric,
+    Text,
+    Bytea,
+    Decimal(u32),
+}
+
+impl NumericColumn {
+    pub fn postgres_type(&self) -> String {
+        match self {
+            NumericColumn::Numeric => "NUMERIC(78)".to_string(),
+            NumericColumn::Text => "TEXT".to_string(),
+            NumericColumn::Bytea => "BYTEA".to_string(),
+            NumericColumn::Decimal(decimals) => format!("NUMERIC(78, {})", decimals),
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            NumericColumn::Numeric => "numeric".to_string(),
+            NumericColumn::Text => "text".to_string(),
+            NumericColumn::Bytea => "bytea".to_string(),
+            NumericColumn::Decimal(decimals) => format!("decimal{}", decimals),
+        }
+    }
+
+    /// Overridden int inputs keep the column after their solidity type for example `uint256:text`
+    pub fn from_abi_type(abi_type: &str) -> Option<Self> {
+        abi_type.split_once(':').and_then(|(_, name)| Self::from_name(name))
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "numeric" => Some(NumericColumn::Numeric),
+            "text" => Some(NumericColumn::Text),
+            "bytea" => Some(NumericColumn::Bytea),
+            _ => name.strip_prefix("decimal")?.parse().ok().map(NumericColumn::Decimal),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TableColumn {
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub type_: ColumnType,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TableAction {
+    Set,
+    Add,
+    Subtract,
+    Increment,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TableOperation {
+    pub column: String,
+
+    pub action: TableAction,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TableEvent {
+    pub event_name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+
+    pub keys: BTreeMap<String, String>,
+
+    pub operations: Vec<TableOperation>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Table {
+    pub name: String,
+
+    pub keys: Vec<TableColumn>,
+
+    pub columns: Vec<TableColumn>,
+
+    pub events: Vec<TableEvent>,
+}
+
+impl Table {
+    pub fn column(&self, name: &str) -> Option<&TableColumn> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RollupInterval {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl RollupInterval {
+    pub fn name(&self) -> &'static str {
+        match self {
+            RollupInterval::Minute => "minute",
+            RollupInterval::Hour => "hour",
+            RollupInterval::Day => "day",
+            RollupInterval::Week => "week",
+            RollupInterval::Month => "month",
+        }
+    }
+
+    /// The start of the UTC bucket a block timestamp falls in, weeks start on a Monday
+    pub fn bucket_start(&self, block_timestamp: u64) -> DateTime<Utc> {
+        let timestamp = DateTime::from_timestamp(block_timestamp as i64, 0).unwrap_or_default();
+        let day = Utc.from_utc_datetime(&timestamp.date_naive().and_hms_opt(0, 0, 0).unwrap());
+
+        match self {
+            RollupInterval::Minute => timestamp - Duration::seconds(block_timestamp as i64 % 60),
+            RollupInterval::Hour => timestamp - Duration::seconds(block_timestamp as i64 % 3600),
+            RollupInterval::Day => day,
+            RollupInterval::Week => {
+                day - Duration::days(timestamp.weekday().num_days_from_monday() as i64)
+            }
+            RollupInterval::Month => day - Duration::days(timestamp.day0() as i64),
+        }
+    }
+
+    /// The start of the bucket after the one starting at `bucket_start`
+    pub fn next_bucket_start(&self, bucket_start: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            RollupInterval::Minute => bucket_start + Duration::minutes(1),
+            RollupInterval::Hour => bucket_start + Duration::hours(1),
+            RollupInterval::Day => bucket_start + Duration::days(1),
+            RollupInterval::Week => bucket_start + Duration::weeks(1),
+            RollupInterval::Month => bucket_start + Months::new(1),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RollupFunction {
+    Sum,
+    Count,
+    Min,
+    Max,
+    First,
+    Last,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RollupKey {
+    pub name: String,
+
+    #[serde(rename = "type")]
+    pub type_: ColumnType,
+
+    pub value: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RollupMetric {
+    pub name: String,
+
+    pub function: RollupFunction,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Rollup {
+    pub name: String,
+
+    pub event_name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub condition: Option<String>,
+
+    pub intervals: Vec<RollupInterval>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keys: Option<Vec<RollupKey>>,
+
+    pub metrics: Vec<RollupMetric>,
+}
+
+impl Rollup {
+    pub fn keys(&self) -> &[RollupKey] {
+        self.keys.as_deref().unwrap_or_default()
+    }
+
+    /// Each interval is stored in its own table
+    pub fn table_name(&self, interval: RollupInterval) -> String {
+        format!("{}_{}", self.name, interval.name())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventRetention {
+    pub event_name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Retention {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub days: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<EventRetention>>,
+}
+
+/// Retention is only for pruning old rows, longer than this would reach before the unix epoch
+pub const MAX_RETENTION_DAYS: u64 = 36_500;
+
+/// How long the rows of an event table are kept for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RetentionPolicy {
+    Blocks(u64),
+    Days(u64),
+}
+
+impl RetentionPolicy {
+    pub fn new(blocks: Option<u64>, days: Option<u64>) -> Option<Self> {
+        blocks.map(RetentionPolicy::Blocks).or(days.map(RetentionPolicy::Days))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AbiVersion {
+    pub abi: String,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_option_u64_from_string",
+        serialize_with = "serialize_option_u64_as_string"
+    )]
+    pub start_block: Option<U64>,
+
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "deserialize_option_u64_from_string",
+        serialize_with = "serialize_option_u64_as_string"
+    )]
+    pub end_block: Option<U64>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Contract {
     pub name: String,
@@ -204,6 +558,9 @@ pub struct Contract {
 
     pub abi: StringOrArray,
 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abi_versions: Option<Vec<AbiVersion>>,
+
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub include_events: Option<Vec<String>>,
 
@@ -224,6 +581,36 @@ pub struct Contract {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub chat: Option<ChatConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_logs: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Vec<EventConditions>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub computed: Option<Vec<EventComputedColumns>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tables: Option<Vec<Table>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rollups: Option<Vec<Rollup>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tuple_storage: Option<TupleStorage>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tuple_inputs: Option<Vec<EventTupleInput>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numeric_type: Option<NumericType>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numeric_inputs: Option<Vec<NumericInput>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<Retention>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -284,6 +671,24 @@ impl Contract {
         }
     }
 
+    pub fn parse_abi_version(
+        &self,
+        project_path: &Path,
+        abi_version: &AbiVersion,
+    ) -> Result<String, ParseAbiError> {
+        let full_path = get_full_path(project_path, &abi_version.abi)?;
+        let abi_str = fs::read_to_string(full_path)?;
+        Ok(abi_str)
+    }
+
+    /// The current `abi` is used for any blocks after the last ABI version ends
+    pub fn current_abi_start_block(&self) -> Option<U64> {
+        self.abi_versions
+            .as_ref()
+            .and_then(|versions| versions.iter().filter_map(|v| v.end_block).max())
+            .map(|end_block| end_block + 1)
+    }
+
     pub fn convert_dependency_event_tree_yaml(
         &self,
         yaml: DependencyEventTreeYaml,
@@ -305,6 +710,129 @@ impl Contract {
         }
     }
 
+    /// The conditions an event must match to be written to storage, events can be referenced by
+    /// name or by the full signature
+    pub fn storage_conditions(
+        &self,
+        event_name: &str,
+        event_signature: &str,
+    ) -> Option<&Vec<Map<String, Value>>> {
+        self.conditions
+            .as_ref()?
+            .iter()
+            .find(|c| c.event_name == event_name || c.event_name == event_signature)
+            .map(|c| &c.conditions)
+    }
+
+    /// The extra columns worked out from the event data, events can be referenced by name or by
+    /// the full signature
+    pub fn computed_columns(&self, event_name: &str, event_signature: &str) -> &[ComputedColumn] {
+        self.computed
+            .as_ref()
+            .and_then(|computed| {
+                computed
+                    .iter()
+                    .find(|c| c.event_name == event_name || c.event_name == event_signature)
+            })
+            .map_or(&[], |c| &c.columns)
+    }
+
+    /// The derived tables updated by an event alongside the operations it runs on each of them,
+    /// events can be referenced by name or by the full signature
+    pub fn table_events(
+        &self,
+        event_name: &str,
+        event_signature: &str,
+    ) -> Vec<(&Table, &TableEvent)> {
+        self.tables
+            .iter()
+            .flatten()
+            .flat_map(|table| {
+                table
+                    .events
+                    .iter()
+                    .filter(|e| e.event_name == event_name || e.event_name == event_signature)
+                    .map(move |table_event| (table, table_event))
+            })
+            .collect()
+    }
+
+    /// The rollups built from an event, events can be referenced by name or by the full signature
+    pub fn event_rollups(&self, event_name: &str, event_signature: &str) -> Vec<&Rollup> {
+        self.rollups
+            .iter()
+            .flatten()
+            .filter(|r| r.event_name == event_name || r.event_name == event_signature)
+            .collect()
+    }
+
+    /// How a tuple input of an event is stored, a setting for the input wins over the contract
+    /// wide setting, events can be referenced by name or by the full signature
+    pub fn tuple_storage(
+        &self,
+        event_name: &str,
+        event_signature: &str,
+        input_name: &str,
+    ) -> TupleStorage {
+        self.tuple_inputs
+            .iter()
+            .flatten()
+            .find(|t| {
+                (t.event_name == event_name || t.event_name == event_signature) &&
+                    t.input_name == input_name
+            })
+            .map(|t| t.storage)
+            .or(self.tuple_storage)
+            .unwrap_or_default()
+    }
+
+    /// The column an int input is stored in when the default mapping is overridden, a setting for
+    /// the input wins over the contract wide setting, decimal can only be set for an input as it
+    /// needs the decimals
+    pub fn numeric_column(
+        &self,
+        event_name: &str,
+        event_signature: &str,
+        input_name: &str,
+    ) -> Option<NumericColumn> {
+        let numeric_input = self.numeric_inputs.iter().flatten().find(|n| {
+            (n.event_name == event_name || n.event_name == event_signature) &&
+                n.input_name == input_name
+        });
+        let (numeric_type, decimals) = match numeric_input {
+            Some(numeric_input) => (numeric_input.type_, numeric_input.decimals),
+            None => (self.numeric_type?, None),
+        };
+
+        match numeric_type {
+            NumericType::Numeric => Some(NumericColumn::Numeric),
+            NumericType::Text => Some(NumericColumn::Text),
+            NumericType::Bytea => Some(NumericColumn::Bytea),
+            NumericType::Decimal => decimals.map(NumericColumn::Decimal),
+        }
+    }
+
+    /// How long the rows of an event are kept, a setting for the event wins over the contract
+    /// wide setting, events can be referenced by name or by the full signature
+    pub fn event_retention(
+        &self,
+        event_name: &str,
+        event_signature: &str,
+    ) -> Option<RetentionPolicy> {
+        let retention = self.retention.as_ref()?;
+        retention
+            .events
+            .iter()
+            .flatten()
+            .find(|e| e.event_name == event_name || e.event_name == event_signature)
+            .and_then(|e| RetentionPolicy::new(e.blocks, e.days))
+            .or_else(|| RetentionPolicy::new(retention.blocks, retention.days))
+    }
+
+    pub fn raw_logs_enabled(&self) -> bool {
+        self.raw_logs.unwrap_or_default()
+    }
+
     pub fn is_filter(&self) -> bool {
         let filter_count = self
             .details
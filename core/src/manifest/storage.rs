@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 use tracing::info;
@@ -9,12 +9,14 @@ use crate::{
             drop_last_known_indexes, prepare_indexes, DropLastKnownIndexesError,
             PostgresIndexResult, PrepareIndexesError,
         },
+        partitions::PartitionedTables,
         relationship::{
             create_relationships, drop_last_known_relationships, CreateRelationshipError,
             DropLastKnownRelationshipsError, Relationship,
         },
     },
-    manifest::contract::Contract,
+    manifest::contract::{Contract, RollupInterval},
+    types::aws_config::AwsConfig,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -38,9 +40,62 @@ pub struct ForeignKeys {
     pub foreign_keys: Vec<ForeignKey>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexMethod {
+    #[default]
+    Btree,
+    Brin,
+    Hash,
+    Gin,
+}
+
+impl IndexMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexMethod::Btree => "btree",
+            IndexMethod::Brin => "brin",
+            IndexMethod::Hash => "hash",
+            IndexMethod::Gin => "gin",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexOrder {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventIndexColumn {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub event_input_name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expression: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<IndexOrder>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct EventIndex {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub event_input_names: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<EventIndexColumn>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<IndexMethod>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unique: Option<bool>,
+
+    #[serde(rename = "where", default, skip_serializing_if = "Option::is_none")]
+    pub where_: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -72,6 +127,45 @@ pub struct PostgresIndexes {
     pub contracts: Option<Vec<ContractEventsIndexes>>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PartitionBy {
+    BlockNumber,
+    Timestamp,
+}
+
+impl PartitionBy {
+    pub fn column(&self) -> &'static str {
+        match self {
+            PartitionBy::BlockNumber => "block_number",
+            PartitionBy::Timestamp => "block_timestamp",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EventPartition {
+    pub name: String,
+
+    pub by: PartitionBy,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocks: Option<u64>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval: Option<RollupInterval>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timescale: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContractEventsPartitions {
+    pub name: String,
+
+    pub events: Vec<EventPartition>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PostgresDetails {
     pub enabled: bool,
@@ -87,6 +181,9 @@ pub struct PostgresDetails {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub disable_create_tables: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub partitions: Option<Vec<ContractEventsPartitions>>,
 }
 
 fn default_csv_path() -> String {
@@ -102,6 +199,182 @@ pub struct CsvDetails {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub disable_create_headers: Option<bool>,
+
+    /// Setting any of these writes rolling files per network instead of a file per event
+    #[serde(flatten)]
+    pub rotation: FileRotationDetails,
+}
+
+impl CsvDetails {
+    pub fn rolls(&self) -> bool {
+        self.rotation.max_blocks_per_file.is_some() ||
+            self.rotation.max_file_size_mb.is_some() ||
+            self.rotation.rotate_daily.is_some()
+    }
+}
+
+/// When a rolling file is finished and the next one is started
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct FileRotationDetails {
+    /// A file is finished once it covers this many blocks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_blocks_per_file: Option<u64>,
+
+    /// A file is finished once it is this many megabytes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_file_size_mb: Option<u64>,
+
+    /// A file is finished when the block timestamp moves to the next UTC day
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate_daily: Option<bool>,
+}
+
+impl FileRotationDetails {
+    pub fn max_blocks_per_file(&self) -> u64 {
+        self.max_blocks_per_file.unwrap_or(100_000)
+    }
+
+    pub fn max_file_size_bytes(&self) -> usize {
+        self.max_file_size_mb.unwrap_or(256) as usize * 1024 * 1024
+    }
+
+    pub fn rotate_daily(&self) -> bool {
+        self.rotate_daily.unwrap_or_default()
+    }
+}
+
+fn default_parquet_path() -> String {
+    "./generated_parquet".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ParquetCompression {
+    Uncompressed,
+    #[default]
+    Snappy,
+    Zstd,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ParquetDetails {
+    pub enabled: bool,
+
+    #[serde(default = "default_parquet_path")]
+    pub path: String,
+
+    #[serde(flatten)]
+    pub rotation: FileRotationDetails,
+
+    /// The rows buffered in memory before they are flushed to the file as a row group
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row_group_size: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<ParquetCompression>,
+}
+
+impl ParquetDetails {
+    pub fn row_group_size(&self) -> usize {
+        self.row_group_size.unwrap_or(100_000)
+    }
+}
+
+fn default_jsonl_path() -> String {
+    "./generated_jsonl".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonlCompression {
+    #[default]
+    Uncompressed,
+    Gzip,
+    Zstd,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JsonlDetails {
+    pub enabled: bool,
+
+    #[serde(default = "default_jsonl_path")]
+    pub path: String,
+
+    #[serde(flatten)]
+    pub rotation: FileRotationDetails,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<JsonlCompression>,
+}
+
+/// Uploads the finished rolling csv, parquet and jsonl files to S3 or any S3 compatible object
+/// storage
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct S3Details {
+    pub enabled: bool,
+
+    pub bucket: String,
+
+    /// Put in front of the `{indexer}/{contract}/{event}/{network}/{file}` keys
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+
+    /// The endpoint of S3 compatible storage like MinIO
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub force_path_style: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delete_local_files: Option<bool>,
+
+    pub aws_config: AwsConfig,
+}
+
+impl S3Details {
+    pub fn force_path_style(&self) -> bool {
+        self.force_path_style.unwrap_or_default()
+    }
+
+    pub fn delete_local_files(&self) -> bool {
+        self.delete_local_files.unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClickhouseDetails {
+    pub enabled: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drop_each_run: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_create_tables: Option<bool>,
+}
+
+fn default_sqlite_path() -> String {
+    "./rindexer.db".to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SqliteDetails {
+    pub enabled: bool,
+
+    #[serde(default = "default_sqlite_path")]
+    pub path: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub drop_each_run: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relationships: Option<Vec<ForeignKeys>>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexes: Option<PostgresIndexes>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disable_create_tables: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
@@ -111,6 +384,21 @@ pub struct Storage {
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub csv: Option<CsvDetails>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parquet: Option<ParquetDetails>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub jsonl: Option<JsonlDetails>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub s3: Option<S3Details>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clickhouse: Option<ClickhouseDetails>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sqlite: Option<SqliteDetails>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -156,6 +444,87 @@ impl Storage {
         self.postgres.as_ref().map_or(false, |details| details.drop_each_run.unwrap_or_default())
     }
 
+    pub fn postgres_partitions(&self) -> &[ContractEventsPartitions] {
+        self.postgres
+            .as_ref()
+            .filter(|details| details.enabled)
+            .and_then(|details| details.partitions.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn postgres_event_partition(
+        &self,
+        contract_name: &str,
+        event_name: &str,
+    ) -> Option<&EventPartition> {
+        self.postgres_partitions()
+            .iter()
+            .filter(|contract| contract.name == contract_name)
+            .flat_map(|contract| &contract.events)
+            .find(|event| event.name == event_name)
+    }
+
+    pub fn clickhouse_enabled(&self) -> bool {
+        match &self.clickhouse {
+            Some(details) => details.enabled,
+            None => false,
+        }
+    }
+
+    pub fn clickhouse_disable_create_tables(&self) -> bool {
+        let enabled = self.clickhouse_enabled();
+        if !enabled {
+            return true;
+        }
+
+        self.clickhouse
+            .as_ref()
+            .is_some_and(|details| details.disable_create_tables.unwrap_or_default())
+    }
+
+    pub fn clickhouse_drop_each_run(&self) -> bool {
+        let enabled = self.clickhouse_enabled();
+        if !enabled {
+            return false;
+        }
+
+        self.clickhouse.as_ref().is_some_and(|details| details.drop_each_run.unwrap_or_default())
+    }
+
+    pub fn sqlite_enabled(&self) -> bool {
+        match &self.sqlite {
+            Some(details) => details.enabled,
+            None => false,
+        }
+    }
+
+    pub fn sqlite_disable_create_tables(&self) -> bool {
+        let enabled = self.sqlite_enabled();
+        if !enabled {
+            return true;
+        }
+
+        self.sqlite
+            .as_ref()
+            .is_some_and(|details| details.disable_create_tables.unwrap_or_default())
+    }
+
+    pub fn sqlite_drop_each_run(&self) -> bool {
+        let enabled = self.sqlite_enabled();
+        if !enabled {
+            return false;
+        }
+
+        self.sqlite.as_ref().is_some_and(|details| details.drop_each_run.unwrap_or_default())
+    }
+
+    /// The sqlite database file, a relative path is relative to the project
+    pub fn sqlite_path(&self, project_path: &Path) -> PathBuf {
+        let path =
+            self.sqlite.as_ref().map_or_else(default_sqlite_path, |details| details.path.clone());
+        project_path.join(path)
+    }
+
     pub fn csv_enabled(&self) -> bool {
         match &self.csv {
             Some(details) => details.enabled,
@@ -174,6 +543,52 @@ impl Storage {
             .map_or(false, |details| details.disable_create_headers.unwrap_or_default())
     }
 
+    pub fn parquet_enabled(&self) -> bool {
+        match &self.parquet {
+            Some(details) => details.enabled,
+            None => false,
+        }
+    }
+
+    /// The folder the parquet files are written to, a relative path is relative to the project
+    pub fn parquet_path(&self, project_path: &Path) -> PathBuf {
+        let path =
+            self.parquet.as_ref().map_or_else(default_parquet_path, |details| details.path.clone());
+        project_path.join(path)
+    }
+
+    pub fn jsonl_enabled(&self) -> bool {
+        match &self.jsonl {
+            Some(details) => details.enabled,
+            None => false,
+        }
+    }
+
+    /// The folder the jsonl files are written to, a relative path is relative to the project
+    pub fn jsonl_path(&self, project_path: &Path) -> PathBuf {
+        let path =
+            self.jsonl.as_ref().map_or_else(default_jsonl_path, |details| details.path.clone());
+        project_path.join(path)
+    }
+
+    pub fn s3_enabled(&self) -> bool {
+        match &self.s3 {
+            Some(details) => details.enabled,
+            None => false,
+        }
+    }
+
+    /// If the csv files are rotated rather than one file per event
+    pub fn csv_rolls(&self) -> bool {
+        self.csv.as_ref().is_some_and(|details| details.enabled && details.rolls())
+    }
+
+    /// The folder the csv files are written to, a relative path is relative to the project
+    pub fn csv_path(&self, project_path: &Path) -> PathBuf {
+        let path = self.csv.as_ref().map_or_else(default_csv_path, |details| details.path.clone());
+        project_path.join(path)
+    }
+
     pub async fn create_relationships_and_indexes(
         &self,
         project_path: &Path,
@@ -213,8 +628,18 @@ impl Storage {
                 drop_last_known_indexes(manifest_name).await?;
 
                 if let Some(indexes) = &storage.indexes {
-                    let indexes_result =
-                        prepare_indexes(project_path, manifest_name, indexes, contracts).await;
+                    // only whether a table is partitioned matters for indexes so timescale does
+                    // not need to be checked
+                    let partitioned_tables =
+                        PartitionedTables::new(manifest_name, self.postgres_partitions(), false);
+                    let indexes_result = prepare_indexes(
+                        project_path,
+                        manifest_name,
+                        indexes,
+                        &partitioned_tables,
+                        contracts,
+                    )
+                    .await;
 
                     match indexes_result {
                         Ok(result) => {
@@ -1,26 +1,94 @@
-use std::path::Path;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
+use ethers::utils::keccak256;
 use lapin::ExchangeKind;
 use serde::{Deserialize, Deserializer, Serialize};
-use serde_json::{Map, Value};
-use tokio::fs;
+use url::Url;
 
-use crate::types::aws_config::AwsConfig;
+use crate::{event::Conditions, types::aws_config::AwsConfig};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct StreamEvent {
     pub event_name: String,
 
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub conditions: Option<Vec<Map<String, Value>>>,
+    pub conditions: Option<Conditions>,
 }
 
+/// How a sink retries the messages in its outbox before moving them to its dead letters
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StreamDeliveryConfig {
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+fn default_max_attempts() -> u32 {
+    20
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    100
+}
+
+fn default_max_backoff_ms() -> u64 {
+    15_000
+}
+
+impl Default for StreamDeliveryConfig {
+    fn default() -> Self {
+        StreamDeliveryConfig {
+            max_attempts: default_max_attempts(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+        }
+    }
+}
+
+impl StreamDeliveryConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.max_attempts == 0 {
+            return Err("Stream delivery max_attempts must be at least 1".to_string());
+        }
+
+        if self.initial_backoff_ms > self.max_backoff_ms {
+            return Err(
+                "Stream delivery initial_backoff_ms can not be more than max_backoff_ms".to_string()
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn initial_backoff(&self) -> Duration {
+        Duration::from_millis(self.initial_backoff_ms)
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        Duration::from_millis(self.max_backoff_ms)
+    }
+}
+
+// keeps the sink folder names well under the file name limit
+const MAX_SINK_LABEL_LENGTH: usize = 40;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SNSStreamTopicConfig {
     pub prefix_id: Option<String>,
     pub topic_arn: String,
     pub networks: Vec<String>,
     pub events: Vec<StreamEvent>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<StreamDeliveryConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -35,6 +103,9 @@ pub struct WebhookStreamConfig {
     pub shared_secret: String,
     pub networks: Vec<String>,
     pub events: Vec<StreamEvent>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<StreamDeliveryConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -54,6 +125,23 @@ pub struct RedisStreamStreamConfig {
     pub stream_name: String,
     pub networks: Vec<String>,
     pub events: Vec<StreamEvent>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<StreamDeliveryConfig>,
+}
+
+/// A stream sent to a sink the rust project registered with `register_stream_sink`
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CustomStreamConfig {
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix_id: Option<String>,
+    pub networks: Vec<String>,
+    pub events: Vec<StreamEvent>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<StreamDeliveryConfig>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Serialize)]
@@ -85,6 +173,9 @@ pub struct RabbitMQStreamQueueConfig {
     pub routing_key: Option<String>,
     pub networks: Vec<String>,
     pub events: Vec<StreamEvent>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<StreamDeliveryConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -132,6 +223,9 @@ pub struct KafkaStreamQueueConfig {
     pub key: Option<String>,
     pub networks: Vec<String>,
     pub events: Vec<StreamEvent>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<StreamDeliveryConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -167,18 +261,98 @@ pub struct StreamsConfig {
     pub kafka: Option<KafkaStreamConfig>,
 
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub redis: Option<RedisStreamConfig>
+    pub redis: Option<RedisStreamConfig>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom: Option<Vec<CustomStreamConfig>>,
+
+    /// The delivery of every sink which does not set its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery: Option<StreamDeliveryConfig>,
 }
 
 impl StreamsConfig {
     pub fn validate(&self) -> Result<(), String> {
         if let Some(rabbitmq) = &self.rabbitmq {
-            return rabbitmq.validate();
+            rabbitmq.validate()?;
+        }
+
+        let mut deliveries = vec![&self.delivery];
+        if let Some(sns) = &self.sns {
+            deliveries.extend(sns.topics.iter().map(|topic| &topic.delivery));
+        }
+        if let Some(webhooks) = &self.webhooks {
+            deliveries.extend(webhooks.iter().map(|webhook| &webhook.delivery));
+        }
+        if let Some(rabbitmq) = &self.rabbitmq {
+            deliveries.extend(rabbitmq.exchanges.iter().map(|exchange| &exchange.delivery));
+        }
+        if let Some(kafka) = &self.kafka {
+            deliveries.extend(kafka.topics.iter().map(|topic| &topic.delivery));
+        }
+        if let Some(redis) = &self.redis {
+            deliveries.extend(redis.streams.iter().map(|stream| &stream.delivery));
+        }
+        if let Some(custom) = &self.custom {
+            deliveries.extend(custom.iter().map(|stream| &stream.delivery));
+        }
+
+        for delivery in deliveries.into_iter().flatten() {
+            delivery.validate()?;
+        }
+
+        // sinks sending to the same target would share a last synced block and an outbox
+        let sinks = self.sinks();
+        for (index, sink) in sinks.iter().enumerate() {
+            let duplicate = sinks[..index]
+                .iter()
+                .any(|other| other.kind == sink.kind && other.target == sink.target);
+            if duplicate {
+                return Err(format!(
+                    "Two {} streams send to {}, combine them into one",
+                    sink.kind, sink.label
+                ));
+            }
         }
 
         Ok(())
     }
 
+    /// The delivery a sink uses, its own or the one set for all the streams
+    pub fn sink_delivery(&self, delivery: &Option<StreamDeliveryConfig>) -> StreamDeliveryConfig {
+        delivery.as_ref().or(self.delivery.as_ref()).cloned().unwrap_or_default()
+    }
+
+    /// Every event configured across the streams with the conditions it is filtered by
+    pub fn event_conditions(&self) -> Vec<(&String, &Conditions)> {
+        let mut events: Vec<&StreamEvent> = vec![];
+        if let Some(sns) = &self.sns {
+            events.extend(sns.topics.iter().flat_map(|topic| &topic.events));
+        }
+        if let Some(webhooks) = &self.webhooks {
+            events.extend(webhooks.iter().flat_map(|webhook| &webhook.events));
+        }
+        if let Some(rabbitmq) = &self.rabbitmq {
+            events.extend(rabbitmq.exchanges.iter().flat_map(|exchange| &exchange.events));
+        }
+        if let Some(kafka) = &self.kafka {
+            events.extend(kafka.topics.iter().flat_map(|topic| &topic.events));
+        }
+        if let Some(redis) = &self.redis {
+            events.extend(redis.streams.iter().flat_map(|stream| &stream.events));
+        }
+        if let Some(custom) = &self.custom {
+            events.extend(custom.iter().flat_map(|stream| &stream.events));
+        }
+
+        events
+            .into_iter()
+            .filter_map(|event| event.conditions.as_ref().map(|c| (&event.event_name, c)))
+            .collect()
+    }
+
+    /// The folder every stream shared for its last synced blocks before each sink kept its own,
+    /// it is only read so existing projects carry on from where they were
     pub fn get_streams_last_synced_block_path(&self) -> String {
         let mut path = ".rindexer/".to_string();
         if self.rabbitmq.is_some() {
@@ -191,23 +365,204 @@ impl StreamsConfig {
             path.push_str("kafka_");
         } else if self.redis.is_some() {
             path.push_str("redis_");
+        } else if self.custom.is_some() {
+            path.push_str("custom_");
         }
 
         path.trim_end_matches('_').to_string()
     }
 
-    pub async fn create_full_streams_last_synced_block_path(
-        &self,
-        project_path: &Path,
-        contract_name: &str,
-    ) {
-        let streams_last_synced_block_path = self.get_streams_last_synced_block_path();
-        let base_path = Path::new(&streams_last_synced_block_path);
-        let path = base_path.join(contract_name).join("last-synced-blocks");
-        let full_path = project_path.join(path);
+    /// What each sink sends to, in the order `StreamsClients` creates the sinks
+    fn sinks(&self) -> Vec<SinkTarget> {
+        let mut sinks = vec![];
+        if let Some(sns) = &self.sns {
+            sinks.extend(sns.topics.iter().map(|topic| SinkTarget {
+                kind: "sns",
+                label: topic.topic_arn.rsplit(':').next().unwrap_or_default().to_string(),
+                target: topic.topic_arn.clone(),
+            }));
+        }
+        if let Some(webhooks) = &self.webhooks {
+            // only the host as the path and query of a webhook often hold a token
+            sinks.extend(webhooks.iter().map(|webhook| SinkTarget {
+                kind: "webhook",
+                label: Url::parse(&webhook.endpoint)
+                    .ok()
+                    .and_then(|url| url.host_str().map(|host| host.to_string()))
+                    .unwrap_or_default(),
+                target: webhook.endpoint.clone(),
+            }));
+        }
+        if let Some(rabbitmq) = &self.rabbitmq {
+            sinks.extend(rabbitmq.exchanges.iter().map(|exchange| SinkTarget {
+                kind: "rabbitmq",
+                label: exchange.exchange.clone(),
+                target: format!(
+                    "{}\n{}",
+                    exchange.exchange,
+                    exchange.routing_key.as_deref().unwrap_or_default()
+                ),
+            }));
+        }
+        if let Some(kafka) = &self.kafka {
+            sinks.extend(kafka.topics.iter().map(|topic| SinkTarget {
+                kind: "kafka",
+                label: topic.topic.clone(),
+                target: format!("{}\n{}", topic.topic, topic.key.as_deref().unwrap_or_default()),
+            }));
+        }
+        if let Some(redis) = &self.redis {
+            sinks.extend(redis.streams.iter().map(|stream| SinkTarget {
+                kind: "redis",
+                label: stream.stream_name.clone(),
+                target: stream.stream_name.clone(),
+            }));
+        }
+        if let Some(custom) = &self.custom {
+            sinks.extend(custom.iter().map(|stream| SinkTarget {
+                kind: "custom",
+                label: stream.name.clone(),
+                target: stream.name.clone(),
+            }));
+        }
+
+        sinks
+    }
+
+    /// A folder name for each sink in the order `StreamsClients` creates the sinks, it comes from
+    /// what the sink sends to so it stays the same when the streams are reordered
+    pub fn sink_names(&self) -> Vec<String> {
+        self.sinks().iter().map(SinkTarget::folder_name).collect()
+    }
+
+    /// The folder with a folder for each sink
+    pub fn sinks_path(project_path: &Path) -> PathBuf {
+        project_path.join(".rindexer").join("streams")
+    }
+
+    /// The folder the last synced blocks, outbox and dead letters of a sink are kept in
+    pub fn sink_last_synced_block_path(project_path: &Path, sink_name: &str) -> PathBuf {
+        Self::sinks_path(project_path).join(sink_name)
+    }
+}
 
-        if !full_path.exists() {
-            fs::create_dir_all(&full_path).await.expect("Failed to create directory for stream");
+struct SinkTarget {
+    kind: &'static str,
+    /// A readable part of the target which is safe to keep on disk
+    label: String,
+    target: String,
+}
+
+impl SinkTarget {
+    fn folder_name(&self) -> String {
+        let label: String = self
+            .label
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+            .take(MAX_SINK_LABEL_LENGTH)
+            .collect();
+        let label = label.trim_matches(['-', '_']);
+        let hash = hex::encode(&keccak256(format!("{}\n{}", self.kind, self.target))[..6]);
+
+        if label.is_empty() {
+            format!("{}-{}", self.kind, hash)
+        } else {
+            format!("{}-{}-{}", self.kind, label, hash)
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn streams(yaml: &str) -> StreamsConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    const WEBHOOK: &str = r#"
+  - endpoint: https://hooks.example.com/services/T000/B000/secret-token-value
+    shared_secret: secret
+    networks: [ethereum]
+    events: [{ event_name: Transfer }]"#;
+
+    const OTHER_WEBHOOK: &str = r#"
+  - endpoint: https://api.example.org/webhook?token=another-secret
+    shared_secret: secret
+    networks: [ethereum]
+    events: [{ event_name: Approval }]"#;
+
+    #[test]
+    fn test_sink_names_do_not_change_when_the_streams_are_reordered() {
+        let names = streams(&format!("webhooks:{}{}", WEBHOOK, OTHER_WEBHOOK)).sink_names();
+        let reordered = streams(&format!("webhooks:{}{}", OTHER_WEBHOOK, WEBHOOK)).sink_names();
+
+        assert_eq!(names.len(), 2);
+        assert_ne!(names[0], names[1]);
+        assert_eq!(names[0], reordered[1]);
+        assert_eq!(names[1], reordered[0]);
+        assert!(names[0].starts_with("webhook-hooks_example_com-"));
+        assert!(names[1].starts_with("webhook-api_example_org-"));
+    }
+
+    #[test]
+    fn test_sink_names_keep_secrets_off_disk_and_stay_short() {
+        let long_name = "a".repeat(300);
+        let config = streams(&format!(
+            r#"
+webhooks:{}
+sns:
+  aws_config:
+    region: us-east-1
+    access_key: key
+    secret_key: secret
+  topics:
+    - topic_arn: arn:aws:sns:us-east-1:123456789012:transfers
+      networks: [ethereum]
+      events: [{{ event_name: Transfer }}]
+custom:
+  - name: {}
+    networks: [ethereum]
+    events: [{{ event_name: Transfer }}]
+"#,
+            WEBHOOK, long_name
+        ));
+
+        let names = config.sink_names();
+        assert_eq!(names.len(), 3);
+        assert!(names[0].starts_with("sns-transfers-"));
+        assert!(!names[0].contains("123456789012"));
+        assert!(!names[1].contains("secret") && !names[1].contains("services"));
+        assert!(names[2].starts_with("custom-aaaa"));
+        for name in &names {
+            assert!(name.len() <= 64, "{} is too long", name);
+            assert!(name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        }
+    }
+
+    #[test]
+    fn test_sinks_sending_to_the_same_target_are_rejected() {
+        assert!(streams(&format!("webhooks:{}{}", WEBHOOK, OTHER_WEBHOOK)).validate().is_ok());
+
+        let error = streams(&format!("webhooks:{}{}", WEBHOOK, WEBHOOK)).validate().unwrap_err();
+        assert_eq!(error, "Two webhook streams send to hooks.example.com, combine them into one");
+
+        let kafka = r#"
+kafka:
+  brokers: [localhost:9092]
+  acks: all
+  security_protocol: PLAINTEXT
+  topics:
+    - topic: transfers
+      key: a
+      networks: [ethereum]
+      events: [{ event_name: Transfer }]
+    - topic: transfers
+      key: b
+      networks: [ethereum]
+      events: [{ event_name: Transfer }]
+"#;
+        assert!(streams(kafka).validate().is_ok());
+    }
+}
@@ -6,17 +6,28 @@ use std::{
     path::{Path, PathBuf},
 };
 
-use ethers::types::ValueOrArray;
+use ethers::types::{ValueOrArray, U64};
 use regex::{Captures, Regex};
 use serde::{Deserialize, Serialize};
 use tracing::error;
 
 use crate::{
-    abi::ABIItem,
-    helpers::{load_env_from_full_path, replace_env_variable_to_raw_name},
+    abi::{versioned_event_name, ABIInput, ABIItem, EventInfo},
+    database::postgres::{
+        generate::generate_column_names_only_with_base_properties,
+        indexes::{resolve_event_index, InvalidEventIndexError},
+    },
+    event::{parse_conditions, Condition, ConditionError, ValueExpression},
+    helpers::{camel_to_snake, load_env_from_full_path, replace_env_variable_to_raw_name},
+    indexer::{EventDependencyGraph, EventDependencyGraphError, RAW_LOGS_EVENT_NAME},
     manifest::{
+        contract::{
+            ColumnType, Contract, NumericType, RollupFunction, RollupInterval, TableAction,
+            MAX_RETENTION_DAYS,
+        },
         core::{Manifest, ProjectType},
         network::Network,
+        storage::{IndexMethod, PartitionBy},
     },
     StringOrArray,
 };
@@ -43,6 +54,9 @@ pub enum ValidateManifestError {
     #[error("Contract names {0} must be unique")]
     ContractNameMustBeUnique(String),
 
+    #[error("Event name {0} for contract {1} must be unique, overloaded events are numbered so can clash with other events")]
+    EventNameMustBeUnique(String, String),
+
     #[error("Contract name {0} can not include 'Filter' in the name as it is a reserved word")]
     ContractNameCanNotIncludeFilter(String),
 
@@ -75,6 +89,771 @@ pub enum ValidateManifestError {
 
     #[error("Global ABI can only be a single string")]
     GlobalAbiCanOnlyBeASingleString(String),
+
+    #[error("Dependency event contract {0} defined in contract {1} not found")]
+    DependencyEventContractNotFound(String, String),
+
+    #[error("Dependency event {0} defined in contract {1} not found in ABI for contract {2}")]
+    DependencyEventNotFoundInABI(String, String, String),
+
+    #[error("{0}")]
+    EventDependencyGraphError(#[from] EventDependencyGraphError),
+
+    #[error("Raw logs can only be enabled for contracts indexed by address - contract {0}")]
+    RawLogsOnlySupportedForAddressContracts(String),
+
+    #[error("Raw logs can not be enabled for contract {0} as the ABI has an event named {1}")]
+    RawLogsEventNameClashesWithABI(String, String),
+
+    #[error("Raw logs are only supported for no-code projects - contract {0}")]
+    RawLogsOnlySupportedForNoCode(String),
+
+    #[error("Storage conditions are only supported for no-code projects - contract {0}")]
+    StorageConditionsOnlySupportedForNoCode(String),
+
+    #[error("Storage condition event {0} not found in ABI for contract {1}")]
+    StorageConditionEventNotFoundInABI(String, String),
+
+    #[error("Condition on {0} for event {1} in contract {2} is invalid: {3}")]
+    InvalidCondition(String, String, String, ConditionError),
+
+    #[error("Condition field {0} for event {1} in contract {2} does not exist on the event")]
+    ConditionFieldNotFound(String, String, String),
+
+    #[error("Computed columns are only supported for no-code projects - contract {0}")]
+    ComputedColumnsOnlySupportedForNoCode(String),
+
+    #[error("Computed column event {0} not found in ABI for contract {1}")]
+    ComputedColumnEventNotFoundInABI(String, String),
+
+    #[error("Computed column {0} for event {1} in contract {2} clashes with another column")]
+    ComputedColumnNameClash(String, String, String),
+
+    #[error("Computed column {0} for event {1} in contract {2} is invalid: {3}")]
+    InvalidComputedColumn(String, String, String, ConditionError),
+
+    #[error("Computed column {0} for event {1} in contract {2} reads field {3} which does not exist on the event")]
+    ComputedColumnFieldNotFound(String, String, String, String),
+
+    #[error("Tables are only supported for no-code projects - contract {0}")]
+    TablesOnlySupportedForNoCode(String),
+
+    #[error("Tables are stored in postgres so postgres storage must be enabled - contract {0}")]
+    TablesRequirePostgres(String),
+
+    #[error("Table {0} in contract {1} clashes with another table or an event table")]
+    TableNameClash(String, String),
+
+    #[error("Table {0} in contract {1} must have at least one key")]
+    TableMissingKeys(String, String),
+
+    #[error("Table column {0} in table {1} for contract {2} clashes with another column")]
+    TableColumnNameClash(String, String, String),
+
+    #[error("Table {0} event {1} not found in ABI for contract {2}")]
+    TableEventNotFoundInABI(String, String, String),
+
+    #[error("Table {0} event {1} in contract {2} must set exactly the keys of the table")]
+    TableEventKeysMismatch(String, String, String),
+
+    #[error("Table column {0} for event {1} in contract {2} has an invalid operation: {3}")]
+    InvalidTableOperation(String, String, String, &'static str),
+
+    #[error("Table expression {0} for event {1} in contract {2} is invalid: {3}")]
+    InvalidTableExpression(String, String, String, ConditionError),
+
+    #[error(
+        "Table {0} event {1} in contract {2} reads field {3} which does not exist on the event"
+    )]
+    TableFieldNotFound(String, String, String, String),
+
+    #[error("Rollups are only supported for no-code projects - contract {0}")]
+    RollupsOnlySupportedForNoCode(String),
+
+    #[error("Rollups are stored in postgres so postgres storage must be enabled - contract {0}")]
+    RollupsRequirePostgres(String),
+
+    #[error("Rollup {0} event {1} not found in ABI for contract {2}")]
+    RollupEventNotFoundInABI(String, String, String),
+
+    #[error("Rollup table {0} in contract {1} clashes with another table or an event table")]
+    RollupNameClash(String, String),
+
+    #[error("Rollup {0} in contract {1} must have at least one interval")]
+    RollupMissingIntervals(String, String),
+
+    #[error("Rollup column {0} in rollup {1} for contract {2} clashes with another column")]
+    RollupColumnNameClash(String, String, String),
+
+    #[error("Rollup metric {0} in contract {1} is invalid: {2}")]
+    InvalidRollupMetric(String, String, &'static str),
+
+    #[error("Rollup expression {0} in contract {1} is invalid: {2}")]
+    InvalidRollupExpression(String, String, ConditionError),
+
+    #[error("Rollup {0} in contract {1} reads field {2} which does not exist on the event")]
+    RollupFieldNotFound(String, String, String),
+
+    #[error("Index contract {0} not found")]
+    IndexContractNotFound(String),
+
+    #[error("Index event {0} not found in ABI for contract {1}")]
+    IndexEventNotFoundInABI(String, String),
+
+    #[error("Index for event {0} in contract {1} is invalid: {2}")]
+    InvalidIndex(String, String, InvalidEventIndexError),
+
+    #[error("Partition contract {0} not found")]
+    PartitionContractNotFound(String),
+
+    #[error("Partition event {0} not found in ABI for contract {1}")]
+    PartitionEventNotFoundInABI(String, String),
+
+    #[error("Partition for event {0} in contract {1} is invalid: {2}")]
+    InvalidPartition(String, String, &'static str),
+
+    #[error("Partitioning by timestamp is only supported for no-code projects - event {0} in contract {1}")]
+    PartitionByTimestampOnlySupportedForNoCode(String, String),
+
+    #[error("Relationships can not use the partitioned table for event {0} in contract {1}")]
+    PartitionedTableInRelationship(String, String),
+
+    #[error("Tuple storage is only supported for no-code projects - contract {0}")]
+    TupleStorageOnlySupportedForNoCode(String),
+
+    #[error("Tuple input event {0} not found in ABI for contract {1}")]
+    TupleInputEventNotFoundInABI(String, String),
+
+    #[error("Tuple input {0} for event {1} in contract {2} is not a tuple input of the event")]
+    TupleInputNotFound(String, String, String),
+
+    #[error("Numeric types are only supported for no-code projects - contract {0}")]
+    NumericTypeOnlySupportedForNoCode(String),
+
+    #[error("Numeric type for contract {0} can not be decimal as it needs decimals, set it in numeric_inputs")]
+    ContractNumericTypeCanNotBeDecimal(String),
+
+    #[error("Numeric input event {0} not found in ABI for contract {1}")]
+    NumericInputEventNotFoundInABI(String, String),
+
+    #[error(
+        "Numeric input {0} for event {1} in contract {2} is not an int or uint input of the event"
+    )]
+    NumericInputNotFound(String, String, String),
+
+    #[error("Numeric input {0} in contract {1} is invalid: {2}")]
+    InvalidNumericInput(String, String, &'static str),
+
+    #[error("Retention requires postgres storage to be enabled - contract {0}")]
+    RetentionRequiresPostgres(String),
+
+    #[error("Retention for contract {0} is invalid: {1}")]
+    InvalidRetention(String, &'static str),
+
+    #[error("Retention event {0} not found in ABI for contract {1}")]
+    RetentionEventNotFoundInABI(String, String),
+
+    #[error("Relationships can not use the retained table for event {0} in contract {1}")]
+    RetainedTableInRelationship(String, String),
+
+    #[error("ClickHouse storage is only supported for no-code projects")]
+    ClickhouseOnlySupportedForNoCode,
+
+    #[error("S3 uploads need rolling csv, parquet or jsonl files to upload")]
+    S3RequiresRollingFiles,
+
+    #[error("CSV rotation is only supported for no-code projects")]
+    CsvRotationOnlySupportedForNoCode,
+
+    #[error("CSV max_blocks_per_file must be greater than 0")]
+    InvalidCsvFileLimits,
+
+    #[error("Parquet storage is only supported for no-code projects")]
+    ParquetOnlySupportedForNoCode,
+
+    #[error("Parquet row_group_size and max_blocks_per_file must be greater than 0")]
+    InvalidParquetFileLimits,
+
+    #[error("JSON lines storage is only supported for no-code projects")]
+    JsonlOnlySupportedForNoCode,
+
+    #[error("JSON lines max_blocks_per_file must be greater than 0")]
+    InvalidJsonlFileLimits,
+
+    #[error("SQLite indexes can only use the btree method - event {0} in contract {1}")]
+    SqliteIndexMethodNotSupported(String, String),
+
+    #[error("ABI versions are only supported for no-code projects - contract {0}")]
+    AbiVersionsOnlySupportedForNoCode(String),
+
+    #[error("ABI versions can only be used for contracts indexed by address - contract {0}")]
+    AbiVersionsOnlySupportedForAddressContracts(String),
+
+    #[error("ABI version {0} for contract {1} must have an end_block")]
+    AbiVersionMissingEndBlock(String, String),
+
+    #[error("ABI version {0} for contract {1} has a start_block after the end_block")]
+    AbiVersionStartBlockAfterEndBlock(String, String),
+
+    #[error("ABI versions can only be used for contracts on one network - contract {0}")]
+    AbiVersionsOnlySupportedForOneNetwork(String),
+
+    #[error("ABI versions {0} and {1} for contract {2} have overlapping block ranges")]
+    AbiVersionsOverlap(String, String, String),
+
+    #[error("ABI version event {0} clashes with an event with the same name in the ABI for contract {1}")]
+    AbiVersionEventNameClashesWithABI(String, String),
+}
+
+/// The fields added to the event data under `transaction_information` which conditions can use
+const TRANSACTION_INFORMATION_FIELDS: [&str; 9] = [
+    "network",
+    "address",
+    "block_hash",
+    "block_number",
+    "block_timestamp",
+    "transaction_hash",
+    "log_index",
+    "transaction_index",
+    "input",
+];
+
+fn condition_field_exists(inputs: &[ABIInput], path: &str) -> bool {
+    if let Some(field) = path.strip_prefix("transaction_information.") {
+        return TRANSACTION_INFORMATION_FIELDS.contains(&field);
+    }
+
+    let mut segments = path.split('.');
+    let mut inputs = inputs;
+    while let Some(segment) = segments.next() {
+        let input = match inputs.iter().find(|input| input.name == segment) {
+            Some(input) => input,
+            None => return false,
+        };
+
+        match &input.components {
+            Some(components) if input.type_ == "tuple" => inputs = components,
+            // array items can be read by their index
+            _ if input.type_.ends_with(']') => {
+                return segments.all(|segment| segment.parse::<usize>().is_ok())
+            }
+            _ => return segments.next().is_none(),
+        }
+    }
+
+    true
+}
+
+fn validate_conditions(
+    contract: &Contract,
+    event_name: &str,
+    conditions: Result<&[Condition], (String, ConditionError)>,
+    event_infos: &[EventInfo],
+) -> Result<(), ValidateManifestError> {
+    let conditions = conditions.map_err(|(field, e)| {
+        ValidateManifestError::InvalidCondition(
+            field,
+            event_name.to_string(),
+            contract.name.clone(),
+            e,
+        )
+    })?;
+
+    // events which are not in the ABI such as raw logs can only have their syntax checked
+    let event_info =
+        match event_infos.iter().find(|e| e.name == event_name || e.signature() == event_name) {
+            Some(event_info) => event_info,
+            None => return Ok(()),
+        };
+
+    // computed columns are added to the event data before conditions are checked
+    let computed_columns = contract.computed_columns(&event_info.name, event_info.signature());
+    for condition in conditions {
+        if let Some(field) = condition.fields().into_iter().find(|field| {
+            !condition_field_exists(&event_info.inputs, field) &&
+                !computed_columns.iter().any(|column| column.name == *field)
+        }) {
+            return Err(ValidateManifestError::ConditionFieldNotFound(
+                field.to_string(),
+                event_name.to_string(),
+                contract.name.clone(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_computed_columns(
+    contract: &Contract,
+    event_info: &EventInfo,
+) -> Result<(), ValidateManifestError> {
+    let mut column_names = generate_column_names_only_with_base_properties(&event_info.inputs);
+    column_names.push("rindexer_id".to_string());
+
+    let columns = contract.computed_columns(&event_info.name, event_info.signature());
+    for (index, column) in columns.iter().enumerate() {
+        let column_name = camel_to_snake(&column.name);
+        if column_names.contains(&column_name) {
+            return Err(ValidateManifestError::ComputedColumnNameClash(
+                column.name.clone(),
+                event_info.name.clone(),
+                contract.name.clone(),
+            ));
+        }
+        column_names.push(column_name);
+
+        let expression = ValueExpression::parse(&column.expression).map_err(|e| {
+            ValidateManifestError::InvalidComputedColumn(
+                column.name.clone(),
+                event_info.name.clone(),
+                contract.name.clone(),
+                e,
+            )
+        })?;
+
+        // columns are worked out in order so they can use the columns before them
+        let previous_columns = &columns[..index];
+        if let Some(field) = expression.fields().into_iter().find(|field| {
+            !condition_field_exists(&event_info.inputs, field) &&
+                !previous_columns.iter().any(|previous| previous.name == *field)
+        }) {
+            return Err(ValidateManifestError::ComputedColumnFieldNotFound(
+                column.name.clone(),
+                event_info.name.clone(),
+                contract.name.clone(),
+                field.to_string(),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_tables(
+    contract: &Contract,
+    event_infos: &[EventInfo],
+) -> Result<(), ValidateManifestError> {
+    let mut table_names: Vec<String> =
+        event_infos.iter().map(|event_info| camel_to_snake(&event_info.name)).collect();
+    table_names.push(camel_to_snake(RAW_LOGS_EVENT_NAME));
+
+    for table in contract.tables.iter().flatten() {
+        let table_name = camel_to_snake(&table.name);
+        if table_names.contains(&table_name) {
+            return Err(ValidateManifestError::TableNameClash(
+                table.name.clone(),
+                contract.name.clone(),
+            ));
+        }
+        table_names.push(table_name);
+
+        if table.keys.is_empty() {
+            return Err(ValidateManifestError::TableMissingKeys(
+                table.name.clone(),
+                contract.name.clone(),
+            ));
+        }
+
+        let mut column_names = vec!["network".to_string(), "last_updated_block".to_string()];
+        for column in table.keys.iter().chain(table.columns.iter()) {
+            let column_name = camel_to_snake(&column.name);
+            if column_names.contains(&column_name) {
+                return Err(ValidateManifestError::TableColumnNameClash(
+                    column.name.clone(),
+                    table.name.clone(),
+                    contract.name.clone(),
+                ));
+            }
+            column_names.push(column_name);
+        }
+
+        for table_event in &table.events {
+            let invalid_operation = |column: &str, reason: &'static str| {
+                ValidateManifestError::InvalidTableOperation(
+                    format!("{}.{}", table.name, column),
+                    table_event.event_name.clone(),
+                    contract.name.clone(),
+                    reason,
+                )
+            };
+
+            let event_info = event_infos
+                .iter()
+                .find(|e| {
+                    e.name == table_event.event_name || e.signature() == table_event.event_name
+                })
+                .ok_or_else(|| {
+                    ValidateManifestError::TableEventNotFoundInABI(
+                        table.name.clone(),
+                        table_event.event_name.clone(),
+                        contract.name.clone(),
+                    )
+                })?;
+
+            if table_event.keys.len() != table.keys.len() ||
+                !table.keys.iter().all(|key| table_event.keys.contains_key(&key.name))
+            {
+                return Err(ValidateManifestError::TableEventKeysMismatch(
+                    table.name.clone(),
+                    table_event.event_name.clone(),
+                    contract.name.clone(),
+                ));
+            }
+
+            let mut expressions: Vec<(&str, &str)> = table_event
+                .keys
+                .iter()
+                .map(|(key, expression)| (key.as_str(), expression.as_str()))
+                .collect();
+            if let Some(condition) = &table_event.condition {
+                expressions.push(("condition", condition));
+            }
+
+            let mut updated_columns = HashSet::new();
+            for operation in &table_event.operations {
+                let column = table
+                    .column(&operation.column)
+                    .ok_or_else(|| invalid_operation(&operation.column, "column not found"))?;
+
+                if !updated_columns.insert(&operation.column) {
+                    return Err(invalid_operation(
+                        &operation.column,
+                        "column is updated more than once",
+                    ));
+                }
+
+                if operation.action != TableAction::Set && column.type_ != ColumnType::Numeric {
+                    return Err(invalid_operation(
+                        &operation.column,
+                        "only numeric columns can be added to, subtracted from or incremented",
+                    ));
+                }
+
+                match (&operation.value, operation.action) {
+                    (Some(_), TableAction::Increment) => {
+                        return Err(invalid_operation(
+                            &operation.column,
+                            "increment does not take a value",
+                        ));
+                    }
+                    (None, action) if action != TableAction::Increment => {
+                        return Err(invalid_operation(&operation.column, "value is required"));
+                    }
+                    (Some(value), _) => expressions.push((&operation.column, value)),
+                    _ => {}
+                }
+            }
+
+            // computed columns are added to the event data before the tables are updated
+            let computed_columns =
+                contract.computed_columns(&event_info.name, event_info.signature());
+            for (name, expression) in expressions {
+                let expression = ValueExpression::parse(expression).map_err(|e| {
+                    ValidateManifestError::InvalidTableExpression(
+                        format!("{}.{}", table.name, name),
+                        table_event.event_name.clone(),
+                        contract.name.clone(),
+                        e,
+                    )
+                })?;
+
+                if let Some(field) = expression.fields().into_iter().find(|field| {
+                    !condition_field_exists(&event_info.inputs, field) &&
+                        !computed_columns.iter().any(|column| column.name == *field)
+                }) {
+                    return Err(ValidateManifestError::TableFieldNotFound(
+                        table.name.clone(),
+                        table_event.event_name.clone(),
+                        contract.name.clone(),
+                        field.to_string(),
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_rollups(
+    contract: &Contract,
+    event_infos: &[EventInfo],
+) -> Result<(), ValidateManifestError> {
+    let mut table_names: Vec<String> =
+        event_infos.iter().map(|event_info| camel_to_snake(&event_info.name)).collect();
+    table_names.push(camel_to_snake(RAW_LOGS_EVENT_NAME));
+    table_names.extend(contract.tables.iter().flatten().map(|table| camel_to_snake(&table.name)));
+
+    for rollup in contract.rollups.iter().flatten() {
+        let event_info = event_infos
+            .iter()
+            .find(|e| e.name == rollup.event_name || e.signature() == rollup.event_name)
+            .ok_or_else(|| {
+                ValidateManifestError::RollupEventNotFoundInABI(
+                    rollup.name.clone(),
+                    rollup.event_name.clone(),
+                    contract.name.clone(),
+                )
+            })?;
+
+        if rollup.intervals.is_empty() {
+            return Err(ValidateManifestError::RollupMissingIntervals(
+                rollup.name.clone(),
+                contract.name.clone(),
+            ));
+        }
+
+        for interval in &rollup.intervals {
+            let table_name = camel_to_snake(&rollup.table_name(*interval));
+            if table_names.contains(&table_name) {
+                return Err(ValidateManifestError::RollupNameClash(
+                    rollup.table_name(*interval),
+                    contract.name.clone(),
+                ));
+            }
+            table_names.push(table_name);
+        }
+
+        let mut column_names: Vec<String> = [
+            "network",
+            "bucket",
+            "first_block_number",
+            "first_log_index",
+            "last_block_number",
+            "last_log_index",
+        ]
+        .iter()
+        .map(|column| column.to_string())
+        .collect();
+        let columns = rollup
+            .keys()
+            .iter()
+            .map(|key| &key.name)
+            .chain(rollup.metrics.iter().map(|metric| &metric.name));
+        for column in columns {
+            let column_name = camel_to_snake(column);
+            if column_names.contains(&column_name) {
+                return Err(ValidateManifestError::RollupColumnNameClash(
+                    column.clone(),
+                    rollup.name.clone(),
+                    contract.name.clone(),
+                ));
+            }
+            column_names.push(column_name);
+        }
+
+        let mut expressions: Vec<(&str, &str)> =
+            rollup.keys().iter().map(|key| (key.name.as_str(), key.value.as_str())).collect();
+        if let Some(condition) = &rollup.condition {
+            expressions.push(("condition", condition));
+        }
+
+        for metric in &rollup.metrics {
+            let invalid_metric = |reason: &'static str| {
+                ValidateManifestError::InvalidRollupMetric(
+                    format!("{}.{}", rollup.name, metric.name),
+                    contract.name.clone(),
+                    reason,
+                )
+            };
+
+            match (&metric.value, metric.function) {
+                (Some(_), RollupFunction::Count) => {
+                    return Err(invalid_metric("count does not take a value"));
+                }
+                (None, function) if function != RollupFunction::Count => {
+                    return Err(invalid_metric("value is required"));
+                }
+                (Some(value), _) => expressions.push((&metric.name, value)),
+                _ => {}
+            }
+        }
+
+        // computed columns are added to the event data before the rollups are updated
+        let computed_columns = contract.computed_columns(&event_info.name, event_info.signature());
+        for (name, expression) in expressions {
+            let expression = ValueExpression::parse(expression).map_err(|e| {
+                ValidateManifestError::InvalidRollupExpression(
+                    format!("{}.{}", rollup.name, name),
+                    contract.name.clone(),
+                    e,
+                )
+            })?;
+
+            if let Some(field) = expression.fields().into_iter().find(|field| {
+                !condition_field_exists(&event_info.inputs, field) &&
+                    !computed_columns.iter().any(|column| column.name == *field)
+            }) {
+                return Err(ValidateManifestError::RollupFieldNotFound(
+                    rollup.name.clone(),
+                    contract.name.clone(),
+                    field.to_string(),
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_partitions(
+    manifest: &Manifest,
+    contract: &Contract,
+    event_infos: &[EventInfo],
+) -> Result<(), ValidateManifestError> {
+    let partitions = manifest
+        .storage
+        .postgres_partitions()
+        .iter()
+        .filter(|partitions| partitions.name == contract.name)
+        .flat_map(|partitions| &partitions.events);
+
+    for partition in partitions {
+        let event_info =
+            event_infos.iter().find(|e| e.name == partition.name).ok_or_else(|| {
+                ValidateManifestError::PartitionEventNotFoundInABI(
+                    partition.name.clone(),
+                    contract.name.clone(),
+                )
+            })?;
+
+        let invalid_partition = |reason: &'static str| {
+            ValidateManifestError::InvalidPartition(
+                partition.name.clone(),
+                contract.name.clone(),
+                reason,
+            )
+        };
+
+        match partition.by {
+            PartitionBy::BlockNumber => {
+                if partition.blocks == Some(0) {
+                    return Err(invalid_partition("blocks must be more than 0"));
+                }
+                if partition.interval.is_some() || partition.timescale.is_some() {
+                    return Err(invalid_partition(
+                        "interval and timescale can only be used when partitioning by timestamp",
+                    ));
+                }
+            }
+            PartitionBy::Timestamp => {
+                if manifest.project_type != ProjectType::NoCode {
+                    return Err(ValidateManifestError::PartitionByTimestampOnlySupportedForNoCode(
+                        partition.name.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+                if partition.blocks.is_some() {
+                    return Err(invalid_partition(
+                        "blocks can only be used when partitioning by block_number",
+                    ));
+                }
+                if partition.interval == Some(RollupInterval::Minute) {
+                    return Err(invalid_partition("interval must be hour, day, week or month"));
+                }
+                if contract
+                    .computed_columns(&event_info.name, event_info.signature())
+                    .iter()
+                    .any(|column| camel_to_snake(&column.name) == "block_timestamp")
+                {
+                    return Err(invalid_partition(
+                        "a computed column is already named block_timestamp",
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_raw_logs(
+    project_type: &ProjectType,
+    contract: &Contract,
+    event_infos: &[EventInfo],
+) -> Result<(), ValidateManifestError> {
+    if *project_type != ProjectType::NoCode {
+        return Err(ValidateManifestError::RawLogsOnlySupportedForNoCode(contract.name.clone()));
+    }
+
+    if contract.details.iter().any(|d| d.address().is_none()) {
+        return Err(ValidateManifestError::RawLogsOnlySupportedForAddressContracts(
+            contract.name.clone(),
+        ));
+    }
+
+    if event_infos.iter().any(|e| e.name == RAW_LOGS_EVENT_NAME) {
+        return Err(ValidateManifestError::RawLogsEventNameClashesWithABI(
+            contract.name.clone(),
+            RAW_LOGS_EVENT_NAME.to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_abi_versions(
+    project_path: &Path,
+    contract: &Contract,
+    event_infos: &[EventInfo],
+) -> Result<(), ValidateManifestError> {
+    if contract.details.iter().any(|d| d.address().is_none()) {
+        return Err(ValidateManifestError::AbiVersionsOnlySupportedForAddressContracts(
+            contract.name.clone(),
+        ));
+    }
+
+    // the block ranges of the versions are the same for every network the contract is on
+    if contract.details.iter().any(|d| d.network != contract.details[0].network) {
+        return Err(ValidateManifestError::AbiVersionsOnlySupportedForOneNetwork(
+            contract.name.clone(),
+        ));
+    }
+
+    let mut block_ranges: Vec<(String, U64, U64)> = Vec::new();
+    for (index, abi_version) in contract.abi_versions.iter().flatten().enumerate() {
+        let start_block = abi_version.start_block.unwrap_or_default();
+        let end_block = abi_version.end_block.ok_or_else(|| {
+            ValidateManifestError::AbiVersionMissingEndBlock(
+                abi_version.abi.clone(),
+                contract.name.clone(),
+            )
+        })?;
+
+        if start_block > end_block {
+            return Err(ValidateManifestError::AbiVersionStartBlockAfterEndBlock(
+                abi_version.abi.clone(),
+                contract.name.clone(),
+            ));
+        }
+
+        if let Some((other, _, _)) = block_ranges.iter().find(|(_, other_start, other_end)| {
+            start_block <= *other_end && *other_start <= end_block
+        }) {
+            return Err(ValidateManifestError::AbiVersionsOverlap(
+                other.clone(),
+                abi_version.abi.clone(),
+                contract.name.clone(),
+            ));
+        }
+        block_ranges.push((abi_version.abi.clone(), start_block, end_block));
+
+        let version_events = ABIItem::read_abi_version_items(project_path, contract, abi_version)
+            .and_then(|abi_items| Ok(ABIItem::extract_contract_events(contract, abi_items)?))
+            .map_err(|e| ValidateManifestError::InvalidABI(contract.name.clone(), e.to_string()))?;
+        for version_event in version_events.iter() {
+            let event_name = versioned_event_name(&version_event.name, index);
+            if event_infos.iter().any(|e| e.name == event_name) {
+                return Err(ValidateManifestError::AbiVersionEventNameClashesWithABI(
+                    event_name,
+                    contract.name.clone(),
+                ));
+            }
+        }
+    }
+
+    Ok(())
 }
 
 fn validate_manifest(
@@ -94,6 +873,50 @@ fn validate_manifest(
         ));
     }
 
+    if manifest.storage.clickhouse_enabled() && manifest.project_type != ProjectType::NoCode {
+        return Err(ValidateManifestError::ClickhouseOnlySupportedForNoCode);
+    }
+
+    if let Some(csv) =
+        manifest.storage.csv.as_ref().filter(|details| details.enabled && details.rolls())
+    {
+        if manifest.project_type != ProjectType::NoCode {
+            return Err(ValidateManifestError::CsvRotationOnlySupportedForNoCode);
+        }
+
+        if csv.rotation.max_blocks_per_file() == 0 {
+            return Err(ValidateManifestError::InvalidCsvFileLimits);
+        }
+    }
+
+    if let Some(parquet) = manifest.storage.parquet.as_ref().filter(|details| details.enabled) {
+        if manifest.project_type != ProjectType::NoCode {
+            return Err(ValidateManifestError::ParquetOnlySupportedForNoCode);
+        }
+
+        if parquet.row_group_size() == 0 || parquet.rotation.max_blocks_per_file() == 0 {
+            return Err(ValidateManifestError::InvalidParquetFileLimits);
+        }
+    }
+
+    if let Some(jsonl) = manifest.storage.jsonl.as_ref().filter(|details| details.enabled) {
+        if manifest.project_type != ProjectType::NoCode {
+            return Err(ValidateManifestError::JsonlOnlySupportedForNoCode);
+        }
+
+        if jsonl.rotation.max_blocks_per_file() == 0 {
+            return Err(ValidateManifestError::InvalidJsonlFileLimits);
+        }
+    }
+
+    if manifest.storage.s3_enabled() &&
+        !manifest.storage.csv_rolls() &&
+        !manifest.storage.parquet_enabled() &&
+        !manifest.storage.jsonl_enabled()
+    {
+        return Err(ValidateManifestError::S3RequiresRollingFiles);
+    }
+
     for contract in &manifest.contracts {
         if contract.name.to_lowercase().contains("filter") {
             return Err(ValidateManifestError::ContractNameCanNotIncludeFilter(
@@ -103,6 +926,16 @@ fn validate_manifest(
 
         let events = ABIItem::read_abi_items(project_path, contract)
             .map_err(|e| ValidateManifestError::InvalidABI(contract.name.clone(), e.to_string()))?;
+        let event_infos = ABIItem::extract_contract_events(contract, events.clone())
+            .map_err(|e| ValidateManifestError::InvalidABI(contract.name.clone(), e.to_string()))?;
+
+        let mut seen_event_names = HashSet::new();
+        if let Some(duplicate) = event_infos.iter().find(|e| !seen_event_names.insert(&e.name)) {
+            return Err(ValidateManifestError::EventNameMustBeUnique(
+                duplicate.name.clone(),
+                contract.name.clone(),
+            ));
+        }
 
         for detail in &contract.details {
             let has_network = manifest.networks.iter().any(|n| n.name == detail.network);
@@ -142,7 +975,7 @@ fn validate_manifest(
 
             if let Some(indexed_filters) = &detail.indexed_filters {
                 for indexed_filter in indexed_filters.iter() {
-                    let event = events.iter().find(|e| e.name == indexed_filter.event_name);
+                    let event = event_infos.iter().find(|e| e.name == indexed_filter.event_name);
                     if let Some(event) = event {
                         let indexed_allowed_length =
                             event.inputs.iter().filter(|i| i.indexed.unwrap_or(false)).count();
@@ -171,7 +1004,10 @@ fn validate_manifest(
 
         if let Some(include_events) = &contract.include_events {
             for event in include_events {
-                if !events.iter().any(|e| e.name == *event && e.type_ == "event") {
+                if !events
+                    .iter()
+                    .any(|e| e.type_ == "event" && e.is_included_in(std::slice::from_ref(event)))
+                {
                     return Err(ValidateManifestError::EventIncludedNotFoundInABI(
                         event.clone(),
                         contract.name.clone(),
@@ -180,14 +1016,301 @@ fn validate_manifest(
             }
         }
 
-        if let Some(_dependency_events) = &contract.dependency_events {
-            // TODO - validate the events all exist in the contract ABIs
+        if let Some(dependency_events) = &contract.dependency_events {
+            let dependency_event_tree =
+                contract.convert_dependency_event_tree_yaml(dependency_events.clone());
+            for dependency in dependency_event_tree.collect_dependency_events() {
+                let dependency_contract = manifest
+                    .contracts
+                    .iter()
+                    .find(|c| c.name == dependency.contract_name)
+                    .ok_or_else(|| {
+                        ValidateManifestError::DependencyEventContractNotFound(
+                            dependency.contract_name.clone(),
+                            contract.name.clone(),
+                        )
+                    })?;
+
+                let dependency_contract_events = if dependency_contract.name == contract.name {
+                    event_infos.clone()
+                } else {
+                    ABIItem::read_abi_events(project_path, dependency_contract).map_err(|e| {
+                        ValidateManifestError::InvalidABI(
+                            dependency_contract.name.clone(),
+                            e.to_string(),
+                        )
+                    })?
+                };
+
+                if !dependency_contract_events.iter().any(|e| e.name == dependency.event_name) {
+                    return Err(ValidateManifestError::DependencyEventNotFoundInABI(
+                        dependency.event_name,
+                        contract.name.clone(),
+                        dependency.contract_name,
+                    ));
+                }
+            }
+        }
+
+        if contract.raw_logs_enabled() {
+            validate_raw_logs(&manifest.project_type, contract, &event_infos)?;
+        }
+
+        if let Some(conditions) = &contract.conditions {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::StorageConditionsOnlySupportedForNoCode(
+                    contract.name.clone(),
+                ));
+            }
+
+            for condition in conditions {
+                if !event_infos.iter().any(|e| {
+                    e.name == condition.event_name || e.signature() == condition.event_name
+                }) {
+                    return Err(ValidateManifestError::StorageConditionEventNotFoundInABI(
+                        condition.event_name.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+
+                let conditions = parse_conditions(&condition.conditions);
+                validate_conditions(
+                    contract,
+                    &condition.event_name,
+                    conditions.as_deref().map_err(Clone::clone),
+                    &event_infos,
+                )?;
+            }
+        }
+
+        if let Some(computed) = &contract.computed {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::ComputedColumnsOnlySupportedForNoCode(
+                    contract.name.clone(),
+                ));
+            }
+
+            for event_computed_columns in computed {
+                let event_info = event_infos
+                    .iter()
+                    .find(|e| {
+                        e.name == event_computed_columns.event_name ||
+                            e.signature() == event_computed_columns.event_name
+                    })
+                    .ok_or_else(|| {
+                        ValidateManifestError::ComputedColumnEventNotFoundInABI(
+                            event_computed_columns.event_name.clone(),
+                            contract.name.clone(),
+                        )
+                    })?;
+
+                validate_computed_columns(contract, event_info)?;
+            }
+        }
+
+        if contract.tables.is_some() {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::TablesOnlySupportedForNoCode(
+                    contract.name.clone(),
+                ));
+            }
+
+            if !manifest.storage.postgres_enabled() {
+                return Err(ValidateManifestError::TablesRequirePostgres(contract.name.clone()));
+            }
+
+            validate_tables(contract, &event_infos)?;
+        }
+
+        if contract.rollups.is_some() {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::RollupsOnlySupportedForNoCode(
+                    contract.name.clone(),
+                ));
+            }
+
+            if !manifest.storage.postgres_enabled() {
+                return Err(ValidateManifestError::RollupsRequirePostgres(contract.name.clone()));
+            }
+
+            validate_rollups(contract, &event_infos)?;
+        }
+
+        validate_partitions(manifest, contract, &event_infos)?;
+
+        if contract.tuple_storage.is_some() || contract.tuple_inputs.is_some() {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::TupleStorageOnlySupportedForNoCode(
+                    contract.name.clone(),
+                ));
+            }
+
+            for tuple_input in contract.tuple_inputs.iter().flatten() {
+                let event_info = event_infos
+                    .iter()
+                    .find(|e| {
+                        e.name == tuple_input.event_name || e.signature() == tuple_input.event_name
+                    })
+                    .ok_or_else(|| {
+                        ValidateManifestError::TupleInputEventNotFoundInABI(
+                            tuple_input.event_name.clone(),
+                            contract.name.clone(),
+                        )
+                    })?;
+
+                if !event_info
+                    .inputs
+                    .iter()
+                    .any(|input| input.name == tuple_input.input_name && input.is_tuple())
+                {
+                    return Err(ValidateManifestError::TupleInputNotFound(
+                        tuple_input.input_name.clone(),
+                        tuple_input.event_name.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+            }
+        }
+
+        if contract.numeric_type.is_some() || contract.numeric_inputs.is_some() {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::NumericTypeOnlySupportedForNoCode(
+                    contract.name.clone(),
+                ));
+            }
+
+            if contract.numeric_type == Some(NumericType::Decimal) {
+                return Err(ValidateManifestError::ContractNumericTypeCanNotBeDecimal(
+                    contract.name.clone(),
+                ));
+            }
+
+            for numeric_input in contract.numeric_inputs.iter().flatten() {
+                let event_info = event_infos
+                    .iter()
+                    .find(|e| {
+                        e.name == numeric_input.event_name ||
+                            e.signature() == numeric_input.event_name
+                    })
+                    .ok_or_else(|| {
+                        ValidateManifestError::NumericInputEventNotFoundInABI(
+                            numeric_input.event_name.clone(),
+                            contract.name.clone(),
+                        )
+                    })?;
+
+                if !event_info
+                    .inputs
+                    .iter()
+                    .any(|input| input.name == numeric_input.input_name && input.is_int())
+                {
+                    return Err(ValidateManifestError::NumericInputNotFound(
+                        numeric_input.input_name.clone(),
+                        numeric_input.event_name.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+
+                let invalid_numeric_input = |reason: &'static str| {
+                    ValidateManifestError::InvalidNumericInput(
+                        numeric_input.input_name.clone(),
+                        contract.name.clone(),
+                        reason,
+                    )
+                };
+                match (numeric_input.type_, numeric_input.decimals) {
+                    (NumericType::Decimal, None) => {
+                        return Err(invalid_numeric_input("decimal needs decimals"));
+                    }
+                    (NumericType::Decimal, Some(decimals)) if decimals > 78 => {
+                        return Err(invalid_numeric_input("decimals can not be more than 78"));
+                    }
+                    (NumericType::Numeric | NumericType::Text | NumericType::Bytea, Some(_)) => {
+                        return Err(invalid_numeric_input(
+                            "decimals can only be used with the decimal type",
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(retention) = &contract.retention {
+            if !manifest.storage.postgres_enabled() {
+                return Err(ValidateManifestError::RetentionRequiresPostgres(contract.name.clone()));
+            }
+
+            let invalid_retention = |reason: &'static str| {
+                ValidateManifestError::InvalidRetention(contract.name.clone(), reason)
+            };
+            match (retention.blocks, retention.days) {
+                (Some(_), Some(_)) => {
+                    return Err(invalid_retention("set either blocks or days, not both"));
+                }
+                (None, None) if retention.events.is_none() => {
+                    return Err(invalid_retention("set blocks, days or events"));
+                }
+                (Some(0), _) | (_, Some(0)) => {
+                    return Err(invalid_retention("blocks and days must be more than 0"));
+                }
+                (_, Some(days)) if days > MAX_RETENTION_DAYS => {
+                    return Err(invalid_retention("days can not be more than 36500"));
+                }
+                _ => {}
+            }
+
+            for event_retention in retention.events.iter().flatten() {
+                if !event_infos.iter().any(|e| {
+                    e.name == event_retention.event_name ||
+                        e.signature() == event_retention.event_name
+                }) {
+                    return Err(ValidateManifestError::RetentionEventNotFoundInABI(
+                        event_retention.event_name.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+
+                match (event_retention.blocks, event_retention.days) {
+                    (Some(_), Some(_)) | (None, None) => {
+                        return Err(invalid_retention(
+                            "set either blocks or days on each event, not both",
+                        ));
+                    }
+                    (Some(0), _) | (_, Some(0)) => {
+                        return Err(invalid_retention("blocks and days must be more than 0"));
+                    }
+                    (_, Some(days)) if days > MAX_RETENTION_DAYS => {
+                        return Err(invalid_retention("days can not be more than 36500"));
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if contract.abi_versions.is_some() {
+            if manifest.project_type != ProjectType::NoCode {
+                return Err(ValidateManifestError::AbiVersionsOnlySupportedForNoCode(
+                    contract.name.clone(),
+                ));
+            }
+
+            validate_abi_versions(project_path, contract, &event_infos)?;
         }
 
         if let Some(streams) = &contract.streams {
             if let Err(e) = streams.validate() {
                 return Err(ValidateManifestError::StreamsConfigValidationError(e));
             }
+
+            for (event_name, conditions) in streams.event_conditions() {
+                validate_conditions(contract, event_name, conditions.parsed(), &event_infos)?;
+            }
+        }
+
+        if let Some(chat) = &contract.chat {
+            for (event_name, conditions) in chat.event_conditions() {
+                validate_conditions(contract, event_name, conditions.parsed(), &event_infos)?;
+            }
         }
     }
 
@@ -209,10 +1332,173 @@ fn validate_manifest(
                 }
 
                 // TODO - Add validation for the event names and event inputs match the ABIs
+
+                // partitioned tables can not have the unique constraints foreign keys need
+                let events = relationship
+                    .foreign_keys
+                    .iter()
+                    .map(|foreign_key| (&foreign_key.contract_name, &foreign_key.event_name));
+                for (contract_name, event_name) in
+                    [(&relationship.contract_name, &relationship.event_name)]
+                        .into_iter()
+                        .chain(events)
+                {
+                    if manifest
+                        .storage
+                        .postgres_event_partition(contract_name, event_name)
+                        .is_some()
+                    {
+                        return Err(ValidateManifestError::PartitionedTableInRelationship(
+                            event_name.clone(),
+                            contract_name.clone(),
+                        ));
+                    }
+
+                    // pruning would break the foreign keys pointing at the deleted rows
+                    let retained = manifest
+                        .contracts
+                        .iter()
+                        .find(|c| &c.name == contract_name)
+                        .and_then(|c| c.event_retention(event_name, event_name))
+                        .is_some();
+                    if retained {
+                        return Err(ValidateManifestError::RetainedTableInRelationship(
+                            event_name.clone(),
+                            contract_name.clone(),
+                        ));
+                    }
+                }
+            }
+        }
+
+        let contract_events_indexes =
+            postgres.indexes.as_ref().and_then(|indexes| indexes.contracts.as_ref());
+        for contract_event_indexes in contract_events_indexes.into_iter().flatten() {
+            let contract = manifest
+                .contracts
+                .iter()
+                .find(|c| c.name == contract_event_indexes.name)
+                .ok_or_else(|| {
+                    ValidateManifestError::IndexContractNotFound(
+                        contract_event_indexes.name.clone(),
+                    )
+                })?;
+            let events = ABIItem::read_abi_events(project_path, contract).map_err(|e| {
+                ValidateManifestError::InvalidABI(contract.name.clone(), e.to_string())
+            })?;
+
+            for event_indexes in &contract_event_indexes.events {
+                if !events.iter().any(|e| e.name == event_indexes.name) {
+                    return Err(ValidateManifestError::IndexEventNotFoundInABI(
+                        event_indexes.name.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+
+                let partition_column = manifest
+                    .storage
+                    .postgres_event_partition(&contract.name, &event_indexes.name)
+                    .map(|partition| partition.by.column());
+                for index in &event_indexes.indexes {
+                    resolve_event_index(&events, &event_indexes.name, index, partition_column)
+                        .map_err(|e| {
+                            ValidateManifestError::InvalidIndex(
+                                event_indexes.name.clone(),
+                                contract.name.clone(),
+                                e,
+                            )
+                        })?;
+                }
+            }
+        }
+
+        for partitions in manifest.storage.postgres_partitions() {
+            if !manifest.contracts.iter().any(|c| c.name == partitions.name) {
+                return Err(ValidateManifestError::PartitionContractNotFound(
+                    partitions.name.clone(),
+                ));
             }
         }
     }
 
+    if let Some(sqlite) = &manifest.storage.sqlite {
+        for relationship in sqlite.relationships.iter().flatten() {
+            if !manifest.contracts.iter().any(|c| c.name == relationship.contract_name) {
+                return Err(ValidateManifestError::RelationshipContractNotFound(
+                    relationship.contract_name.clone(),
+                ));
+            }
+
+            for foreign_key in &relationship.foreign_keys {
+                if !manifest.contracts.iter().any(|c| c.name == foreign_key.contract_name) {
+                    return Err(ValidateManifestError::RelationshipForeignKeyContractNotFound(
+                        foreign_key.contract_name.clone(),
+                    ));
+                }
+            }
+        }
+
+        let contract_events_indexes =
+            sqlite.indexes.as_ref().and_then(|indexes| indexes.contracts.as_ref());
+        for contract_event_indexes in contract_events_indexes.into_iter().flatten() {
+            let contract = manifest
+                .contracts
+                .iter()
+                .find(|c| c.name == contract_event_indexes.name)
+                .ok_or_else(|| {
+                    ValidateManifestError::IndexContractNotFound(
+                        contract_event_indexes.name.clone(),
+                    )
+                })?;
+            let events = ABIItem::read_abi_events(project_path, contract).map_err(|e| {
+                ValidateManifestError::InvalidABI(contract.name.clone(), e.to_string())
+            })?;
+
+            for event_indexes in &contract_event_indexes.events {
+                if !events.iter().any(|e| e.name == event_indexes.name) {
+                    return Err(ValidateManifestError::IndexEventNotFoundInABI(
+                        event_indexes.name.clone(),
+                        contract.name.clone(),
+                    ));
+                }
+
+                for index in &event_indexes.indexes {
+                    if index.method.is_some_and(|method| method != IndexMethod::Btree) {
+                        return Err(ValidateManifestError::SqliteIndexMethodNotSupported(
+                            event_indexes.name.clone(),
+                            contract.name.clone(),
+                        ));
+                    }
+
+                    resolve_event_index(&events, &event_indexes.name, index, None).map_err(
+                        |e| {
+                            ValidateManifestError::InvalidIndex(
+                                event_indexes.name.clone(),
+                                contract.name.clone(),
+                                e,
+                            )
+                        },
+                    )?;
+                }
+            }
+        }
+    }
+
+    // events can depend on events in any other contract, so cycles can only be found once the
+    // whole graph is built
+    let mut dependencies = EventDependencyGraph::parse(manifest);
+    if let Some(relationships) =
+        manifest.storage.postgres.as_ref().and_then(|postgres| postgres.relationships.as_ref())
+    {
+        dependencies.merge(&EventDependencyGraph::from_foreign_keys(relationships));
+    }
+    if let Some(relationships) =
+        manifest.storage.sqlite.as_ref().and_then(|sqlite| sqlite.relationships.as_ref())
+    {
+        dependencies.merge(&EventDependencyGraph::from_foreign_keys(relationships));
+    }
+    dependencies.topological_order()?;
+
     if let Some(global) = &manifest.global {
         if let Some(contracts) = &global.contracts {
             for contract in contracts {
@@ -352,3 +1638,145 @@ pub fn write_manifest(data: &Manifest, file_path: &PathBuf) -> Result<(), WriteM
     file.write_all(yaml_string.as_bytes()).map_err(WriteManifestError::CouldNotWriteToFile)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SWAP_ABI: &str = r#"[{"type":"event","name":"Swap","inputs":[{"name":"amount","type":"uint256","indexed":false}]}]"#;
+
+    fn contract(networks: &[&str], abi_versions: &[(&str, &str)]) -> Contract {
+        let details: String = networks
+            .iter()
+            .map(|network| {
+                format!(
+                    "\n  - network: {}\n    address: '0x0000000000000000000000000000000000000001'",
+                    network
+                )
+            })
+            .collect();
+        let abi_versions: String = abi_versions
+            .iter()
+            .map(|(abi, blocks)| format!("\n  - abi: {}\n    {}", abi, blocks))
+            .collect();
+
+        serde_yaml::from_str(&format!(
+            "name: Pool\ndetails:{}\nabi: Pool.abi.json\nabi_versions:{}",
+            details, abi_versions
+        ))
+        .unwrap()
+    }
+
+    fn validate(contract: &Contract) -> Result<(), ValidateManifestError> {
+        let project = tempfile::tempdir().unwrap();
+        for abi in ["Pool.abi.json", "PoolV1.abi.json", "PoolV2.abi.json", "PoolV3.abi.json"] {
+            std::fs::write(project.path().join(abi), SWAP_ABI).unwrap();
+        }
+        let event_infos = ABIItem::extract_contract_events(
+            contract,
+            ABIItem::read_abi_items(project.path(), contract).unwrap(),
+        )
+        .unwrap();
+
+        validate_abi_versions(project.path(), contract, &event_infos)
+    }
+
+    #[test]
+    fn test_abi_versions_can_be_defined_in_any_order() {
+        let contract = contract(
+            &["ethereum"],
+            &[
+                ("PoolV3.abi.json", "start_block: 200\n    end_block: 299"),
+                ("PoolV1.abi.json", "end_block: 99"),
+                ("PoolV2.abi.json", "start_block: 100\n    end_block: 199"),
+            ],
+        );
+
+        assert!(validate(&contract).is_ok());
+        assert_eq!(contract.current_abi_start_block(), Some(U64::from(300)));
+    }
+
+    #[test]
+    fn test_abi_versions_which_overlap_are_rejected() {
+        let contract = contract(
+            &["ethereum"],
+            &[
+                ("PoolV3.abi.json", "start_block: 200\n    end_block: 299"),
+                ("PoolV1.abi.json", "end_block: 100"),
+                ("PoolV2.abi.json", "start_block: 100\n    end_block: 199"),
+            ],
+        );
+
+        match validate(&contract) {
+            Err(ValidateManifestError::AbiVersionsOverlap(first, second, _)) => {
+                assert_eq!(first, "PoolV1.abi.json");
+                assert_eq!(second, "PoolV2.abi.json");
+            }
+            result => panic!("expected the versions to overlap, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_abi_versions_need_a_block_range() {
+        let missing_end_block = contract(&["ethereum"], &[("PoolV1.abi.json", "start_block: 100")]);
+        assert!(matches!(
+            validate(&missing_end_block),
+            Err(ValidateManifestError::AbiVersionMissingEndBlock(..))
+        ));
+
+        let start_after_end =
+            contract(&["ethereum"], &[("PoolV1.abi.json", "start_block: 100\n    end_block: 99")]);
+        assert!(matches!(
+            validate(&start_after_end),
+            Err(ValidateManifestError::AbiVersionStartBlockAfterEndBlock(..))
+        ));
+    }
+
+    #[test]
+    fn test_abi_versions_are_only_supported_on_one_network() {
+        let versions = [("PoolV1.abi.json", "end_block: 99")];
+
+        assert!(validate(&contract(&["ethereum", "ethereum"], &versions)).is_ok());
+        assert!(matches!(
+            validate(&contract(&["ethereum", "base"], &versions)),
+            Err(ValidateManifestError::AbiVersionsOnlySupportedForOneNetwork(..))
+        ));
+    }
+
+    #[test]
+    fn test_raw_logs_are_only_supported_for_no_code_address_contracts() {
+        let event_infos = |abi: &str| {
+            ABIItem::extract_contract_events(
+                &contract(&["ethereum"], &[]),
+                serde_json::from_str(abi).unwrap(),
+            )
+            .unwrap()
+        };
+        let by_address = contract(&["ethereum"], &[]);
+        let by_filter: Contract = serde_yaml::from_str(
+            "name: Pool\ndetails:\n  - network: ethereum\n    filter:\n      event_name: Swap\n\
+             abi: Pool.abi.json",
+        )
+        .unwrap();
+
+        assert!(
+            validate_raw_logs(&ProjectType::NoCode, &by_address, &event_infos(SWAP_ABI)).is_ok()
+        );
+        assert!(matches!(
+            validate_raw_logs(&ProjectType::Rust, &by_address, &event_infos(SWAP_ABI)),
+            Err(ValidateManifestError::RawLogsOnlySupportedForNoCode(..))
+        ));
+        assert!(matches!(
+            validate_raw_logs(&ProjectType::NoCode, &by_filter, &event_infos(SWAP_ABI)),
+            Err(ValidateManifestError::RawLogsOnlySupportedForAddressContracts(..))
+        ));
+        assert!(matches!(
+            validate_raw_logs(
+                &ProjectType::NoCode,
+                &by_address,
+                &event_infos(&SWAP_ABI.replace("Swap", RAW_LOGS_EVENT_NAME))
+            ),
+            Err(ValidateManifestError::RawLogsEventNameClashesWithABI(..))
+        ));
+    }
+}
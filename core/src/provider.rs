@@ -87,6 +87,14 @@ impl JsonRpcCachedProvider {
         Ok(result)
     }
 
+    pub async fn get_block_timestamp(
+        &self,
+        block_number: U64,
+    ) -> Result<Option<U256>, ProviderError> {
+        let block = self.provider.get_block(block_number).await?;
+        Ok(block.map(|block| block.timestamp))
+    }
+
     pub async fn get_chain_id(&self) -> Result<U256, ProviderError> {
         self.provider.get_chainid().await
     }
@@ -1,12 +1,18 @@
 use std::{
-    fs::File,
+    fs::{self, File},
     path::{Path, PathBuf},
     sync::Arc,
 };
 
-use csv::Writer;
+use csv::{Reader, ReaderBuilder, StringRecord, Writer};
+use ethers::types::U64;
 use tokio::sync::Mutex;
 
+use crate::{
+    simple_file_formatters::rolling::{RollingFileError, RollingFileFormat, RollingFileWriter},
+    storage::StorageRow,
+};
+
 pub struct AsyncCsvAppender {
     path: Arc<Path>,
     writer_lock: Arc<Mutex<()>>,
@@ -25,7 +31,7 @@ impl AsyncCsvAppender {
         let path = Arc::clone(&self.path);
 
         tokio::task::spawn_blocking(move || {
-            let _guard = lock.lock();
+            let _guard = lock.blocking_lock();
             let file = File::options().create(true).append(true).open(path)?;
             let mut writer = Writer::from_writer(file);
 
@@ -42,7 +48,7 @@ impl AsyncCsvAppender {
         let path = Arc::clone(&self.path);
 
         tokio::task::spawn_blocking(move || {
-            let _guard = lock.lock();
+            let _guard = lock.blocking_lock();
             let file = File::options().create(true).append(true).open(&path)?;
             let mut writer = Writer::from_writer(file);
 
@@ -61,7 +67,7 @@ impl AsyncCsvAppender {
         let path = Arc::clone(&self.path);
 
         tokio::task::spawn_blocking(move || {
-            let _guard = lock.lock();
+            let _guard = lock.blocking_lock();
             let file = File::options().create(true).append(true).open(&path)?;
             let mut writer = Writer::from_writer(file);
 
@@ -72,4 +78,161 @@ impl AsyncCsvAppender {
         .await
         .expect("Failed to run CSV write operation")
     }
+
+    /// The highest block of the network in the file, `None` when the file has no rows for it
+    pub async fn last_block(&self, network: &str) -> Result<Option<U64>, csv::Error> {
+        let lock = Arc::clone(&self.writer_lock);
+        let path = Arc::clone(&self.path);
+        let network = network.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let _guard = lock.blocking_lock();
+            let (mut reader, _, network_index, block_number_index) =
+                match block_columns_reader(&path)? {
+                    Some(reader) => reader,
+                    None => return Ok(None),
+                };
+
+            let mut last_block: Option<U64> = None;
+            for record in reader.records() {
+                let record = record?;
+                if record.get(network_index) == Some(network.as_str()) {
+                    last_block = last_block.max(record_block_number(&record, block_number_index));
+                }
+            }
+
+            Ok(last_block)
+        })
+        .await
+        .expect("Failed to run CSV read operation")
+    }
+
+    /// Rewrites the file without the rows of the network in the block range, both ends included
+    pub async fn remove_block_range(
+        &self,
+        network: &str,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<(), csv::Error> {
+        let lock = Arc::clone(&self.writer_lock);
+        let path = Arc::clone(&self.path);
+        let network = network.to_string();
+
+        tokio::task::spawn_blocking(move || {
+            let _guard = lock.blocking_lock();
+            let (mut reader, headers, network_index, block_number_index) =
+                match block_columns_reader(&path)? {
+                    Some(reader) => reader,
+                    None => return Ok(()),
+                };
+
+            let temp_path = path.with_extension("csv.tmp");
+            let mut writer = Writer::from_path(&temp_path)?;
+            writer.write_record(&headers)?;
+            for record in reader.records() {
+                let record = record?;
+                let in_range = record.get(network_index) == Some(network.as_str()) &&
+                    record_block_number(&record, block_number_index).is_some_and(
+                        |block_number| block_number >= from_block && block_number <= to_block,
+                    );
+                if !in_range {
+                    writer.write_record(&record)?;
+                }
+            }
+            writer.flush()?;
+            drop(writer);
+
+            fs::rename(&temp_path, &*path)?;
+
+            Ok(())
+        })
+        .await
+        .expect("Failed to run CSV rewrite operation")
+    }
+}
+
+/// A reader with the headers of the file and the index of its `network` and `block_number` columns
+type BlockColumnsReader = (Reader<File>, StringRecord, usize, usize);
+
+/// The reader of a csv file, `None` when there is no file or it has no `network` and
+/// `block_number` headers so its rows can not be matched
+fn block_columns_reader(path: &Path) -> Result<Option<BlockColumnsReader>, csv::Error> {
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut reader = ReaderBuilder::new().has_headers(true).from_path(path)?;
+    let headers = reader.headers()?.clone();
+    match (
+        headers.iter().position(|header| header == "network"),
+        headers.iter().position(|header| header == "block_number"),
+    ) {
+        (Some(network_index), Some(block_number_index)) => {
+            Ok(Some((reader, headers, network_index, block_number_index)))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn record_block_number(record: &StringRecord, block_number_index: usize) -> Option<U64> {
+    record.get(block_number_index).and_then(|block_number| U64::from_dec_str(block_number).ok())
+}
+
+/// Writes the rows of an event as csv files which each start with the headers
+pub struct CsvFormat {
+    headers: Option<Vec<String>>,
+}
+
+impl CsvFormat {
+    pub fn new(headers: Vec<String>, disable_create_headers: bool) -> Self {
+        CsvFormat { headers: if disable_create_headers { None } else { Some(headers) } }
+    }
+}
+
+impl RollingFileFormat for CsvFormat {
+    type Row = Vec<String>;
+    type Writer = CsvFileWriter;
+
+    fn extension(&self) -> String {
+        "csv".to_string()
+    }
+
+    fn create(&self, file: File) -> Result<Self::Writer, RollingFileError> {
+        let mut writer = Writer::from_writer(file);
+        if let Some(headers) = &self.headers {
+            writer.write_record(headers)?;
+        }
+
+        Ok(CsvFileWriter { writer })
+    }
+
+    fn row(&self, row: &StorageRow) -> Self::Row {
+        row.text_values.clone()
+    }
+}
+
+pub struct CsvFileWriter {
+    writer: Writer<File>,
+}
+
+impl RollingFileWriter for CsvFileWriter {
+    type Row = Vec<String>;
+
+    fn write(&mut self, rows: &[Self::Row]) -> Result<(), RollingFileError> {
+        for row in rows {
+            self.writer.write_record(row)?;
+        }
+        // flushed so the size of the file is known without tracking the csv buffer
+        self.writer.flush()?;
+
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.writer.get_ref().metadata().map_or(0, |metadata| metadata.len() as usize)
+    }
+
+    fn finish(self) -> Result<File, RollingFileError> {
+        Ok(self.writer.into_inner().map_err(|e| e.into_error())?)
+    }
 }
@@ -0,0 +1,152 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use flate2::{write::GzEncoder, Compression};
+use serde_json::Value;
+
+use crate::{
+    manifest::storage::JsonlCompression,
+    simple_file_formatters::rolling::{RollingFileError, RollingFileFormat, RollingFileWriter},
+    storage::StorageRow,
+};
+
+/// Writes each event as a line of JSON in the same shape streams receive it
+pub struct JsonlFormat {
+    compression: JsonlCompression,
+}
+
+impl JsonlFormat {
+    pub fn new(compression: JsonlCompression) -> Self {
+        JsonlFormat { compression }
+    }
+}
+
+impl RollingFileFormat for JsonlFormat {
+    type Row = Value;
+    type Writer = JsonlFileWriter;
+
+    fn extension(&self) -> String {
+        match self.compression {
+            JsonlCompression::Uncompressed => "jsonl".to_string(),
+            JsonlCompression::Gzip => "jsonl.gz".to_string(),
+            JsonlCompression::Zstd => "jsonl.zst".to_string(),
+        }
+    }
+
+    fn create(&self, file: File) -> Result<Self::Writer, RollingFileError> {
+        let file = CountingFile { file, written: 0 };
+        let encoder = match self.compression {
+            JsonlCompression::Uncompressed => JsonlEncoder::Uncompressed(BufWriter::new(file)),
+            JsonlCompression::Gzip => {
+                JsonlEncoder::Gzip(GzEncoder::new(file, Compression::default()))
+            }
+            JsonlCompression::Zstd => JsonlEncoder::Zstd(zstd::Encoder::new(file, 0)?),
+        };
+
+        Ok(JsonlFileWriter { encoder })
+    }
+
+    fn row(&self, row: &StorageRow) -> Self::Row {
+        row.event_data.clone()
+    }
+}
+
+/// Keeps count of the bytes which reached the file so compressed files rotate on their real size
+struct CountingFile {
+    file: File,
+    written: usize,
+}
+
+impl Write for CountingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.file.write(buf)?;
+        self.written += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+enum JsonlEncoder {
+    Uncompressed(BufWriter<CountingFile>),
+    Gzip(GzEncoder<CountingFile>),
+    Zstd(zstd::Encoder<'static, CountingFile>),
+}
+
+pub struct JsonlFileWriter {
+    encoder: JsonlEncoder,
+}
+
+impl JsonlFileWriter {
+    fn writer(&mut self) -> &mut dyn Write {
+        match &mut self.encoder {
+            JsonlEncoder::Uncompressed(writer) => writer,
+            JsonlEncoder::Gzip(writer) => writer,
+            JsonlEncoder::Zstd(writer) => writer,
+        }
+    }
+}
+
+impl RollingFileWriter for JsonlFileWriter {
+    type Row = Value;
+
+    fn write(&mut self, rows: &[Self::Row]) -> Result<(), RollingFileError> {
+        let writer = self.writer();
+        for row in rows {
+            serde_json::to_writer(&mut *writer, row)?;
+            writer.write_all(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        match &self.encoder {
+            JsonlEncoder::Uncompressed(writer) => writer.get_ref().written + writer.buffer().len(),
+            JsonlEncoder::Gzip(writer) => writer.get_ref().written,
+            JsonlEncoder::Zstd(writer) => writer.get_ref().written,
+        }
+    }
+
+    fn finish(self) -> Result<File, RollingFileError> {
+        let file = match self.encoder {
+            JsonlEncoder::Uncompressed(writer) => {
+                writer.into_inner().map_err(|e| e.into_error())?
+            }
+            JsonlEncoder::Gzip(writer) => writer.finish()?,
+            JsonlEncoder::Zstd(writer) => writer.finish()?,
+        };
+
+        Ok(file.file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_gzip_lines() {
+        let path = std::env::temp_dir().join(format!("rindexer-jsonl-{}.gz", std::process::id()));
+        let format = JsonlFormat::new(JsonlCompression::Gzip);
+        assert_eq!(format.extension(), "jsonl.gz");
+
+        let mut writer = format.create(File::create(&path).unwrap()).unwrap();
+        writer.write(&[json!({"value": "1"}), json!({"value": [1, 2]})]).unwrap();
+        writer.finish().unwrap();
+
+        let mut lines = String::new();
+        GzDecoder::new(File::open(&path).unwrap()).read_to_string(&mut lines).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(lines, "{\"value\":\"1\"}\n{\"value\":[1,2]}\n");
+    }
+}
@@ -1 +1,5 @@
 pub mod csv;
+pub mod jsonl;
+pub mod parquet;
+pub mod rolling;
+pub mod s3;
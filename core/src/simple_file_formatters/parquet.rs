@@ -0,0 +1,448 @@
+use std::{fs::File, sync::Arc};
+
+use arrow_array::{
+    ArrayRef, BinaryArray, BooleanArray, Decimal256Array, FixedSizeBinaryArray, Int64Array,
+    RecordBatch, StringArray, TimestampSecondArray, UInt64Array,
+};
+use arrow_buffer::i256;
+use arrow_schema::{ArrowError, DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+use serde_json::Value;
+
+use crate::{
+    abi::{ABIInput, GenerateAbiPropertiesType, JSONB_TUPLE_TYPE},
+    database::postgres::sql_type_wrapper::{
+        ethereum_wrapper_to_json, scale_decimal_string, EthereumSqlTypeWrapper,
+    },
+    manifest::{
+        contract::{ColumnType, NumericColumn},
+        storage::{ParquetCompression, ParquetDetails},
+    },
+    simple_file_formatters::rolling::{RollingFileError, RollingFileFormat, RollingFileWriter},
+    storage::StorageRow,
+};
+
+/// The arrow type an event input is written as, ints which do not fit in 64 bits are written as
+/// 32 big endian bytes so no precision is lost
+pub fn solidity_type_to_arrow_type(abi_type: &str) -> DataType {
+    if let Some(numeric_column) = NumericColumn::from_abi_type(abi_type) {
+        return match numeric_column {
+            NumericColumn::Numeric => DataType::Decimal256(76, 0),
+            NumericColumn::Decimal(decimals) => DataType::Decimal256(76, decimals as i8),
+            NumericColumn::Text => DataType::Utf8,
+            NumericColumn::Bytea => DataType::FixedSizeBinary(32),
+        };
+    }
+
+    // arrays are written as JSON
+    if abi_type.ends_with("[]") {
+        return DataType::Utf8;
+    }
+
+    match abi_type {
+        "address" | "string" | JSONB_TUPLE_TYPE => DataType::Utf8,
+        "bool" => DataType::Boolean,
+        "bytes" => DataType::Binary,
+        t if t.starts_with("bytes") => match t[5..].parse::<i32>() {
+            Ok(size) => DataType::FixedSizeBinary(size),
+            Err(_) => panic!("Invalid bytesN type"),
+        },
+        t if t.starts_with("int") => match t[3..].parse::<usize>() {
+            Ok(size) if size <= 64 => DataType::Int64,
+            Ok(_) => DataType::FixedSizeBinary(32),
+            Err(_) => panic!("Invalid intN type"),
+        },
+        t if t.starts_with("uint") => match t[4..].parse::<usize>() {
+            Ok(size) if size <= 64 => DataType::UInt64,
+            Ok(_) => DataType::FixedSizeBinary(32),
+            Err(_) => panic!("Invalid uintN type"),
+        },
+        _ => panic!("Unsupported type: {}", abi_type),
+    }
+}
+
+/// The schema of the event files, the columns are in the same order as the postgres event table
+pub fn parquet_schema_for_event(
+    inputs: &[ABIInput],
+    store_block_timestamp: bool,
+    computed_columns: &[(String, ColumnType)],
+) -> Schema {
+    let mut fields = vec![Field::new("contract_address", DataType::Utf8, false)];
+    fields.extend(
+        ABIInput::generate_abi_name_properties(
+            inputs,
+            &GenerateAbiPropertiesType::PostgresColumnsNamesOnly,
+            None,
+        )
+        .into_iter()
+        .map(|m| Field::new(m.value, solidity_type_to_arrow_type(&m.abi_type), true)),
+    );
+    fields.extend([
+        Field::new("tx_hash", DataType::Utf8, false),
+        Field::new("block_number", DataType::UInt64, false),
+        Field::new("block_hash", DataType::Utf8, false),
+        Field::new("network", DataType::Utf8, false),
+        Field::new("tx_index", DataType::UInt64, false),
+        Field::new("log_index", DataType::UInt64, false),
+        Field::new("input", DataType::Utf8, true),
+    ]);
+    if store_block_timestamp {
+        fields.push(Field::new(
+            "block_timestamp",
+            DataType::Timestamp(TimeUnit::Second, Some("UTC".into())),
+            true,
+        ));
+    }
+    fields.extend(computed_columns.iter().map(|(name, column_type)| {
+        let data_type = match column_type {
+            // computed numbers can have decimals so they are kept as they are evaluated
+            ColumnType::Numeric | ColumnType::Text => DataType::Utf8,
+            ColumnType::Bool => DataType::Boolean,
+        };
+        Field::new(name, data_type, true)
+    }));
+
+    Schema::new(fields)
+}
+
+fn unexpected_value(field: &Field, wrapper: &EthereumSqlTypeWrapper) -> RollingFileError {
+    RollingFileError::UnexpectedValue(field.name().clone(), format!("{:?}", wrapper))
+}
+
+fn bool_value(
+    field: &Field,
+    wrapper: &EthereumSqlTypeWrapper,
+) -> Result<Option<bool>, RollingFileError> {
+    match wrapper {
+        EthereumSqlTypeWrapper::Bool(b) => Ok(Some(*b)),
+        _ => Err(unexpected_value(field, wrapper)),
+    }
+}
+
+fn unsigned_value(
+    field: &Field,
+    wrapper: &EthereumSqlTypeWrapper,
+) -> Result<Option<u64>, RollingFileError> {
+    match wrapper {
+        EthereumSqlTypeWrapper::U8(u) => Ok(Some(*u as u64)),
+        EthereumSqlTypeWrapper::U16(u) => Ok(Some(*u as u64)),
+        EthereumSqlTypeWrapper::U32(u) => Ok(Some(*u as u64)),
+        EthereumSqlTypeWrapper::U64(u) => Ok(Some(u.as_u64())),
+        EthereumSqlTypeWrapper::U256Nullable(u) if u.is_zero() => Ok(None),
+        EthereumSqlTypeWrapper::U256(u) | EthereumSqlTypeWrapper::U256Nullable(u)
+            if u.bits() <= 64 =>
+        {
+            Ok(Some(u.as_u64()))
+        }
+        _ => Err(unexpected_value(field, wrapper)),
+    }
+}
+
+fn signed_value(
+    field: &Field,
+    wrapper: &EthereumSqlTypeWrapper,
+) -> Result<Option<i64>, RollingFileError> {
+    match wrapper {
+        EthereumSqlTypeWrapper::I8(i) => Ok(Some(*i as i64)),
+        EthereumSqlTypeWrapper::I16(i) => Ok(Some(*i as i64)),
+        EthereumSqlTypeWrapper::I32(i) => Ok(Some(*i as i64)),
+        EthereumSqlTypeWrapper::I64(i) => Ok(Some(*i)),
+        _ => Err(unexpected_value(field, wrapper)),
+    }
+}
+
+fn string_value(wrapper: &EthereumSqlTypeWrapper) -> Option<String> {
+    match wrapper {
+        EthereumSqlTypeWrapper::AddressNullable(address) if address.is_zero() => None,
+        EthereumSqlTypeWrapper::StringNullable(s) |
+        EthereumSqlTypeWrapper::StringVarcharNullable(s) |
+        EthereumSqlTypeWrapper::StringCharNullable(s) |
+        EthereumSqlTypeWrapper::NumericNullable(s)
+            if s.is_empty() =>
+        {
+            None
+        }
+        EthereumSqlTypeWrapper::NumericScaled(s, decimals) => {
+            Some(scale_decimal_string(s, *decimals))
+        }
+        _ => match ethereum_wrapper_to_json(wrapper) {
+            Value::Null => None,
+            Value::String(s) => Some(s),
+            // arrays and tuples are written as JSON
+            value => Some(value.to_string()),
+        },
+    }
+}
+
+fn bytes_value(
+    field: &Field,
+    wrapper: &EthereumSqlTypeWrapper,
+) -> Result<Option<Vec<u8>>, RollingFileError> {
+    match wrapper {
+        EthereumSqlTypeWrapper::U256Nullable(u) | EthereumSqlTypeWrapper::U256BytesNullable(u)
+            if u.is_zero() =>
+        {
+            Ok(None)
+        }
+        EthereumSqlTypeWrapper::I256Nullable(i) | EthereumSqlTypeWrapper::I256BytesNullable(i)
+            if i.is_zero() =>
+        {
+            Ok(None)
+        }
+        EthereumSqlTypeWrapper::BytesNullable(bytes) if bytes.is_empty() => Ok(None),
+        EthereumSqlTypeWrapper::U256(u) |
+        EthereumSqlTypeWrapper::U256Nullable(u) |
+        EthereumSqlTypeWrapper::U256Bytes(u) |
+        EthereumSqlTypeWrapper::U256BytesNullable(u) => {
+            let mut bytes = [0u8; 32];
+            u.to_big_endian(&mut bytes);
+            Ok(Some(bytes.to_vec()))
+        }
+        EthereumSqlTypeWrapper::I256(i) |
+        EthereumSqlTypeWrapper::I256Nullable(i) |
+        EthereumSqlTypeWrapper::I256Bytes(i) |
+        EthereumSqlTypeWrapper::I256BytesNullable(i) => {
+            let mut bytes = [0u8; 32];
+            i.into_raw().to_big_endian(&mut bytes);
+            Ok(Some(bytes.to_vec()))
+        }
+        EthereumSqlTypeWrapper::U128(u) => {
+            let mut bytes = [0u8; 32];
+            bytes[16..].copy_from_slice(&u.to_be_bytes());
+            Ok(Some(bytes.to_vec()))
+        }
+        EthereumSqlTypeWrapper::I128(i) => {
+            // sign extended so it reads the same as a 256 bit two's complement value
+            let mut bytes = if *i < 0 { [0xffu8; 32] } else { [0u8; 32] };
+            bytes[16..].copy_from_slice(&i.to_be_bytes());
+            Ok(Some(bytes.to_vec()))
+        }
+        EthereumSqlTypeWrapper::Bytes(bytes) | EthereumSqlTypeWrapper::BytesNullable(bytes) => {
+            Ok(Some(bytes.to_vec()))
+        }
+        EthereumSqlTypeWrapper::H256(h) | EthereumSqlTypeWrapper::H256Bytes(h) => {
+            Ok(Some(h.as_bytes().to_vec()))
+        }
+        _ => Err(unexpected_value(field, wrapper)),
+    }
+}
+
+fn decimal_value(
+    field: &Field,
+    wrapper: &EthereumSqlTypeWrapper,
+) -> Result<Option<i256>, RollingFileError> {
+    let value = match wrapper {
+        EthereumSqlTypeWrapper::NumericNullable(s) if s.is_empty() => return Ok(None),
+        // scaled values keep the integer and the decimals are part of the column type
+        EthereumSqlTypeWrapper::NumericNullable(s) |
+        EthereumSqlTypeWrapper::NumericScaled(s, _) => s.clone(),
+        _ => return Err(unexpected_value(field, wrapper)),
+    };
+
+    match i256::from_string(&value) {
+        Some(value) => Ok(Some(value)),
+        None => Err(unexpected_value(field, wrapper)),
+    }
+}
+
+fn timestamp_value(
+    field: &Field,
+    wrapper: &EthereumSqlTypeWrapper,
+) -> Result<Option<i64>, RollingFileError> {
+    match wrapper {
+        EthereumSqlTypeWrapper::DateTime(date_time) => Ok(Some(date_time.timestamp())),
+        _ => Err(unexpected_value(field, wrapper)),
+    }
+}
+
+fn build_column(
+    field: &Field,
+    values: Vec<&EthereumSqlTypeWrapper>,
+) -> Result<ArrayRef, RollingFileError> {
+    let values = values.into_iter();
+    let array: ArrayRef = match field.data_type() {
+        DataType::Boolean => Arc::new(
+            values.map(|value| bool_value(field, value)).collect::<Result<BooleanArray, _>>()?,
+        ),
+        DataType::UInt64 => Arc::new(
+            values.map(|value| unsigned_value(field, value)).collect::<Result<UInt64Array, _>>()?,
+        ),
+        DataType::Int64 => Arc::new(
+            values.map(|value| signed_value(field, value)).collect::<Result<Int64Array, _>>()?,
+        ),
+        DataType::Utf8 => Arc::new(values.map(string_value).collect::<StringArray>()),
+        DataType::Binary => Arc::new(
+            values.map(|value| bytes_value(field, value)).collect::<Result<BinaryArray, _>>()?,
+        ),
+        DataType::FixedSizeBinary(size) => {
+            let bytes =
+                values.map(|value| bytes_value(field, value)).collect::<Result<Vec<_>, _>>()?;
+            Arc::new(FixedSizeBinaryArray::try_from_sparse_iter_with_size(
+                bytes.into_iter(),
+                *size,
+            )?)
+        }
+        DataType::Decimal256(precision, scale) => {
+            let array = values
+                .map(|value| decimal_value(field, value))
+                .collect::<Result<Decimal256Array, _>>()?
+                .with_precision_and_scale(*precision, *scale)?;
+            array.validate_decimal_precision(*precision)?;
+            Arc::new(array)
+        }
+        DataType::Timestamp(TimeUnit::Second, _) => Arc::new(
+            values
+                .map(|value| timestamp_value(field, value))
+                .collect::<Result<TimestampSecondArray, _>>()?
+                .with_timezone_utc(),
+        ),
+        data_type => {
+            return Err(RollingFileError::Arrow(ArrowError::NotYetImplemented(format!(
+                "parquet column type {}",
+                data_type
+            ))))
+        }
+    };
+
+    Ok(array)
+}
+
+/// Turns the rows which are inserted into the postgres event table into a record batch
+pub fn build_record_batch(
+    schema: &SchemaRef,
+    rows: &[Vec<EthereumSqlTypeWrapper>],
+) -> Result<RecordBatch, RollingFileError> {
+    let columns = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .map(|(index, field)| build_column(field, rows.iter().map(|row| &row[index]).collect()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(RecordBatch::try_new(Arc::clone(schema), columns)?)
+}
+
+/// Writes the rows which are inserted into the postgres event table as parquet
+pub struct ParquetFormat {
+    schema: SchemaRef,
+    properties: WriterProperties,
+}
+
+impl ParquetFormat {
+    pub fn new(schema: Schema, details: &ParquetDetails) -> Self {
+        let compression = match details.compression.unwrap_or_default() {
+            ParquetCompression::Uncompressed => Compression::UNCOMPRESSED,
+            ParquetCompression::Snappy => Compression::SNAPPY,
+            ParquetCompression::Zstd => Compression::ZSTD(ZstdLevel::default()),
+        };
+        let properties = WriterProperties::builder()
+            .set_compression(compression)
+            .set_max_row_group_size(details.row_group_size())
+            .build();
+
+        ParquetFormat { schema: Arc::new(schema), properties }
+    }
+}
+
+impl RollingFileFormat for ParquetFormat {
+    type Row = Vec<EthereumSqlTypeWrapper>;
+    type Writer = ParquetFileWriter;
+
+    fn extension(&self) -> String {
+        "parquet".to_string()
+    }
+
+    fn create(&self, file: File) -> Result<Self::Writer, RollingFileError> {
+        let writer =
+            ArrowWriter::try_new(file, Arc::clone(&self.schema), Some(self.properties.clone()))?;
+
+        Ok(ParquetFileWriter { writer, schema: Arc::clone(&self.schema) })
+    }
+
+    fn row(&self, row: &StorageRow) -> Self::Row {
+        row.values.clone()
+    }
+}
+
+pub struct ParquetFileWriter {
+    writer: ArrowWriter<File>,
+    schema: SchemaRef,
+}
+
+impl RollingFileWriter for ParquetFileWriter {
+    type Row = Vec<EthereumSqlTypeWrapper>;
+
+    /// Rows are buffered until there is a full row group to flush
+    fn write(&mut self, rows: &[Self::Row]) -> Result<(), RollingFileError> {
+        let batch = build_record_batch(&self.schema, rows)?;
+        self.writer.write(&batch)?;
+
+        Ok(())
+    }
+
+    fn size(&self) -> usize {
+        self.writer.bytes_written() + self.writer.in_progress_size()
+    }
+
+    fn finish(self) -> Result<File, RollingFileError> {
+        Ok(self.writer.into_inner()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{Address, U256, U64};
+
+    use super::*;
+
+    #[test]
+    fn test_solidity_type_to_arrow_type() {
+        assert_eq!(solidity_type_to_arrow_type("address"), DataType::Utf8);
+        assert_eq!(solidity_type_to_arrow_type("uint256[]"), DataType::Utf8);
+        assert_eq!(solidity_type_to_arrow_type("bool"), DataType::Boolean);
+        assert_eq!(solidity_type_to_arrow_type("bytes"), DataType::Binary);
+        assert_eq!(solidity_type_to_arrow_type("bytes32"), DataType::FixedSizeBinary(32));
+        assert_eq!(solidity_type_to_arrow_type("int64"), DataType::Int64);
+        assert_eq!(solidity_type_to_arrow_type("uint64"), DataType::UInt64);
+        assert_eq!(solidity_type_to_arrow_type("uint256"), DataType::FixedSizeBinary(32));
+        assert_eq!(solidity_type_to_arrow_type("uint256:text"), DataType::Utf8);
+        assert_eq!(solidity_type_to_arrow_type("uint256:decimal6"), DataType::Decimal256(76, 6));
+    }
+
+    #[test]
+    fn test_build_record_batch() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("from", DataType::Utf8, false),
+            Field::new("value", DataType::FixedSizeBinary(32), true),
+            Field::new("amount", DataType::Decimal256(76, 6), true),
+            Field::new("block_number", DataType::UInt64, false),
+        ]));
+        let rows = vec![vec![
+            EthereumSqlTypeWrapper::Address(Address::zero()),
+            EthereumSqlTypeWrapper::U256(U256::MAX),
+            EthereumSqlTypeWrapper::NumericScaled("1500000".to_string(), 6),
+            EthereumSqlTypeWrapper::U64(U64::from(10)),
+        ]];
+
+        let batch = build_record_batch(&schema, &rows).unwrap();
+        assert_eq!(batch.num_rows(), 1);
+
+        let value = batch.column(1).as_any().downcast_ref::<FixedSizeBinaryArray>().unwrap();
+        assert_eq!(value.value(0), [0xffu8; 32]);
+
+        let amount = batch.column(2).as_any().downcast_ref::<Decimal256Array>().unwrap();
+        assert_eq!(amount.value_as_string(0), "1.500000");
+
+        let rows = vec![vec![
+            EthereumSqlTypeWrapper::Address(Address::zero()),
+            EthereumSqlTypeWrapper::String("not bytes".to_string()),
+            EthereumSqlTypeWrapper::NumericNullable("".to_string()),
+            EthereumSqlTypeWrapper::U64(U64::from(10)),
+        ]];
+        assert!(build_record_batch(&schema, &rows).is_err());
+    }
+}
@@ -0,0 +1,1057 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use arrow_schema::ArrowError;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate};
+use ethers::types::{U256, U64};
+use parquet::errors::ParquetError;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinError;
+use tracing::{error, info, warn};
+
+use crate::{
+    indexer::{
+        get_last_synced_block_number_file, update_last_synced_block_number_file,
+        UpdateLastSyncedBlockNumberFile,
+    },
+    manifest::storage::FileRotationDetails,
+    simple_file_formatters::s3::S3Client,
+    storage::{StorageBackend, StorageBackendError, StorageBatch, StorageEvent, StorageRow},
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum RollingFileError {
+    #[error("File IO error: {0}")]
+    FileIo(#[from] std::io::Error),
+
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] ParquetError),
+
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] ArrowError),
+
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    #[error("Column {0} can not be written from the value {1}")]
+    UnexpectedValue(String, String),
+
+    #[error("Could not update the last synced block: {0}")]
+    LastSyncedBlock(#[from] UpdateLastSyncedBlockNumberFile),
+
+    #[error("File write task failed: {0}")]
+    TaskFailed(#[from] JoinError),
+}
+
+/// A file format which rows can be appended to until the file is finished
+pub trait RollingFileFormat: Send + Sync + 'static {
+    type Row: Send + 'static;
+    type Writer: RollingFileWriter<Row = Self::Row>;
+
+    /// The extension of the files without the leading dot, for example `jsonl.gz`
+    fn extension(&self) -> String;
+
+    fn create(&self, file: File) -> Result<Self::Writer, RollingFileError>;
+
+    /// The row of the format from a stored row
+    fn row(&self, row: &StorageRow) -> Self::Row;
+}
+
+pub trait RollingFileWriter: Send + 'static {
+    type Row;
+
+    fn write(&mut self, rows: &[Self::Row]) -> Result<(), RollingFileError>;
+
+    /// The size of the file including anything which is still buffered
+    fn size(&self) -> usize;
+
+    /// Writes anything buffered and the footer of the format
+    fn finish(self) -> Result<File, RollingFileError>;
+}
+
+/// A row with the block it came from so files can be rotated on block ranges and days
+pub struct RollingRow<R> {
+    pub block_number: U64,
+    pub day: Option<NaiveDate>,
+    pub row: R,
+}
+
+impl<R> RollingRow<R> {
+    pub fn new(block_number: U64, block_timestamp: Option<U256>, row: R) -> Self {
+        let day = block_timestamp
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp.as_u64() as i64, 0))
+            .map(|date_time| date_time.date_naive());
+
+        RollingRow { block_number, day, row }
+    }
+}
+
+/// A file which has been finished and moved to its final name
+#[derive(Debug, Clone)]
+pub struct FinishedFile {
+    pub path: PathBuf,
+    pub network: String,
+    pub from_block: U64,
+    pub to_block: U64,
+}
+
+struct OpenFile<W> {
+    writer: W,
+    path: PathBuf,
+    network: String,
+    from_block: U64,
+    to_block: U64,
+    day: Option<NaiveDate>,
+}
+
+impl<W: RollingFileWriter> OpenFile<W> {
+    /// Writes the footer and moves the file to its final name, until then readers never see it
+    fn finish(self, event_name: &str, extension: &str) -> Result<FinishedFile, RollingFileError> {
+        let file = self.writer.finish()?;
+        file.sync_all()?;
+
+        let day = self.day.map(|day| format!("{}-", day)).unwrap_or_default();
+        let path = self.path.with_file_name(format!(
+            "{}-{}{:012}-{:012}.{}",
+            event_name.to_lowercase(),
+            day,
+            self.from_block.as_u64(),
+            self.to_block.as_u64(),
+            extension
+        ));
+        fs::rename(&self.path, &path)?;
+
+        Ok(FinishedFile {
+            path,
+            network: self.network,
+            from_block: self.from_block,
+            to_block: self.to_block,
+        })
+    }
+}
+
+/// A finished file listed in the manifest of a network folder
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ManifestFile {
+    pub file: String,
+    pub from_block: u64,
+    pub to_block: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub day: Option<NaiveDate>,
+    pub size_bytes: u64,
+}
+
+/// The finished files of an event on a network ordered by block so readers can pick up new
+/// files without listing the folder
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+    pub event_name: String,
+    pub network: String,
+    pub files: Vec<ManifestFile>,
+}
+
+/// Reads the day and block range from the name of a finished file of the event, `None` for any
+/// other file in the folder
+fn parse_finished_file_name(
+    event_name: &str,
+    extension: &str,
+    file_name: &str,
+) -> Option<(Option<NaiveDate>, U64, U64)> {
+    let name = file_name
+        .strip_suffix(extension)?
+        .strip_suffix('.')?
+        .strip_prefix(&event_name.to_lowercase())?
+        .strip_prefix('-')?;
+    let (name, to_block) = name.rsplit_once('-')?;
+    let (day, from_block) = match name.rsplit_once('-') {
+        Some((day, from_block)) => {
+            (Some(NaiveDate::parse_from_str(day, "%Y-%m-%d").ok()?), from_block)
+        }
+        None => (None, name),
+    };
+
+    Some((day, U64::from_dec_str(from_block).ok()?, U64::from_dec_str(to_block).ok()?))
+}
+
+fn manifest_file_name(event_name: &str) -> String {
+    format!("{}-manifest.json", event_name.to_lowercase())
+}
+
+/// The finished files of the event in a network folder ordered by block
+fn finished_files(
+    network_folder: &Path,
+    event_name: &str,
+    extension: &str,
+) -> Result<Vec<ManifestFile>, RollingFileError> {
+    let mut files = vec![];
+    for entry in fs::read_dir(network_folder)? {
+        let entry = entry?;
+        let file = entry.file_name().to_string_lossy().into_owned();
+        if let Some((day, from_block, to_block)) =
+            parse_finished_file_name(event_name, extension, &file)
+        {
+            files.push(ManifestFile {
+                file,
+                from_block: from_block.as_u64(),
+                to_block: to_block.as_u64(),
+                day,
+                size_bytes: entry.metadata()?.len(),
+            });
+        }
+    }
+    files.sort_by_key(|file| file.from_block);
+
+    Ok(files)
+}
+
+fn read_manifest(network_folder: &Path, event_name: &str) -> Option<Manifest> {
+    let contents = fs::read(network_folder.join(manifest_file_name(event_name))).ok()?;
+    serde_json::from_slice(&contents).ok()
+}
+
+/// Adds the finished files in the folder to the manifest, files which were removed once they
+/// were uploaded stay listed. It is written to a temporary file first so readers never see half
+/// of it.
+fn write_manifest(
+    network_folder: &Path,
+    event_name: &str,
+    extension: &str,
+) -> Result<(), RollingFileError> {
+    let mut files = finished_files(network_folder, event_name, extension)?;
+    if let Some(manifest) = read_manifest(network_folder, event_name) {
+        let on_disk: HashSet<String> = files.iter().map(|file| file.file.clone()).collect();
+        files.extend(manifest.files.into_iter().filter(|file| !on_disk.contains(&file.file)));
+        files.sort_by_key(|file| file.from_block);
+    }
+
+    let manifest = Manifest {
+        event_name: event_name.to_string(),
+        network: network_folder.file_name().unwrap_or_default().to_string_lossy().into_owned(),
+        files,
+    };
+
+    let path = network_folder.join(manifest_file_name(event_name));
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec_pretty(&manifest)?)?;
+    fs::rename(&temp_path, &path)?;
+
+    Ok(())
+}
+
+fn in_progress_file_name(event_name: &str, extension: &str) -> String {
+    // hidden so query engines reading the folder skip it
+    format!(".{}.{}.inprogress", event_name.to_lowercase(), extension)
+}
+
+/// The rows of a block range split by the UTC day of their block
+struct Segment<R> {
+    from_block: U64,
+    to_block: U64,
+    day: Option<NaiveDate>,
+    rows: Vec<R>,
+}
+
+fn split_by_day<R>(
+    from_block: U64,
+    to_block: U64,
+    rows: Vec<RollingRow<R>>,
+    rotate_daily: bool,
+) -> Vec<Segment<R>> {
+    let mut segments = vec![Segment { from_block, to_block, day: None, rows: vec![] }];
+
+    for row in rows {
+        let segment = segments.last_mut().expect("there is always a segment");
+        let next_day = rotate_daily &&
+            !segment.rows.is_empty() &&
+            segment.day.is_some() &&
+            row.day.is_some() &&
+            segment.day != row.day;
+
+        if next_day {
+            // the previous day ends on the block before the first one of the next day
+            segment.to_block = row.block_number - 1;
+            segments.push(Segment {
+                from_block: row.block_number,
+                to_block,
+                day: row.day,
+                rows: vec![row.row],
+            });
+        } else {
+            if segment.day.is_none() {
+                segment.day = row.day;
+            }
+            segment.rows.push(row.row);
+        }
+    }
+
+    segments
+}
+
+/// Where the finished files are uploaded to
+pub struct RollingFileUpload {
+    pub client: Arc<S3Client>,
+    pub indexer_name: String,
+}
+
+impl RollingFileUpload {
+    pub fn new(client: &Arc<S3Client>, indexer_name: &str) -> Self {
+        RollingFileUpload { client: Arc::clone(client), indexer_name: indexer_name.to_string() }
+    }
+}
+
+/// Writes the rows of an event to a file per network which is finished once it covers enough
+/// blocks, gets big enough or the day changes. The last synced block only moves when a file is
+/// finished, or uploaded when there is an upload, so it always matches the data on disk.
+pub struct RollingFiles<F: RollingFileFormat> {
+    name: String,
+    format: Arc<F>,
+    folder: PathBuf,
+    checkpoint_path: PathBuf,
+    contract_name: String,
+    table_event_name: String,
+    event_name: String,
+    max_blocks_per_file: u64,
+    max_file_size_bytes: usize,
+    rotate_daily: bool,
+    files: Arc<Mutex<HashMap<String, OpenFile<F::Writer>>>>,
+    upload: Option<RollingFileUpload>,
+    /// Finished files in block order which still have to be uploaded
+    pending_uploads: tokio::sync::Mutex<Vec<FinishedFile>>,
+    /// The blocks of each network already in finished files when indexing resumed before them
+    skip_up_to: Mutex<HashMap<String, U64>>,
+}
+
+impl<F: RollingFileFormat> RollingFiles<F> {
+    /// The files of the event are written to `{path}/{contract_name}/{table_event_name}/{network}`
+    pub async fn new(
+        path: &Path,
+        rotation: &FileRotationDetails,
+        contract_name: &str,
+        table_event_name: &str,
+        event_name: &str,
+        format: F,
+        upload: Option<RollingFileUpload>,
+    ) -> Result<Self, RollingFileError> {
+        let folder = path.join(contract_name).join(table_event_name.to_lowercase());
+        fs::create_dir_all(&folder)?;
+        fs::create_dir_all(path.join(contract_name).join("last-synced-blocks"))?;
+
+        let rolling_files = RollingFiles {
+            name: format!("{} files", format.extension()),
+            format: Arc::new(format),
+            folder,
+            checkpoint_path: path.to_path_buf(),
+            contract_name: contract_name.to_string(),
+            table_event_name: table_event_name.to_string(),
+            event_name: event_name.to_string(),
+            max_blocks_per_file: rotation.max_blocks_per_file(),
+            max_file_size_bytes: rotation.max_file_size_bytes(),
+            rotate_daily: rotation.rotate_daily(),
+            files: Arc::new(Mutex::new(HashMap::new())),
+            upload,
+            pending_uploads: tokio::sync::Mutex::new(vec![]),
+            skip_up_to: Mutex::new(HashMap::new()),
+        };
+        rolling_files.recover().await?;
+
+        Ok(rolling_files)
+    }
+
+    /// If the rows need the block timestamp to know which day file they go in
+    pub fn rotates_daily(&self) -> bool {
+        self.rotate_daily
+    }
+
+    /// Files which were not finished before the last shutdown can not be read so they are removed
+    /// and indexed again, a file which was finished without its checkpoint being written moves
+    /// the checkpoint forward or is uploaded again and the manifest is rebuilt from the finished
+    /// files
+    async fn recover(&self) -> Result<(), RollingFileError> {
+        let extension = self.format.extension();
+
+        for entry in fs::read_dir(&self.folder)? {
+            let network_folder = entry?.path();
+            if !network_folder.is_dir() {
+                continue;
+            }
+
+            let in_progress_path =
+                network_folder.join(in_progress_file_name(&self.event_name, &extension));
+            if in_progress_path.exists() {
+                warn!(
+                    "{}::{} - removing unfinished file {} the blocks will be indexed again",
+                    self.contract_name,
+                    self.event_name,
+                    in_progress_path.display()
+                );
+                fs::remove_file(&in_progress_path)?;
+            }
+
+            let files = finished_files(&network_folder, &self.event_name, &extension)?;
+            if files.is_empty() {
+                continue;
+            }
+            write_manifest(&network_folder, &self.event_name, &extension)?;
+
+            let network =
+                network_folder.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            if self.upload.is_some() {
+                // the checkpoint only covers uploaded files so anything after it was not uploaded
+                let last_synced_block = get_last_synced_block_number_file(
+                    &self.checkpoint_path,
+                    &self.contract_name,
+                    &network,
+                    &self.event_name,
+                )
+                .await
+                .ok()
+                .flatten()
+                .unwrap_or_default();
+
+                let mut pending_uploads = self.pending_uploads.lock().await;
+                for file in
+                    files.into_iter().filter(|file| file.to_block > last_synced_block.as_u64())
+                {
+                    pending_uploads.push(FinishedFile {
+                        path: network_folder.join(&file.file),
+                        network: network.clone(),
+                        from_block: U64::from(file.from_block),
+                        to_block: U64::from(file.to_block),
+                    });
+                }
+            } else if let Some(to_block) = files.iter().map(|file| file.to_block).max() {
+                update_last_synced_block_number_file(
+                    &self.checkpoint_path,
+                    &self.contract_name,
+                    &network,
+                    &self.event_name,
+                    U64::from(to_block),
+                )
+                .await?;
+            }
+        }
+
+        if !self.pending_uploads.lock().await.is_empty() {
+            self.upload_pending(vec![]).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the finished files in block order and moves the checkpoint of each network to the
+    /// last uploaded one, a file which fails to upload stops its network until the next append
+    async fn upload_pending(&self, finished: Vec<FinishedFile>) -> Result<(), RollingFileError> {
+        let upload = match &self.upload {
+            Some(upload) => upload,
+            None => return Ok(()),
+        };
+
+        let mut pending_uploads = self.pending_uploads.lock().await;
+        pending_uploads.extend(finished);
+
+        let mut failed_networks: HashSet<String> = HashSet::new();
+        let mut uploaded_to_blocks: HashMap<String, U64> = HashMap::new();
+        let mut still_pending: Vec<FinishedFile> = vec![];
+        for file in pending_uploads.drain(..) {
+            if failed_networks.contains(&file.network) {
+                still_pending.push(file);
+                continue;
+            }
+
+            let key = upload.client.key(
+                &upload.indexer_name,
+                &self.contract_name,
+                &self.table_event_name,
+                &file.network,
+                &file.path.file_name().unwrap_or_default().to_string_lossy(),
+            );
+            match upload.client.upload_file(&key, &file.path).await {
+                Ok(()) => {
+                    info!(
+                        "{}::{} - uploaded file {} - network: {}",
+                        self.contract_name, self.event_name, key, file.network
+                    );
+                    if upload.client.delete_local_files() {
+                        if let Err(e) = fs::remove_file(&file.path) {
+                            warn!(
+                                "{}::{} - could not remove uploaded file {}: {}",
+                                self.contract_name,
+                                self.event_name,
+                                file.path.display(),
+                                e
+                            );
+                        }
+                    }
+                    uploaded_to_blocks.insert(file.network.clone(), file.to_block);
+                }
+                Err(e) => {
+                    error!(
+                        "{}::{} - could not upload file {} it will be retried with the next batch: {}",
+                        self.contract_name,
+                        self.event_name,
+                        file.path.display(),
+                        e
+                    );
+                    failed_networks.insert(file.network.clone());
+                    still_pending.push(file);
+                }
+            }
+        }
+        *pending_uploads = still_pending;
+
+        for (network, to_block) in uploaded_to_blocks {
+            self.upload_manifest(upload, &network, to_block).await;
+            update_last_synced_block_number_file(
+                &self.checkpoint_path,
+                &self.contract_name,
+                &network,
+                &self.event_name,
+                to_block,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Uploads the manifest with the files up to the last uploaded one, a failure is retried with
+    /// the next uploaded file
+    async fn upload_manifest(&self, upload: &RollingFileUpload, network: &str, to_block: U64) {
+        let mut manifest = match read_manifest(&self.folder.join(network), &self.event_name) {
+            Some(manifest) => manifest,
+            None => return,
+        };
+        manifest.files.retain(|file| file.to_block <= to_block.as_u64());
+
+        let key = upload.client.key(
+            &upload.indexer_name,
+            &self.contract_name,
+            &self.table_event_name,
+            network,
+            &manifest_file_name(&self.event_name),
+        );
+        let result = match serde_json::to_vec_pretty(&manifest) {
+            Ok(bytes) => upload.client.upload_bytes(&key, bytes).await.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = result {
+            error!(
+                "{}::{} - could not upload manifest {}: {}",
+                self.contract_name, self.event_name, key, e
+            );
+        }
+    }
+
+    /// Appends the rows of a block range, a range without rows still extends the file so the
+    /// checkpoint covers it
+    pub async fn append(
+        &self,
+        network: &str,
+        from_block: U64,
+        to_block: U64,
+        rows: Vec<RollingRow<F::Row>>,
+    ) -> Result<Vec<FinishedFile>, RollingFileError> {
+        let format = Arc::clone(&self.format);
+        let files = Arc::clone(&self.files);
+        let folder = self.folder.join(network);
+        let event_name = self.event_name.clone();
+        let network = network.to_string();
+        let max_blocks_per_file = self.max_blocks_per_file;
+        let max_file_size_bytes = self.max_file_size_bytes;
+        let rotate_daily = self.rotate_daily;
+
+        let finished = tokio::task::spawn_blocking(move || {
+            let extension = format.extension();
+            // a panic while holding the lock only loses the unfinished files
+            let mut files = files.lock().unwrap_or_else(|e| e.into_inner());
+            let mut finished: Vec<FinishedFile> = vec![];
+
+            for segment in split_by_day(from_block, to_block, rows, rotate_daily) {
+                let next_day = files.get(&network).is_some_and(|file| {
+                    file.day.is_some() && segment.day.is_some() && file.day != segment.day
+                });
+                if next_day {
+                    if let Some(file) = files.remove(&network) {
+                        finished.push(file.finish(&event_name, &extension)?);
+                    }
+                }
+
+                if !files.contains_key(&network) {
+                    fs::create_dir_all(&folder)?;
+                    let path = folder.join(in_progress_file_name(&event_name, &extension));
+                    let writer = format.create(File::create(&path)?)?;
+                    files.insert(
+                        network.clone(),
+                        OpenFile {
+                            writer,
+                            path,
+                            network: network.clone(),
+                            from_block: segment.from_block,
+                            to_block: segment.to_block,
+                            day: segment.day,
+                        },
+                    );
+                }
+
+                let file = files.get_mut(&network).expect("file was just opened");
+                file.writer.write(&segment.rows)?;
+                file.from_block = file.from_block.min(segment.from_block);
+                file.to_block = file.to_block.max(segment.to_block);
+                if file.day.is_none() {
+                    file.day = segment.day;
+                }
+
+                let blocks = (file.to_block - file.from_block).as_u64() + 1;
+                if blocks >= max_blocks_per_file || file.writer.size() >= max_file_size_bytes {
+                    if let Some(file) = files.remove(&network) {
+                        finished.push(file.finish(&event_name, &extension)?);
+                    }
+                }
+            }
+
+            if !finished.is_empty() {
+                write_manifest(&folder, &event_name, &extension)?;
+            }
+
+            Ok::<_, RollingFileError>(finished)
+        })
+        .await??;
+
+        for file in &finished {
+            info!(
+                "{}::{} - finished file {} - blocks: {} - {} - network: {}",
+                self.contract_name,
+                self.event_name,
+                file.path.display(),
+                file.from_block,
+                file.to_block,
+                file.network
+            );
+            if self.upload.is_none() {
+                update_last_synced_block_number_file(
+                    &self.checkpoint_path,
+                    &self.contract_name,
+                    &file.network,
+                    &self.event_name,
+                    file.to_block,
+                )
+                .await?;
+            }
+        }
+
+        // failed uploads are retried with each batch even when it finishes no file
+        if self.upload.is_some() {
+            self.upload_pending(finished.clone()).await?;
+        }
+
+        Ok(finished)
+    }
+}
+
+/// If the block is in a file finished before indexing resumed
+fn is_skipped(skip_up_to: Option<U64>, block_number: U64) -> bool {
+    skip_up_to.is_some_and(|value| block_number <= value)
+}
+
+/// The first block of a range which is not in a file yet
+fn first_unskipped_block(skip_up_to: Option<U64>, from_block: U64) -> U64 {
+    skip_up_to.map_or(from_block, |value| from_block.max(value + 1))
+}
+
+#[async_trait]
+impl<F: RollingFileFormat> StorageBackend for RollingFiles<F> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn stores(&self, event: &StorageEvent) -> bool {
+        event.contract_name == self.contract_name && event.event_name == self.event_name
+    }
+
+    /// The folders are created with the files
+    async fn create_schema(&self, _event: &StorageEvent) -> Result<(), StorageBackendError> {
+        Ok(())
+    }
+
+    async fn write_batch(&self, batch: StorageBatch<'_>) -> Result<(), StorageBackendError> {
+        let skip_up_to =
+            self.skip_up_to.lock().unwrap_or_else(|e| e.into_inner()).get(batch.network).copied();
+        if is_skipped(skip_up_to, batch.to_block) {
+            return Ok(());
+        }
+
+        let rows = batch
+            .rows
+            .iter()
+            .filter(|row| !is_skipped(skip_up_to, row.block_number))
+            .map(|row| RollingRow::new(row.block_number, row.block_timestamp, self.format.row(row)))
+            .collect();
+        self.append(
+            batch.network,
+            first_unskipped_block(skip_up_to, batch.from_block),
+            batch.to_block,
+            rows,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// The file still being written counts as it is finished before the process stops, a file
+    /// left unfinished by a crash is removed when the files are set up
+    async fn last_synced_block(
+        &self,
+        _event: &StorageEvent,
+        network: &str,
+    ) -> Result<Option<U64>, StorageBackendError> {
+        let last_synced_block = get_last_synced_block_number_file(
+            &self.checkpoint_path,
+            &self.contract_name,
+            network,
+            &self.event_name,
+        )
+        .await?
+        .filter(|value| !value.is_zero());
+        let open_to_block = self
+            .files
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(network)
+            .map(|file| file.to_block);
+
+        Ok(last_synced_block.max(open_to_block))
+    }
+
+    /// The checkpoint moves when a file is finished
+    async fn advance_checkpoint(
+        &self,
+        _event: &StorageEvent,
+        _network: &str,
+        _to_block: U64,
+    ) -> Result<(), StorageBackendError> {
+        Ok(())
+    }
+
+    /// Files are never changed once written so the blocks they already have are skipped instead
+    async fn delete_range(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        from_block: U64,
+        _to_block: U64,
+    ) -> Result<(), StorageBackendError> {
+        if let Some(last_synced_block) =
+            self.last_synced_block(event, network).await?.filter(|value| *value >= from_block)
+        {
+            self.skip_up_to
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(network.to_string(), last_synced_block);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simple_file_formatters::csv::CsvFormat;
+
+    fn row(block_number: u64, day: u32) -> RollingRow<u64> {
+        RollingRow {
+            block_number: U64::from(block_number),
+            day: NaiveDate::from_ymd_opt(2024, 1, day),
+            row: block_number,
+        }
+    }
+
+    #[test]
+    fn test_split_by_day() {
+        let rows = vec![row(10, 1), row(12, 1), row(15, 2), row(18, 3)];
+        let segments = split_by_day(U64::from(5), U64::from(20), rows, true);
+
+        let ranges: Vec<_> = segments
+            .iter()
+            .map(|segment| {
+                (segment.from_block.as_u64(), segment.to_block.as_u64(), segment.rows.len())
+            })
+            .collect();
+        assert_eq!(ranges, vec![(5, 14, 2), (15, 17, 1), (18, 20, 1)]);
+
+        let rows = vec![row(10, 1), row(15, 2)];
+        let segments = split_by_day(U64::from(5), U64::from(20), rows, false);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].rows.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_finished_file_name() {
+        assert_eq!(
+            parse_finished_file_name(
+                "Transfer",
+                "parquet",
+                "transfer-000000000001-000000000100.parquet"
+            ),
+            Some((None, U64::from(1), U64::from(100)))
+        );
+        assert_eq!(
+            parse_finished_file_name(
+                "Transfer",
+                "jsonl.gz",
+                "transfer-2024-01-05-000000000001-000000000100.jsonl.gz"
+            ),
+            Some((NaiveDate::from_ymd_opt(2024, 1, 5), U64::from(1), U64::from(100)))
+        );
+        assert_eq!(
+            parse_finished_file_name(
+                "Transfer",
+                "jsonl",
+                "transfer-000000000001-000000000100.jsonl.gz"
+            ),
+            None
+        );
+        assert_eq!(parse_finished_file_name("Transfer", "json", "transfer-manifest.json"), None);
+    }
+
+    async fn csv_files(path: &Path, rotation: FileRotationDetails) -> RollingFiles<CsvFormat> {
+        let format = CsvFormat::new(vec!["block_number".to_string(), "value".to_string()], false);
+        RollingFiles::new(path, &rotation, "Token", "Transfer", "Transfer", format, None)
+            .await
+            .unwrap()
+    }
+
+    fn csv_row(block_number: u64, timestamp: Option<u64>, value: &str) -> RollingRow<Vec<String>> {
+        RollingRow::new(
+            U64::from(block_number),
+            timestamp.map(U256::from),
+            vec![block_number.to_string(), value.to_string()],
+        )
+    }
+
+    async fn last_synced_block(path: &Path) -> Option<u64> {
+        get_last_synced_block_number_file(path, "Token", "ethereum", "Transfer")
+            .await
+            .unwrap()
+            .map(|block| block.as_u64())
+    }
+
+    fn file_names(finished: &[FinishedFile]) -> Vec<String> {
+        finished
+            .iter()
+            .map(|file| file.path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_files_roll_over_once_they_cover_max_blocks_per_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = FileRotationDetails { max_blocks_per_file: Some(100), ..Default::default() };
+        let files = csv_files(dir.path(), rotation).await;
+        let folder = dir.path().join("Token").join("transfer").join("ethereum");
+
+        let finished = files
+            .append("ethereum", 1.into(), 60.into(), vec![csv_row(10, None, "1")])
+            .await
+            .unwrap();
+        assert!(finished.is_empty());
+        assert!(folder.join(".transfer.csv.inprogress").exists());
+        assert_eq!(last_synced_block(dir.path()).await, None);
+
+        // a range without rows still counts towards the file
+        let finished = files.append("ethereum", 61.into(), 99.into(), vec![]).await.unwrap();
+        assert!(finished.is_empty());
+
+        let finished = files
+            .append("ethereum", 100.into(), 130.into(), vec![csv_row(120, None, "2")])
+            .await
+            .unwrap();
+        assert_eq!(file_names(&finished), ["transfer-000000000001-000000000130.csv"]);
+        assert!(!folder.join(".transfer.csv.inprogress").exists());
+        assert_eq!(
+            fs::read_to_string(&finished[0].path).unwrap(),
+            "block_number,value\n10,1\n120,2\n"
+        );
+        assert_eq!(last_synced_block(dir.path()).await, Some(130));
+    }
+
+    #[tokio::test]
+    async fn test_files_roll_over_once_they_reach_max_file_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = FileRotationDetails { max_file_size_mb: Some(1), ..Default::default() };
+        let files = csv_files(dir.path(), rotation).await;
+        let half_a_megabyte = "a".repeat(512 * 1024);
+
+        let finished = files
+            .append("ethereum", 1.into(), 10.into(), vec![csv_row(5, None, &half_a_megabyte)])
+            .await
+            .unwrap();
+        assert!(finished.is_empty());
+
+        let finished = files
+            .append("ethereum", 11.into(), 20.into(), vec![csv_row(15, None, &half_a_megabyte)])
+            .await
+            .unwrap();
+        assert_eq!(file_names(&finished), ["transfer-000000000001-000000000020.csv"]);
+        assert!(fs::metadata(&finished[0].path).unwrap().len() >= 1024 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_files_roll_over_when_the_day_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = FileRotationDetails { rotate_daily: Some(true), ..Default::default() };
+        let files = csv_files(dir.path(), rotation).await;
+        // 2024-01-01 23:00 and 2024-01-02 01:00 UTC
+        let (first_day, second_day) = (1_704_150_000, 1_704_157_200);
+
+        let finished = files
+            .append(
+                "ethereum",
+                1.into(),
+                50.into(),
+                vec![csv_row(10, Some(first_day), "1"), csv_row(30, Some(second_day), "2")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(file_names(&finished), ["transfer-2024-01-01-000000000001-000000000029.csv"]);
+        assert_eq!(last_synced_block(dir.path()).await, Some(29));
+
+        let finished = files
+            .append("ethereum", 51.into(), 60.into(), vec![csv_row(55, Some(second_day), "3")])
+            .await
+            .unwrap();
+        assert!(finished.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_the_manifest_lists_finished_files_in_block_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = FileRotationDetails { max_blocks_per_file: Some(10), ..Default::default() };
+        let files = csv_files(dir.path(), rotation).await;
+        let folder = dir.path().join("Token").join("transfer").join("ethereum");
+
+        let first = files
+            .append("ethereum", 1.into(), 10.into(), vec![csv_row(5, None, "1")])
+            .await
+            .unwrap();
+        files.append("ethereum", 11.into(), 20.into(), vec![csv_row(15, None, "2")]).await.unwrap();
+        // files removed after they are uploaded stay listed
+        fs::remove_file(&first[0].path).unwrap();
+        files.append("ethereum", 21.into(), 25.into(), vec![]).await.unwrap();
+        files.append("ethereum", 26.into(), 30.into(), vec![]).await.unwrap();
+
+        let manifest = read_manifest(&folder, "Transfer").unwrap();
+        assert_eq!(manifest.event_name, "Transfer");
+        assert_eq!(manifest.network, "ethereum");
+        let listed: Vec<(&str, u64, u64)> = manifest
+            .files
+            .iter()
+            .map(|file| (file.file.as_str(), file.from_block, file.to_block))
+            .collect();
+        assert_eq!(
+            listed,
+            [
+                ("transfer-000000000001-000000000010.csv", 1, 10),
+                ("transfer-000000000011-000000000020.csv", 11, 20),
+                ("transfer-000000000021-000000000030.csv", 21, 30),
+            ]
+        );
+        assert_eq!(
+            manifest.files[1].size_bytes,
+            fs::metadata(folder.join(&manifest.files[1].file)).unwrap().len()
+        );
+        assert!(manifest.files.iter().all(|file| file.day.is_none()));
+        assert!(!folder.join("transfer-manifest.json.tmp").exists());
+    }
+
+    #[tokio::test]
+    async fn test_unfinished_files_are_removed_on_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = FileRotationDetails { max_blocks_per_file: Some(10), ..Default::default() };
+        let files = csv_files(dir.path(), rotation.clone()).await;
+        let folder = dir.path().join("Token").join("transfer").join("ethereum");
+
+        files.append("ethereum", 1.into(), 10.into(), vec![csv_row(5, None, "1")]).await.unwrap();
+        files.append("ethereum", 11.into(), 15.into(), vec![csv_row(12, None, "2")]).await.unwrap();
+        drop(files);
+
+        csv_files(dir.path(), rotation).await;
+        assert!(!folder.join(".transfer.csv.inprogress").exists());
+        assert!(folder.join("transfer-000000000001-000000000010.csv").exists());
+        assert_eq!(last_synced_block(dir.path()).await, Some(10));
+    }
+
+    #[test]
+    fn test_blocks_up_to_the_files_resumed_before_are_skipped() {
+        let skip_up_to = Some(U64::from(100));
+
+        assert!(is_skipped(skip_up_to, U64::from(99)));
+        assert!(is_skipped(skip_up_to, U64::from(100)));
+        assert!(!is_skipped(skip_up_to, U64::from(101)));
+        assert!(!is_skipped(None, U64::from(1)));
+
+        assert_eq!(first_unskipped_block(skip_up_to, U64::from(50)), U64::from(101));
+        assert_eq!(first_unskipped_block(skip_up_to, U64::from(150)), U64::from(150));
+        assert_eq!(first_unskipped_block(None, U64::from(50)), U64::from(50));
+    }
+
+    fn storage_row(block_number: u64, value: &str) -> StorageRow {
+        StorageRow {
+            block_number: U64::from(block_number),
+            block_timestamp: None,
+            log_index: U256::zero(),
+            values: vec![],
+            text_values: vec![block_number.to_string(), value.to_string()],
+            event_data: serde_json::Value::Null,
+        }
+    }
+
+    async fn write_batch(files: &RollingFiles<CsvFormat>, from: u64, to: u64, rows: &[StorageRow]) {
+        files
+            .write_batch(StorageBatch {
+                event: &StorageEvent::new("Indexer", "Token", "Transfer"),
+                network: "ethereum",
+                from_block: U64::from(from),
+                to_block: U64::from(to),
+                column_names: &[],
+                rows,
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resuming_before_the_files_skips_the_blocks_they_already_have() {
+        let dir = tempfile::tempdir().unwrap();
+        let rotation = FileRotationDetails { max_blocks_per_file: Some(10), ..Default::default() };
+        let files = csv_files(dir.path(), rotation).await;
+        let event = StorageEvent::new("Indexer", "Token", "Transfer");
+        assert!(files.stores(&event));
+        assert!(!files.stores(&StorageEvent::new("Indexer", "Token", "Approval")));
+
+        write_batch(&files, 1, 10, &[storage_row(5, "1")]).await;
+        write_batch(&files, 11, 15, &[storage_row(12, "2")]).await;
+        // the open file counts as it is finished before the process stops
+        assert_eq!(files.last_synced_block(&event, "ethereum").await.unwrap(), Some(U64::from(15)));
+
+        // another store was behind so indexing resumes from block 6
+        files.delete_range(&event, "ethereum", U64::from(6), U64::from(100)).await.unwrap();
+        write_batch(&files, 6, 12, &[storage_row(8, "old"), storage_row(12, "old")]).await;
+        write_batch(&files, 13, 20, &[storage_row(18, "3")]).await;
+
+        let folder = dir.path().join("Token").join("transfer").join("ethereum");
+        assert_eq!(
+            fs::read_to_string(folder.join("transfer-000000000011-000000000020.csv")).unwrap(),
+            "block_number,value\n12,2\n18,3\n"
+        );
+        assert_eq!(files.last_synced_block(&event, "ethereum").await.unwrap(), Some(U64::from(20)));
+    }
+}
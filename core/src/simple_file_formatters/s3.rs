@@ -0,0 +1,262 @@
+use std::path::Path;
+
+use aws_config::{meta::region::RegionProviderChain, BehaviorVersion, Region};
+use aws_sdk_s3::{
+    config::{Builder, Credentials},
+    error::DisplayErrorContext,
+    primitives::{ByteStream, ByteStreamError},
+    Client,
+};
+use tracing::info;
+
+use crate::manifest::storage::S3Details;
+
+#[derive(thiserror::Error, Debug)]
+pub enum S3Error {
+    #[error("Could not reach bucket {0}: {1}")]
+    BucketNotReachable(String, String),
+
+    #[error("Could not read file {0}: {1}")]
+    ReadFile(String, ByteStreamError),
+
+    #[error("Could not upload {0}: {1}")]
+    Upload(String, String),
+}
+
+/// Uploads finished files to S3 or any S3 compatible object storage like MinIO
+#[derive(Debug)]
+pub struct S3Client {
+    client: Client,
+    bucket: String,
+    prefix: Option<String>,
+    delete_local_files: bool,
+}
+
+impl S3Client {
+    pub async fn new(details: &S3Details) -> Result<Self, S3Error> {
+        let region_provider =
+            RegionProviderChain::first_try(Region::new(details.aws_config.region.clone()));
+
+        let credentials_provider = Credentials::new(
+            &details.aws_config.access_key,
+            &details.aws_config.secret_key,
+            details.aws_config.session_token.clone(),
+            None,
+            "manual",
+        );
+
+        let config = aws_config::defaults(BehaviorVersion::latest())
+            .region(region_provider)
+            .credentials_provider(credentials_provider)
+            .load()
+            .await;
+
+        let mut builder = Builder::from(&config).force_path_style(details.force_path_style());
+        if let Some(endpoint) = &details.endpoint {
+            builder = builder.endpoint_url(endpoint);
+        }
+        let client = Client::from_conf(builder.build());
+
+        if let Err(e) = client.head_bucket().bucket(&details.bucket).send().await {
+            return Err(S3Error::BucketNotReachable(
+                details.bucket.clone(),
+                DisplayErrorContext(&e).to_string(),
+            ));
+        }
+        info!("Successfully connected to S3 bucket {}.", details.bucket);
+
+        Ok(S3Client {
+            client,
+            bucket: details.bucket.clone(),
+            prefix: details.prefix.clone(),
+            delete_local_files: details.delete_local_files(),
+        })
+    }
+
+    /// The key of a file, `{prefix}/{indexer}/{contract}/{event}/{network}/{file_name}`
+    pub fn key(
+        &self,
+        indexer_name: &str,
+        contract_name: &str,
+        event_name: &str,
+        network: &str,
+        file_name: &str,
+    ) -> String {
+        let key = format!(
+            "{}/{}/{}/{}/{}",
+            indexer_name,
+            contract_name,
+            event_name.to_lowercase(),
+            network,
+            file_name
+        );
+
+        match self.prefix.as_deref().map(|prefix| prefix.trim_matches('/')) {
+            Some(prefix) if !prefix.is_empty() => format!("{}/{}", prefix, key),
+            _ => key,
+        }
+    }
+
+    /// If the local copy of a file is removed once it is uploaded
+    pub fn delete_local_files(&self) -> bool {
+        self.delete_local_files
+    }
+
+    pub async fn upload_file(&self, key: &str, path: &Path) -> Result<(), S3Error> {
+        let body = ByteStream::from_path(path)
+            .await
+            .map_err(|e| S3Error::ReadFile(path.display().to_string(), e))?;
+
+        self.put(key, body).await
+    }
+
+    pub async fn upload_bytes(&self, key: &str, bytes: Vec<u8>) -> Result<(), S3Error> {
+        self.put(key, ByteStream::from(bytes)).await
+    }
+
+    async fn put(&self, key: &str, body: ByteStream) -> Result<(), S3Error> {
+        self.client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(key)
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| S3Error::Upload(key.to_string(), DisplayErrorContext(&e).to_string()))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use aws_config::retry::RetryConfig;
+    use ethers::types::U64;
+    use mockito::{mock, Matcher, Mock};
+
+    use super::*;
+    use crate::{
+        indexer::get_last_synced_block_number_file,
+        manifest::storage::FileRotationDetails,
+        simple_file_formatters::{
+            csv::CsvFormat,
+            rolling::{RollingFileUpload, RollingFiles, RollingRow},
+        },
+    };
+
+    fn s3_client(prefix: Option<&str>) -> S3Client {
+        let config = Builder::new()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("key", "secret", None, None, "test"))
+            .endpoint_url(mockito::server_url())
+            .force_path_style(true)
+            .retry_config(RetryConfig::disabled())
+            .build();
+
+        S3Client {
+            client: Client::from_conf(config),
+            bucket: "exports".to_string(),
+            prefix: prefix.map(|prefix| prefix.to_string()),
+            delete_local_files: true,
+        }
+    }
+
+    fn put(key: &str, status: usize) -> Mock {
+        mock("PUT", Matcher::Regex(format!("^/exports/{}($|\\?)", regex::escape(key))))
+            .with_status(status)
+            .create()
+    }
+
+    fn put_in_folder(folder: &str, status: usize) -> Mock {
+        mock("PUT", Matcher::Regex(format!("^/exports/{}", regex::escape(folder))))
+            .with_status(status)
+            .create()
+    }
+
+    async fn rolling_files(path: &Path) -> RollingFiles<CsvFormat> {
+        let rotation = FileRotationDetails { max_blocks_per_file: Some(10), ..Default::default() };
+        let upload = RollingFileUpload::new(&Arc::new(s3_client(None)), "indexer");
+        let format = CsvFormat::new(vec!["block_number".to_string()], false);
+
+        RollingFiles::new(path, &rotation, "Token", "Transfer", "Transfer", format, Some(upload))
+            .await
+            .unwrap()
+    }
+
+    fn row(block_number: u64) -> Vec<RollingRow<Vec<String>>> {
+        vec![RollingRow::new(U64::from(block_number), None, vec![block_number.to_string()])]
+    }
+
+    async fn last_synced_block(path: &Path, network: &str) -> Option<u64> {
+        get_last_synced_block_number_file(path, "Token", network, "Transfer")
+            .await
+            .unwrap()
+            .map(|block| block.as_u64())
+    }
+
+    #[test]
+    fn test_keys_are_grouped_by_indexer_contract_event_and_network() {
+        let key = |prefix: Option<&str>| {
+            s3_client(prefix).key("indexer", "Token", "Transfer", "ethereum", "transfer.csv")
+        };
+
+        assert_eq!(key(None), "indexer/Token/transfer/ethereum/transfer.csv");
+        assert_eq!(
+            key(Some("exports/raw/")),
+            "exports/raw/indexer/Token/transfer/ethereum/transfer.csv"
+        );
+        assert_eq!(key(Some("/")), "indexer/Token/transfer/ethereum/transfer.csv");
+    }
+
+    #[tokio::test]
+    async fn test_finished_files_are_uploaded_before_the_checkpoint_moves() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = rolling_files(dir.path()).await;
+        let file =
+            put("indexer/Token/transfer/ethereum/transfer-000000000001-000000000010.csv", 200)
+                .expect(1);
+        let manifest = put("indexer/Token/transfer/ethereum/transfer-manifest.json", 200).expect(1);
+
+        let finished = files.append("ethereum", 1.into(), 10.into(), row(5)).await.unwrap();
+
+        file.assert();
+        manifest.assert();
+        assert!(!finished[0].path.exists());
+        assert_eq!(last_synced_block(dir.path(), "ethereum").await, Some(10));
+    }
+
+    #[tokio::test]
+    async fn test_a_failed_upload_holds_back_its_network_until_it_is_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = rolling_files(dir.path()).await;
+        let ethereum = "indexer/Token/transfer/ethereum/";
+        // every append retries the failed file, the next file waits behind it so the checkpoint
+        // never skips a file
+        let failing =
+            put(&format!("{}transfer-000000000001-000000000010.csv", ethereum), 500).expect(3);
+        let waiting =
+            put(&format!("{}transfer-000000000011-000000000020.csv", ethereum), 200).expect(0);
+        let _base = put_in_folder("indexer/Token/transfer/base/", 200);
+
+        let first = files.append("ethereum", 1.into(), 10.into(), row(5)).await.unwrap();
+        let second = files.append("ethereum", 11.into(), 20.into(), row(15)).await.unwrap();
+        files.append("base", 1.into(), 10.into(), row(5)).await.unwrap();
+
+        failing.assert();
+        waiting.assert();
+        assert!(first[0].path.exists() && second[0].path.exists());
+        assert_eq!(last_synced_block(dir.path(), "ethereum").await, None);
+        assert_eq!(last_synced_block(dir.path(), "base").await, Some(10));
+        drop((failing, waiting));
+
+        let _ethereum = put_in_folder(ethereum, 200);
+        let finished = files.append("ethereum", 21.into(), 25.into(), row(22)).await.unwrap();
+
+        assert!(finished.is_empty());
+        assert!(!first[0].path.exists() && !second[0].path.exists());
+        assert_eq!(last_synced_block(dir.path(), "ethereum").await, Some(20));
+    }
+}
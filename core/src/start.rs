@@ -5,18 +5,29 @@ use tracing::{error, info};
 
 use crate::{
     api::{start_graphql_server, GraphqlOverrideSettings, StartGraphqlServerError},
-    database::postgres::{
-        client::PostgresConnectionError,
-        indexes::{ApplyPostgresIndexesError, PostgresIndexResult},
-        relationship::{ApplyAllRelationships, Relationship},
-        setup::{setup_postgres, SetupPostgresError},
+    database::{
+        postgres::{
+            client::PostgresConnectionError,
+            indexes::{ApplyPostgresIndexesError, PostgresIndexResult},
+            relationship::{ApplyAllRelationships, Relationship},
+            setup::{setup_postgres, SetupPostgresError},
+        },
+        sqlite::{
+            client::SqliteError,
+            indexes::{prepare_sqlite_indexes, PrepareSqliteIndexesError, SqliteIndexResult},
+            setup::{setup_sqlite, SetupSqliteError},
+        },
     },
     event::callback_registry::EventCallbackRegistry,
     indexer::{
         native_transfer::NativeTransferIndexer,
         no_code::{setup_no_code, SetupNoCodeError},
-        start::{initialize_database, start_indexing, StartIndexingError},
-        ContractEventDependencies, ContractEventDependenciesMapFromRelationshipsError,
+        retention::{RetentionError, RetentionPruner},
+        start::{
+            initialize_database, initialize_sqlite, initialize_storage_backends, start_indexing,
+            StartIndexingError,
+        },
+        EventDependencyGraph, EventDependencyGraphError,
     },
     initiate_shutdown,
     logger::mark_shutdown_started,
@@ -26,10 +37,14 @@ use crate::{
         yaml::{read_manifest, ReadManifestError},
     },
     setup_info_logger,
+    storage::StorageBackend,
 };
 
 pub struct IndexingDetails {
     pub registry: EventCallbackRegistry,
+    /// The stores events are written to, the built in storage is added to the ones a rust
+    /// project registers, see [`StorageBackend`]
+    pub storage_backends: Vec<Arc<dyn StorageBackend>>,
 }
 
 pub struct StartDetails<'a> {
@@ -55,6 +70,9 @@ pub enum StartRindexerError {
     #[error("Could not setup postgres: {0}")]
     SetupPostgresError(#[from] SetupPostgresError),
 
+    #[error("Could not setup sqlite: {0}")]
+    SetupSqliteError(#[from] SetupSqliteError),
+
     #[error("Could not start indexing: {0}")]
     CouldNotStartIndexing(#[from] StartIndexingError),
 
@@ -68,13 +86,20 @@ pub enum StartRindexerError {
     ApplyPostgresIndexesError(#[from] ApplyPostgresIndexesError),
 
     #[error("{0}")]
-    ContractEventDependenciesMapFromRelationshipsError(
-        #[from] ContractEventDependenciesMapFromRelationshipsError,
-    ),
+    EventDependencyGraphError(#[from] EventDependencyGraphError),
 
     #[error("{0}")]
     RelationshipsAndIndexersError(#[from] RelationshipsAndIndexersError),
 
+    #[error("Could not prepare sqlite indexes: {0}")]
+    PrepareSqliteIndexesError(#[from] PrepareSqliteIndexesError),
+
+    #[error("Could not apply sqlite indexes: {0}")]
+    ApplySqliteIndexesError(#[from] SqliteError),
+
+    #[error("Could not start the retention pruner: {0}")]
+    RetentionError(#[from] RetentionError),
+
     #[error("Shutdown handler failed with error: {0}")]
     ShutdownHandlerFailed(String),
 }
@@ -157,6 +182,20 @@ pub async fn start_rindexer(details: StartDetails<'_>) -> Result<(), StartRindex
                     setup_postgres(project_path, &manifest).await?;
                 }
 
+                // setup sqlite is already called in no-code startup
+                if manifest.project_type != ProjectType::NoCode && manifest.storage.sqlite_enabled()
+                {
+                    setup_sqlite(project_path, &manifest).await?;
+                }
+
+                // no-code projects register their built in storage for each event when set up
+                if manifest.project_type != ProjectType::NoCode {
+                    let mut storage_backends =
+                        initialize_storage_backends(&manifest, project_path).await?;
+                    storage_backends.append(&mut indexing_details.storage_backends);
+                    indexing_details.storage_backends = storage_backends;
+                }
+
                 let (relationships, postgres_indexes) = manifest
                     .storage
                     .create_relationships_and_indexes(
@@ -166,8 +205,23 @@ pub async fn start_rindexer(details: StartDetails<'_>) -> Result<(), StartRindex
                     )
                     .await?;
 
-                let mut dependencies: Vec<ContractEventDependencies> =
-                    ContractEventDependencies::parse(&manifest);
+                let sqlite = initialize_sqlite(&manifest, project_path)?;
+                let (sqlite_relationships, sqlite_indexes) = match (
+                    &sqlite,
+                    &manifest.storage.sqlite,
+                ) {
+                    (Some(sqlite), Some(details))
+                        if !manifest.storage.sqlite_disable_create_tables() =>
+                    {
+                        info!("Temp dropping sqlite indexes and relationships for historic indexing for speed reasons");
+                        prepare_sqlite_indexes(sqlite, project_path, &manifest, details).await?
+                    }
+                    _ => (vec![], vec![]),
+                };
+                let has_relationships =
+                    !relationships.is_empty() || !sqlite_relationships.is_empty();
+
+                let mut dependencies = EventDependencyGraph::parse(&manifest);
                 // let network_providers = CreateNetworkProvider::create(&manifest);
 
                 let database = initialize_database(&manifest).await?;
@@ -183,6 +237,13 @@ pub async fn start_rindexer(details: StartDetails<'_>) -> Result<(), StartRindex
                         tokio::spawn(async move {
                             native_transfer_indexer.start().await;
                         });
+
+                        if manifest.contracts.iter().any(|c| c.retention.is_some()) {
+                            let pruner =
+                                RetentionPruner::new(postgres.clone(), project_path, &manifest)
+                                    .await?;
+                            tokio::spawn(pruner.start());
+                        }
                     }
                 }
                 let processed_network_contracts = start_indexing(
@@ -190,8 +251,9 @@ pub async fn start_rindexer(details: StartDetails<'_>) -> Result<(), StartRindex
                     project_path,
                     &dependencies,
                     // we index all the historic data first before then applying FKs
-                    !relationships.is_empty(),
+                    has_relationships,
                     indexing_details.registry.complete(),
+                    &indexing_details.storage_backends,
                 )
                 .await?;
 
@@ -201,8 +263,11 @@ pub async fn start_rindexer(details: StartDetails<'_>) -> Result<(), StartRindex
                     "Applying indexes if any back to the database as historic resync is complete"
                 );
                 PostgresIndexResult::apply_indexes(postgres_indexes).await?;
+                if let Some(sqlite) = &sqlite {
+                    SqliteIndexResult::apply_indexes(sqlite, sqlite_indexes).await?;
+                }
 
-                if !relationships.is_empty() {
+                if has_relationships {
                     // TODO if graphql isn't up yet, and we apply this on graphql wont refresh we
                     // need to handle this
                     info!("Applying constraints relationships back to the database as historic resync is complete");
@@ -211,12 +276,14 @@ pub async fn start_rindexer(details: StartDetails<'_>) -> Result<(), StartRindex
                     if manifest.has_any_contracts_live_indexing() {
                         info!("Starting live indexing now relationship re-applied..");
 
-                        if dependencies.is_empty() {
-                            dependencies =
-                                ContractEventDependencies::map_from_relationships(&relationships)?;
-                        } else {
-                            info!("Manual dependency_events found, skipping auto-applying the dependency_events with the relationships");
-                        }
+                        // relationships need the linked event written first so merge them
+                        // into any manual dependency_events
+                        dependencies
+                            .merge(&EventDependencyGraph::from_relationships(&relationships));
+                        dependencies.merge(&EventDependencyGraph::from_relationships(
+                            &sqlite_relationships,
+                        ));
+                        dependencies.topological_order()?;
 
                         start_indexing(
                             &manifest,
@@ -226,6 +293,7 @@ pub async fn start_rindexer(details: StartDetails<'_>) -> Result<(), StartRindex
                             indexing_details
                                 .registry
                                 .reapply_after_historic(processed_network_contracts),
+                            &indexing_details.storage_backends,
                         )
                         .await
                         .map_err(StartRindexerError::CouldNotStartIndexing)?;
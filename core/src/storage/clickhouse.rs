@@ -0,0 +1,193 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::types::U64;
+
+use crate::{
+    database::postgres::generate::generate_event_table_full_name,
+    helpers::camel_to_snake,
+    storage::{StorageBackend, StorageBackendError, StorageBatch, StorageEvent},
+    ClickhouseClient,
+};
+
+/// Stores events in the tables `setup_clickhouse` creates from the ABI, named like the postgres
+/// ones, and keeps the last synced block in the `rindexer_internal` database
+pub struct ClickhouseStorageBackend {
+    client: Arc<ClickhouseClient>,
+    /// The only event stored and its table when the backend is for a single event
+    event: Option<(StorageEvent, String)>,
+}
+
+impl ClickhouseStorageBackend {
+    pub fn new(client: Arc<ClickhouseClient>) -> Self {
+        ClickhouseStorageBackend { client, event: None }
+    }
+
+    /// Only stores the event in the table given, versioned events share the table of the event
+    /// of the current ABI
+    pub fn for_event(mut self, event: &StorageEvent, table_name: &str) -> Self {
+        self.event = Some((event.clone(), table_name.to_string()));
+        self
+    }
+
+    fn internal_table_name(event: &StorageEvent) -> String {
+        format!(
+            "rindexer_internal.{}_{}_{}",
+            camel_to_snake(&event.indexer_name),
+            camel_to_snake(&event.contract_name),
+            camel_to_snake(&event.event_name)
+        )
+    }
+
+    fn event_table_name(&self, event: &StorageEvent) -> String {
+        match &self.event {
+            Some((_, table_name)) => table_name.clone(),
+            None => generate_event_table_full_name(
+                &event.indexer_name,
+                &event.contract_name,
+                &event.event_name,
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for ClickhouseStorageBackend {
+    fn name(&self) -> &str {
+        "clickhouse"
+    }
+
+    fn stores(&self, event: &StorageEvent) -> bool {
+        self.event.as_ref().is_none_or(|(stored_event, _)| stored_event == event)
+    }
+
+    /// The event tables need the ABI so are created by `setup_clickhouse`, this makes sure the
+    /// table holding the last synced blocks exists
+    async fn create_schema(&self, event: &StorageEvent) -> Result<(), StorageBackendError> {
+        self.client
+            .execute_all(&[
+                "CREATE DATABASE IF NOT EXISTS rindexer_internal".to_string(),
+                format!(
+                    "CREATE TABLE IF NOT EXISTS {} (\
+                        `network` String, \
+                        `last_synced_block` UInt64\
+                    ) ENGINE = ReplacingMergeTree(last_synced_block) \
+                    ORDER BY network",
+                    Self::internal_table_name(event)
+                ),
+            ])
+            .await?;
+
+        Ok(())
+    }
+
+    async fn write_batch(&self, batch: StorageBatch<'_>) -> Result<(), StorageBackendError> {
+        if batch.rows.is_empty() {
+            return Ok(());
+        }
+
+        let data: Vec<_> = batch.rows.iter().map(|row| row.values.clone()).collect();
+        self.client
+            .insert_bulk(&self.event_table_name(batch.event), batch.column_names, &data)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn last_synced_block(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+    ) -> Result<Option<U64>, StorageBackendError> {
+        let rows = self
+            .client
+            .query(&format!(
+                "SELECT max(last_synced_block) AS last_synced_block FROM {} WHERE network = '{}'",
+                Self::internal_table_name(event),
+                network
+            ))
+            .await?;
+
+        // UInt64 is quoted in JSON by default
+        let last_synced_block = rows
+            .first()
+            .and_then(|row| row.get("last_synced_block"))
+            .and_then(|value| match value {
+                serde_json::Value::String(value) => value.parse::<u64>().ok(),
+                value => value.as_u64(),
+            })
+            .filter(|value| *value != 0)
+            .map(U64::from);
+
+        Ok(last_synced_block)
+    }
+
+    /// The highest block wins when the parts merge and reads take the max so a lower block is
+    /// ignored
+    async fn advance_checkpoint(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        to_block: U64,
+    ) -> Result<(), StorageBackendError> {
+        self.client
+            .execute(&format!(
+                "INSERT INTO {} (network, last_synced_block) VALUES ('{}', {})",
+                Self::internal_table_name(event),
+                network,
+                to_block
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Deletes are mutations which rewrite the parts so the rows are counted first, a rust
+    /// project which does not create the event tables has nothing to delete
+    async fn delete_range(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<(), StorageBackendError> {
+        let table_name = self.event_table_name(event);
+        let (database, table) = table_name.split_once('.').unwrap_or(("default", &table_name));
+        let tables = self
+            .client
+            .query(&format!(
+                "SELECT name FROM system.tables WHERE database = '{}' AND name = '{}'",
+                database, table
+            ))
+            .await?;
+        if tables.is_empty() {
+            return Ok(());
+        }
+
+        let condition = format!(
+            "network = '{}' AND block_number >= {} AND block_number <= {}",
+            network, from_block, to_block
+        );
+
+        let rows = self
+            .client
+            .query(&format!("SELECT count() AS rows FROM {} WHERE {}", table_name, condition))
+            .await?;
+        let has_rows =
+            rows.first().and_then(|row| row.get("rows")).is_some_and(|value| match value {
+                serde_json::Value::String(value) => value != "0",
+                value => value.as_u64().is_some_and(|value| value > 0),
+            });
+
+        if has_rows {
+            self.client
+                .execute(&format!(
+                    "ALTER TABLE {} DELETE WHERE {} SETTINGS mutations_sync = 1",
+                    table_name, condition
+                ))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+use ethers::types::U64;
+use tokio::sync::Mutex;
+
+use crate::{
+    indexer::{get_last_synced_block_number_file, update_last_synced_block_number_file},
+    storage::{StorageBackend, StorageBackendError, StorageBatch, StorageEvent},
+    AsyncCsvAppender,
+};
+
+/// Stores each event in a `{contract_name}-{event_name}.csv` file under the contract folder and
+/// keeps the last synced block in its `last-synced-blocks` folder
+pub struct CsvStorageBackend {
+    path: PathBuf,
+    /// The only event stored and the file it is appended to when the backend is for a single
+    /// event
+    event: Option<(StorageEvent, Arc<AsyncCsvAppender>)>,
+    appenders: Mutex<HashMap<PathBuf, Arc<AsyncCsvAppender>>>,
+}
+
+impl CsvStorageBackend {
+    /// The folder the csv files are written to
+    pub fn new(path: &Path) -> Self {
+        CsvStorageBackend {
+            path: path.to_path_buf(),
+            event: None,
+            appenders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Only stores the event in the file of the appender, versioned events share the file of the
+    /// event of the current ABI so share its appender
+    pub fn for_event(mut self, event: &StorageEvent, appender: Arc<AsyncCsvAppender>) -> Self {
+        self.event = Some((event.clone(), appender));
+        self
+    }
+
+    fn file_path(&self, event: &StorageEvent) -> PathBuf {
+        self.path
+            .join(&event.contract_name)
+            .join(format!("{}-{}.csv", event.contract_name, event.event_name).to_lowercase())
+    }
+
+    async fn appender(&self, event: &StorageEvent) -> Arc<AsyncCsvAppender> {
+        if let Some((_, appender)) = &self.event {
+            return Arc::clone(appender);
+        }
+
+        let path = self.file_path(event);
+        let mut appenders = self.appenders.lock().await;
+        let appender = appenders
+            .entry(path.clone())
+            .or_insert_with(|| Arc::new(AsyncCsvAppender::new(&path.to_string_lossy())));
+
+        Arc::clone(appender)
+    }
+}
+
+#[async_trait]
+impl StorageBackend for CsvStorageBackend {
+    fn name(&self) -> &str {
+        "csv"
+    }
+
+    fn stores(&self, event: &StorageEvent) -> bool {
+        self.event.as_ref().is_none_or(|(stored_event, _)| stored_event == event)
+    }
+
+    async fn create_schema(&self, event: &StorageEvent) -> Result<(), StorageBackendError> {
+        fs::create_dir_all(self.path.join(&event.contract_name).join("last-synced-blocks"))?;
+
+        Ok(())
+    }
+
+    /// A file of a single event gets its headers when it is set up, any other file gets them when
+    /// it is created
+    async fn write_batch(&self, batch: StorageBatch<'_>) -> Result<(), StorageBackendError> {
+        if batch.rows.is_empty() {
+            return Ok(());
+        }
+
+        let appender = self.appender(batch.event).await;
+        if self.event.is_none() && !self.file_path(batch.event).exists() {
+            appender.append_header(batch.column_names.to_vec()).await?;
+        }
+
+        appender
+            .append_bulk(batch.rows.iter().map(|row| row.text_values.clone()).collect())
+            .await?;
+
+        Ok(())
+    }
+
+    /// Files written before the csv kept its own checkpoint next to a database resume after
+    /// their last row
+    async fn last_synced_block(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+    ) -> Result<Option<U64>, StorageBackendError> {
+        let last_synced_block = get_last_synced_block_number_file(
+            &self.path,
+            &event.contract_name,
+            network,
+            &event.event_name,
+        )
+        .await?;
+
+        match last_synced_block {
+            Some(last_synced_block) => Ok(Some(last_synced_block).filter(|value| !value.is_zero())),
+            None => Ok(self.appender(event).await.last_block(network).await?),
+        }
+    }
+
+    async fn advance_checkpoint(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        to_block: U64,
+    ) -> Result<(), StorageBackendError> {
+        update_last_synced_block_number_file(
+            &self.path,
+            &event.contract_name,
+            network,
+            &event.event_name,
+            to_block,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Rewrites the file without the rows of the range, rows are matched on the `network` and
+    /// `block_number` columns so a file without headers is left as it is
+    async fn delete_range(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<(), StorageBackendError> {
+        self.appender(event).await.remove_block_range(network, from_block, to_block).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+    use serde_json::Value;
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::storage::StorageRow;
+
+    fn event() -> StorageEvent {
+        StorageEvent::new("Indexer", "Token", "Transfer")
+    }
+
+    fn row(network: &str, block_number: u64) -> StorageRow {
+        StorageRow {
+            block_number: U64::from(block_number),
+            block_timestamp: None,
+            log_index: U256::zero(),
+            values: vec![],
+            text_values: vec![network.to_string(), block_number.to_string()],
+            event_data: Value::Null,
+        }
+    }
+
+    async fn write_rows(backend: &CsvStorageBackend, network: &str, blocks: &[u64]) {
+        let column_names = vec!["network".to_string(), "block_number".to_string()];
+        let rows: Vec<StorageRow> = blocks.iter().map(|block| row(network, *block)).collect();
+        backend
+            .write_batch(StorageBatch {
+                event: &event(),
+                network,
+                from_block: U64::from(blocks[0]),
+                to_block: U64::from(blocks[blocks.len() - 1]),
+                column_names: &column_names,
+                rows: &rows,
+            })
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_delete_range_only_removes_the_rows_of_the_network_in_the_range() {
+        let dir = tempdir().unwrap();
+        let backend = CsvStorageBackend::new(dir.path());
+        backend.create_schema(&event()).await.unwrap();
+        write_rows(&backend, "ethereum", &[10, 20, 30]).await;
+        write_rows(&backend, "base", &[20]).await;
+
+        backend.delete_range(&event(), "ethereum", U64::from(20), U64::from(30)).await.unwrap();
+
+        let contents =
+            fs::read_to_string(dir.path().join("Token").join("token-transfer.csv")).unwrap();
+        assert_eq!(contents, "network,block_number\nethereum,10\nbase,20\n");
+    }
+
+    #[tokio::test]
+    async fn test_a_file_without_a_checkpoint_resumes_after_its_last_row() {
+        let dir = tempdir().unwrap();
+        let backend = CsvStorageBackend::new(dir.path());
+        backend.create_schema(&event()).await.unwrap();
+
+        assert_eq!(backend.last_synced_block(&event(), "ethereum").await.unwrap(), None);
+
+        write_rows(&backend, "ethereum", &[10, 30, 20]).await;
+        write_rows(&backend, "base", &[50]).await;
+        assert_eq!(
+            backend.last_synced_block(&event(), "ethereum").await.unwrap(),
+            Some(U64::from(30))
+        );
+
+        backend.advance_checkpoint(&event(), "ethereum", U64::from(40)).await.unwrap();
+        assert_eq!(
+            backend.last_synced_block(&event(), "ethereum").await.unwrap(),
+            Some(U64::from(40))
+        );
+    }
+}
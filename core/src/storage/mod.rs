@@ -0,0 +1,143 @@
+mod clickhouse;
+mod csv;
+mod postgres;
+mod sqlite;
+
+use async_trait::async_trait;
+pub use clickhouse::ClickhouseStorageBackend;
+pub use csv::CsvStorageBackend;
+use ethers::types::{U256, U64};
+pub use postgres::PostgresStorageBackend;
+use serde_json::Value;
+pub use sqlite::SqliteStorageBackend;
+
+use crate::{
+    database::{
+        clickhouse::client::ClickhouseError,
+        postgres::client::{BulkInsertPostgresError, PostgresError},
+        sqlite::client::SqliteError,
+    },
+    indexer::UpdateLastSyncedBlockNumberFile,
+    simple_file_formatters::rolling::RollingFileError,
+    EthereumSqlTypeWrapper,
+};
+
+#[derive(thiserror::Error, Debug)]
+pub enum StorageBackendError {
+    #[error("{0}")]
+    Postgres(#[from] PostgresError),
+
+    #[error("{0}")]
+    PostgresBulkInsert(#[from] BulkInsertPostgresError),
+
+    #[error("{0}")]
+    Clickhouse(#[from] ClickhouseError),
+
+    #[error("{0}")]
+    Sqlite(#[from] SqliteError),
+
+    #[error("CSV error: {0}")]
+    Csv(#[from] ::csv::Error),
+
+    #[error("{0}")]
+    RollingFile(#[from] RollingFileError),
+
+    #[error("File IO error: {0}")]
+    FileIo(#[from] std::io::Error),
+
+    #[error("{0}")]
+    LastSyncedBlockFile(#[from] UpdateLastSyncedBlockNumberFile),
+
+    #[error("{0}")]
+    Custom(String),
+}
+
+/// The event a backend stores, the same names the built in storage uses for its tables and files
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageEvent {
+    pub indexer_name: String,
+    pub contract_name: String,
+    pub event_name: String,
+}
+
+impl StorageEvent {
+    pub fn new(indexer_name: &str, contract_name: &str, event_name: &str) -> Self {
+        StorageEvent {
+            indexer_name: indexer_name.to_string(),
+            contract_name: contract_name.to_string(),
+            event_name: event_name.to_string(),
+        }
+    }
+}
+
+/// A row of an event with the typed values for databases, the same values as text for files and
+/// the event as JSON with its transaction information
+#[derive(Debug, Clone)]
+pub struct StorageRow {
+    pub block_number: U64,
+    pub block_timestamp: Option<U256>,
+    pub log_index: U256,
+    pub values: Vec<EthereumSqlTypeWrapper>,
+    pub text_values: Vec<String>,
+    pub event_data: Value,
+}
+
+/// The rows of an event found in a block range of a network, a range without rows is still
+/// written so stores which track ranges cover it
+#[derive(Debug)]
+pub struct StorageBatch<'a> {
+    pub event: &'a StorageEvent,
+    pub network: &'a str,
+    pub from_block: U64,
+    pub to_block: U64,
+    pub column_names: &'a [String],
+    pub rows: &'a [StorageRow],
+}
+
+/// A store events are written to. The storage set in the yaml is made of the built in backends
+/// and rust projects can register their own. The backends storing an event are asked for their
+/// last synced block when indexing starts, indexing resumes after the lowest one of them and
+/// anything a backend holds from there on is deleted so a half written batch is never stored
+/// twice. Their checkpoint is advanced after the batch is written.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// The name used in logs
+    fn name(&self) -> &str;
+
+    /// If the backend stores the event, a backend which does not is left out of resuming it
+    fn stores(&self, _event: &StorageEvent) -> bool {
+        true
+    }
+
+    /// Creates anything the event needs before it is indexed, it is called on every start
+    async fn create_schema(&self, event: &StorageEvent) -> Result<(), StorageBackendError>;
+
+    /// Writes the rows of a batch, no-code projects call it for every batch and the handlers of
+    /// a rust project call it themselves
+    async fn write_batch(&self, batch: StorageBatch<'_>) -> Result<(), StorageBackendError>;
+
+    /// The last block of the network which has been fully written, `None` to start from the
+    /// start block
+    async fn last_synced_block(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+    ) -> Result<Option<U64>, StorageBackendError>;
+
+    /// Moves the last synced block forward, a lower block than the current one is ignored
+    async fn advance_checkpoint(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        to_block: U64,
+    ) -> Result<(), StorageBackendError>;
+
+    /// Removes the rows of the network in the block range, both ends included
+    async fn delete_range(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<(), StorageBackendError>;
+}
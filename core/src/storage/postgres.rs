@@ -0,0 +1,303 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::types::U64;
+use rust_decimal::Decimal;
+
+use crate::{
+    database::postgres::generate::generate_event_table_full_name,
+    helpers::camel_to_snake,
+    indexer::{Rollups, TableUpdates},
+    storage::{StorageBackend, StorageBackendError, StorageBatch, StorageEvent},
+    EthereumSqlTypeWrapper, PostgresClient,
+};
+
+/// Stores events in the tables `setup_postgres` creates from the ABI and keeps the last synced
+/// block in the `rindexer_internal` schema
+pub struct PostgresStorageBackend {
+    client: Arc<PostgresClient>,
+    /// The only event stored and its table when the backend is for a single event
+    event: Option<(StorageEvent, String)>,
+    table_updates: TableUpdates,
+    rollups: Rollups,
+}
+
+impl PostgresStorageBackend {
+    pub fn new(client: Arc<PostgresClient>) -> Self {
+        PostgresStorageBackend {
+            client,
+            event: None,
+            table_updates: TableUpdates::default(),
+            rollups: Rollups::default(),
+        }
+    }
+
+    /// Only stores the event in the table given, versioned events share the table of the event
+    /// of the current ABI
+    pub fn for_event(mut self, event: &StorageEvent, table_name: &str) -> Self {
+        self.event = Some((event.clone(), table_name.to_string()));
+        self
+    }
+
+    /// Updates the tables and rollups derived from the event in the same transaction as its rows
+    pub fn with_derived_tables(mut self, table_updates: TableUpdates, rollups: Rollups) -> Self {
+        self.table_updates = table_updates;
+        self.rollups = rollups;
+        self
+    }
+
+    fn internal_table_name(event: &StorageEvent) -> String {
+        format!(
+            "rindexer_internal.{}_{}_{}",
+            camel_to_snake(&event.indexer_name),
+            camel_to_snake(&event.contract_name),
+            camel_to_snake(&event.event_name)
+        )
+    }
+
+    fn event_table_name(&self, event: &StorageEvent) -> String {
+        match &self.event {
+            Some((_, table_name)) => table_name.clone(),
+            None => generate_event_table_full_name(
+                &event.indexer_name,
+                &event.contract_name,
+                &event.event_name,
+            ),
+        }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for PostgresStorageBackend {
+    fn name(&self) -> &str {
+        "postgres"
+    }
+
+    fn stores(&self, event: &StorageEvent) -> bool {
+        self.event.as_ref().is_none_or(|(stored_event, _)| stored_event == event)
+    }
+
+    /// The event tables need the ABI so are created by `setup_postgres`, this makes sure the
+    /// table holding the last synced blocks exists
+    async fn create_schema(&self, event: &StorageEvent) -> Result<(), StorageBackendError> {
+        self.client
+            .batch_execute(&format!(
+                r#"CREATE SCHEMA IF NOT EXISTS rindexer_internal;
+                CREATE TABLE IF NOT EXISTS {} ("network" TEXT PRIMARY KEY, "last_synced_block" NUMERIC);"#,
+                Self::internal_table_name(event)
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn write_batch(&self, batch: StorageBatch<'_>) -> Result<(), StorageBackendError> {
+        let table_name = self.event_table_name(batch.event);
+        let data: Vec<Vec<EthereumSqlTypeWrapper>> =
+            batch.rows.iter().map(|row| row.values.clone()).collect();
+
+        let mut statements: Vec<(&str, Vec<EthereumSqlTypeWrapper>)> = Vec::new();
+        for row in batch.rows {
+            statements.extend(self.table_updates.statements(
+                batch.network,
+                row.block_number,
+                row.log_index.as_u64(),
+                &row.event_data,
+            ));
+
+            if let Some(block_timestamp) = row.block_timestamp {
+                statements.extend(self.rollups.statements(
+                    batch.network,
+                    row.block_number,
+                    row.log_index.as_u64(),
+                    block_timestamp.as_u64(),
+                    &row.event_data,
+                ));
+            }
+        }
+
+        // derived tables are updated in the same transaction as the event insert
+        if !statements.is_empty() {
+            self.client
+                .insert_bulk_with_statements(&table_name, batch.column_names, &data, &statements)
+                .await?;
+        } else if data.len() > 100 {
+            // anything over 100 events is considered bulk and goes the COPY route
+            let column_types: Vec<_> = data[0].iter().map(|value| value.to_type()).collect();
+            self.client
+                .bulk_insert_via_copy(&table_name, batch.column_names, &column_types, &data)
+                .await?;
+        } else if !data.is_empty() {
+            self.client.bulk_insert(&table_name, batch.column_names, &data).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn last_synced_block(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+    ) -> Result<Option<U64>, StorageBackendError> {
+        let row = self
+            .client
+            .query_one_or_none(
+                &format!(
+                    "SELECT last_synced_block FROM {} WHERE network = $1",
+                    Self::internal_table_name(event)
+                ),
+                &[&network],
+            )
+            .await?;
+
+        let last_synced_block = row
+            .and_then(|row| row.get::<_, Option<Decimal>>("last_synced_block"))
+            .and_then(|value| U64::from_dec_str(&value.to_string()).ok())
+            .filter(|value| !value.is_zero());
+
+        Ok(last_synced_block)
+    }
+
+    async fn advance_checkpoint(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        to_block: U64,
+    ) -> Result<(), StorageBackendError> {
+        self.client
+            .execute(
+                &format!(
+                    r#"INSERT INTO {} AS checkpoint ("network", "last_synced_block") VALUES ($2, $1)
+                    ON CONFLICT ("network") DO UPDATE SET last_synced_block = $1
+                    WHERE $1 > checkpoint.last_synced_block"#,
+                    Self::internal_table_name(event)
+                ),
+                &[&EthereumSqlTypeWrapper::U64(to_block), &network],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// The derived tables are not rolled back as they skip the events they already applied, a
+    /// rust project which does not create the event tables has nothing to delete
+    async fn delete_range(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<(), StorageBackendError> {
+        let table_name = self.event_table_name(event);
+        let table_exists: bool = self
+            .client
+            .query_one("SELECT to_regclass($1) IS NOT NULL AS table_exists", &[&table_name])
+            .await?
+            .get("table_exists");
+        if !table_exists {
+            return Ok(());
+        }
+
+        self.client
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE network = $1 AND block_number >= $2 AND block_number <= $3",
+                    table_name
+                ),
+                &[
+                    &network,
+                    &EthereumSqlTypeWrapper::U64(from_block),
+                    &EthereumSqlTypeWrapper::U64(to_block),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::U256;
+    use serde_json::Value;
+
+    use super::*;
+    use crate::storage::StorageRow;
+
+    fn row(block_number: u64) -> StorageRow {
+        StorageRow {
+            block_number: U64::from(block_number),
+            block_timestamp: None,
+            log_index: U256::zero(),
+            values: vec![
+                EthereumSqlTypeWrapper::String("ethereum".to_string()),
+                EthereumSqlTypeWrapper::U64(U64::from(block_number)),
+            ],
+            text_values: vec![],
+            event_data: Value::Null,
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs a postgres database at DATABASE_URL"]
+    async fn test_checkpoints_only_move_forward_and_ranges_are_deleted() {
+        let client = Arc::new(PostgresClient::new().await.unwrap());
+        let event = StorageEvent::new("RindexerTest", "Token", "Transfer");
+        let table_name =
+            generate_event_table_full_name(&event.indexer_name, &event.contract_name, "Transfer");
+        client
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS rindexer_test_token CASCADE; \
+                CREATE SCHEMA rindexer_test_token; \
+                CREATE TABLE {table_name} (network TEXT NOT NULL, block_number NUMERIC NOT NULL); \
+                DROP TABLE IF EXISTS {};",
+                PostgresStorageBackend::internal_table_name(&event)
+            ))
+            .await
+            .unwrap();
+        let backend = PostgresStorageBackend::new(Arc::clone(&client));
+        backend.create_schema(&event).await.unwrap();
+
+        assert_eq!(backend.last_synced_block(&event, "ethereum").await.unwrap(), None);
+        let column_names = vec!["network".to_string(), "block_number".to_string()];
+        let rows = [row(10), row(20), row(30)];
+        backend
+            .write_batch(StorageBatch {
+                event: &event,
+                network: "ethereum",
+                from_block: U64::from(1),
+                to_block: U64::from(30),
+                column_names: &column_names,
+                rows: &rows,
+            })
+            .await
+            .unwrap();
+        backend.advance_checkpoint(&event, "ethereum", U64::from(30)).await.unwrap();
+        backend.advance_checkpoint(&event, "ethereum", U64::from(25)).await.unwrap();
+        assert_eq!(
+            backend.last_synced_block(&event, "ethereum").await.unwrap(),
+            Some(U64::from(30))
+        );
+
+        backend.delete_range(&event, "ethereum", U64::from(20), U64::from(40)).await.unwrap();
+        let blocks: Vec<String> = client
+            .query(&format!("SELECT block_number::TEXT FROM {table_name}"), &[])
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(blocks, ["10"]);
+
+        // a rust project may not create the event tables
+        backend
+            .delete_range(
+                &StorageEvent::new("RindexerTest", "Token", "Approval"),
+                "ethereum",
+                U64::from(1),
+                U64::from(40),
+            )
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ethers::types::U64;
+use rusqlite::types::Value;
+
+use crate::{
+    database::{
+        postgres::generate::generate_event_table_full_name,
+        sqlite::generate::{generate_internal_event_table_name_sqlite, sqlite_table_name},
+    },
+    storage::{StorageBackend, StorageBackendError, StorageBatch, StorageEvent},
+    SqliteClient,
+};
+
+/// Stores events in the tables `setup_sqlite` creates from the ABI and keeps the last synced
+/// block in the `rindexer_internal` tables
+pub struct SqliteStorageBackend {
+    client: Arc<SqliteClient>,
+    /// The only event stored and its table when the backend is for a single event
+    event: Option<(StorageEvent, String)>,
+}
+
+impl SqliteStorageBackend {
+    pub fn new(client: Arc<SqliteClient>) -> Self {
+        SqliteStorageBackend { client, event: None }
+    }
+
+    /// Only stores the event in the table given, versioned events share the table of the event
+    /// of the current ABI
+    pub fn for_event(mut self, event: &StorageEvent, table_name: &str) -> Self {
+        self.event = Some((event.clone(), table_name.to_string()));
+        self
+    }
+
+    fn internal_table_name(event: &StorageEvent) -> String {
+        generate_internal_event_table_name_sqlite(
+            &event.indexer_name,
+            &event.contract_name,
+            &event.event_name,
+        )
+    }
+
+    fn event_table_name(&self, event: &StorageEvent) -> String {
+        match &self.event {
+            Some((_, table_name)) => table_name.clone(),
+            None => sqlite_table_name(&generate_event_table_full_name(
+                &event.indexer_name,
+                &event.contract_name,
+                &event.event_name,
+            )),
+        }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for SqliteStorageBackend {
+    fn name(&self) -> &str {
+        "sqlite"
+    }
+
+    fn stores(&self, event: &StorageEvent) -> bool {
+        self.event.as_ref().is_none_or(|(stored_event, _)| stored_event == event)
+    }
+
+    /// The event tables need the ABI so are created by `setup_sqlite`, this makes sure the table
+    /// holding the last synced blocks exists
+    async fn create_schema(&self, event: &StorageEvent) -> Result<(), StorageBackendError> {
+        self.client
+            .execute_batch(&format!(
+                "CREATE TABLE IF NOT EXISTS {} (network TEXT PRIMARY KEY, last_synced_block INTEGER);",
+                Self::internal_table_name(event)
+            ))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn write_batch(&self, batch: StorageBatch<'_>) -> Result<(), StorageBackendError> {
+        let data: Vec<_> = batch.rows.iter().map(|row| row.values.clone()).collect();
+        self.client
+            .bulk_insert(&self.event_table_name(batch.event), batch.column_names, &data)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn last_synced_block(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+    ) -> Result<Option<U64>, StorageBackendError> {
+        let rows = self
+            .client
+            .query(
+                &format!(
+                    "SELECT last_synced_block FROM {} WHERE network = ?1",
+                    Self::internal_table_name(event)
+                ),
+                vec![Value::Text(network.to_string())],
+            )
+            .await?;
+
+        let last_synced_block = match rows.first().and_then(|row| row.first()) {
+            Some(Value::Integer(last_synced_block)) if *last_synced_block > 0 => {
+                Some(U64::from(*last_synced_block as u64))
+            }
+            _ => None,
+        };
+
+        Ok(last_synced_block)
+    }
+
+    async fn advance_checkpoint(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        to_block: U64,
+    ) -> Result<(), StorageBackendError> {
+        self.client
+            .execute(
+                &format!(
+                    "INSERT INTO {} (network, last_synced_block) VALUES (?2, ?1) \
+                    ON CONFLICT (network) DO UPDATE SET last_synced_block = ?1 \
+                    WHERE ?1 > last_synced_block",
+                    Self::internal_table_name(event)
+                ),
+                vec![Value::Integer(to_block.as_u64() as i64), Value::Text(network.to_string())],
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// A rust project which does not create the event tables has nothing to delete
+    async fn delete_range(
+        &self,
+        event: &StorageEvent,
+        network: &str,
+        from_block: U64,
+        to_block: U64,
+    ) -> Result<(), StorageBackendError> {
+        let table_name = self.event_table_name(event);
+        let tables = self
+            .client
+            .query(
+                "SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                vec![Value::Text(table_name.clone())],
+            )
+            .await?;
+        if tables.is_empty() {
+            return Ok(());
+        }
+
+        self.client
+            .execute(
+                &format!(
+                    "DELETE FROM {} WHERE network = ?1 AND block_number >= ?2 AND block_number <= ?3",
+                    table_name
+                ),
+                vec![
+                    Value::Text(network.to_string()),
+                    Value::Integer(from_block.as_u64() as i64),
+                    Value::Integer(to_block.as_u64() as i64),
+                ],
+            )
+            .await?;
+
+        Ok(())
+    }
+}
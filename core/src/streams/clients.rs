@@ -1,24 +1,33 @@
-use std::sync::Arc;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
+use async_trait::async_trait;
 use aws_sdk_sns::{config::http::HttpResponse, error::SdkError, operation::publish::PublishError};
+use ethers::types::U64;
 use futures::future::join_all;
 use serde_json::Value;
 use thiserror::Error;
 use tokio::{
-    task,
-    task::{JoinError, JoinHandle},
+    sync::{Mutex, Notify},
+    task::JoinError,
+    time::{sleep, timeout},
 };
-use tracing::error;
+use tracing::{debug, error, info, warn};
 
 use crate::{
-    event::{filter_event_data_by_conditions, EventMessage},
-    manifest::stream::{
-        KafkaStreamConfig, KafkaStreamQueueConfig, RabbitMQStreamConfig, RabbitMQStreamQueueConfig,
-        SNSStreamTopicConfig, StreamEvent, StreamsConfig, WebhookStreamConfig, RedisStreamConfig, RedisStreamStreamConfig
-    },
+    event::EventMessage,
+    indexer::{get_last_synced_block_number_file, update_sink_last_synced_block_number_file},
+    is_running,
+    manifest::stream::{ExchangeKindWrapper, StreamDeliveryConfig, StreamEvent, StreamsConfig},
     streams::{
         kafka::{Kafka, KafkaError},
-        RabbitMQ, RabbitMQError, Webhook, WebhookError, SNS, Redis, RedisError
+        outbox::{OutboxEntry, StreamOutbox},
+        registered_stream_sink, RabbitMQ, RabbitMQError, Redis, RedisError, StreamOutboxError,
+        StreamSink, Webhook, WebhookError, SNS,
     },
 };
 
@@ -26,13 +35,12 @@ use crate::{
 // we can add this to yaml if people need it
 const MAX_CHUNK_SIZE: usize = 75 * 1024; // 75 KB
 
-type StreamPublishes = Vec<JoinHandle<Result<usize, StreamError>>>;
+// how many messages a sink takes from its outbox at a time
+const MAX_DELIVERY_BATCH: usize = 100;
 
-#[derive(Debug, Clone)]
-struct SNSStream {
-    config: Vec<SNSStreamTopicConfig>,
-    client: Arc<SNS>,
-}
+// redriven dead letters are written to the outbox by the cli so it is checked even when nothing
+// new is queued
+const OUTBOX_POLL_INTERVAL: Duration = Duration::from_secs(5);
 
 #[derive(Error, Debug)]
 pub enum StreamError {
@@ -51,99 +59,505 @@ pub enum StreamError {
     #[error("Redis could not publish: {0}")]
     RedisCouldNotPublish(#[from] RedisError),
 
+    #[error("{0} could not publish: {1}")]
+    SinkCouldNotPublish(String, String),
+
+    #[error("Could not serialize the stream message: {0}")]
+    SerializeError(#[from] serde_json::Error),
+
+    #[error("Could not queue the stream message: {0}")]
+    OutboxError(#[from] StreamOutboxError),
+
+    #[error("No stream sink registered with the name {0} - register it with register_stream_sink before starting rindexer")]
+    SinkNotRegistered(String),
+
     #[error("Task failed: {0}")]
     JoinError(JoinError),
 }
 
-#[derive(Debug, Clone)]
-struct WebhookStream {
-    config: Vec<WebhookStreamConfig>,
+struct SNSTopicSink {
+    client: Arc<SNS>,
+    topic_arn: String,
+}
+
+#[async_trait]
+impl StreamSink for SNSTopicSink {
+    fn name(&self) -> &str {
+        "sns"
+    }
+
+    async fn publish(&self, id: &str, message: &EventMessage) -> Result<(), StreamError> {
+        let message = serde_json::to_string(message)?;
+        self.client.publish(id, &self.topic_arn, &message).await?;
+
+        Ok(())
+    }
+}
+
+struct WebhookSink {
     client: Arc<Webhook>,
+    endpoint: String,
+    shared_secret: String,
+}
+
+#[async_trait]
+impl StreamSink for WebhookSink {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn publish(&self, id: &str, message: &EventMessage) -> Result<(), StreamError> {
+        let message = serde_json::to_value(message)?;
+        self.client.publish(id, &self.endpoint, &self.shared_secret, &message).await?;
+
+        Ok(())
+    }
 }
 
-pub struct RabbitMQStream {
-    config: RabbitMQStreamConfig,
+struct RabbitMQExchangeSink {
     client: Arc<RabbitMQ>,
+    exchange: String,
+    exchange_type: ExchangeKindWrapper,
+    routing_key: Option<String>,
 }
 
-pub struct KafkaStream {
-    config: KafkaStreamConfig,
+#[async_trait]
+impl StreamSink for RabbitMQExchangeSink {
+    fn name(&self) -> &str {
+        "rabbitmq"
+    }
+
+    async fn publish(&self, id: &str, message: &EventMessage) -> Result<(), StreamError> {
+        let message = serde_json::to_value(message)?;
+        self.client
+            .publish(id, &self.exchange, &self.exchange_type, &self.routing_key, &message)
+            .await?;
+
+        Ok(())
+    }
+}
+
+struct KafkaTopicSink {
     client: Arc<Kafka>,
+    topic: String,
+    key: Option<String>,
+}
+
+#[async_trait]
+impl StreamSink for KafkaTopicSink {
+    fn name(&self) -> &str {
+        "kafka"
+    }
+
+    async fn publish(&self, id: &str, message: &EventMessage) -> Result<(), StreamError> {
+        let message = serde_json::to_value(message)?;
+        self.client.publish(id, &self.topic, &self.key, &message).await?;
+
+        Ok(())
+    }
+}
+
+struct RedisStreamSink {
+    client: Arc<Redis>,
+    stream_name: String,
+}
+
+#[async_trait]
+impl StreamSink for RedisStreamSink {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    async fn publish(&self, id: &str, message: &EventMessage) -> Result<(), StreamError> {
+        let message = serde_json::to_value(message)?;
+        self.client.publish(id, &self.stream_name, &message).await?;
+
+        Ok(())
+    }
+}
+
+/// A sink with the networks and events which are sent to it
+struct StreamTarget {
+    sink: Arc<dyn StreamSink>,
+    prefix_id: Option<String>,
+    networks: Vec<String>,
+    events: Vec<StreamEvent>,
+    delivery: StreamDeliveryConfig,
 }
 
-pub struct RedisStream {
-    config: RedisStreamConfig,
-    client: Arc<Redis>
+impl StreamTarget {
+    fn new(
+        sink: Arc<dyn StreamSink>,
+        prefix_id: Option<String>,
+        networks: &[String],
+        events: &[StreamEvent],
+        delivery: StreamDeliveryConfig,
+    ) -> Self {
+        StreamTarget {
+            sink,
+            prefix_id,
+            networks: networks.to_vec(),
+            events: events.to_vec(),
+            delivery,
+        }
+    }
+}
+
+/// A sink which keeps its own outbox and last synced block so one which is down does not hold up
+/// the others
+struct StreamRoute {
+    name: String,
+    target: StreamTarget,
+    last_synced_block_path: PathBuf,
+    contract_name: String,
+    event_name: String,
+    outbox: StreamOutbox,
+    /// The last block queued for the sink for each network
+    last_synced_blocks: Mutex<HashMap<String, Option<U64>>>,
+    queued: Notify,
+}
+
+impl StreamRoute {
+    fn matches(&self, event_message: &EventMessage) -> bool {
+        self.target.events.iter().any(|e| event_message.matches_event_name(&e.event_name)) &&
+            self.target.networks.contains(&event_message.network)
+    }
+
+    async fn last_synced_block(&self, network: &str) -> Option<U64> {
+        let mut last_synced_blocks = self.last_synced_blocks.lock().await;
+        if let Some(last_synced_block) = last_synced_blocks.get(network) {
+            return *last_synced_block;
+        }
+
+        let last_synced_block = match get_last_synced_block_number_file(
+            &self.last_synced_block_path,
+            &self.contract_name,
+            network,
+            &self.event_name,
+        )
+        .await
+        {
+            Ok(result) => result.filter(|value| !value.is_zero()),
+            Err(e) => {
+                error!("Error fetching last synced block of stream {}: {:?}", self.name, e);
+                None
+            }
+        };
+        last_synced_blocks.insert(network.to_string(), last_synced_block);
+
+        last_synced_block
+    }
+
+    async fn update_last_synced_block(&self, network: &str, to_block: U64) {
+        if let Err(e) = update_sink_last_synced_block_number_file(
+            &self.last_synced_block_path,
+            &self.contract_name,
+            network,
+            &self.event_name,
+            to_block,
+        )
+        .await
+        {
+            error!("Error updating last synced block of stream {}: {:?}", self.name, e);
+        }
+
+        let mut last_synced_blocks = self.last_synced_blocks.lock().await;
+        let last_synced_block = last_synced_blocks.entry(network.to_string()).or_default();
+        if last_synced_block.is_none_or(|value| to_block > value) {
+            *last_synced_block = Some(to_block);
+        }
+    }
+
+    /// Sends the messages, in order ones are sent one after the other and stop at the first
+    /// which fails
+    async fn publish(&self, entries: &[OutboxEntry]) -> Vec<Result<(), StreamError>> {
+        if entries.iter().any(|entry| entry.message.in_order) {
+            let mut results = vec![];
+            for entry in entries {
+                let result =
+                    self.target.sink.publish(&entry.message.id, &entry.message.message).await;
+                let failed = result.is_err();
+                results.push(result);
+                if failed {
+                    break;
+                }
+            }
+
+            return results;
+        }
+
+        join_all(
+            entries
+                .iter()
+                .map(|entry| self.target.sink.publish(&entry.message.id, &entry.message.message)),
+        )
+        .await
+    }
+
+    /// Sends what is in the outbox until rindexer stops, a message which keeps failing is moved
+    /// to the dead letters once it has used all of its attempts and in order messages wait for it
+    /// to be redriven
+    async fn deliver(self: Arc<Self>) {
+        let delivery = &self.target.delivery;
+        let mut delay = delivery.initial_backoff();
+        let mut retrying = false;
+        let mut paused = false;
+        while is_running() {
+            let entries = match self.outbox.pending(MAX_DELIVERY_BATCH).await {
+                Ok(entries) => entries,
+                Err(e) => {
+                    error!("Error reading the outbox of stream {}: {}", self.name, e);
+                    sleep(delivery.max_backoff()).await;
+                    continue;
+                }
+            };
+
+            if entries.is_empty() {
+                let _ = timeout(OUTBOX_POLL_INTERVAL, self.queued.notified()).await;
+                continue;
+            }
+
+            // sending past a dead letter would break the order so it waits for the redrive
+            if entries.iter().any(|entry| entry.message.in_order) {
+                match self.outbox.has_dead_letters().await {
+                    Ok(false) => {}
+                    Ok(true) => {
+                        if !paused {
+                            warn!(
+                                "Stream {} is paused as its events are sent in order and it has dead letters - run `rindexer streams redrive` to carry on",
+                                self.name
+                            );
+                            paused = true;
+                        }
+                        let _ = timeout(OUTBOX_POLL_INTERVAL, self.queued.notified()).await;
+                        continue;
+                    }
+                    Err(e) => {
+                        error!("Error reading the dead letters of stream {}: {}", self.name, e);
+                        sleep(delivery.max_backoff()).await;
+                        continue;
+                    }
+                }
+            }
+            paused = false;
+
+            let results = self.publish(&entries).await;
+            let mut streamed = 0;
+            let mut error = None;
+            for (entry, result) in entries.into_iter().zip(results) {
+                match result {
+                    Ok(()) => {
+                        streamed += message_events_len(&entry.message.message);
+                        if let Err(e) = self.outbox.delivered(&entry).await {
+                            error!("Error removing a sent message of stream {}: {}", self.name, e);
+                        }
+                    }
+                    Err(e) => {
+                        let id = entry.message.id.clone();
+                        match self.outbox.failed(entry, e.to_string(), delivery.max_attempts).await
+                        {
+                            Ok(true) => error!(
+                                "Stream {} could not publish message {} after {} attempts - it was moved to the dead letters, run `rindexer streams redrive` to send it again - {}",
+                                self.name, id, delivery.max_attempts, e
+                            ),
+                            Ok(false) => {}
+                            Err(outbox_error) => error!(
+                                "Error keeping a failed message of stream {}: {}",
+                                self.name, outbox_error
+                            ),
+                        }
+                        error = Some(e);
+                    }
+                }
+            }
+
+            match error {
+                Some(e) => {
+                    error!(
+                        "Stream {} could not publish - retrying in {:?} - {}",
+                        self.name, delay, e
+                    );
+                    sleep(delay).await;
+                    delay = (delay * 2).min(delivery.max_backoff());
+                    retrying = true;
+                }
+                None => {
+                    if retrying {
+                        info!("Stream {} is publishing again", self.name);
+                        retrying = false;
+                    }
+                    debug!("Stream {} published {} events", self.name, streamed);
+                    delay = delivery.initial_backoff();
+                }
+            }
+        }
+    }
+}
+
+fn message_events_len(message: &EventMessage) -> usize {
+    match &message.event_data {
+        Value::Array(events) => events.len(),
+        _ => 0,
+    }
+}
+
+fn event_block_number(event_data: &Value) -> Option<U64> {
+    event_data
+        .get("transaction_information")
+        .and_then(|transaction_information| transaction_information.get("block_number"))
+        .and_then(|block_number| serde_json::from_value(block_number.clone()).ok())
 }
 
 pub struct StreamsClients {
-    sns: Option<SNSStream>,
-    webhook: Option<WebhookStream>,
-    rabbitmq: Option<RabbitMQStream>,
-    kafka: Option<KafkaStream>,
-    redis: Option<RedisStream>,
+    routes: Vec<Arc<StreamRoute>>,
 }
 
 impl StreamsClients {
-    pub async fn new(stream_config: StreamsConfig) -> Self {
-        let sns = if let Some(config) = &stream_config.sns {
-            Some(SNSStream {
-                config: config.topics.clone(),
-                client: Arc::new(SNS::new(&config.aws_config).await),
-            })
-        } else {
-            None
-        };
+    /// The last synced blocks of each sink are kept for the contract and event in the project,
+    /// errors when a `custom` stream uses a sink which was not registered
+    pub async fn new(
+        stream_config: StreamsConfig,
+        project_path: &Path,
+        contract_name: &str,
+        event_name: &str,
+    ) -> Result<Self, StreamError> {
+        let mut targets = Vec::new();
+
+        if let Some(config) = &stream_config.sns {
+            let client = Arc::new(SNS::new(&config.aws_config).await);
+            for topic in &config.topics {
+                targets.push(StreamTarget::new(
+                    Arc::new(SNSTopicSink {
+                        client: Arc::clone(&client),
+                        topic_arn: topic.topic_arn.clone(),
+                    }),
+                    topic.prefix_id.clone(),
+                    &topic.networks,
+                    &topic.events,
+                    stream_config.sink_delivery(&topic.delivery),
+                ));
+            }
+        }
 
-        let webhook = stream_config.webhooks.as_ref().map(|config| WebhookStream {
-            config: config.clone(),
-            client: Arc::new(Webhook::new()),
-        });
+        if let Some(config) = &stream_config.webhooks {
+            let client = Arc::new(Webhook::new());
+            for webhook in config {
+                targets.push(StreamTarget::new(
+                    Arc::new(WebhookSink {
+                        client: Arc::clone(&client),
+                        endpoint: webhook.endpoint.clone(),
+                        shared_secret: webhook.shared_secret.clone(),
+                    }),
+                    None,
+                    &webhook.networks,
+                    &webhook.events,
+                    stream_config.sink_delivery(&webhook.delivery),
+                ));
+            }
+        }
 
-        let rabbitmq = if let Some(config) = stream_config.rabbitmq.as_ref() {
-            Some(RabbitMQStream {
-                config: config.clone(),
-                client: Arc::new(RabbitMQ::new(&config.url).await),
-            })
-        } else {
-            None
-        };
+        if let Some(config) = &stream_config.rabbitmq {
+            let client = Arc::new(RabbitMQ::new(&config.url).await);
+            for exchange in &config.exchanges {
+                targets.push(StreamTarget::new(
+                    Arc::new(RabbitMQExchangeSink {
+                        client: Arc::clone(&client),
+                        exchange: exchange.exchange.clone(),
+                        exchange_type: exchange.exchange_type.clone(),
+                        routing_key: exchange.routing_key.clone(),
+                    }),
+                    None,
+                    &exchange.networks,
+                    &exchange.events,
+                    stream_config.sink_delivery(&exchange.delivery),
+                ));
+            }
+        }
 
-        let kafka = if let Some(config) = stream_config.kafka.as_ref() {
-            Some(KafkaStream {
-                config: config.clone(),
-                client: Arc::new(
-                    Kafka::new(config)
-                        .await
-                        .unwrap_or_else(|e| panic!("Failed to create Kafka client: {:?}", e)),
-                ),
-            })
-        } else {
-            None
-        };
+        if let Some(config) = &stream_config.kafka {
+            let client = Arc::new(
+                Kafka::new(config)
+                    .await
+                    .unwrap_or_else(|e| panic!("Failed to create Kafka client: {:?}", e)),
+            );
+            for topic in &config.topics {
+                targets.push(StreamTarget::new(
+                    Arc::new(KafkaTopicSink {
+                        client: Arc::clone(&client),
+                        topic: topic.topic.clone(),
+                        key: topic.key.clone(),
+                    }),
+                    None,
+                    &topic.networks,
+                    &topic.events,
+                    stream_config.sink_delivery(&topic.delivery),
+                ));
+            }
+        }
 
-        let redis = if let Some(config) = stream_config.redis.as_ref() {
-            Some(RedisStream {
-                config: config.clone(),
-                client: Arc::new(
-                    Redis::new(config)
-                        .await
-                        .unwrap_or_else(|e| panic!("Failed to create Redis client: {:?}", e)),
-                )
+        if let Some(config) = &stream_config.redis {
+            let client = Arc::new(
+                Redis::new(config)
+                    .await
+                    .unwrap_or_else(|e| panic!("Failed to create Redis client: {:?}", e)),
+            );
+            for stream in &config.streams {
+                targets.push(StreamTarget::new(
+                    Arc::new(RedisStreamSink {
+                        client: Arc::clone(&client),
+                        stream_name: stream.stream_name.clone(),
+                    }),
+                    None,
+                    &stream.networks,
+                    &stream.events,
+                    stream_config.sink_delivery(&stream.delivery),
+                ));
+            }
+        }
+
+        if let Some(config) = &stream_config.custom {
+            for stream in config {
+                let sink = registered_stream_sink(&stream.name)
+                    .ok_or_else(|| StreamError::SinkNotRegistered(stream.name.clone()))?;
+                targets.push(StreamTarget::new(
+                    sink,
+                    stream.prefix_id.clone(),
+                    &stream.networks,
+                    &stream.events,
+                    stream_config.sink_delivery(&stream.delivery),
+                ));
+            }
+        }
+
+        let routes: Vec<Arc<StreamRoute>> = targets
+            .into_iter()
+            .zip(stream_config.sink_names())
+            .map(|(target, name)| {
+                let last_synced_block_path =
+                    StreamsConfig::sink_last_synced_block_path(project_path, &name);
+                Arc::new(StreamRoute {
+                    outbox: StreamOutbox::new(&last_synced_block_path, contract_name, event_name),
+                    last_synced_block_path,
+                    name,
+                    target,
+                    contract_name: contract_name.to_string(),
+                    event_name: event_name.to_string(),
+                    last_synced_blocks: Mutex::new(HashMap::new()),
+                    queued: Notify::new(),
+                })
             })
-        } else {
-            None
-        };
+            .collect();
+
+        // sends what was left in the outboxes when rindexer stopped as well
+        for route in &routes {
+            tokio::spawn(Arc::clone(route).deliver());
+        }
 
-        Self { sns, webhook, rabbitmq, kafka, redis }
+        Ok(Self { routes })
     }
 
     fn has_any_streams(&self) -> bool {
-        self.sns.is_some() ||
-            self.webhook.is_some() ||
-            self.rabbitmq.is_some() ||
-            self.kafka.is_some() ||
-            self.redis.is_some()
+        !self.routes.is_empty()
     }
 
     fn chunk_data(&self, data_array: &Vec<Value>) -> Vec<Vec<Value>> {
@@ -172,26 +586,13 @@ impl StreamsClients {
         chunks
     }
 
-    fn create_chunk_message_raw(&self, event_message: &EventMessage, chunk: &[Value]) -> String {
-        let chunk_message = EventMessage {
+    fn create_chunk_message(&self, event_message: &EventMessage, chunk: &[Value]) -> EventMessage {
+        EventMessage {
             event_name: event_message.event_name.clone(),
             event_data: Value::Array(chunk.to_vec()),
-            event_signature_hash: event_message.event_signature_hash.clone(),
+            event_signature_hash: event_message.event_signature_hash,
             network: event_message.network.clone(),
-        };
-
-        serde_json::to_string(&chunk_message).unwrap()
-    }
-
-    fn create_chunk_message_json(&self, event_message: &EventMessage, chunk: &[Value]) -> Value {
-        let chunk_message = EventMessage {
-            event_name: event_message.event_name.clone(),
-            event_data: Value::Array(chunk.to_vec()),
-            event_signature_hash: event_message.event_signature_hash.clone(),
-            network: event_message.network.clone(),
-        };
-
-        serde_json::to_value(&chunk_message).unwrap()
+        }
     }
 
     fn generate_publish_message_id(
@@ -216,14 +617,14 @@ impl StreamsClients {
     ) -> Vec<Value> {
         let stream_event = events
             .iter()
-            .find(|e| e.event_name == event_message.event_name)
+            .find(|e| event_message.matches_event_name(&e.event_name))
             .expect("Failed to find stream event - should never happen please raise an issue");
 
         let filtered_chunk: Vec<Value> = chunk
             .iter()
             .filter(|event_data| {
                 if let Some(conditions) = &stream_event.conditions {
-                    filter_event_data_by_conditions(event_data, conditions)
+                    conditions.matches(event_data)
                 } else {
                     true
                 }
@@ -234,192 +635,52 @@ impl StreamsClients {
         filtered_chunk
     }
 
-    fn sns_stream_tasks(
+    /// The chunk messages of a sink with the events it has not been sent before
+    fn route_messages(
         &self,
-        config: &SNSStreamTopicConfig,
-        client: Arc<SNS>,
+        route: &StreamRoute,
         id: &str,
         event_message: &EventMessage,
-        chunks: Arc<Vec<Vec<Value>>>,
-    ) -> StreamPublishes {
-        let tasks: Vec<_> = chunks
-            .iter()
-            .enumerate()
-            .map(|(index, chunk)| {
-                let filtered_chunk: Vec<Value> = self.filter_chunk_event_data_by_conditions(
-                    &config.events,
-                    event_message,
-                    chunk,
-                );
-
-                let publish_message_id =
-                    self.generate_publish_message_id(id, index, &config.prefix_id);
-                let client = Arc::clone(&client);
-                let topic_arn = config.topic_arn.clone();
-                let publish_message = self.create_chunk_message_raw(event_message, &filtered_chunk);
-                task::spawn(async move {
-                    let _ =
-                        client.publish(&publish_message_id, &topic_arn, &publish_message).await?;
-
-                    Ok(filtered_chunk.len())
-                })
-            })
-            .collect();
-
-        tasks
-    }
-
-    fn webhook_stream_tasks(
-        &self,
-        config: &WebhookStreamConfig,
-        client: Arc<Webhook>,
-        id: &str,
-        event_message: &EventMessage,
-        chunks: Arc<Vec<Vec<Value>>>,
-    ) -> StreamPublishes {
-        let tasks: Vec<_> = chunks
-            .iter()
-            .enumerate()
-            .map(|(index, chunk)| {
-                let filtered_chunk: Vec<Value> = self.filter_chunk_event_data_by_conditions(
-                    &config.events,
-                    event_message,
-                    chunk,
-                );
-
-                let publish_message_id = self.generate_publish_message_id(id, index, &None);
-                let endpoint = config.endpoint.clone();
-                let shared_secret = config.shared_secret.clone();
-                let client = Arc::clone(&client);
-                let publish_message =
-                    self.create_chunk_message_json(event_message, &filtered_chunk);
-                task::spawn(async move {
-                    client
-                        .publish(&publish_message_id, &endpoint, &shared_secret, &publish_message)
-                        .await?;
-
-                    Ok(filtered_chunk.len())
-                })
-            })
-            .collect();
-
-        tasks
-    }
-
-    fn rabbitmq_stream_tasks(
-        &self,
-        config: &RabbitMQStreamQueueConfig,
-        client: Arc<RabbitMQ>,
-        id: &str,
-        event_message: &EventMessage,
-        chunks: Arc<Vec<Vec<Value>>>,
-    ) -> StreamPublishes {
-        let tasks: Vec<_> = chunks
+        chunks: &[Vec<Value>],
+        last_synced_block: Option<U64>,
+    ) -> Vec<(String, EventMessage)> {
+        chunks
             .iter()
             .enumerate()
             .map(|(index, chunk)| {
-                let filtered_chunk: Vec<Value> = self.filter_chunk_event_data_by_conditions(
-                    &config.events,
-                    event_message,
-                    chunk,
-                );
-
-                let publish_message_id = self.generate_publish_message_id(id, index, &None);
-                let client = Arc::clone(&client);
-                let exchange = config.exchange.clone();
-                let exchange_type = config.exchange_type.clone();
-                let routing_key = config.routing_key.clone();
-                let publish_message =
-                    self.create_chunk_message_json(event_message, &filtered_chunk);
-
-                task::spawn(async move {
-                    client
-                        .publish(
-                            &publish_message_id,
-                            &exchange,
-                            &exchange_type,
-                            &routing_key,
-                            &publish_message,
-                        )
-                        .await?;
-                    Ok(filtered_chunk.len())
-                })
-            })
-            .collect();
-        tasks
-    }
+                let filtered_chunk: Vec<Value> = self
+                    .filter_chunk_event_data_by_conditions(
+                        &route.target.events,
+                        event_message,
+                        chunk,
+                    )
+                    .into_iter()
+                    .filter(|event_data| {
+                        match (last_synced_block, event_block_number(event_data)) {
+                            (Some(last_synced_block), Some(block_number)) => {
+                                block_number > last_synced_block
+                            }
+                            _ => true,
+                        }
+                    })
+                    .collect();
 
-    fn kafka_stream_tasks(
-        &self,
-        config: &KafkaStreamQueueConfig,
-        client: Arc<Kafka>,
-        id: &str,
-        event_message: &EventMessage,
-        chunks: Arc<Vec<Vec<Value>>>,
-    ) -> StreamPublishes {
-        let tasks: Vec<_> = chunks
-            .iter()
-            .enumerate()
-            .map(|(index, chunk)| {
-                let filtered_chunk: Vec<Value> = self.filter_chunk_event_data_by_conditions(
-                    &config.events,
-                    event_message,
-                    chunk,
-                );
-
-                let publish_message_id = self.generate_publish_message_id(id, index, &None);
-                let client = Arc::clone(&client);
-                let exchange = config.topic.clone();
-                let routing_key = config.key.clone();
-                let publish_message =
-                    self.create_chunk_message_json(event_message, &filtered_chunk);
-                task::spawn(async move {
-                    client
-                        .publish(&publish_message_id, &exchange, &routing_key, &publish_message)
-                        .await?;
-                    Ok(filtered_chunk.len())
-                })
+                (
+                    self.generate_publish_message_id(id, index, &route.target.prefix_id),
+                    self.create_chunk_message(event_message, &filtered_chunk),
+                )
             })
-            .collect();
-        tasks
-    }
-
-    fn redis_stream_tasks(
-        &self,
-        config: &RedisStreamStreamConfig,
-        client: Arc<Redis>,
-        id: &str,
-        event_message: &EventMessage,
-        chunks: Arc<Vec<Vec<Value>>>,
-    ) -> StreamPublishes {
-        let tasks: Vec<_> = chunks
-            .iter()
-            .enumerate()
-            .map(|(index, chunk)| {
-                let filtered_chunk: Vec<Value> = self.filter_chunk_event_data_by_conditions(
-                    &config.events,
-                    event_message,
-                    chunk
-                );
-
-                let publish_message_id = self.generate_publish_message_id(id, index, &None);
-                let client = Arc::clone(&client);
-                let stream_name = config.stream_name.clone();
-                let publish_message = self.create_chunk_message_json(event_message, &filtered_chunk);
-
-                task::spawn(async move {
-                    client.publish(&publish_message_id, &stream_name, &publish_message)
-                        .await?;
-                    Ok(filtered_chunk.len())
-                })
-            }).collect();
-        tasks
+            .collect()
     }
 
+    /// Queues the events of a block range in the outbox of each sink they are sent to, the sinks
+    /// send them in the background so one which is down does not hold up indexing. Returns how
+    /// many events were queued.
     pub async fn stream(
         &self,
         id: String,
         event_message: &EventMessage,
+        to_block: U64,
         index_event_in_order: bool,
     ) -> Result<usize, StreamError> {
         if !self.has_any_streams() {
@@ -428,120 +689,141 @@ impl StreamsClients {
 
         // will always have something even if the event has no parameters due to the tx_information
         if let Value::Array(data_array) = &event_message.event_data {
-            let chunks = Arc::new(self.chunk_data(data_array));
-            let mut streams: Vec<StreamPublishes> = Vec::new();
-
-            if let Some(sns) = &self.sns {
-                for config in &sns.config {
-                    if config.events.iter().any(|e| e.event_name == event_message.event_name) &&
-                        config.networks.contains(&event_message.network)
-                    {
-                        streams.push(self.sns_stream_tasks(
-                            config,
-                            Arc::clone(&sns.client),
-                            &id,
-                            event_message,
-                            Arc::clone(&chunks),
-                        ));
-                    }
+            let chunks = self.chunk_data(data_array);
+            let network = &event_message.network;
+
+            let mut queued_total = 0;
+            for route in self.routes.iter().filter(|route| route.matches(event_message)) {
+                let last_synced_block = route.last_synced_block(network).await;
+                // the range was queued before, the outbox still has anything not sent
+                if last_synced_block.is_some_and(|value| value >= to_block) {
+                    continue;
                 }
-            };
 
-            if let Some(webhook) = &self.webhook {
-                for config in &webhook.config {
-                    if config.events.iter().any(|e| e.event_name == event_message.event_name) &&
-                        config.networks.contains(&event_message.network)
-                    {
-                        streams.push(self.webhook_stream_tasks(
-                            config,
-                            Arc::clone(&webhook.client),
-                            &id,
-                            event_message,
-                            Arc::clone(&chunks),
-                        ));
-                    }
+                for (message_id, message) in
+                    self.route_messages(route, &id, event_message, &chunks, last_synced_block)
+                {
+                    queued_total += message_events_len(&message);
+                    route.outbox.push(message_id, to_block, message, index_event_in_order).await?;
                 }
-            }
 
-            if let Some(rabbitmq) = &self.rabbitmq {
-                for config in &rabbitmq.config.exchanges {
-                    if config.events.iter().any(|e| e.event_name == event_message.event_name) &&
-                        config.networks.contains(&event_message.network)
-                    {
-                        streams.push(self.rabbitmq_stream_tasks(
-                            config,
-                            Arc::clone(&rabbitmq.client),
-                            &id,
-                            event_message,
-                            Arc::clone(&chunks),
-                        ));
-                    }
-                }
+                route.update_last_synced_block(network, to_block).await;
+                route.queued.notify_one();
             }
 
-            if let Some(kafka) = &self.kafka {
-                for config in &kafka.config.topics {
-                    if config.events.iter().any(|e| e.event_name == event_message.event_name) &&
-                        config.networks.contains(&event_message.network)
-                    {
-                        streams.push(self.kafka_stream_tasks(
-                            config,
-                            Arc::clone(&kafka.client),
-                            &id,
-                            event_message,
-                            Arc::clone(&chunks),
-                        ));
-                    }
-                }
-            }
+            Ok(queued_total)
+        } else {
+            unreachable!("Event data should be an array");
+        }
+    }
+}
 
-            if let Some(redis) = &self.redis {
-                for config in &redis.config.streams {
-                    if config.events.iter().any(|e| e.event_name == event_message.event_name) &&
-                        config.networks.contains(&event_message.network)
-                    {
-                        streams.push(self.redis_stream_tasks(
-                            config,
-                            Arc::clone(&redis.client),
-                            &id,
-                            event_message,
-                            Arc::clone(&chunks),
-                        ));
-                    }
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::tempdir;
 
-            let mut streamed_total = 0;
+    use super::*;
+    use crate::streams::register_stream_sink;
 
-            if index_event_in_order {
-                for stream in streams {
-                    for task in stream {
-                        match task.await {
-                            Ok(Ok(streamed)) => {
-                                streamed_total += streamed;
-                            }
-                            Ok(Err(e)) => return Err(e),
-                            Err(e) => return Err(StreamError::JoinError(e)),
-                        }
-                    }
-                }
-            } else {
-                let tasks: Vec<_> = streams.into_iter().flatten().collect();
-                let results = join_all(tasks).await;
-                for result in results {
-                    match result {
-                        Ok(Ok(streamed)) => {
-                            streamed_total += streamed;
-                        }
-                        Ok(Err(e)) => return Err(e),
-                        Err(e) => return Err(StreamError::JoinError(e)),
-                    }
-                }
-            }
+    struct NoopSink;
 
-            Ok(streamed_total)
-        } else {
-            unreachable!("Event data should be an array");
+    #[async_trait]
+    impl StreamSink for NoopSink {
+        fn name(&self) -> &str {
+            "noop"
         }
+
+        async fn publish(&self, _id: &str, _message: &EventMessage) -> Result<(), StreamError> {
+            Ok(())
+        }
+    }
+
+    fn streams_config(yaml: &str) -> StreamsConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn event_message(event_name: &str, network: &str, events: Value) -> EventMessage {
+        EventMessage {
+            event_name: event_name.to_string(),
+            event_data: events,
+            event_signature_hash: Default::default(),
+            network: network.to_string(),
+        }
+    }
+
+    fn transfer(value: u64, block_number: u64) -> Value {
+        json!({
+            "value": value.to_string(),
+            "transaction_information": { "block_number": format!("{:#x}", block_number) }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_unregistered_custom_sink_is_an_error() {
+        let project = tempdir().unwrap();
+        let config = streams_config(
+            "custom:\n  - name: never-registered\n    networks: [ethereum]\n    events: [{ event_name: Transfer }]\n",
+        );
+
+        match StreamsClients::new(config, project.path(), "Token", "Transfer").await {
+            Err(StreamError::SinkNotRegistered(name)) => assert_eq!(name, "never-registered"),
+            _ => panic!("expected the sink to not be registered"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_routes_filter_by_network_event_conditions_and_last_synced_block() {
+        register_stream_sink("routing-test", Arc::new(NoopSink));
+        let project = tempdir().unwrap();
+        let config = streams_config(
+            "custom:\n  - name: routing-test\n    prefix_id: p\n    networks: [ethereum]\n    events:\n      - event_name: Transfer\n        conditions:\n          - value: \">5\"\n",
+        );
+        let clients =
+            StreamsClients::new(config, project.path(), "Token", "Transfer").await.unwrap();
+        let route = &clients.routes[0];
+
+        assert!(route.matches(&event_message("Transfer", "ethereum", json!([]))));
+        assert!(!route.matches(&event_message("Transfer", "base", json!([]))));
+        assert!(!route.matches(&event_message("Approval", "ethereum", json!([]))));
+
+        let events = vec![transfer(3, 10), transfer(8, 11), transfer(9, 12)];
+        let message = event_message("Transfer", "ethereum", Value::Array(events.clone()));
+        let chunks = clients.chunk_data(&events);
+
+        let messages =
+            clients.route_messages(route, "Token-Transfer-ethereum-10-12", &message, &chunks, None);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, "rindexer_stream__p-token-transfer-ethereum-10-12-chunk-0");
+        assert_eq!(messages[0].1.event_data, json!([transfer(8, 11), transfer(9, 12)]));
+
+        // events the sink was sent before a restart are left out
+        let messages = clients.route_messages(
+            route,
+            "Token-Transfer-ethereum-10-12",
+            &message,
+            &chunks,
+            Some(U64::from(11)),
+        );
+        assert_eq!(messages[0].1.event_data, json!([transfer(9, 12)]));
+    }
+
+    #[tokio::test]
+    async fn test_a_block_range_is_only_queued_once() {
+        register_stream_sink("queue-once-test", Arc::new(NoopSink));
+        let project = tempdir().unwrap();
+        let config = streams_config(
+            "custom:\n  - name: queue-once-test\n    networks: [ethereum]\n    events: [{ event_name: Transfer }]\n",
+        );
+        let clients =
+            StreamsClients::new(config, project.path(), "Token", "Transfer").await.unwrap();
+        let message =
+            event_message("Transfer", "ethereum", json!([transfer(1, 10), transfer(2, 20)]));
+
+        let queued = clients.stream("a".to_string(), &message, U64::from(20), false).await.unwrap();
+        assert_eq!(queued, 2);
+
+        let queued = clients.stream("a".to_string(), &message, U64::from(20), false).await.unwrap();
+        assert_eq!(queued, 0);
     }
 }
@@ -12,8 +12,16 @@ mod kafka;
 mod clients;
 
 mod redis;
+pub use clients::{StreamError, StreamsClients};
 pub use redis::{Redis, RedisError};
 
-pub use clients::StreamsClients;
+mod sink;
+pub use sink::{register_stream_sink, registered_stream_sink, StreamSink};
+
+mod outbox;
+pub use outbox::{
+    redrive_stream_dead_letters, stream_dead_letters, stream_outbox_status, OutboxMessage,
+    StreamDeadLetter, StreamOutboxError, StreamOutboxStatus,
+};
 
 pub const STREAM_MESSAGE_ID_KEY: &str = "x-rindexer-id";
@@ -0,0 +1,545 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ethers::types::U64;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use tokio::{fs, fs::OpenOptions, io::AsyncWriteExt, sync::Mutex};
+
+use crate::{event::EventMessage, manifest::stream::StreamsConfig};
+
+const OUTBOX_FOLDER: &str = "outbox";
+const DEAD_LETTER_FOLDER: &str = "dead-letter";
+
+#[derive(Error, Debug)]
+pub enum StreamOutboxError {
+    #[error("Could not read or write the stream outbox: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Could not serialize the stream outbox message: {0}")]
+    Serialize(#[from] serde_json::Error),
+}
+
+/// A message waiting in the outbox of a sink or moved to its dead letters
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OutboxMessage {
+    pub id: String,
+    pub contract_name: String,
+    pub event_name: String,
+    pub to_block: U64,
+    pub message: EventMessage,
+
+    /// Sent after the messages queued before it have been sent
+    #[serde(default)]
+    pub in_order: bool,
+
+    #[serde(default)]
+    pub attempts: u32,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// A message in the outbox with the file it is kept in
+pub struct OutboxEntry {
+    path: PathBuf,
+    pub message: OutboxMessage,
+}
+
+/// Keeps the messages of a sink for a contract event on disk until they are sent, each message is
+/// a json file named after the time it was queued so they are sent in the order they were queued
+pub struct StreamOutbox {
+    path: PathBuf,
+    dead_letter_path: PathBuf,
+    contract_name: String,
+    event_name: String,
+    last_sequence: Mutex<u64>,
+}
+
+impl StreamOutbox {
+    /// `sink_path` is the folder of the sink from `StreamsConfig::sink_last_synced_block_path`
+    pub fn new(sink_path: &Path, contract_name: &str, event_name: &str) -> Self {
+        let folder_name = format!("{}-{}", contract_name, event_name).to_lowercase();
+        StreamOutbox {
+            path: sink_path.join(OUTBOX_FOLDER).join(&folder_name),
+            dead_letter_path: sink_path.join(DEAD_LETTER_FOLDER).join(folder_name),
+            contract_name: contract_name.to_string(),
+            event_name: event_name.to_string(),
+            last_sequence: Mutex::new(0),
+        }
+    }
+
+    pub async fn push(
+        &self,
+        id: String,
+        to_block: U64,
+        message: EventMessage,
+        in_order: bool,
+    ) -> Result<(), StreamOutboxError> {
+        let message = OutboxMessage {
+            id,
+            contract_name: self.contract_name.clone(),
+            event_name: self.event_name.clone(),
+            to_block,
+            message,
+            in_order,
+            attempts: 0,
+            last_error: None,
+        };
+
+        let mut last_sequence = self.last_sequence.lock().await;
+        let sequence = next_sequence(*last_sequence);
+        *last_sequence = queue_message(&self.path, sequence, &message).await?;
+
+        Ok(())
+    }
+
+    /// The oldest messages in the outbox
+    pub async fn pending(&self, limit: usize) -> Result<Vec<OutboxEntry>, StreamOutboxError> {
+        let mut entries = vec![];
+        for path in message_paths(&self.path).await?.into_iter().take(limit) {
+            let message = read_message(&path).await?;
+            entries.push(OutboxEntry { path, message });
+        }
+
+        Ok(entries)
+    }
+
+    pub async fn delivered(&self, entry: &OutboxEntry) -> Result<(), StreamOutboxError> {
+        remove_message(&entry.path).await
+    }
+
+    /// Messages sent in order wait behind a dead letter until it is redriven
+    pub async fn has_dead_letters(&self) -> Result<bool, StreamOutboxError> {
+        Ok(!message_paths(&self.dead_letter_path).await?.is_empty())
+    }
+
+    /// Counts the attempt and moves the message to the dead letters once it has used all of its
+    /// attempts, returns if it was moved
+    pub async fn failed(
+        &self,
+        mut entry: OutboxEntry,
+        error: String,
+        max_attempts: u32,
+    ) -> Result<bool, StreamOutboxError> {
+        entry.message.attempts += 1;
+        entry.message.last_error = Some(error);
+
+        if entry.message.attempts < max_attempts {
+            write_message(&entry.path, &entry.message).await?;
+            return Ok(false);
+        }
+
+        fs::create_dir_all(&self.dead_letter_path).await?;
+        let file_name = entry.path.file_name().expect("outbox messages are files");
+        write_message(&self.dead_letter_path.join(file_name), &entry.message).await?;
+        remove_message(&entry.path).await?;
+
+        Ok(true)
+    }
+}
+
+/// How many messages a sink has waiting to be sent and how many failed
+pub struct StreamOutboxStatus {
+    pub sink: String,
+    pub pending: usize,
+    pub dead_letters: usize,
+}
+
+/// A message which used all of its attempts
+pub struct StreamDeadLetter {
+    pub sink: String,
+    pub message: OutboxMessage,
+}
+
+async fn sink_names(project_path: &Path) -> Result<Vec<String>, StreamOutboxError> {
+    let sinks_path = StreamsConfig::sinks_path(project_path);
+    let mut sinks = vec![];
+    let mut entries = match fs::read_dir(&sinks_path).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(sinks),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_dir() {
+            sinks.push(entry.file_name().to_string_lossy().to_string());
+        }
+    }
+    sinks.sort();
+
+    Ok(sinks)
+}
+
+/// The message files of every contract event of a sink in one of its queues
+async fn sink_message_paths(
+    project_path: &Path,
+    sink: &str,
+    folder: &str,
+) -> Result<Vec<PathBuf>, StreamOutboxError> {
+    let queues_path = StreamsConfig::sink_last_synced_block_path(project_path, sink).join(folder);
+    let mut paths = vec![];
+    let mut entries = match fs::read_dir(&queues_path).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(paths),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_type().await?.is_dir() {
+            paths.extend(message_paths(&entry.path()).await?);
+        }
+    }
+
+    Ok(paths)
+}
+
+/// The outbox of each sink of the project
+pub async fn stream_outbox_status(
+    project_path: &Path,
+) -> Result<Vec<StreamOutboxStatus>, StreamOutboxError> {
+    let mut statuses = vec![];
+    for sink in sink_names(project_path).await? {
+        let pending = sink_message_paths(project_path, &sink, OUTBOX_FOLDER).await?.len();
+        let dead_letters = sink_message_paths(project_path, &sink, DEAD_LETTER_FOLDER).await?.len();
+        statuses.push(StreamOutboxStatus { sink, pending, dead_letters });
+    }
+
+    Ok(statuses)
+}
+
+/// The dead letters of every sink or only of `sink`
+pub async fn stream_dead_letters(
+    project_path: &Path,
+    sink: Option<&str>,
+) -> Result<Vec<StreamDeadLetter>, StreamOutboxError> {
+    let mut dead_letters = vec![];
+    for sink_name in sink_names(project_path).await? {
+        if sink.is_some_and(|sink| sink != sink_name) {
+            continue;
+        }
+
+        for path in sink_message_paths(project_path, &sink_name, DEAD_LETTER_FOLDER).await? {
+            dead_letters.push(StreamDeadLetter {
+                sink: sink_name.clone(),
+                message: read_message(&path).await?,
+            });
+        }
+    }
+
+    Ok(dead_letters)
+}
+
+/// Moves dead letters back to the outbox of their sink with their attempts reset, only the ones of
+/// `sink` or with the message `id` when given. A dead letter keeps the place it had in the outbox
+/// so it is sent before the messages queued after it. Returns how many were moved.
+pub async fn redrive_stream_dead_letters(
+    project_path: &Path,
+    sink: Option<&str>,
+    id: Option<&str>,
+) -> Result<usize, StreamOutboxError> {
+    let mut redriven = 0;
+    for sink_name in sink_names(project_path).await? {
+        if sink.is_some_and(|sink| sink != sink_name) {
+            continue;
+        }
+
+        for path in sink_message_paths(project_path, &sink_name, DEAD_LETTER_FOLDER).await? {
+            let mut message = read_message(&path).await?;
+            if id.is_some_and(|id| id != message.id) {
+                continue;
+            }
+
+            message.attempts = 0;
+            message.last_error = None;
+            let queue_name = path
+                .parent()
+                .and_then(|queue_path| queue_path.file_name())
+                .expect("dead letters are in a contract event folder");
+            let outbox_path = StreamsConfig::sink_last_synced_block_path(project_path, &sink_name)
+                .join(OUTBOX_FOLDER)
+                .join(queue_name);
+            let sequence = path
+                .file_stem()
+                .and_then(|file_stem| file_stem.to_str())
+                .and_then(|file_stem| file_stem.parse::<u64>().ok())
+                .unwrap_or_else(|| next_sequence(0));
+            queue_message(&outbox_path, sequence, &message).await?;
+            remove_message(&path).await?;
+            redriven += 1;
+        }
+    }
+
+    Ok(redriven)
+}
+
+/// The sequence of a message queued now, after `last_sequence`
+fn next_sequence(last_sequence: u64) -> u64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+    now.max(last_sequence + 1)
+}
+
+/// Adds the message to the queue at `sequence` or the first free sequence after it, returns the
+/// sequence it was given
+async fn queue_message(
+    queue_path: &Path,
+    sequence: u64,
+    message: &OutboxMessage,
+) -> Result<u64, StreamOutboxError> {
+    fs::create_dir_all(queue_path).await?;
+
+    // the message is written in full before it gets its name, the link fails instead of
+    // replacing a message the indexer or a redrive in another process queued at the same sequence
+    let temp_path = queue_path.join(format!("{}-{}.json.tmp", sequence, process::id()));
+    let mut file = OpenOptions::new().write(true).create_new(true).open(&temp_path).await?;
+    file.write_all(&serde_json::to_vec(message)?).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    let mut sequence = sequence;
+    loop {
+        match fs::hard_link(&temp_path, queue_path.join(message_file_name(sequence))).await {
+            Ok(()) => break,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => sequence += 1,
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path).await;
+                return Err(e.into());
+            }
+        }
+    }
+    fs::remove_file(&temp_path).await?;
+
+    Ok(sequence)
+}
+
+fn message_file_name(sequence: u64) -> String {
+    format!("{:020}.json", sequence)
+}
+
+/// The message files of a queue oldest first
+async fn message_paths(queue_path: &Path) -> Result<Vec<PathBuf>, StreamOutboxError> {
+    let mut paths = vec![];
+    let mut entries = match fs::read_dir(queue_path).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(paths),
+        Err(e) => return Err(e.into()),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().is_some_and(|extension| extension == "json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    Ok(paths)
+}
+
+async fn read_message(path: &Path) -> Result<OutboxMessage, StreamOutboxError> {
+    let contents = fs::read(path).await?;
+
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+/// Writes to a temporary file first so a message is never half written
+async fn write_message(path: &Path, message: &OutboxMessage) -> Result<(), StreamOutboxError> {
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec(message)?).await?;
+    fs::rename(&temp_path, path).await?;
+
+    Ok(())
+}
+
+async fn remove_message(path: &Path) -> Result<(), StreamOutboxError> {
+    match fs::remove_file(path).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn event_message(value: u64) -> EventMessage {
+        EventMessage {
+            event_name: "Transfer".to_string(),
+            event_data: json!([{ "value": value.to_string() }]),
+            event_signature_hash: Default::default(),
+            network: "ethereum".to_string(),
+        }
+    }
+
+    fn sink_outbox(project_path: &Path, sink: &str) -> StreamOutbox {
+        StreamOutbox::new(
+            &StreamsConfig::sink_last_synced_block_path(project_path, sink),
+            "Token",
+            "Transfer",
+        )
+    }
+
+    async fn pending_ids(outbox: &StreamOutbox) -> Vec<String> {
+        outbox.pending(100).await.unwrap().into_iter().map(|entry| entry.message.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_pending_messages_come_out_in_the_order_they_were_pushed() {
+        let project = tempdir().unwrap();
+        let outbox = sink_outbox(project.path(), "webhook");
+
+        for i in 0..5 {
+            outbox.push(format!("id-{}", i), U64::from(i), event_message(i), true).await.unwrap();
+        }
+
+        assert_eq!(pending_ids(&outbox).await, vec!["id-0", "id-1", "id-2", "id-3", "id-4"]);
+        assert_eq!(outbox.pending(2).await.unwrap().len(), 2);
+
+        let entries = outbox.pending(1).await.unwrap();
+        assert_eq!(entries[0].message.contract_name, "Token");
+        assert_eq!(entries[0].message.event_name, "Transfer");
+        assert!(entries[0].message.in_order);
+        outbox.delivered(&entries[0]).await.unwrap();
+        assert_eq!(pending_ids(&outbox).await, vec!["id-1", "id-2", "id-3", "id-4"]);
+    }
+
+    #[tokio::test]
+    async fn test_a_failed_message_is_retried_until_it_is_dead_lettered() {
+        let project = tempdir().unwrap();
+        let outbox = sink_outbox(project.path(), "webhook");
+        outbox.push("id-0".to_string(), U64::from(1), event_message(1), false).await.unwrap();
+
+        for attempt in 1..3 {
+            let entry = outbox.pending(1).await.unwrap().remove(0);
+            assert!(!outbox.failed(entry, format!("error {}", attempt), 3).await.unwrap());
+
+            let entry = outbox.pending(1).await.unwrap().remove(0);
+            assert_eq!(entry.message.attempts, attempt);
+            assert_eq!(entry.message.last_error, Some(format!("error {}", attempt)));
+            assert!(!outbox.has_dead_letters().await.unwrap());
+        }
+
+        let entry = outbox.pending(1).await.unwrap().remove(0);
+        assert!(outbox.failed(entry, "error 3".to_string(), 3).await.unwrap());
+        assert!(outbox.pending(100).await.unwrap().is_empty());
+        assert!(outbox.has_dead_letters().await.unwrap());
+
+        let dead_letters = stream_dead_letters(project.path(), None).await.unwrap();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].sink, "webhook");
+        assert_eq!(dead_letters[0].message.id, "id-0");
+        assert_eq!(dead_letters[0].message.attempts, 3);
+        assert_eq!(dead_letters[0].message.last_error.as_deref(), Some("error 3"));
+    }
+
+    #[tokio::test]
+    async fn test_a_redriven_dead_letter_keeps_its_place_in_the_outbox() {
+        let project = tempdir().unwrap();
+        let outbox = sink_outbox(project.path(), "webhook");
+        outbox.push("id-0".to_string(), U64::from(1), event_message(1), true).await.unwrap();
+        let entry = outbox.pending(1).await.unwrap().remove(0);
+        assert!(outbox.failed(entry, "down".to_string(), 1).await.unwrap());
+
+        outbox.push("id-1".to_string(), U64::from(2), event_message(2), true).await.unwrap();
+        outbox.push("id-2".to_string(), U64::from(3), event_message(3), true).await.unwrap();
+
+        assert_eq!(redrive_stream_dead_letters(project.path(), None, None).await.unwrap(), 1);
+        assert!(!outbox.has_dead_letters().await.unwrap());
+
+        let entries = outbox.pending(100).await.unwrap();
+        let ids: Vec<&str> = entries.iter().map(|entry| entry.message.id.as_str()).collect();
+        assert_eq!(ids, vec!["id-0", "id-1", "id-2"]);
+        assert_eq!(entries[0].message.attempts, 0);
+        assert_eq!(entries[0].message.last_error, None);
+
+        assert_eq!(redrive_stream_dead_letters(project.path(), None, None).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_redrive_and_dead_letters_filter_by_sink_and_id() {
+        let project = tempdir().unwrap();
+        for sink in ["kafka", "webhook"] {
+            let outbox = sink_outbox(project.path(), sink);
+            for i in 0..2 {
+                outbox
+                    .push(format!("{}-{}", sink, i), U64::from(i), event_message(i), false)
+                    .await
+                    .unwrap();
+                let entry = outbox.pending(1).await.unwrap().remove(0);
+                outbox.failed(entry, "down".to_string(), 1).await.unwrap();
+            }
+        }
+        sink_outbox(project.path(), "kafka")
+            .push("kafka-pending".to_string(), U64::from(5), event_message(5), false)
+            .await
+            .unwrap();
+
+        let statuses = stream_outbox_status(project.path()).await.unwrap();
+        let statuses: Vec<(&str, usize, usize)> = statuses
+            .iter()
+            .map(|status| (status.sink.as_str(), status.pending, status.dead_letters))
+            .collect();
+        assert_eq!(statuses, vec![("kafka", 1, 2), ("webhook", 0, 2)]);
+
+        let webhook = stream_dead_letters(project.path(), Some("webhook")).await.unwrap();
+        assert_eq!(webhook.len(), 2);
+        assert!(webhook.iter().all(|dead_letter| dead_letter.sink == "webhook"));
+
+        assert_eq!(
+            redrive_stream_dead_letters(project.path(), Some("webhook"), Some("webhook-1"))
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(pending_ids(&sink_outbox(project.path(), "webhook")).await, vec!["webhook-1"]);
+        assert_eq!(
+            redrive_stream_dead_letters(project.path(), Some("webhook"), Some("kafka-0"))
+                .await
+                .unwrap(),
+            0
+        );
+
+        assert_eq!(
+            redrive_stream_dead_letters(project.path(), Some("kafka"), None).await.unwrap(),
+            2
+        );
+        assert_eq!(
+            pending_ids(&sink_outbox(project.path(), "kafka")).await,
+            vec!["kafka-0", "kafka-1", "kafka-pending"]
+        );
+        assert_eq!(stream_dead_letters(project.path(), None).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_queueing_at_a_taken_sequence_does_not_replace_the_message() {
+        let project = tempdir().unwrap();
+        let outbox = sink_outbox(project.path(), "webhook");
+        let message = |id: &str| OutboxMessage {
+            id: id.to_string(),
+            contract_name: "Token".to_string(),
+            event_name: "Transfer".to_string(),
+            to_block: U64::from(1),
+            message: event_message(1),
+            in_order: true,
+            attempts: 0,
+            last_error: None,
+        };
+
+        assert_eq!(queue_message(&outbox.path, 7, &message("first")).await.unwrap(), 7);
+        assert_eq!(queue_message(&outbox.path, 7, &message("second")).await.unwrap(), 8);
+
+        assert_eq!(pending_ids(&outbox).await, vec!["first", "second"]);
+        let mut files = fs::read_dir(&outbox.path).await.unwrap();
+        let mut count = 0;
+        while let Some(file) = files.next_entry().await.unwrap() {
+            assert!(!file.file_name().to_string_lossy().ends_with(".tmp"));
+            count += 1;
+        }
+        assert_eq!(count, 2);
+    }
+}
@@ -0,0 +1,61 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+};
+
+use async_trait::async_trait;
+use once_cell::sync::Lazy;
+
+use crate::{event::EventMessage, streams::StreamError};
+
+/// Somewhere events are streamed to. rindexer splits the events into chunks, filters them by the
+/// conditions of the stream and gives each chunk a message id before calling `publish` so a sink
+/// only has to send the message it is given.
+#[async_trait]
+pub trait StreamSink: Send + Sync {
+    /// The name used in logs
+    fn name(&self) -> &str;
+
+    /// Sends a chunk of events, `id` is the same each time the chunk is sent so it can be used to
+    /// deduplicate messages
+    async fn publish(&self, id: &str, message: &EventMessage) -> Result<(), StreamError>;
+}
+
+static STREAM_SINKS: Lazy<RwLock<HashMap<String, Arc<dyn StreamSink>>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Registers a sink which the `custom` streams in the YAML can use by its name, it has to be
+/// registered before indexing starts
+pub fn register_stream_sink(name: &str, sink: Arc<dyn StreamSink>) {
+    STREAM_SINKS.write().expect("stream sinks lock poisoned").insert(name.to_string(), sink);
+}
+
+pub fn registered_stream_sink(name: &str) -> Option<Arc<dyn StreamSink>> {
+    STREAM_SINKS.read().expect("stream sinks lock poisoned").get(name).cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NamedSink;
+
+    #[async_trait]
+    impl StreamSink for NamedSink {
+        fn name(&self) -> &str {
+            "named"
+        }
+
+        async fn publish(&self, _id: &str, _message: &EventMessage) -> Result<(), StreamError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_registered_sinks_are_found_by_name() {
+        register_stream_sink("registry-test", Arc::new(NamedSink));
+
+        assert_eq!(registered_stream_sink("registry-test").unwrap().name(), "named");
+        assert!(registered_stream_sink("registry-missing").is_none());
+    }
+}
@@ -87,7 +87,8 @@ export default defineConfig({
             { text: 'Kafka', link: '/docs/start-building/streams/kafka' },
             { text: 'Rabbitmq', link: '/docs/start-building/streams/rabbitmq' },
             { text: 'SNS/SQS', link: '/docs/start-building/streams/sns' },
-            { text: "Redis", link: '/docs/start-building/streams/redis' }
+            { text: "Redis", link: '/docs/start-building/streams/redis' },
+            { text: 'Custom', link: '/docs/start-building/streams/custom' }
           ],
         },
         {
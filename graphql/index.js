@@ -92,6 +92,11 @@ const options = {
             "greaterThanOrEqualTo",
             "in",
             "notIn",
+            "contains",
+            "containedBy",
+            "containsKey",
+            "containsAllKeys",
+            "containsAnyKeys",
         ],
     },
 };
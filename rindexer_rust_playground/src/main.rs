@@ -57,7 +57,10 @@ async fn main() {
                 manifest_path: &manifest_path,
                 indexing_details: if enable_indexer {
                     // EventCallbackRegistry { events: vec![] }
-                    Some(IndexingDetails { registry: register_all_handlers(&manifest_path).await })
+                    Some(IndexingDetails {
+                        registry: register_all_handlers(&manifest_path).await,
+                        storage_backends: vec![],
+                    })
                 } else {
                     None
                 },